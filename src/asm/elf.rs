@@ -0,0 +1,242 @@
+// The ELF64 relocatable object file for x86-64, as the System V ABI describes
+// it.  The assembler hands over the sections and the symbol table in their
+// final order, this only lays them out: the header, the contents of the
+// sections, a `.rela` section for each of them with relocations, `.symtab`,
+// `.strtab`, `.shstrtab` and at the end the section headers.
+
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOTE: u32 = 7;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_X86_64_UNWIND: u32 = 0x7000_0001;
+
+pub const SHF_WRITE: u64 = 0x1;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+pub const SHF_MERGE: u64 = 0x10;
+pub const SHF_STRINGS: u64 = 0x20;
+const SHF_INFO_LINK: u64 = 0x40;
+
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+pub const STT_FILE: u8 = 4;
+
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+
+pub const R_X86_64_64: u32 = 1;
+pub const R_X86_64_PC32: u32 = 2;
+pub const R_X86_64_PLT32: u32 = 4;
+pub const R_X86_64_GOTPCREL: u32 = 9;
+pub const R_X86_64_32: u32 = 10;
+pub const R_X86_64_32S: u32 = 11;
+pub const R_X86_64_PC64: u32 = 24;
+pub const R_X86_64_GOTOFF64: u32 = 25;
+pub const R_X86_64_GOTPC32: u32 = 26;
+pub const R_X86_64_GOT64: u32 = 27;
+pub const R_X86_64_GOTPC64: u32 = 29;
+pub const R_X86_64_PLTOFF64: u32 = 31;
+pub const R_X86_64_GOTPCRELX: u32 = 41;
+pub const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+const EHDR_SIZE: usize = 64;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const RELA_SIZE: usize = 24;
+
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub data: Vec<u8>, // only the size counts for SHT_NOBITS
+    pub align: u64,
+    pub entsize: u64,
+    pub relocs: Vec<Rela>,
+}
+
+pub struct Rela {
+    pub offset: u64,
+    pub sym: usize, // index into the symbol table
+    pub kind: u32,
+    pub addend: i64,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub bind: u8,
+    pub kind: u8,
+    pub shndx: u16, // the section `i` of the assembler is `i + 1` here
+    pub value: u64,
+    pub size: u64,
+}
+
+// `symbols` starts with the null symbol and has all the local ones before
+// the first global one, which is `first_global`
+pub fn write(sections: &[Section], symbols: &[Symbol], first_global: usize) -> Vec<u8> {
+    let mut strtab = Strtab::default();
+    let mut shstrtab = Strtab::default();
+
+    // (name, type, flags, offset, size, link, info, align, entsize)
+    let mut headers: Vec<Header> = vec![(0, 0, 0, 0, 0, 0, 0, 0, 0)];
+    let mut out = vec![0; EHDR_SIZE];
+    for s in sections.iter() {
+        let offset = align_to(&mut out, s.align.max(1));
+        if s.sh_type != SHT_NOBITS {
+            out.extend_from_slice(&s.data);
+        }
+        headers.push((
+            shstrtab.add(&s.name),
+            s.sh_type,
+            s.flags,
+            offset,
+            s.data.len() as u64,
+            0,
+            0,
+            s.align,
+            s.entsize,
+        ));
+    }
+    let symtab_index =
+        sections.len() + sections.iter().filter(|s| !s.relocs.is_empty()).count() + 1;
+    for (i, s) in sections.iter().enumerate() {
+        if s.relocs.is_empty() {
+            continue;
+        }
+        let offset = align_to(&mut out, 8);
+        for r in s.relocs.iter() {
+            out.extend_from_slice(&r.offset.to_le_bytes());
+            out.extend_from_slice(&((r.sym as u64) << 32 | u64::from(r.kind)).to_le_bytes());
+            out.extend_from_slice(&r.addend.to_le_bytes());
+        }
+        headers.push((
+            shstrtab.add(&format!(".rela{}", s.name)),
+            SHT_RELA,
+            SHF_INFO_LINK,
+            offset,
+            (s.relocs.len() * RELA_SIZE) as u64,
+            symtab_index as u32,
+            i as u32 + 1,
+            8,
+            RELA_SIZE as u64,
+        ));
+    }
+
+    let offset = align_to(&mut out, 8);
+    for sym in symbols.iter() {
+        let name = if sym.name.is_empty() {
+            0
+        } else {
+            strtab.add(&sym.name)
+        };
+        out.extend_from_slice(&name.to_le_bytes());
+        out.push(sym.bind << 4 | sym.kind);
+        out.push(0); // default visibility
+        out.extend_from_slice(&sym.shndx.to_le_bytes());
+        out.extend_from_slice(&sym.value.to_le_bytes());
+        out.extend_from_slice(&sym.size.to_le_bytes());
+    }
+    headers.push((
+        shstrtab.add(".symtab"),
+        SHT_SYMTAB,
+        0,
+        offset,
+        (symbols.len() * SYM_SIZE) as u64,
+        symtab_index as u32 + 1,
+        first_global as u32,
+        8,
+        SYM_SIZE as u64,
+    ));
+    let name = shstrtab.add(".strtab");
+    headers.push(strtab_header(&mut out, name, &strtab));
+    // the name of `.shstrtab` is in `.shstrtab` too, so it is added first
+    let name = shstrtab.add(".shstrtab");
+    headers.push(strtab_header(&mut out, name, &shstrtab));
+
+    let shoff = align_to(&mut out, 8);
+    for h in headers.iter() {
+        out.extend_from_slice(&h.0.to_le_bytes());
+        out.extend_from_slice(&h.1.to_le_bytes());
+        out.extend_from_slice(&h.2.to_le_bytes());
+        out.extend_from_slice(&0u64.to_le_bytes()); // address
+        out.extend_from_slice(&h.3.to_le_bytes());
+        out.extend_from_slice(&h.4.to_le_bytes());
+        out.extend_from_slice(&h.5.to_le_bytes());
+        out.extend_from_slice(&h.6.to_le_bytes());
+        out.extend_from_slice(&h.7.to_le_bytes());
+        out.extend_from_slice(&h.8.to_le_bytes());
+    }
+
+    let mut ehdr = Vec::with_capacity(EHDR_SIZE);
+    ehdr.extend_from_slice(b"\x7fELF");
+    ehdr.extend_from_slice(&[2, 1, 1, 0]); // 64-bit, little endian, version 1, System V
+    ehdr.extend_from_slice(&[0; 8]);
+    ehdr.extend_from_slice(&1u16.to_le_bytes()); // ET_REL
+    ehdr.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    ehdr.extend_from_slice(&1u32.to_le_bytes());
+    ehdr.extend_from_slice(&0u64.to_le_bytes()); // entry
+    ehdr.extend_from_slice(&0u64.to_le_bytes()); // program headers
+    ehdr.extend_from_slice(&shoff.to_le_bytes());
+    ehdr.extend_from_slice(&0u32.to_le_bytes()); // flags
+    ehdr.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+    ehdr.extend_from_slice(&0u16.to_le_bytes());
+    ehdr.extend_from_slice(&0u16.to_le_bytes());
+    ehdr.extend_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16 - 1).to_le_bytes()); // .shstrtab is last
+    out[..EHDR_SIZE].copy_from_slice(&ehdr);
+    out
+}
+
+type Header = (u32, u32, u64, u64, u64, u32, u32, u64, u64);
+
+fn strtab_header(out: &mut Vec<u8>, name: u32, table: &Strtab) -> Header {
+    let offset = out.len() as u64;
+    out.extend_from_slice(&table.data);
+    (
+        name,
+        SHT_STRTAB,
+        0,
+        offset,
+        table.data.len() as u64,
+        0,
+        0,
+        1,
+        0,
+    )
+}
+
+fn align_to(out: &mut Vec<u8>, align: u64) -> u64 {
+    while !(out.len() as u64).is_multiple_of(align) {
+        out.push(0);
+    }
+    out.len() as u64
+}
+
+// a string table: NUL terminated names after a leading NUL
+struct Strtab {
+    data: Vec<u8>,
+}
+
+impl Default for Strtab {
+    fn default() -> Strtab {
+        Strtab { data: vec![0] }
+    }
+}
+
+impl Strtab {
+    fn add(&mut self, name: &str) -> u32 {
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.push(0);
+        offset
+    }
+}
@@ -0,0 +1,1005 @@
+use super::operand::{Expr, Mem, Modifier, Operand, Reg};
+
+// The machine code of one instruction.  Where it refers to a symbol it leaves
+// a fixup, the assembler fills it in or turns it into a relocation once all
+// the symbols are known.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Abs64,    // R_X86_64_64
+    Abs32,    // R_X86_64_32, zero extended
+    Abs32S,   // R_X86_64_32S, sign extended
+    Pc32,     // R_X86_64_PC32
+    Plt32,    // R_X86_64_PLT32
+    GotPcRel, // R_X86_64_GOTPCREL
+}
+
+impl Kind {
+    pub fn is_pc_relative(self) -> bool {
+        matches!(self, Kind::Pc32 | Kind::Plt32 | Kind::GotPcRel)
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Kind::Abs64 => 8,
+            _ => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Fixup {
+    pub offset: usize, // from the start of the instruction or the data
+    pub expr: Expr,
+    pub kind: Kind,
+}
+
+#[derive(Default, Debug)]
+pub struct Code {
+    pub bytes: Vec<u8>,
+    pub fixups: Vec<Fixup>,
+}
+
+// the operand in the r/m field of the ModRM byte
+enum Rm<'a> {
+    Reg(Reg),
+    Mem(&'a Mem),
+}
+
+pub fn encode(mnemonic: &str, ops: &[Operand]) -> Result<Code, String> {
+    let mut code = Code::default();
+    inst(&mut code, mnemonic, ops)?;
+    // the processor adds a displacement to the address of the next
+    // instruction, not to that of the field
+    let len = code.bytes.len();
+    for f in code.fixups.iter_mut() {
+        if f.kind.is_pc_relative() {
+            f.expr.value -= (len - f.offset) as i64;
+        }
+    }
+    Ok(code)
+}
+
+// the condition codes, as the low nibble of jcc, setcc and cmovcc
+fn cond(cc: &str) -> Option<u8> {
+    Some(match cc {
+        "o" => 0x0,
+        "no" => 0x1,
+        "b" | "c" | "nae" => 0x2,
+        "ae" | "nb" | "nc" => 0x3,
+        "e" | "z" => 0x4,
+        "ne" | "nz" => 0x5,
+        "be" | "na" => 0x6,
+        "a" | "nbe" => 0x7,
+        "s" => 0x8,
+        "ns" => 0x9,
+        "p" | "pe" => 0xa,
+        "np" | "po" => 0xb,
+        "l" | "nge" => 0xc,
+        "ge" | "nl" => 0xd,
+        "le" | "ng" => 0xe,
+        "g" | "nle" => 0xf,
+        _ => return None,
+    })
+}
+
+// the instructions that take an operand size suffix, with what tells them apart
+#[derive(Clone, Copy)]
+enum Op {
+    Mov,
+    Lea,
+    Push,
+    Pop,
+    Alu(u8),   // add, or, adc, sbb, and, sub, xor, cmp: the /digit of 0x80
+    Unary(u8), // not, neg, mul, div, idiv: the /digit of 0xf6
+    IncDec(u8),
+    Imul,
+    Test,
+    Shift(u8), // the /digit of 0xc0
+}
+
+const SIZED: &[(&str, Op)] = &[
+    ("mov", Op::Mov),
+    ("lea", Op::Lea),
+    ("push", Op::Push),
+    ("pop", Op::Pop),
+    ("add", Op::Alu(0)),
+    ("or", Op::Alu(1)),
+    ("adc", Op::Alu(2)),
+    ("sbb", Op::Alu(3)),
+    ("and", Op::Alu(4)),
+    ("sub", Op::Alu(5)),
+    ("xor", Op::Alu(6)),
+    ("cmp", Op::Alu(7)),
+    ("not", Op::Unary(2)),
+    ("neg", Op::Unary(3)),
+    ("mul", Op::Unary(4)),
+    ("div", Op::Unary(6)),
+    ("idiv", Op::Unary(7)),
+    ("inc", Op::IncDec(0)),
+    ("dec", Op::IncDec(1)),
+    ("imul", Op::Imul),
+    ("test", Op::Test),
+    ("rol", Op::Shift(0)),
+    ("ror", Op::Shift(1)),
+    ("shl", Op::Shift(4)),
+    ("sal", Op::Shift(4)),
+    ("shr", Op::Shift(5)),
+    ("sar", Op::Shift(7)),
+];
+
+// the SSE2 instructions on a scalar in an xmm register, from an xmm register
+// or memory: the mandatory prefix, none for 0, and the opcode after 0x0f
+const SSE: &[(&str, u8, u8)] = &[
+    ("movss", 0xf3, 0x10),
+    ("movsd", 0xf2, 0x10),
+    ("addss", 0xf3, 0x58),
+    ("addsd", 0xf2, 0x58),
+    ("mulss", 0xf3, 0x59),
+    ("mulsd", 0xf2, 0x59),
+    ("subss", 0xf3, 0x5c),
+    ("subsd", 0xf2, 0x5c),
+    ("divss", 0xf3, 0x5e),
+    ("divsd", 0xf2, 0x5e),
+    ("cvtss2sd", 0xf3, 0x5a),
+    ("cvtsd2ss", 0xf2, 0x5a),
+    ("ucomiss", 0, 0x2e),
+    ("ucomisd", 0x66, 0x2e),
+    ("xorps", 0, 0x57),
+    ("xorpd", 0x66, 0x57),
+];
+
+// the x87 instructions on memory, `fldt` and `fstpt` the loads and stores of
+// the 80-bit format, `fildq` and `fistpq` of an i64, and the control word:
+// the opcode and the /digit
+const X87: &[(&str, u8, u8)] = &[
+    ("flds", 0xd9, 0),
+    ("fstps", 0xd9, 3),
+    ("fldl", 0xdd, 0),
+    ("fstpl", 0xdd, 3),
+    ("fldt", 0xdb, 5),
+    ("fstpt", 0xdb, 7),
+    ("fildq", 0xdf, 5),
+    ("fistpq", 0xdf, 7),
+    ("fadds", 0xd8, 0),
+    ("fsubs", 0xd8, 4),
+    ("fldcw", 0xd9, 5),
+    ("fnstcw", 0xd9, 7),
+];
+
+// the x87 instructions on the registers of its stack, `%st(i)` adding i to
+// the second byte: the operands they take, `st` for `%st` and `sti` for
+// `%st(i)`, and the two bytes.  gas names `fsubp` and `fsubrp`, and `fdivp`
+// and `fdivrp`, the other way round from Intel.
+const X87_STACK: &[(&str, &[&str], [u8; 2])] = &[
+    ("faddp", &["st", "sti"], [0xde, 0xc0]),
+    ("fmulp", &["st", "sti"], [0xde, 0xc8]),
+    ("fsubp", &["st", "sti"], [0xde, 0xe0]),
+    ("fsubrp", &["st", "sti"], [0xde, 0xe8]),
+    ("fdivp", &["st", "sti"], [0xde, 0xf0]),
+    ("fdivrp", &["st", "sti"], [0xde, 0xf8]),
+    ("fucomip", &["sti", "st"], [0xdf, 0xe8]),
+    ("fstp", &["sti"], [0xdd, 0xd8]),
+    ("fxch", &["sti"], [0xd9, 0xc8]),
+    ("fchs", &[], [0xd9, 0xe0]),
+];
+
+fn suffix_size(c: char) -> Option<u8> {
+    match c {
+        'b' => Some(1),
+        'w' => Some(2),
+        'l' => Some(4),
+        'q' => Some(8),
+        _ => None,
+    }
+}
+
+fn inst(code: &mut Code, mnemonic: &str, ops: &[Operand]) -> Result<(), String> {
+    if let Some(res) = float_inst(code, mnemonic, ops) {
+        return res;
+    }
+    // the ones whose names would be taken apart wrongly below come first
+    let fixed: &[u8] = match mnemonic {
+        "ret" | "retq" => &[0xc3],
+        "leave" | "leaveq" => &[0xc9],
+        "nop" => &[0x90],
+        "hlt" => &[0xf4],
+        "ud2" => &[0x0f, 0x0b],
+        "syscall" => &[0x0f, 0x05],
+        "cltd" | "cdq" => &[0x99],
+        "cqto" | "cqo" => &[0x48, 0x99],
+        "cltq" | "cdqe" => &[0x48, 0x98],
+        "jmp" | "jmpq" => return jump(code, ops, None),
+        "call" | "callq" => return call(code, ops),
+        "movabs" | "movabsq" => return movabs(code, ops),
+        "movslq" | "movsxd" => return extend(code, ops, 4, 8, &[0x63]),
+        _ => &[],
+    };
+    if !fixed.is_empty() {
+        if !ops.is_empty() {
+            return Err(format!("`{}` takes no operands", mnemonic));
+        }
+        code.bytes.extend_from_slice(fixed);
+        return Ok(());
+    }
+    if let Some(cc) = mnemonic.strip_prefix('j').and_then(cond) {
+        return jump(code, ops, Some(cc));
+    }
+    if let Some(cc) = mnemonic.strip_prefix("set").and_then(cond) {
+        return setcc(code, ops, cc);
+    }
+    if let Some(rest) = mnemonic.strip_prefix("cmov") {
+        let cc = cond(rest).or_else(|| {
+            let cut = rest.len().checked_sub(1)?;
+            suffix_size(rest[cut..].chars().next()?)?;
+            cond(&rest[..cut])
+        });
+        if let Some(cc) = cc {
+            return cmov(code, ops, cc);
+        }
+    }
+    // movsbq, movzwl and the like: the sizes of the source and the destination
+    let b = mnemonic.as_bytes();
+    if b.len() == 6 && (mnemonic.starts_with("movs") || mnemonic.starts_with("movz")) {
+        if let (Some(from), Some(to)) = (suffix_size(b[4] as char), suffix_size(b[5] as char)) {
+            let opcode = match (b[3], from) {
+                (b'z', 1) => [0x0f, 0xb6],
+                (b'z', 2) => [0x0f, 0xb7],
+                (b's', 1) => [0x0f, 0xbe],
+                (b's', 2) => [0x0f, 0xbf],
+                _ => return Err(format!("unknown instruction `{}`", mnemonic)),
+            };
+            if from >= to {
+                return Err(format!("`{}` does not extend", mnemonic));
+            }
+            return extend(code, ops, from, to, &opcode);
+        }
+    }
+
+    let (op, size) = match SIZED.iter().find(|(name, _)| *name == mnemonic) {
+        Some((_, op)) => (*op, None),
+        None => {
+            let cut = mnemonic.len().saturating_sub(1);
+            let size = mnemonic[cut..].chars().next().and_then(suffix_size);
+            match SIZED.iter().find(|(name, _)| *name == &mnemonic[..cut]) {
+                Some((_, op)) if size.is_some() => (*op, size),
+                _ => return Err(format!("unknown instruction `{}`", mnemonic)),
+            }
+        }
+    };
+    let size = op_size(mnemonic, op, size, ops)?;
+    match op {
+        Op::Mov => mov(code, size, ops),
+        Op::Lea => match ops {
+            [Operand::Mem(m), Operand::Reg(r)] if size > 1 => {
+                modrm(code, size, &[0x8d], r.num, Some(*r), Rm::Mem(m), None)
+            }
+            _ => Err("`lea` takes a memory operand and a register".to_string()),
+        },
+        Op::Push | Op::Pop => push_pop(code, matches!(op, Op::Push), size, ops),
+        Op::Alu(ext) => alu(code, ext, size, ops),
+        Op::Unary(ext) => match ops {
+            [dst] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xf6, 0xf7)],
+                ext,
+                None,
+                rm(dst)?,
+                None,
+            ),
+            _ => Err(format!("`{}` takes one operand", mnemonic)),
+        },
+        Op::IncDec(ext) => match ops {
+            [dst] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xfe, 0xff)],
+                ext,
+                None,
+                rm(dst)?,
+                None,
+            ),
+            _ => Err(format!("`{}` takes one operand", mnemonic)),
+        },
+        Op::Imul => imul(code, size, ops),
+        Op::Test => match ops {
+            [Operand::Imm(e), dst] => {
+                let rm = rm(dst)?;
+                modrm(
+                    code,
+                    size,
+                    &[byte_or(size, 0xf6, 0xf7)],
+                    0,
+                    None,
+                    rm,
+                    Some((e, size.min(4))),
+                )
+            }
+            [Operand::Reg(r), dst] | [dst, Operand::Reg(r)] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0x84, 0x85)],
+                r.num,
+                Some(*r),
+                rm(dst)?,
+                None,
+            ),
+            _ => Err("invalid operands for `test`".to_string()),
+        },
+        Op::Shift(ext) => match ops {
+            [dst] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xd0, 0xd1)],
+                ext,
+                None,
+                rm(dst)?,
+                None,
+            ),
+            [Operand::Imm(e), dst] if e.constant() == Some(1) => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xd0, 0xd1)],
+                ext,
+                None,
+                rm(dst)?,
+                None,
+            ),
+            [Operand::Imm(e), dst] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xc0, 0xc1)],
+                ext,
+                None,
+                rm(dst)?,
+                Some((e, 1)),
+            ),
+            [Operand::Reg(Reg {
+                num: 1,
+                size: 1,
+                high: false,
+            }), dst] => modrm(
+                code,
+                size,
+                &[byte_or(size, 0xd2, 0xd3)],
+                ext,
+                None,
+                rm(dst)?,
+                None,
+            ),
+            _ => Err(format!("invalid operands for `{}`", mnemonic)),
+        },
+    }
+}
+
+// the operand size from the suffix or the registers, they must agree
+fn op_size(mnemonic: &str, op: Op, suffix: Option<u8>, ops: &[Operand]) -> Result<u8, String> {
+    let regs: Vec<u8> = match op {
+        // the count of a shift is %cl whatever the size
+        Op::Shift(_) => ops
+            .iter()
+            .skip(ops.len().saturating_sub(1))
+            .filter_map(reg_size)
+            .collect(),
+        Op::Push | Op::Pop => return Ok(suffix.unwrap_or(8)),
+        _ => ops.iter().filter_map(reg_size).collect(),
+    };
+    let size = match (suffix, regs.first()) {
+        (Some(s), _) => s,
+        (None, Some(s)) => *s,
+        (None, None) => {
+            return Err(format!(
+                "the operand size of `{}` is not known, it needs a suffix",
+                mnemonic
+            ))
+        }
+    };
+    if regs.iter().any(|r| *r != size) {
+        return Err(format!("operand size mismatch for `{}`", mnemonic));
+    }
+    Ok(size)
+}
+
+fn reg_size(op: &Operand) -> Option<u8> {
+    match op {
+        Operand::Reg(r) => Some(r.size),
+        _ => None,
+    }
+}
+
+fn byte_or(size: u8, byte_op: u8, op: u8) -> u8 {
+    if size == 1 {
+        byte_op
+    } else {
+        op
+    }
+}
+
+fn rm(op: &Operand) -> Result<Rm<'_>, String> {
+    match op {
+        Operand::Reg(r) => Ok(Rm::Reg(*r)),
+        Operand::Mem(m) if m.modifier == Modifier::Plt => {
+            Err("`@PLT` is only for jumps and calls".to_string())
+        }
+        Operand::Mem(m) => Ok(Rm::Mem(m)),
+        _ => Err("expected a register or memory operand".to_string()),
+    }
+}
+
+fn fits_i8(v: i64) -> bool {
+    v >= i64::from(i8::MIN) && v <= i64::from(i8::MAX)
+}
+
+fn fits_i32(v: i64) -> bool {
+    v >= i64::from(i32::MIN) && v <= i64::from(i32::MAX)
+}
+
+// an immediate of `size` bytes for an operation of `op_size` bytes; an imm32
+// of a 64-bit operation is sign extended, so it must fit
+fn imm(code: &mut Code, e: &Expr, size: u8, op_size: u8) -> Result<(), String> {
+    match e.constant() {
+        Some(v) => {
+            let ok = match (size, op_size) {
+                (_, 8) => fits_i32(v) || size == 8,
+                (1, _) => (-0x80..=0xff).contains(&v),
+                (2, _) => (-0x8000..=0xffff).contains(&v),
+                _ => v >= i64::from(i32::MIN) && v <= i64::from(u32::MAX),
+            };
+            if !ok {
+                return Err(format!("immediate {} does not fit in {} bytes", v, size));
+            }
+            code.bytes
+                .extend_from_slice(&v.to_le_bytes()[..size as usize]);
+        }
+        None => {
+            let kind = match (size, op_size) {
+                (8, _) => Kind::Abs64,
+                (4, 8) => Kind::Abs32S,
+                (4, _) => Kind::Abs32,
+                _ => return Err("a symbol does not fit in a small immediate".to_string()),
+            };
+            code.fixups.push(Fixup {
+                offset: code.bytes.len(),
+                expr: e.clone(),
+                kind,
+            });
+            code.bytes.extend_from_slice(&[0; 8][..size as usize]);
+        }
+    }
+    Ok(())
+}
+
+// prefixes, opcode, ModRM, SIB, displacement and immediate; `size` is the
+// operand size, 2 takes the 0x66 prefix and 8 takes REX.W
+fn modrm(
+    code: &mut Code,
+    size: u8,
+    opcode: &[u8],
+    reg: u8,
+    reg_op: Option<Reg>,
+    rm: Rm,
+    immediate: Option<(&Expr, u8)>,
+) -> Result<(), String> {
+    let mut rex = 0u8;
+    if size == 8 {
+        rex |= 0x8;
+    }
+    if reg & 8 != 0 {
+        rex |= 0x4;
+    }
+    let mut byte_regs: Vec<Reg> = reg_op.into_iter().collect();
+    match &rm {
+        Rm::Reg(r) => {
+            if r.num & 8 != 0 {
+                rex |= 0x1;
+            }
+            byte_regs.push(*r);
+        }
+        Rm::Mem(m) => {
+            if m.base.is_some_and(|b| b.num & 8 != 0) {
+                rex |= 0x1;
+            }
+            if m.index.is_some_and(|i| i.num & 8 != 0) {
+                rex |= 0x2;
+            }
+        }
+    }
+    let wants_rex = rex != 0 || byte_regs.iter().any(|r| r.wants_rex());
+    if wants_rex && byte_regs.iter().any(|r| r.high) {
+        return Err("%ah, %bh, %ch and %dh can not be used here".to_string());
+    }
+    if size == 2 {
+        code.bytes.push(0x66);
+    }
+    if wants_rex {
+        code.bytes.push(0x40 | rex);
+    }
+    code.bytes.extend_from_slice(opcode);
+    let reg = (reg & 7) << 3;
+    match rm {
+        Rm::Reg(r) => code.bytes.push(0xc0 | reg | (r.num & 7)),
+        Rm::Mem(m) => mem_operand(code, reg, m)?,
+    }
+    if let Some((e, imm_size)) = immediate {
+        imm(code, e, imm_size, size)?;
+    }
+    Ok(())
+}
+
+// ModRM, SIB and displacement of a memory operand, `reg` is already shifted
+fn mem_operand(code: &mut Code, reg: u8, m: &Mem) -> Result<(), String> {
+    if m.rip {
+        code.bytes.push(reg | 0x05);
+        let kind = match m.modifier {
+            Modifier::GotPcRel => Kind::GotPcRel,
+            _ => Kind::Pc32,
+        };
+        return disp32(code, &m.disp, kind);
+    }
+    if m.modifier != Modifier::None {
+        return Err("`@GOTPCREL` is only for `(%rip)`".to_string());
+    }
+    let scale = match m.scale {
+        1 => 0,
+        2 => 1 << 6,
+        4 => 2 << 6,
+        _ => 3 << 6,
+    };
+    let base = match m.base {
+        Some(b) => b,
+        None => {
+            // an absolute address, with an index or without one (100b)
+            let index = m.index.map_or(4, |i| i.num & 7);
+            code.bytes.push(reg | 0x04);
+            code.bytes.push(scale | index << 3 | 0x05);
+            return disp32(code, &m.disp, Kind::Abs32S);
+        }
+    };
+    let md = match m.disp.constant() {
+        // %rbp and %r13 as base always have a displacement
+        Some(0) if base.num & 7 != 5 => 0x00,
+        Some(d) if fits_i8(d) => 0x40,
+        _ => 0x80,
+    };
+    match m.index {
+        None if base.num & 7 != 4 => code.bytes.push(md | reg | (base.num & 7)),
+        index => {
+            // %rsp and %r12 as base need the SIB byte
+            code.bytes.push(md | reg | 0x04);
+            let index = index.map_or(4, |i| i.num & 7);
+            code.bytes.push(scale | index << 3 | (base.num & 7));
+        }
+    }
+    match md {
+        0x00 => Ok(()),
+        0x40 => {
+            code.bytes.push(m.disp.value as u8);
+            Ok(())
+        }
+        _ => disp32(code, &m.disp, Kind::Abs32S),
+    }
+}
+
+fn disp32(code: &mut Code, e: &Expr, kind: Kind) -> Result<(), String> {
+    match e.constant() {
+        Some(v) if !fits_i32(v) => Err(format!("displacement {} does not fit in 32 bits", v)),
+        Some(v) if kind != Kind::GotPcRel => {
+            code.bytes.extend_from_slice(&(v as i32).to_le_bytes());
+            Ok(())
+        }
+        _ => {
+            code.fixups.push(Fixup {
+                offset: code.bytes.len(),
+                expr: e.clone(),
+                kind,
+            });
+            code.bytes.extend_from_slice(&[0; 4]);
+            Ok(())
+        }
+    }
+}
+
+// the instructions on floating values, None if `mnemonic` is none of them;
+// `movq` is one when it moves to or from an xmm register
+fn float_inst(code: &mut Code, mnemonic: &str, ops: &[Operand]) -> Option<Result<(), String>> {
+    let xmm = |op: &Operand| matches!(op, Operand::Reg(r) if r.is_xmm());
+    if let Some((_, prefix, opcode)) = SSE.iter().find(|(name, _, _)| *name == mnemonic) {
+        return Some(match ops {
+            [src, Operand::Reg(r)]
+                if r.is_xmm() && (xmm(src) || matches!(src, Operand::Mem(_))) =>
+            {
+                sse(code, *prefix, *opcode, 4, r.num, src)
+            }
+            // movss and movsd store with the next opcode
+            [Operand::Reg(r), dst @ Operand::Mem(_)] if r.is_xmm() && *opcode == 0x10 => {
+                sse(code, *prefix, 0x11, 4, r.num, dst)
+            }
+            _ => Err(format!("invalid operands for `{}`", mnemonic)),
+        });
+    }
+    if let Some((_, opcode, ext)) = X87.iter().find(|(name, _, _)| *name == mnemonic) {
+        return Some(match ops {
+            [m @ Operand::Mem(_)] => {
+                rm(m).and_then(|m| modrm(code, 4, &[*opcode], *ext, None, m, None))
+            }
+            _ => Err(format!("`{}` takes a memory operand", mnemonic)),
+        });
+    }
+    if let Some((_, kinds, bytes)) = X87_STACK.iter().find(|(name, _, _)| *name == mnemonic) {
+        let mut i = None;
+        let fits = ops.len() == kinds.len()
+            && ops.iter().zip(kinds.iter()).all(|(op, kind)| match op {
+                Operand::Reg(r) if r.is_st() && *kind == "sti" => {
+                    i = Some(r.num);
+                    true
+                }
+                Operand::Reg(r) => r.is_st() && r.num == 0 && *kind == "st",
+                _ => false,
+            });
+        if !fits {
+            return Some(Err(format!("invalid operands for `{}`", mnemonic)));
+        }
+        code.bytes
+            .extend_from_slice(&[bytes[0], bytes[1] + i.unwrap_or(0)]);
+        return Some(Ok(()));
+    }
+    // between an integer and a floating value, with an optional operand size
+    // suffix for the integer
+    let (prefix, opcode, rest) = [
+        ("cvtsi2ss", 0xf3, 0x2a),
+        ("cvtsi2sd", 0xf2, 0x2a),
+        ("cvttss2si", 0xf3, 0x2c),
+        ("cvttsd2si", 0xf2, 0x2c),
+    ]
+    .iter()
+    .find_map(|(name, prefix, opcode)| Some((*prefix, *opcode, mnemonic.strip_prefix(name)?)))
+    .unwrap_or((0, 0, mnemonic));
+    if opcode != 0 {
+        let suffix = match rest {
+            "" => None,
+            "l" => Some(4),
+            "q" => Some(8),
+            _ => return None,
+        };
+        return Some(match (opcode, ops) {
+            (0x2a, [src, Operand::Reg(r)]) if r.is_xmm() && !xmm(src) => {
+                let size = suffix.or_else(|| reg_size(src)).unwrap_or(4);
+                sse(code, prefix, opcode, size, r.num, src)
+            }
+            (0x2c, [src, Operand::Reg(r)])
+                if !r.is_xmm() && r.size >= 4 && (xmm(src) || matches!(src, Operand::Mem(_))) =>
+            {
+                sse(code, prefix, opcode, suffix.unwrap_or(r.size), r.num, src)
+            }
+            _ => Err(format!("invalid operands for `{}`", mnemonic)),
+        });
+    }
+    if mnemonic != "movq" || !ops.iter().any(xmm) {
+        return None;
+    }
+    Some(match ops {
+        [src, Operand::Reg(r)] if r.is_xmm() && (xmm(src) || matches!(src, Operand::Mem(_))) => {
+            sse(code, 0xf3, 0x7e, 4, r.num, src)
+        }
+        [Operand::Reg(r), dst @ Operand::Mem(_)] => sse(code, 0x66, 0xd6, 4, r.num, dst),
+        [src @ Operand::Reg(_), Operand::Reg(r)] if r.is_xmm() => {
+            sse(code, 0x66, 0x6e, 8, r.num, src)
+        }
+        [Operand::Reg(r), dst @ Operand::Reg(_)] => sse(code, 0x66, 0x7e, 8, r.num, dst),
+        _ => Err("invalid operands for `movq`".to_string()),
+    })
+}
+
+// `prefix` before REX, which an operand `size` of 8 takes, then 0x0f
+// `opcode` with the register `reg` and `rm`
+fn sse(
+    code: &mut Code,
+    prefix: u8,
+    opcode: u8,
+    size: u8,
+    reg: u8,
+    rm_op: &Operand,
+) -> Result<(), String> {
+    let rm = rm(rm_op)?;
+    if prefix != 0 {
+        code.bytes.push(prefix);
+    }
+    modrm(code, size, &[0x0f, opcode], reg, None, rm, None)
+}
+
+fn mov(code: &mut Code, size: u8, ops: &[Operand]) -> Result<(), String> {
+    match ops {
+        // gas picks movabs when the value needs all 64 bits
+        [Operand::Imm(e), Operand::Reg(_)]
+            if size == 8 && e.constant().is_some_and(|v| !fits_i32(v)) =>
+        {
+            movabs(code, ops)
+        }
+        // B0+r and B8+r take an immediate of the full size, which is only
+        // shorter when it is not sign extended to 64 bits
+        [Operand::Imm(e), Operand::Reg(r)] if size < 8 => {
+            if r.high && r.num >= 4 {
+                code.bytes.push(0xb0 + r.num);
+                return imm(code, e, 1, 1);
+            }
+            if size == 2 {
+                code.bytes.push(0x66);
+            }
+            if r.num & 8 != 0 || r.wants_rex() {
+                code.bytes.push(0x40 | r.num >> 3);
+            }
+            code.bytes.push(byte_or(size, 0xb0, 0xb8) + (r.num & 7));
+            imm(code, e, size, size)
+        }
+        [Operand::Imm(e), dst] => {
+            let rm = rm(dst)?;
+            modrm(
+                code,
+                size,
+                &[byte_or(size, 0xc6, 0xc7)],
+                0,
+                None,
+                rm,
+                Some((e, size.min(4))),
+            )
+        }
+        [Operand::Reg(r), dst] => modrm(
+            code,
+            size,
+            &[byte_or(size, 0x88, 0x89)],
+            r.num,
+            Some(*r),
+            rm(dst)?,
+            None,
+        ),
+        [src @ Operand::Mem(_), Operand::Reg(r)] => modrm(
+            code,
+            size,
+            &[byte_or(size, 0x8a, 0x8b)],
+            r.num,
+            Some(*r),
+            rm(src)?,
+            None,
+        ),
+        _ => Err("invalid operands for `mov`".to_string()),
+    }
+}
+
+fn movabs(code: &mut Code, ops: &[Operand]) -> Result<(), String> {
+    match ops {
+        [Operand::Imm(e), Operand::Reg(r)] if r.size == 8 => {
+            code.bytes.push(0x48 | (r.num >> 3));
+            code.bytes.push(0xb8 + (r.num & 7));
+            imm(code, e, 8, 8)
+        }
+        _ => Err("`movabs` takes an immediate and a 64-bit register".to_string()),
+    }
+}
+
+// movsx, movzx and movsxd, from a `from` byte source to a `to` byte register
+fn extend(code: &mut Code, ops: &[Operand], from: u8, to: u8, opcode: &[u8]) -> Result<(), String> {
+    match ops {
+        [src, Operand::Reg(r)] if r.size == to => {
+            if let Operand::Reg(s) = src {
+                if s.size != from {
+                    return Err("operand size mismatch for the extension".to_string());
+                }
+            }
+            modrm(code, to, opcode, r.num, Some(*r), rm(src)?, None)
+        }
+        _ => Err("invalid operands for the extension".to_string()),
+    }
+}
+
+fn push_pop(code: &mut Code, push: bool, size: u8, ops: &[Operand]) -> Result<(), String> {
+    if size != 8 {
+        return Err("only 64-bit values can be pushed and popped".to_string());
+    }
+    match ops {
+        [Operand::Reg(r)] if r.size == 8 => {
+            if r.num & 8 != 0 {
+                code.bytes.push(0x41);
+            }
+            code.bytes
+                .push(if push { 0x50 } else { 0x58 } + (r.num & 7));
+            Ok(())
+        }
+        [Operand::Imm(e)] if push => match e.constant() {
+            Some(v) if fits_i8(v) => {
+                code.bytes.extend_from_slice(&[0x6a, v as u8]);
+                Ok(())
+            }
+            _ => {
+                code.bytes.push(0x68);
+                imm(code, e, 4, 8)
+            }
+        },
+        // the default operand size of push and pop is 64 bits, no REX.W
+        [dst @ Operand::Mem(_)] => {
+            if push {
+                modrm(code, 4, &[0xff], 6, None, rm(dst)?, None)
+            } else {
+                modrm(code, 4, &[0x8f], 0, None, rm(dst)?, None)
+            }
+        }
+        _ => Err("invalid operand for `push` or `pop`".to_string()),
+    }
+}
+
+fn alu(code: &mut Code, ext: u8, size: u8, ops: &[Operand]) -> Result<(), String> {
+    match ops {
+        [Operand::Imm(e), dst] => {
+            let rm = rm(dst)?;
+            let small = e
+                .constant()
+                .and_then(|v| as_signed(v, size))
+                .is_some_and(fits_i8);
+            if let Rm::Reg(Reg { num: 0, .. }) = rm {
+                // %al, %ax, %eax and %rax have a form one byte shorter
+                if size == 1 || !small {
+                    if size == 2 {
+                        code.bytes.push(0x66);
+                    }
+                    if size == 8 {
+                        code.bytes.push(0x48);
+                    }
+                    code.bytes.push(ext * 8 + byte_or(size, 4, 5));
+                    return imm(code, e, size.min(4), size);
+                }
+            }
+            if size == 1 {
+                return modrm(code, size, &[0x80], ext, None, rm, Some((e, 1)));
+            }
+            match e.constant().and_then(|v| as_signed(v, size)) {
+                Some(v) if fits_i8(v) => modrm(
+                    code,
+                    size,
+                    &[0x83],
+                    ext,
+                    None,
+                    rm,
+                    Some((&Expr::number(v), 1)),
+                ),
+                _ => modrm(code, size, &[0x81], ext, None, rm, Some((e, size.min(4)))),
+            }
+        }
+        [Operand::Reg(r), dst] => modrm(
+            code,
+            size,
+            &[ext * 8 + byte_or(size, 0, 1)],
+            r.num,
+            Some(*r),
+            rm(dst)?,
+            None,
+        ),
+        [src @ Operand::Mem(_), Operand::Reg(r)] => modrm(
+            code,
+            size,
+            &[ext * 8 + byte_or(size, 2, 3)],
+            r.num,
+            Some(*r),
+            rm(src)?,
+            None,
+        ),
+        _ => Err("invalid operands".to_string()),
+    }
+}
+
+// `v` as the signed value of a `size` byte operand, `0xffffffff` is -1 to a
+// 32-bit operation
+fn as_signed(v: i64, size: u8) -> Option<i64> {
+    match size {
+        2 if (0x8000..=0xffff).contains(&v) => Some(v - 0x1_0000),
+        4 if (0x8000_0000..=0xffff_ffff).contains(&v) => Some(v - 0x1_0000_0000),
+        _ => Some(v),
+    }
+}
+
+fn imul(code: &mut Code, size: u8, ops: &[Operand]) -> Result<(), String> {
+    let three = |code: &mut Code, e: &Expr, src: &Operand, dst: &Reg| match e.constant() {
+        Some(v) if fits_i8(v) => modrm(
+            code,
+            size,
+            &[0x6b],
+            dst.num,
+            Some(*dst),
+            rm(src)?,
+            Some((e, 1)),
+        ),
+        _ => modrm(
+            code,
+            size,
+            &[0x69],
+            dst.num,
+            Some(*dst),
+            rm(src)?,
+            Some((e, size.min(4))),
+        ),
+    };
+    match ops {
+        [src] => modrm(
+            code,
+            size,
+            &[byte_or(size, 0xf6, 0xf7)],
+            5,
+            None,
+            rm(src)?,
+            None,
+        ),
+        _ if size == 1 => Err("`imul` with more operands has no 8-bit form".to_string()),
+        [Operand::Imm(e), dst @ Operand::Reg(r)] => three(code, e, dst, r),
+        [src, Operand::Reg(r)] => modrm(code, size, &[0x0f, 0xaf], r.num, Some(*r), rm(src)?, None),
+        [Operand::Imm(e), src, Operand::Reg(r)] => three(code, e, src, r),
+        _ => Err("invalid operands for `imul`".to_string()),
+    }
+}
+
+fn setcc(code: &mut Code, ops: &[Operand], cc: u8) -> Result<(), String> {
+    match ops {
+        [dst] if reg_size(dst).is_none_or(|s| s == 1) => {
+            modrm(code, 1, &[0x0f, 0x90 | cc], 0, None, rm(dst)?, None)
+        }
+        _ => Err("`set` takes an 8-bit register or memory".to_string()),
+    }
+}
+
+fn cmov(code: &mut Code, ops: &[Operand], cc: u8) -> Result<(), String> {
+    match ops {
+        [src, Operand::Reg(r)] if r.size > 1 => modrm(
+            code,
+            r.size,
+            &[0x0f, 0x40 | cc],
+            r.num,
+            Some(*r),
+            rm(src)?,
+            None,
+        ),
+        _ => Err("`cmov` takes a register or memory and a register".to_string()),
+    }
+}
+
+// a jump with a 32-bit displacement, they are never relaxed to 8 bits
+fn jump(code: &mut Code, ops: &[Operand], cc: Option<u8>) -> Result<(), String> {
+    match (ops, cc) {
+        ([Operand::Star(target)], None) => modrm(code, 4, &[0xff], 4, None, rm(target)?, None),
+        ([Operand::Mem(m)], _) if m.is_bare() => {
+            match cc {
+                Some(cc) => code.bytes.extend_from_slice(&[0x0f, 0x80 | cc]),
+                None => code.bytes.push(0xe9),
+            }
+            branch(code, m);
+            Ok(())
+        }
+        _ => Err("a jump takes a label, or `*` and the register or memory to jump to".to_string()),
+    }
+}
+
+fn call(code: &mut Code, ops: &[Operand]) -> Result<(), String> {
+    match ops {
+        [Operand::Star(target)] => modrm(code, 4, &[0xff], 2, None, rm(target)?, None),
+        [Operand::Mem(m)] if m.is_bare() => {
+            code.bytes.push(0xe8);
+            branch(code, m);
+            Ok(())
+        }
+        _ => Err("`call` takes a function, or `*` and the register or memory to call".to_string()),
+    }
+}
+
+// the rel32 of a direct jump or call; a call goes through the PLT as gas
+// does it, the linker makes it direct when it can
+fn branch(code: &mut Code, m: &Mem) {
+    let call = code.bytes.last() == Some(&0xe8);
+    let kind = if m.modifier == Modifier::Plt || call {
+        Kind::Plt32
+    } else {
+        Kind::Pc32
+    };
+    code.fixups.push(Fixup {
+        offset: code.bytes.len(),
+        expr: m.disp.clone(),
+        kind,
+    });
+    code.bytes.extend_from_slice(&[0; 4]);
+}
@@ -0,0 +1,1017 @@
+pub mod elf;
+mod encode;
+mod operand;
+
+use self::elf::Rela;
+use self::encode::{Fixup, Kind};
+use self::operand::{Expr, Modifier, Operand};
+use crate::diagnostic::{code, Diagnostic};
+use crate::loc::Loc;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// The assembler for the AT&T syntax crust writes: the general purpose
+// instructions gen emits and the directives around them, turned into an ELF
+// relocatable object in one pass.  Jumps always take a 32-bit displacement,
+// so every instruction has its final size and address once it is read; what
+// refers to a label further down is left as a fixup and resolved at the end,
+// either in place or as a relocation for the linker.
+
+pub fn assemble(src: &str, file: &str) -> Result<Vec<u8>, Diagnostic> {
+    let mut asm = Assembler::new(file);
+    for (n, line) in src.lines().enumerate() {
+        asm.line = n + 1;
+        for stmt in statements(line) {
+            asm.statement(stmt).map_err(|msg| asm.error(&msg))?;
+        }
+    }
+    asm.finish()
+}
+
+struct Symbol {
+    section: Option<usize>, // None while it is not defined
+    value: u64,
+    global: bool,
+    local: bool, // made local with `.local`, a `.comm` of it goes in .bss
+    kind: u8,
+    size: u64,
+    common: Option<u64>, // the alignment of a `.comm` symbol
+}
+
+// a value the assembler can only fill in when all the labels are known
+struct Pending {
+    section: usize,
+    offset: usize,
+    expr: Expr,
+    kind: Kind,
+    line: usize,
+}
+
+// what a relocation is against, until the symbol table has its final order
+enum Target {
+    Section(usize),
+    Symbol(String),
+}
+
+// a function between .cfi_startproc and .cfi_endproc
+struct Frame {
+    section: usize,
+    label: String,
+    start: usize,
+    end: usize,
+    insts: Vec<(usize, Vec<u8>)>, // call frame instructions at an offset in the section
+}
+
+struct Assembler {
+    file: Rc<str>,
+    line: usize,
+    sections: Vec<elf::Section>,
+    current: usize,
+    symbols: HashMap<String, Symbol>,
+    order: Vec<String>, // the symbols in the order they are first mentioned
+    pending: Vec<Pending>,
+    frames: Vec<Frame>,
+    open_frame: Option<Frame>,
+    source: Option<String>, // from .file
+    temps: usize,
+}
+
+impl Assembler {
+    fn new(file: &str) -> Assembler {
+        let mut asm = Assembler {
+            file: Rc::from(file),
+            line: 0,
+            sections: Vec::new(),
+            current: 0,
+            symbols: HashMap::new(),
+            order: Vec::new(),
+            pending: Vec::new(),
+            frames: Vec::new(),
+            open_frame: None,
+            source: None,
+            temps: 0,
+        };
+        // gas always has these three
+        for name in [".text", ".data", ".bss"].iter() {
+            asm.switch_section(name, None);
+        }
+        asm.current = 0;
+        asm
+    }
+
+    fn error(&self, msg: &str) -> Diagnostic {
+        self.error_at(self.line, msg)
+    }
+
+    fn error_at(&self, line: usize, msg: &str) -> Diagnostic {
+        let loc = Loc::new(self.file.clone(), line, 0, (0, 0));
+        Diagnostic::error(code::ASM, &loc, msg)
+    }
+
+    fn offset(&self) -> usize {
+        self.sections[self.current].data.len()
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.sections[self.current].data.extend_from_slice(bytes);
+    }
+
+    fn symbol(&mut self, name: &str) -> &mut Symbol {
+        if !self.symbols.contains_key(name) {
+            self.order.push(name.to_string());
+            self.symbols.insert(
+                name.to_string(),
+                Symbol {
+                    section: None,
+                    value: 0,
+                    global: false,
+                    local: false,
+                    kind: elf::STT_NOTYPE,
+                    size: 0,
+                    common: None,
+                },
+            );
+        }
+        self.symbols.get_mut(name).unwrap()
+    }
+
+    fn define(&mut self, name: &str) -> Result<(), String> {
+        let (section, value) = (self.current, self.offset() as u64);
+        let sym = self.symbol(name);
+        if sym.section.is_some() || sym.common.is_some() {
+            return Err(format!("symbol `{}` is already defined", name));
+        }
+        sym.section = Some(section);
+        sym.value = value;
+        Ok(())
+    }
+
+    // a new local label at the current location, which is what `.` means
+    fn here(&mut self) -> String {
+        let name = format!(".Ltmp{}", self.temps);
+        self.temps += 1;
+        self.define(&name).unwrap();
+        name
+    }
+
+    fn replace_dot(&mut self, e: &mut Expr) {
+        if e.sym.as_deref() == Some(".") {
+            e.sym = Some(self.here());
+        }
+        if e.minus.as_deref() == Some(".") {
+            e.minus = Some(self.here());
+        }
+    }
+
+    // an expression the assembler must know right away, like the size of a function
+    fn eval(&mut self, e: &Expr) -> Result<i64, String> {
+        let mut e = e.clone();
+        self.replace_dot(&mut e);
+        let mut value = e.value;
+        match (&e.sym, &e.minus) {
+            (None, None) => {}
+            (Some(a), Some(b)) => {
+                let (a, b) = (self.lookup(a)?, self.lookup(b)?);
+                if a.0 != b.0 {
+                    return Err("the symbols are in different sections".to_string());
+                }
+                value = value.wrapping_add(a.1 as i64 - b.1 as i64);
+            }
+            _ => return Err("expected a constant or the difference of two symbols".to_string()),
+        }
+        Ok(value)
+    }
+
+    // the section and value of a symbol that is already defined
+    fn lookup(&self, name: &str) -> Result<(usize, u64), String> {
+        match self.symbols.get(name) {
+            Some(Symbol {
+                section: Some(s),
+                value,
+                ..
+            }) => Ok((*s, *value)),
+            _ => Err(format!("symbol `{}` is not defined", name)),
+        }
+    }
+
+    fn add_fixup(&mut self, base: usize, fixup: Fixup) {
+        if let Some(sym) = &fixup.expr.sym {
+            self.symbol(sym);
+        }
+        self.pending.push(Pending {
+            section: self.current,
+            offset: base + fixup.offset,
+            expr: fixup.expr,
+            kind: fixup.kind,
+            line: self.line,
+        });
+    }
+
+    fn statement(&mut self, stmt: &str) -> Result<(), String> {
+        let mut stmt = stmt.trim();
+        while let Some(i) = label_end(stmt) {
+            self.define(&stmt[..i])?;
+            stmt = stmt[i + 1..].trim_start();
+        }
+        if stmt.is_empty() {
+            return Ok(());
+        }
+        let (head, rest) = match stmt.find(char::is_whitespace) {
+            Some(i) => (&stmt[..i], stmt[i..].trim()),
+            None => (stmt, ""),
+        };
+        if head.starts_with('.') {
+            self.directive(head, rest)
+        } else {
+            self.instruction(head, rest)
+        }
+    }
+
+    fn instruction(&mut self, mnemonic: &str, rest: &str) -> Result<(), String> {
+        if self.sections[self.current].sh_type == elf::SHT_NOBITS {
+            return Err(format!(
+                "instruction in `{}`",
+                self.sections[self.current].name
+            ));
+        }
+        let mut ops = operand::parse_operands(rest)?;
+        for op in ops.iter_mut() {
+            let op = match op {
+                Operand::Star(inner) => inner.as_mut(),
+                op => op,
+            };
+            match op {
+                Operand::Imm(e) => self.replace_dot(e),
+                Operand::Mem(m) => self.replace_dot(&mut m.disp),
+                _ => {}
+            }
+        }
+        let code = encode::encode(mnemonic, &ops)?;
+        let base = self.offset();
+        self.emit(&code.bytes);
+        for fixup in code.fixups {
+            self.add_fixup(base, fixup);
+        }
+        Ok(())
+    }
+
+    fn switch_section(&mut self, name: &str, attrs: Option<(u64, u32, u64)>) {
+        let (flags, sh_type, entsize) = attrs.unwrap_or_else(|| default_attrs(name));
+        self.current = match self.sections.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.sections.push(elf::Section {
+                    name: name.to_string(),
+                    sh_type,
+                    flags,
+                    data: Vec::new(),
+                    align: 1,
+                    entsize,
+                    relocs: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        };
+    }
+
+    fn align(&mut self, align: u64, fill: Option<u8>) -> Result<(), String> {
+        if align == 0 || !align.is_power_of_two() {
+            return Err(format!("alignment {} is not a power of 2", align));
+        }
+        let section = &mut self.sections[self.current];
+        section.align = section.align.max(align);
+        // a NOP in code, in case the padding is ever run
+        let fill = fill.unwrap_or(if section.flags & elf::SHF_EXECINSTR != 0 {
+            0x90
+        } else {
+            0
+        });
+        while !(section.data.len() as u64).is_multiple_of(align) {
+            section.data.push(fill);
+        }
+        Ok(())
+    }
+
+    // .byte, .value, .long and .quad, numbers or symbols of `size` bytes
+    fn data(&mut self, size: usize, rest: &str) -> Result<(), String> {
+        for arg in args(rest) {
+            let (mut e, modifier) = operand::parse_expr(arg)?;
+            if modifier != Modifier::None {
+                return Err(format!("`@` is not allowed in data: `{}`", arg));
+            }
+            self.replace_dot(&mut e);
+            match e.constant() {
+                Some(v) => {
+                    let fits = size == 8 || (v >= -(1 << (size * 8 - 1)) && v < 1 << (size * 8));
+                    if !fits {
+                        return Err(format!("value {} does not fit in {} bytes", v, size));
+                    }
+                    self.emit(&v.to_le_bytes()[..size]);
+                }
+                None => {
+                    let kind = match size {
+                        8 => Kind::Abs64,
+                        4 => Kind::Abs32,
+                        _ => return Err(format!("a symbol does not fit in {} bytes", size)),
+                    };
+                    let base = self.offset();
+                    self.emit(&vec![0; size]);
+                    self.add_fixup(
+                        base,
+                        Fixup {
+                            offset: 0,
+                            expr: e,
+                            kind,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, rest: &str) -> Result<(), String> {
+        let args = args(rest);
+        let number = |i: usize| -> Result<i64, String> {
+            let arg = args
+                .get(i)
+                .ok_or_else(|| format!("`{}` needs more arguments", name))?;
+            match operand::parse_expr(arg)? {
+                (e, Modifier::None) => e
+                    .constant()
+                    .ok_or_else(|| format!("`{}` is not a number", arg)),
+                _ => Err(format!("`{}` is not a number", arg)),
+            }
+        };
+        let symbol = |i: usize| -> Result<&str, String> {
+            match args.get(i) {
+                Some(s) if is_symbol(s) => Ok(s),
+                Some(s) => Err(format!("`{}` is not a symbol", s)),
+                None => Err(format!("`{}` needs a symbol", name)),
+            }
+        };
+        match name {
+            ".text" | ".data" | ".bss" => self.switch_section(name, None),
+            ".section" => {
+                // section names may have a `-`, like .note.GNU-stack
+                let section = match args.first() {
+                    Some(s) if is_symbol(&s.replace('-', "_")) => s,
+                    _ => return Err("`.section` needs a name".to_string()),
+                };
+                let attrs = match args.get(1) {
+                    Some(flags) => Some(section_attrs(flags, args.get(2), args.get(3))?),
+                    None => None,
+                };
+                self.switch_section(section, attrs);
+            }
+            ".globl" | ".global" => {
+                for i in 0..args.len() {
+                    let name = symbol(i)?;
+                    let s = self.symbol(name);
+                    s.global = true;
+                    s.local = false;
+                }
+            }
+            ".local" => {
+                for i in 0..args.len() {
+                    let name = symbol(i)?;
+                    let s = self.symbol(name);
+                    s.global = false;
+                    s.local = true;
+                }
+            }
+            ".type" => {
+                let kind = match args.get(1).map(|t| t.trim_start_matches(['@', '%'])) {
+                    Some("function") => elf::STT_FUNC,
+                    Some("object") => elf::STT_OBJECT,
+                    Some("notype") => elf::STT_NOTYPE,
+                    _ => {
+                        return Err(
+                            "the type must be `@function`, `@object` or `@notype`".to_string()
+                        )
+                    }
+                };
+                let name = symbol(0)?;
+                self.symbol(name).kind = kind;
+            }
+            ".size" => {
+                let name = symbol(0)?;
+                let arg = args
+                    .get(1)
+                    .ok_or_else(|| "`.size` needs a size".to_string())?;
+                let (e, _) = operand::parse_expr(arg)?;
+                let size = self.eval(&e)?;
+                self.symbol(name).size = size as u64;
+            }
+            ".comm" | ".lcomm" => {
+                let sym = symbol(0)?;
+                let size = number(1)? as u64;
+                let align = if args.len() > 2 { number(2)? as u64 } else { 1 };
+                if name == ".lcomm" || self.symbol(sym).local {
+                    let current = self.current;
+                    self.switch_section(".bss", None);
+                    self.align(align, None)?;
+                    self.define(sym)?;
+                    self.emit(&vec![0; size as usize]);
+                    let s = self.symbol(sym);
+                    s.size = size;
+                    s.kind = elf::STT_OBJECT;
+                    self.current = current;
+                } else {
+                    let s = self.symbol(sym);
+                    if s.section.is_some() {
+                        return Err(format!("symbol `{}` is already defined", sym));
+                    }
+                    s.common = Some(align.max(s.common.unwrap_or(1)));
+                    s.size = size.max(s.size);
+                    s.global = true;
+                    s.kind = elf::STT_OBJECT;
+                }
+            }
+            // on x86-64 ELF `.align` counts bytes, like `.balign`
+            ".align" | ".balign" | ".p2align" => {
+                let n = number(0)?;
+                let fill = if args.len() > 1 {
+                    Some(number(1)? as u8)
+                } else {
+                    None
+                };
+                if !(0..64).contains(&n) || (name != ".p2align" && n == 0) {
+                    return Err(format!("bad alignment {}", n));
+                }
+                let align = if name == ".p2align" { 1 << n } else { n as u64 };
+                self.align(align, fill)?;
+            }
+            ".byte" => self.data(1, rest)?,
+            ".value" | ".short" | ".word" | ".2byte" => self.data(2, rest)?,
+            ".long" | ".int" | ".4byte" => self.data(4, rest)?,
+            ".quad" | ".8byte" => self.data(8, rest)?,
+            ".zero" | ".skip" | ".space" => {
+                let n = number(0)?;
+                let fill = if args.len() > 1 { number(1)? as u8 } else { 0 };
+                if n < 0 {
+                    return Err(format!("can not skip {} bytes", n));
+                }
+                self.emit(&vec![fill; n as usize]);
+            }
+            ".string" | ".asciz" | ".ascii" => {
+                for arg in args.iter() {
+                    let mut bytes = parse_string(arg)?;
+                    if name != ".ascii" {
+                        bytes.push(0);
+                    }
+                    self.emit(&bytes);
+                }
+            }
+            ".file" => {
+                let file = args
+                    .first()
+                    .ok_or_else(|| "`.file` needs a name".to_string())?;
+                self.source = Some(String::from_utf8_lossy(&parse_string(file)?).into_owned());
+            }
+            ".ident" => {
+                let arg = args
+                    .first()
+                    .ok_or_else(|| "`.ident` needs a string".to_string())?;
+                let mut bytes = parse_string(arg)?;
+                bytes.push(0);
+                let current = self.current;
+                self.switch_section(".comment", None);
+                if self.offset() == 0 {
+                    self.emit(&[0]);
+                }
+                self.emit(&bytes);
+                self.current = current;
+            }
+            name if name.starts_with(".cfi_") => self.cfi(name, &args)?,
+            _ => return Err(format!("unknown directive `{}`", name)),
+        }
+        Ok(())
+    }
+
+    // the call frame information that ends up in .eh_frame
+    fn cfi(&mut self, name: &str, args: &[&str]) -> Result<(), String> {
+        let reg = |i: usize| -> Result<u64, String> {
+            let arg = args
+                .get(i)
+                .ok_or_else(|| format!("`{}` needs a register", name))?;
+            dwarf_reg(arg).ok_or_else(|| format!("`{}` is not a register", arg))
+        };
+        let number = |i: usize| -> Result<i64, String> {
+            let arg = args
+                .get(i)
+                .ok_or_else(|| format!("`{}` needs an offset", name))?;
+            match operand::parse_expr(arg)? {
+                (e, Modifier::None) if e.constant().is_some() => Ok(e.value),
+                _ => Err(format!("`{}` is not a number", arg)),
+            }
+        };
+        if name == ".cfi_startproc" {
+            if self.open_frame.is_some() {
+                return Err("`.cfi_startproc` inside another one".to_string());
+            }
+            let label = self.here();
+            self.open_frame = Some(Frame {
+                section: self.current,
+                label,
+                start: self.offset(),
+                end: 0,
+                insts: Vec::new(),
+            });
+            return Ok(());
+        }
+        let offset = self.offset();
+        let frame = match self.open_frame.as_mut() {
+            Some(frame) if frame.section == self.current => frame,
+            Some(_) => return Err(format!("`{}` in another section", name)),
+            None => return Err(format!("`{}` without `.cfi_startproc`", name)),
+        };
+        let mut inst = Vec::new();
+        match name {
+            ".cfi_endproc" => {
+                frame.end = offset;
+                let frame = self.open_frame.take().unwrap();
+                self.frames.push(frame);
+                return Ok(());
+            }
+            ".cfi_def_cfa" => {
+                inst.push(0x0c);
+                uleb128(&mut inst, reg(0)?);
+                uleb128(&mut inst, number(1)? as u64);
+            }
+            ".cfi_def_cfa_offset" => {
+                inst.push(0x0e);
+                uleb128(&mut inst, number(0)? as u64);
+            }
+            ".cfi_def_cfa_register" => {
+                inst.push(0x0d);
+                uleb128(&mut inst, reg(0)?);
+            }
+            ".cfi_offset" => {
+                // the offset from the CFA, in units of the data alignment -8
+                let (reg, off) = (reg(0)?, number(1)?);
+                if off % 8 != 0 || off > 0 {
+                    return Err(format!("offset {} is not a negative multiple of 8", off));
+                }
+                if reg < 64 {
+                    inst.push(0x80 | reg as u8);
+                } else {
+                    inst.push(0x05);
+                    uleb128(&mut inst, reg);
+                }
+                uleb128(&mut inst, (off / -8) as u64);
+            }
+            _ => return Err(format!("unknown directive `{}`", name)),
+        }
+        frame.insts.push((offset, inst));
+        Ok(())
+    }
+
+    // .eh_frame: one CIE that all the functions share, and an FDE for each
+    fn eh_frame(&mut self) {
+        let frames = std::mem::take(&mut self.frames);
+        self.switch_section(".eh_frame", None);
+        self.sections[self.current].align = 8;
+        // the CFA is %rsp + 8 and the return address is right below it
+        self.emit(&[
+            0x14, 0, 0, 0, // length
+            0, 0, 0, 0, // CIE id
+            1, // version
+            b'z', b'R', 0,    // augmentation
+            1,    // code alignment factor
+            0x78, // data alignment factor -8
+            16,   // return address column, %rip
+            1,    // augmentation data length
+            0x1b, // FDE pointers are 4 byte signed and pc-relative
+            0x0c, 7, 8, // DW_CFA_def_cfa %rsp, 8
+            0x90, 1, // DW_CFA_offset %rip, cfa-8
+            0, 0, // DW_CFA_nop
+        ]);
+        let count = frames.len();
+        for (n, frame) in frames.into_iter().enumerate() {
+            let start = self.offset();
+            let mut insts = Vec::new();
+            let mut loc = frame.start;
+            for (offset, inst) in frame.insts {
+                let delta = offset - loc;
+                if delta > 0 {
+                    if delta < 0x40 {
+                        insts.push(0x40 | delta as u8);
+                    } else if delta <= 0xff {
+                        insts.extend_from_slice(&[0x02, delta as u8]);
+                    } else if delta <= 0xffff {
+                        insts.push(0x03);
+                        insts.extend_from_slice(&(delta as u16).to_le_bytes());
+                    } else {
+                        insts.push(0x04);
+                        insts.extend_from_slice(&(delta as u32).to_le_bytes());
+                    }
+                }
+                insts.extend_from_slice(&inst);
+                loc = offset;
+            }
+            // length, CIE pointer, start, size, augmentation data length;
+            // like gas, each FDE is padded to 4 bytes and the last one so
+            // that the section ends aligned to 8
+            let mut length = 4 + 4 + 4 + 1 + insts.len();
+            let align = if n + 1 == count { 8 } else { 4 };
+            while !(start + 4 + length).is_multiple_of(align) {
+                insts.push(0); // DW_CFA_nop
+                length += 1;
+            }
+            self.emit(&(length as u32).to_le_bytes());
+            self.emit(&((start + 4) as u32).to_le_bytes());
+            self.add_fixup(
+                self.offset(),
+                Fixup {
+                    offset: 0,
+                    expr: Expr {
+                        sym: Some(frame.label),
+                        minus: None,
+                        value: 0,
+                    },
+                    kind: Kind::Pc32,
+                },
+            );
+            self.emit(&[0; 4]);
+            self.emit(&((frame.end - frame.start) as u32).to_le_bytes());
+            self.emit(&[0]);
+            self.emit(&insts);
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, Diagnostic> {
+        if self.open_frame.is_some() {
+            return Err(self.error("missing `.cfi_endproc` at the end of the file"));
+        }
+        if !self.frames.is_empty() {
+            self.eh_frame();
+        }
+
+        let mut relocs = Vec::new();
+        for p in std::mem::take(&mut self.pending) {
+            let reloc = self
+                .resolve(&p)
+                .map_err(|msg| self.error_at(p.line, &msg))?;
+            if let Some((target, kind, addend)) = reloc {
+                relocs.push((p.section, p.offset, target, kind, addend));
+            }
+        }
+
+        // the null symbol, the file, the sections, the other local symbols
+        // and then the global ones
+        let mut symbols = vec![elf::Symbol {
+            name: String::new(),
+            bind: elf::STB_LOCAL,
+            kind: elf::STT_NOTYPE,
+            shndx: elf::SHN_UNDEF,
+            value: 0,
+            size: 0,
+        }];
+        if let Some(source) = &self.source {
+            symbols.push(elf::Symbol {
+                name: source.clone(),
+                bind: elf::STB_LOCAL,
+                kind: elf::STT_FILE,
+                shndx: elf::SHN_ABS,
+                value: 0,
+                size: 0,
+            });
+        }
+        // only the sections that relocations are against need a symbol
+        let mut section_syms = HashMap::new();
+        for i in 0..self.sections.len() {
+            if !relocs
+                .iter()
+                .any(|r| matches!(r.2, Target::Section(s) if s == i))
+            {
+                continue;
+            }
+            section_syms.insert(i, symbols.len());
+            symbols.push(elf::Symbol {
+                name: String::new(),
+                bind: elf::STB_LOCAL,
+                kind: elf::STT_SECTION,
+                shndx: i as u16 + 1,
+                value: 0,
+                size: 0,
+            });
+        }
+        let mut index = HashMap::new();
+        let mut globals = Vec::new();
+        for name in self.order.iter() {
+            let sym = &self.symbols[name];
+            let global = sym.global || (sym.section.is_none() && sym.common.is_none());
+            if !global && name.starts_with(".L") {
+                continue;
+            }
+            let shndx = match (sym.section, sym.common) {
+                (Some(s), _) => s as u16 + 1,
+                (None, Some(_)) => elf::SHN_COMMON,
+                (None, None) => elf::SHN_UNDEF,
+            };
+            let entry = elf::Symbol {
+                name: name.clone(),
+                bind: if global {
+                    elf::STB_GLOBAL
+                } else {
+                    elf::STB_LOCAL
+                },
+                kind: sym.kind,
+                shndx,
+                value: sym.common.unwrap_or(sym.value),
+                size: sym.size,
+            };
+            if global {
+                globals.push((name, entry));
+            } else {
+                index.insert(name, symbols.len());
+                symbols.push(entry);
+            }
+        }
+        let first_global = symbols.len();
+        for (name, entry) in globals {
+            index.insert(name, symbols.len());
+            symbols.push(entry);
+        }
+
+        for (section, offset, target, kind, addend) in relocs {
+            let sym = match target {
+                Target::Section(s) => section_syms[&s],
+                Target::Symbol(name) => index[&name],
+            };
+            let kind = match kind {
+                Kind::Abs64 => elf::R_X86_64_64,
+                Kind::Abs32 => elf::R_X86_64_32,
+                Kind::Abs32S => elf::R_X86_64_32S,
+                Kind::Pc32 => elf::R_X86_64_PC32,
+                Kind::Plt32 => elf::R_X86_64_PLT32,
+                Kind::GotPcRel => elf::R_X86_64_GOTPCREL,
+            };
+            self.sections[section].relocs.push(Rela {
+                offset: offset as u64,
+                sym,
+                kind,
+                addend,
+            });
+        }
+        Ok(elf::write(&self.sections, &symbols, first_global))
+    }
+
+    // fill in a fixup, or say what relocation it needs
+    fn resolve(&mut self, p: &Pending) -> Result<Option<(Target, Kind, i64)>, String> {
+        let mut kind = p.kind;
+        let mut addend = p.expr.value;
+        let name = match (&p.expr.sym, &p.expr.minus) {
+            (Some(a), Some(b)) => {
+                let b = self.lookup(b)?;
+                match self.lookup(a) {
+                    // the distance between two labels in one section
+                    Ok(a) if a.0 == b.0 => {
+                        self.patch(p, addend + a.1 as i64 - b.1 as i64)?;
+                        return Ok(None);
+                    }
+                    // `sym - label` where the label is in this section is
+                    // `sym` relative to the place of the value, like in a jump table
+                    _ if b.0 == p.section && p.kind == Kind::Abs32 => {
+                        kind = Kind::Pc32;
+                        addend += p.offset as i64 - b.1 as i64;
+                        a
+                    }
+                    _ => return Err("can not subtract symbols of different sections".to_string()),
+                }
+            }
+            (Some(a), None) => a,
+            _ => return Err("a symbol can not be negated".to_string()),
+        };
+        let sym = &self.symbols[name];
+        match sym.section {
+            Some(section) if !sym.global && kind != Kind::GotPcRel => {
+                if section == p.section && kind.is_pc_relative() {
+                    let value = addend + sym.value as i64 - p.offset as i64;
+                    self.patch(p, value)?;
+                    return Ok(None);
+                }
+                // local symbols are not in the symbol table, their sections are
+                Ok(Some((
+                    Target::Section(section),
+                    kind,
+                    addend + sym.value as i64,
+                )))
+            }
+            None if name.starts_with(".L") && !sym.global && sym.common.is_none() => {
+                Err(format!("undefined label `{}`", name))
+            }
+            _ => Ok(Some((Target::Symbol(name.clone()), kind, addend))),
+        }
+    }
+
+    fn patch(&mut self, p: &Pending, value: i64) -> Result<(), String> {
+        let size = p.kind.size();
+        let fits = match p.kind {
+            Kind::Abs64 => true,
+            Kind::Abs32 => value >= i64::from(i32::MIN) && value <= i64::from(u32::MAX),
+            _ => value >= i64::from(i32::MIN) && value <= i64::from(i32::MAX),
+        };
+        if !fits {
+            return Err(format!("value {} does not fit in {} bytes", value, size));
+        }
+        self.sections[p.section].data[p.offset..p.offset + size]
+            .copy_from_slice(&value.to_le_bytes()[..size]);
+        Ok(())
+    }
+}
+
+// the statements of a line: split at `;` and without the `#` comment
+fn statements(line: &str) -> Vec<&str> {
+    let mut stmts = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                stmts.push(&line[start..i]);
+                start = i + 1;
+            }
+            '#' if !in_string => {
+                stmts.push(&line[start..i]);
+                return stmts;
+            }
+            _ => {}
+        }
+    }
+    stmts.push(&line[start..]);
+    stmts
+}
+
+fn is_symbol(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+// the `:` after a label at the start of a statement
+fn label_end(stmt: &str) -> Option<usize> {
+    let i = stmt.find(':')?;
+    if is_symbol(&stmt[..i]) {
+        Some(i)
+    } else {
+        None
+    }
+}
+
+// the arguments of a directive, split at the commas outside strings
+fn args(rest: &str) -> Vec<&str> {
+    if rest.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut args = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                args.push(rest[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(rest[start..].trim());
+    args
+}
+
+// a string in double quotes, with the escapes of C
+fn parse_string(s: &str) -> Result<Vec<u8>, String> {
+    let inner = match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return Err(format!("expected a string in double quotes: `{}`", s)),
+    };
+    let mut bytes = Vec::new();
+    let mut it = inner.bytes().peekable();
+    while let Some(b) = it.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let c = it
+            .next()
+            .ok_or_else(|| format!("`\\` at the end of `{}`", s))?;
+        bytes.push(match c {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'v' => 0x0b,
+            b'0'..=b'7' => {
+                let mut v = u32::from(c - b'0');
+                for _ in 0..2 {
+                    match it.peek() {
+                        Some(d @ b'0'..=b'7') => {
+                            v = v * 8 + u32::from(d - b'0');
+                            it.next();
+                        }
+                        _ => break,
+                    }
+                }
+                v as u8
+            }
+            b'x' => {
+                let mut v = 0u32;
+                while let Some(d) = it.peek().and_then(|d| (*d as char).to_digit(16)) {
+                    v = v * 16 + d;
+                    it.next();
+                }
+                v as u8
+            }
+            c => c,
+        });
+    }
+    Ok(bytes)
+}
+
+// flags, type and entry size of the sections gas knows by name
+fn default_attrs(name: &str) -> (u64, u32, u64) {
+    let alloc = elf::SHF_ALLOC;
+    match name {
+        ".text" => (alloc | elf::SHF_EXECINSTR, elf::SHT_PROGBITS, 0),
+        ".data" => (alloc | elf::SHF_WRITE, elf::SHT_PROGBITS, 0),
+        ".bss" => (alloc | elf::SHF_WRITE, elf::SHT_NOBITS, 0),
+        ".comment" => (elf::SHF_MERGE | elf::SHF_STRINGS, elf::SHT_PROGBITS, 1),
+        ".eh_frame" => (alloc, elf::SHT_X86_64_UNWIND, 0),
+        _ if name.starts_with(".text.") => (alloc | elf::SHF_EXECINSTR, elf::SHT_PROGBITS, 0),
+        _ if name.starts_with(".data.") => (alloc | elf::SHF_WRITE, elf::SHT_PROGBITS, 0),
+        _ if name.starts_with(".bss.") => (alloc | elf::SHF_WRITE, elf::SHT_NOBITS, 0),
+        _ if name.starts_with(".rodata") => (alloc, elf::SHT_PROGBITS, 0),
+        _ if name.starts_with(".note") => (0, elf::SHT_NOTE, 0),
+        _ => (0, elf::SHT_PROGBITS, 0),
+    }
+}
+
+// `.section name, "flags", @type, entsize`
+fn section_attrs(
+    flags: &str,
+    sh_type: Option<&&str>,
+    entsize: Option<&&str>,
+) -> Result<(u64, u32, u64), String> {
+    let mut bits = 0;
+    for c in parse_string(flags)? {
+        bits |= match c {
+            b'a' => elf::SHF_ALLOC,
+            b'w' => elf::SHF_WRITE,
+            b'x' => elf::SHF_EXECINSTR,
+            b'M' => elf::SHF_MERGE,
+            b'S' => elf::SHF_STRINGS,
+            _ => return Err(format!("unknown section flag `{}`", c as char)),
+        };
+    }
+    let sh_type = match sh_type.map(|t| t.trim_start_matches(['@', '%'])) {
+        None | Some("progbits") => elf::SHT_PROGBITS,
+        Some("nobits") => elf::SHT_NOBITS,
+        Some("note") => elf::SHT_NOTE,
+        Some(t) => return Err(format!("unknown section type `{}`", t)),
+    };
+    let entsize = match entsize {
+        Some(n) => n.parse().map_err(|_| format!("bad entry size `{}`", n))?,
+        None if bits & elf::SHF_MERGE != 0 => {
+            return Err("a mergeable section needs an entry size".to_string())
+        }
+        None => 0,
+    };
+    Ok((bits, sh_type, entsize))
+}
+
+// the DWARF number of a register, as a number or by name
+fn dwarf_reg(s: &str) -> Option<u64> {
+    const NAMES: [&str; 17] = [
+        "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12",
+        "r13", "r14", "r15", "rip",
+    ];
+    match s.strip_prefix('%') {
+        Some(name) => NAMES.iter().position(|n| *n == name).map(|n| n as u64),
+        None => s.parse().ok(),
+    }
+}
+
+fn uleb128(out: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,326 @@
+// The operands of the AT&T syntax: `%reg`, `$imm`, `disp(base, index, scale)`,
+// `sym(%rip)`, bare symbols for jumps and calls, and `*` for indirect ones.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Reg {
+    pub num: u8,    // 0 to 15, as the encoding numbers them
+    pub size: u8,   // in bytes: 1, 2, 4 or 8, 16 for %xmm0 to %xmm15 and 10 for %st(0) to %st(7)
+    pub high: bool, // %ah, %ch, %dh or %bh, which no instruction with a REX prefix can use
+}
+
+impl Reg {
+    pub fn is_xmm(&self) -> bool {
+        self.size == 16
+    }
+
+    // one of the x87 stack, `%st` being `%st(0)`
+    pub fn is_st(&self) -> bool {
+        self.size == 10
+    }
+
+    // %spl, %bpl, %sil and %dil are only there with a REX prefix
+    pub fn wants_rex(&self) -> bool {
+        self.size == 1 && !self.high && self.num >= 4
+    }
+}
+
+const REGS64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+const REGS32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+const REGS16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+const REGS8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+const HIGH8: [&str; 4] = ["ah", "ch", "dh", "bh"];
+
+// the register named `name`, without the `%`
+pub fn reg(name: &str) -> Option<Reg> {
+    for (regs, size) in [(&REGS64, 8), (&REGS32, 4), (&REGS16, 2), (&REGS8, 1)].iter() {
+        if let Some(num) = regs.iter().position(|r| *r == name) {
+            return Some(Reg {
+                num: num as u8,
+                size: *size,
+                high: false,
+            });
+        }
+    }
+    if let Some(n) = name.strip_prefix("xmm") {
+        let num = (0..16).find(|i: &u8| i.to_string() == n)?;
+        return Some(Reg {
+            num,
+            size: 16,
+            high: false,
+        });
+    }
+    if let Some(i) = name.strip_prefix("st") {
+        let num = match i.strip_prefix('(').and_then(|i| i.strip_suffix(')')) {
+            Some(i) => (0..8).find(|n: &u8| n.to_string() == i.trim())?,
+            None if i.is_empty() => 0,
+            None => return None,
+        };
+        return Some(Reg {
+            num,
+            size: 10,
+            high: false,
+        });
+    }
+    HIGH8.iter().position(|r| *r == name).map(|num| Reg {
+        num: num as u8 + 4,
+        size: 1,
+        high: true,
+    })
+}
+
+// `sym + value` or `sym - minus + value`; `.` stands for the current location
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Expr {
+    pub sym: Option<String>,
+    pub minus: Option<String>,
+    pub value: i64,
+}
+
+impl Expr {
+    pub fn number(value: i64) -> Expr {
+        Expr {
+            sym: None,
+            minus: None,
+            value,
+        }
+    }
+
+    // the value, if it does not depend on where any symbol ends up
+    pub fn constant(&self) -> Option<i64> {
+        match (&self.sym, &self.minus) {
+            (None, None) => Some(self.value),
+            _ => None,
+        }
+    }
+}
+
+// how a symbol is reached: directly, through the PLT or through the GOT
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Modifier {
+    None,
+    Plt,
+    GotPcRel,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mem {
+    pub disp: Expr,
+    pub modifier: Modifier,
+    pub base: Option<Reg>,
+    pub index: Option<Reg>,
+    pub scale: u8,
+    pub rip: bool, // `disp(%rip)`, the base is the next instruction
+}
+
+impl Mem {
+    // a bare symbol, which is the target of a direct jump or call
+    pub fn is_bare(&self) -> bool {
+        self.base.is_none() && self.index.is_none() && !self.rip
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Operand {
+    Reg(Reg),
+    Imm(Expr),
+    Mem(Mem),
+    Star(Box<Operand>), // `*%rax` or `*mem`, the target of an indirect jump or call
+}
+
+// the operands of an instruction, split at the commas outside parentheses
+pub fn parse_operands(s: &str) -> Result<Vec<Operand>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut ops = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                ops.push(parse_operand(&s[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ops.push(parse_operand(&s[start..])?);
+    Ok(ops)
+}
+
+fn parse_operand(s: &str) -> Result<Operand, String> {
+    let s = s.trim();
+    if let Some(rest) = s.strip_prefix('*') {
+        return Ok(Operand::Star(Box::new(parse_operand(rest)?)));
+    }
+    if let Some(name) = s.strip_prefix('%') {
+        return match reg(name) {
+            Some(r) => Ok(Operand::Reg(r)),
+            None => Err(format!("unknown register `{}`", s)),
+        };
+    }
+    if let Some(imm) = s.strip_prefix('$') {
+        let (e, modifier) = parse_expr(imm)?;
+        if modifier != Modifier::None {
+            return Err(format!("an immediate can not use `@`: `{}`", s));
+        }
+        return Ok(Operand::Imm(e));
+    }
+    // disp(base, index, scale), every part can be left out
+    let (disp, regs) = match s.find('(') {
+        Some(i) if s.ends_with(')') => (&s[..i], Some(&s[i + 1..s.len() - 1])),
+        Some(_) => return Err(format!("missing `)` in `{}`", s)),
+        None => (s, None),
+    };
+    let (disp, modifier) = if disp.trim().is_empty() {
+        (Expr::default(), Modifier::None)
+    } else {
+        parse_expr(disp)?
+    };
+    let mut mem = Mem {
+        disp,
+        modifier,
+        base: None,
+        index: None,
+        scale: 1,
+        rip: false,
+    };
+    if let Some(regs) = regs {
+        let parts: Vec<&str> = regs.split(',').map(|p| p.trim()).collect();
+        let addr_reg = |p: &str| -> Result<Option<Reg>, String> {
+            if p.is_empty() {
+                return Ok(None);
+            }
+            match p.strip_prefix('%').and_then(reg) {
+                Some(r) if r.size == 8 => Ok(Some(r)),
+                _ => Err(format!("`{}` is not a 64-bit register", p)),
+            }
+        };
+        if parts[0] == "%rip" {
+            if parts.len() > 1 {
+                return Err(format!("`%rip` can not have an index: `{}`", s));
+            }
+            mem.rip = true;
+        } else {
+            mem.base = addr_reg(parts[0])?;
+        }
+        if parts.len() > 1 {
+            mem.index = addr_reg(parts[1])?;
+            if mem.index.map(|r| r.num) == Some(4) {
+                return Err(format!("`%rsp` can not be an index: `{}`", s));
+            }
+        }
+        if parts.len() > 2 {
+            mem.scale = match parts[2] {
+                "1" => 1,
+                "2" => 2,
+                "4" => 4,
+                "8" => 8,
+                _ => return Err(format!("scale must be 1, 2, 4 or 8 in `{}`", s)),
+            };
+        }
+        if parts.len() > 3 {
+            return Err(format!("too many registers in `{}`", s));
+        }
+    }
+    Ok(Operand::Mem(mem))
+}
+
+// a sum of numbers and at most one symbol added and one subtracted, the
+// symbol added may be marked `@PLT` or `@GOTPCREL`
+pub fn parse_expr(s: &str) -> Result<(Expr, Modifier), String> {
+    let mut e = Expr::default();
+    let mut modifier = Modifier::None;
+    let mut rest = s.trim();
+    let mut negative = false;
+    if rest.is_empty() {
+        return Err("expected an expression".to_string());
+    }
+    loop {
+        if let Some(r) = rest.strip_prefix('-') {
+            negative = !negative;
+            rest = r.trim_start();
+            continue;
+        }
+        if let Some(r) = rest.strip_prefix('+') {
+            rest = r.trim_start();
+            continue;
+        }
+        let len = rest
+            .find(|c: char| {
+                !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$' || c == '@')
+            })
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!("unexpected `{}` in expression `{}`", rest, s));
+        }
+        let term = &rest[..len];
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            let v = parse_number(term).ok_or_else(|| format!("bad number `{}`", term))?;
+            e.value = if negative {
+                e.value.wrapping_sub(v)
+            } else {
+                e.value.wrapping_add(v)
+            };
+        } else {
+            let (name, m) = match term.find('@') {
+                Some(i) => (
+                    &term[..i],
+                    match &term[i + 1..] {
+                        "PLT" => Modifier::Plt,
+                        "GOTPCREL" => Modifier::GotPcRel,
+                        other => return Err(format!("unknown modifier `@{}`", other)),
+                    },
+                ),
+                None => (term, Modifier::None),
+            };
+            let slot = if negative { &mut e.minus } else { &mut e.sym };
+            if slot.is_some() || (negative && m != Modifier::None) {
+                return Err(format!("expression `{}` is too complex", s));
+            }
+            *slot = Some(name.to_string());
+            if m != Modifier::None {
+                modifier = m;
+            }
+        }
+        rest = rest[len..].trim_start();
+        negative = match rest.chars().next() {
+            None => break,
+            Some('+') => false,
+            Some('-') => true,
+            Some(_) => return Err(format!("unexpected `{}` in expression `{}`", rest, s)),
+        };
+        rest = rest[1..].trim_start();
+    }
+    Ok((e, modifier))
+}
+
+// decimal, `0x` hexadecimal, `0b` binary or `0` octal, as gas reads them
+fn parse_number(s: &str) -> Option<i64> {
+    let (digits, radix) = if let Some(h) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        (h, 16)
+    } else if let Some(b) = s.strip_prefix("0b").or_else(|| s.strip_prefix("0B")) {
+        (b, 2)
+    } else if s.len() > 1 && s.starts_with('0') {
+        (&s[1..], 8)
+    } else {
+        (s, 10)
+    };
+    // values up to 2^64 - 1 are taken as their two's complement
+    u64::from_str_radix(digits, radix).ok().map(|v| v as i64)
+}
@@ -0,0 +1,198 @@
+use crate::loc::{self, Loc, SourceMap};
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+// Every problem found while compiling is reported as a Diagnostic.  The
+// stages return the first error they hit as `Err(Diagnostic)`, `main`
+// renders it together with the source line and decides the exit status.
+// A Diagnostic is only a pointer to its details: it is the error of every
+// recursive descent in parser and sema, and a big one would make each of
+// their frames big.
+
+// exit status for errors in the program being compiled
+pub const EXIT_ERROR: i32 = 1;
+// exit status for bugs in crust, EX_SOFTWARE from sysexits.h
+pub const EXIT_ICE: i32 = 70;
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Ice, // internal compiler error, a bug in crust and not in the program
+}
+
+// error codes, grouped by the stage that reports them
+pub mod code {
+    pub const IO: &str = "E0001"; // can not read or write a file
+    pub const TOOL: &str = "E0002"; // the assembler or linker crust runs failed
+    pub const USAGE: &str = "E0003"; // a command line crust can not act on
+
+    pub const PP_DIRECTIVE: &str = "E0100"; // malformed preprocessing directive
+    pub const PP_ERROR: &str = "E0101"; // #error
+    pub const PP_INCLUDE: &str = "E0102"; // #include file not found
+    pub const PP_MACRO: &str = "E0103"; // bad macro invocation
+    pub const PP_EXPR: &str = "E0104"; // bad #if expression
+    pub const PP_WARNING: &str = "W0100"; // #warning
+
+    pub const LEX: &str = "E0200"; // character that can not start a token
+    pub const LEX_LITERAL: &str = "E0201"; // malformed character or string literal
+
+    pub const SYNTAX: &str = "E0300"; // the tokens do not follow the grammar
+
+    pub const UNDECLARED: &str = "E0400"; // use of an undeclared identifier
+    pub const REDECLARED: &str = "E0401"; // redeclaration in the same scope
+    pub const NOT_IN_LOOP: &str = "E0402"; // break/continue/case outside of a loop or switch
+    pub const NOT_LVALUE: &str = "E0403"; // operand must be an lvalue
+    pub const NOT_CONSTANT: &str = "E0404"; // initializer is not a constant expression
+    pub const UNSUPPORTED: &str = "E0405"; // valid C that crust can not compile yet
+    pub const INVALID_OPERAND: &str = "E0406"; // operand of the wrong type, like *1
+    pub const INCOMPLETE_TYPE: &str = "E0407"; // struct or union used before its members are known
+    pub const BAD_CALL: &str = "E0408"; // call of a non-function, or with the wrong number of arguments
+    pub const INVALID_INIT: &str = "E0409"; // initializer that does not fit the object it is for
+    pub const POINTER_CONVERSION: &str = "W0400"; // pointer and integer mixed without a cast
+    pub const IMPLICIT_DECLARATION: &str = "W0401"; // call of a function with no declaration
+    pub const EXCESS_INIT: &str = "W0402"; // more initializers than the object has room for
+
+    pub const ASM: &str = "E0500"; // assembly the built-in assembler can not encode
+
+    pub const LINK: &str = "E0600"; // objects the built-in linker can not link
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic(Box<Details>);
+
+#[derive(Clone, Debug)]
+pub struct Details {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub loc: Option<Loc>,
+    pub message: String,
+    pub notes: Vec<Diagnostic>, // always Severity::Note
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        code: Option<&'static str>,
+        loc: Option<&Loc>,
+        message: &str,
+    ) -> Diagnostic {
+        Diagnostic(Box::new(Details {
+            severity,
+            code,
+            loc: loc.cloned(),
+            message: message.to_string(),
+            notes: Vec::new(),
+        }))
+    }
+
+    pub fn error(code: &'static str, loc: &Loc, message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Error, Some(code), Some(loc), message)
+    }
+
+    pub fn warning(code: &'static str, loc: &Loc, message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, Some(code), Some(loc), message)
+    }
+
+    pub fn ice(loc: &Loc, message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Ice, None, Some(loc), message)
+    }
+
+    // a file we could not read or write, there is no source location for it
+    pub fn io(path: &Path, err: &io::Error) -> Diagnostic {
+        let msg = format!("{}: {}", path.display(), err);
+        Diagnostic::new(Severity::Error, Some(code::IO), None, &msg)
+    }
+
+    // a problem of the driver, with no place in the source to point at
+    pub fn driver(code: &'static str, message: &str) -> Diagnostic {
+        Diagnostic::new(Severity::Error, Some(code), None, message)
+    }
+
+    // attach a note, e.g. pointing at the `(` a missing `)` should match
+    pub fn note(mut self, loc: Option<&Loc>, message: &str) -> Diagnostic {
+        self.notes
+            .push(Diagnostic::new(Severity::Note, None, loc, message));
+        self
+    }
+
+    // the same problem, found in what crust made itself, so a bug in crust
+    pub fn into_ice(mut self) -> Diagnostic {
+        self.severity = Severity::Ice;
+        self.code = None;
+        self
+    }
+
+    pub fn is_ice(&self) -> bool {
+        self.severity == Severity::Ice
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        if self.is_ice() {
+            EXIT_ICE
+        } else {
+            EXIT_ERROR
+        }
+    }
+
+    // the diagnostic as gcc and clang print it:
+    //
+    //     test.c:2:12: error[E0300]: Expected `;`, found RBrace
+    //         return 0
+    //                ^
+    pub fn render(&self, sources: &mut SourceMap) -> String {
+        let mut s = self.to_string();
+        if let Some(loc) = &self.loc {
+            if let Some(line) = sources.line(&loc.file, loc.line) {
+                s.push('\n');
+                if loc.col == 0 {
+                    s.push_str(line);
+                } else {
+                    s.push_str(&loc::caret(line, loc));
+                }
+            }
+        }
+        for note in self.notes.iter() {
+            s.push('\n');
+            s.push_str(&note.render(sources));
+        }
+        s
+    }
+}
+
+impl Deref for Diagnostic {
+    type Target = Details;
+
+    fn deref(&self) -> &Details {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut Details {
+        &mut self.0
+    }
+}
+
+// the first line of the diagnostic, without the source code
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.loc {
+            Some(loc) => write!(f, "{}: ", loc)?,
+            None => write!(f, "crust: ")?,
+        }
+        match self.severity {
+            Severity::Error => write!(f, "error")?,
+            Severity::Warning => write!(f, "warning")?,
+            Severity::Note => write!(f, "note")?,
+            Severity::Ice => write!(f, "internal compiler error")?,
+        }
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
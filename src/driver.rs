@@ -0,0 +1,80 @@
+use crate::opts::Opts;
+use crust::diagnostic::{code, Diagnostic};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs, process};
+
+// crust is a driver like gcc: it compiles C itself and runs the system
+// assembler and linker for the rest, until it has its own
+
+pub fn assemble(asm: &Path, obj: &Path) -> Result<(), Diagnostic> {
+    run(
+        "as",
+        Command::new("as").arg("--64").arg("-o").arg(obj).arg(asm),
+    )
+}
+
+// `cc` knows where the C runtime and the C library are, `ld` alone does not
+pub fn link(objects: &[PathBuf], output: &Path, opts: &Opts) -> Result<(), Diagnostic> {
+    let mut cmd = Command::new("cc");
+    cmd.args(opts.link_flags())
+        .arg("-o")
+        .arg(output)
+        .args(objects);
+    for dir in opts.lib_dirs() {
+        cmd.arg(format!("-L{}", dir.display()));
+    }
+    for lib in opts.libs() {
+        cmd.arg(format!("-l{}", lib));
+    }
+    for flag in opts.linker_flags() {
+        cmd.arg(format!("-Wl,{}", flag));
+    }
+    run("cc", &mut cmd)
+}
+
+// the tool prints its own errors, we only say that it failed
+fn run(name: &str, cmd: &mut Command) -> Result<(), Diagnostic> {
+    let status = cmd
+        .status()
+        .map_err(|e| Diagnostic::driver(code::TOOL, &format!("can not run `{}`: {}", name, e)))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Diagnostic::driver(
+            code::TOOL,
+            &format!("`{}` failed with {}", name, status),
+        ))
+    }
+}
+
+// the files between the stages the user did not ask for, removed when dropped
+#[derive(Default)]
+pub struct Temps {
+    paths: Vec<PathBuf>,
+}
+
+impl Temps {
+    // a new temporary file for `input` with the suffix `ext`
+    pub fn path(&mut self, input: &Path, ext: &str) -> PathBuf {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let name = format!(
+            "crust-{}-{}-{}.{}",
+            process::id(),
+            self.paths.len(),
+            stem,
+            ext
+        );
+        let path = env::temp_dir().join(name);
+        self.paths.push(path.clone());
+        path
+    }
+}
+
+impl Drop for Temps {
+    fn drop(&mut self) {
+        for path in self.paths.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// The 80-bit extended precision format of the x87, which a long double is
+// in, computed in software for the constants crust folds, as the x87 does
+// with its precision control at 64 bits: every result is rounded to the
+// nearest value, ties to the even one.  A value is a sign, an exponent of
+// 15 bits biased by 16383 and a mantissa of 64 bits whose highest bit, the
+// integer one, is explicit unlike in a double.  A long double takes 16
+// bytes in memory, the 10 of the format and 6 of padding.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Extended {
+    pub sign_exp: u16, // the sign in the highest bit, the biased exponent below
+    pub mantissa: u64,
+}
+
+const BIAS: i32 = 16383;
+const MAX_EXP: i32 = 0x7fff; // of an infinity or a NaN
+                             // the exponent of the lowest mantissa bit of a subnormal, and of the
+                             // smallest normal value
+const MIN_LSB: i32 = 1 - BIAS - 63;
+
+// what the bits of a value stand for
+enum Class {
+    Nan,
+    Inf(bool),
+    Zero(bool),
+    // sign, exponent of the lowest mantissa bit, mantissa with the highest
+    // bit set, even for a subnormal value
+    Finite(bool, i32, u64),
+}
+
+// an integer of any size, its 32-bit digits from the lowest one, which a
+// decimal constant is read into
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Big(Vec<u32>);
+
+impl Big {
+    fn bits(&self) -> i32 {
+        match self.0.iter().rposition(|d| *d != 0) {
+            Some(i) => 32 * i as i32 + 32 - self.0[i].leading_zeros() as i32,
+            None => 0,
+        }
+    }
+
+    // self * m + a
+    fn mul_add(&mut self, m: u32, a: u32) {
+        let mut carry = u64::from(a);
+        for d in self.0.iter_mut() {
+            let v = u64::from(*d) * u64::from(m) + carry;
+            *d = v as u32;
+            carry = v >> 32;
+        }
+        if carry != 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    fn shl(&self, n: i32) -> Big {
+        let (words, bits) = ((n / 32) as usize, n % 32);
+        let mut digits = vec![0; words];
+        let mut carry = 0;
+        for d in self.0.iter() {
+            digits.push(d << bits | carry);
+            carry = if bits == 0 { 0 } else { d >> (32 - bits) };
+        }
+        digits.push(carry);
+        Big(digits)
+    }
+
+    // the bits from the `n`-th on, of which the caller takes 128, and
+    // whether any below them is set
+    fn shr(&self, n: i32) -> (u128, bool) {
+        let mut v = 0;
+        for i in (0..128).step_by(32).rev() {
+            v = v << 32 | u128::from(self.bit_word(n + i));
+        }
+        let sticky = (0..n).any(|i| {
+            self.0
+                .get((i / 32) as usize)
+                .is_some_and(|d| d >> (i % 32) & 1 != 0)
+        });
+        (v, sticky)
+    }
+
+    // the 32 bits from the `i`-th one, 0 where there are none
+    fn bit_word(&self, i: i32) -> u32 {
+        let bit = |j: i32| -> u32 {
+            if j < 0 {
+                return 0;
+            }
+            self.0
+                .get((j / 32) as usize)
+                .map_or(0, |d| d >> (j % 32) & 1)
+        };
+        (0..32).fold(0, |w, k| w | bit(i + k) << k)
+    }
+
+    fn cmp(&self, other: &Big) -> Ordering {
+        let n = self.0.len().max(other.0.len());
+        for i in (0..n).rev() {
+            let (a, b) = (self.0.get(i).unwrap_or(&0), other.0.get(i).unwrap_or(&0));
+            if a != b {
+                return a.cmp(b);
+            }
+        }
+        Ordering::Equal
+    }
+
+    // self - other, which is not more
+    fn sub(&mut self, other: &Big) {
+        let mut borrow = 0;
+        for (i, d) in self.0.iter_mut().enumerate() {
+            let v = i64::from(*d) - i64::from(*other.0.get(i).unwrap_or(&0)) - borrow;
+            *d = v as u32;
+            borrow = i64::from(v < 0);
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|d| *d == 0)
+    }
+}
+
+impl Extended {
+    pub const ZERO: Extended = Extended {
+        sign_exp: 0,
+        mantissa: 0,
+    };
+
+    // what the x87 makes of an invalid operation, the "real indefinite"
+    const NAN: Extended = Extended {
+        sign_exp: 0xffff,
+        mantissa: 0xc000_0000_0000_0000,
+    };
+
+    fn inf(sign: bool) -> Extended {
+        Extended {
+            sign_exp: u16::from(sign) << 15 | MAX_EXP as u16,
+            mantissa: 1 << 63,
+        }
+    }
+
+    fn zero(sign: bool) -> Extended {
+        Extended {
+            sign_exp: u16::from(sign) << 15,
+            mantissa: 0,
+        }
+    }
+
+    fn sign(self) -> bool {
+        self.sign_exp >> 15 != 0
+    }
+
+    fn class(self) -> Class {
+        let sign = self.sign();
+        let exp = i32::from(self.sign_exp & 0x7fff);
+        match (exp, self.mantissa) {
+            (MAX_EXP, m) if m << 1 == 0 => Class::Inf(sign),
+            (MAX_EXP, _) => Class::Nan,
+            (_, 0) => Class::Zero(sign),
+            (0, m) => {
+                let shift = m.leading_zeros() as i32;
+                Class::Finite(sign, MIN_LSB - shift, m << shift)
+            }
+            (exp, m) => Class::Finite(sign, exp - BIAS - 63, m),
+        }
+    }
+
+    // sign * (m, and a bit below its lowest one if `sticky`) * 2^e, rounded
+    fn round(sign: bool, m: u128, e: i32, sticky: bool) -> Extended {
+        if m == 0 {
+            return Extended::zero(sign);
+        }
+        let n = 128 - m.leading_zeros() as i32;
+        // 64 bits are kept, fewer where the exponent would be below the
+        // one of a subnormal
+        let shift = (n - 64).max(MIN_LSB - e);
+        let mantissa = if shift <= 0 {
+            (m << -shift) as u64
+        } else if shift > 128 {
+            0
+        } else {
+            let kept = if shift == 128 { 0 } else { m >> shift };
+            let rest = if shift == 128 {
+                m
+            } else {
+                m & ((1 << shift) - 1)
+            };
+            let half = 1 << (shift - 1);
+            let up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+            let kept = kept + u128::from(up);
+            // rounded up to the next power of 2
+            if kept >> 64 != 0 {
+                return Extended::round(sign, kept, e + shift, false);
+            }
+            kept as u64
+        };
+        let exp = if mantissa >> 63 != 0 {
+            e + shift + 63 + BIAS
+        } else {
+            0
+        };
+        if exp >= MAX_EXP {
+            return Extended::inf(sign);
+        }
+        Extended {
+            sign_exp: u16::from(sign) << 15 | exp as u16,
+            mantissa,
+        }
+    }
+
+    pub fn from_f64(x: f64) -> Extended {
+        let bits = x.to_bits();
+        let sign = bits >> 63 != 0;
+        let exp = ((bits >> 52) & 0x7ff) as i32;
+        let frac = bits & ((1 << 52) - 1);
+        match exp {
+            0x7ff if frac == 0 => Extended::inf(sign),
+            // a quiet NaN keeps its payload
+            0x7ff => Extended {
+                sign_exp: u16::from(sign) << 15 | MAX_EXP as u16,
+                mantissa: 1 << 63 | 1 << 62 | frac << 11,
+            },
+            0 => Extended::round(sign, u128::from(frac), -1074, false),
+            _ => Extended::round(sign, u128::from(1 << 52 | frac), exp - 1075, false),
+        }
+    }
+
+    // the double nearest to it
+    pub fn to_f64(self) -> f64 {
+        let (sign, e, m) = match self.class() {
+            Class::Nan => return f64::NAN,
+            Class::Inf(sign) => {
+                return if sign {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                }
+            }
+            Class::Zero(sign) => return if sign { -0.0 } else { 0.0 },
+            Class::Finite(sign, e, m) => (sign, e, m),
+        };
+        let sign_bit = u64::from(sign) << 63;
+        // 53 bits are kept, fewer for a subnormal double
+        let shift = 11.max(-1074 - e);
+        if shift > 64 {
+            return f64::from_bits(sign_bit);
+        }
+        let m = u128::from(m);
+        let (kept, rest) = (m >> shift, m & ((1 << shift) - 1));
+        let half = 1 << (shift - 1);
+        let mut kept = (kept + u128::from(rest > half || (rest == half && kept & 1 == 1))) as u64;
+        let mut lsb = e + shift;
+        if kept >> 53 != 0 {
+            kept >>= 1;
+            lsb += 1;
+        }
+        if kept >> 52 == 0 {
+            // a subnormal one, or 0
+            return f64::from_bits(sign_bit | kept);
+        }
+        let exp = lsb + 52 + 1023;
+        if exp >= 0x7ff {
+            return f64::from_bits(sign_bit | 0x7ff << 52);
+        }
+        f64::from_bits(sign_bit | (exp as u64) << 52 | (kept & ((1 << 52) - 1)))
+    }
+
+    // an integer is always exact, 64 bits of mantissa hold any
+    pub fn from_i64(v: i64) -> Extended {
+        Extended::round(v < 0, u128::from(v.unsigned_abs()), 0, false)
+    }
+
+    pub fn from_u64(v: u64) -> Extended {
+        Extended::round(false, u128::from(v), 0, false)
+    }
+
+    // rounded towards 0, the bits of the integer kept as far as they go; C
+    // leaves what a value out of the range of the type gives undefined
+    pub fn to_i128(self) -> i128 {
+        let (sign, e, m) = match self.class() {
+            Class::Finite(sign, e, m) => (sign, e, m),
+            _ => return 0,
+        };
+        let v = match e {
+            e if e <= -64 => 0,
+            e if e < 0 => i128::from(m >> -e),
+            e if e < 64 => i128::from(m) << e,
+            _ => i128::MIN,
+        };
+        if sign {
+            v.wrapping_neg()
+        } else {
+            v
+        }
+    }
+
+    pub fn is_nan(self) -> bool {
+        matches!(self.class(), Class::Nan)
+    }
+
+    // None when either is a NaN
+    pub fn compare(self, other: Extended) -> Option<Ordering> {
+        let key = |x: Extended| match x.class() {
+            Class::Zero(_) => Some((false, 0, 0)),
+            Class::Nan => None,
+            Class::Inf(sign) => Some((sign, MAX_EXP, 0)),
+            Class::Finite(sign, e, m) => Some((sign, e + 64 - MIN_LSB, m)),
+        };
+        let ((sa, ea, ma), (sb, eb, mb)) = (key(self)?, key(other)?);
+        let magnitude = (ea, ma).cmp(&(eb, mb));
+        Some(match (sa, sb) {
+            _ if ea == 0 && eb == 0 => Ordering::Equal,
+            (false, false) => magnitude,
+            (true, true) => magnitude.reverse(),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        })
+    }
+
+    // the floating constant `body` without its suffix, in base 16 after
+    // `0x` or in base 10, read to the nearest long double
+    pub fn parse(body: &str) -> Option<Extended> {
+        let (hex, body) = match body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+            Some(rest) => (true, rest),
+            None => (false, body),
+        };
+        let (mantissa, exp) = match body.find(if hex { ['p', 'P'] } else { ['e', 'E'] }) {
+            Some(i) => (&body[..i], &body[i + 1..]),
+            None if hex => return None,
+            None => (body, "0"),
+        };
+        // an exponent too big for any value only needs to stay that big
+        let (negative, exp) = match exp.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, exp.strip_prefix('+').unwrap_or(exp)),
+        };
+        if exp.is_empty() || !exp.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let exp = exp
+            .bytes()
+            .fold(0i64, |e, b| (e * 10 + i64::from(b - b'0')).min(1 << 40));
+        let exp = if negative { -exp } else { exp };
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let radix = if hex { 16 } else { 10 };
+        let mut n = Big(vec![0]);
+        for c in int.chars().chain(frac.chars()) {
+            n.mul_add(radix, c.to_digit(radix)?);
+        }
+        if n.is_zero() {
+            return Some(Extended::ZERO);
+        }
+        if hex {
+            return Some(Extended::scaled(&n, exp - 4 * frac.len() as i64));
+        }
+        let exp10 = exp - frac.len() as i64;
+        // the value is below 10^(digits + exp10) and at least 10^ of one less
+        let digits = int
+            .chars()
+            .chain(frac.chars())
+            .skip_while(|c| *c == '0')
+            .count() as i64;
+        if digits + exp10 > 4934 {
+            return Some(Extended::inf(false));
+        }
+        if digits + exp10 < -4952 {
+            return Some(Extended::ZERO);
+        }
+        if exp10 >= 0 {
+            for _ in 0..exp10 {
+                n.mul_add(10, 0);
+            }
+            return Some(Extended::scaled(&n, 0));
+        }
+        // n / 10^-exp10 to at least 66 bits, what is left only sticks
+        let mut d = Big(vec![1]);
+        for _ in 0..-exp10 {
+            d.mul_add(10, 0);
+        }
+        let shift = (d.bits() + 66 - n.bits()).max(0);
+        let mut rest = n.shl(shift);
+        let mut q: u128 = 0;
+        for i in (0..=rest.bits() - d.bits() + 1).rev() {
+            let part = d.shl(i);
+            if rest.cmp(&part) != Ordering::Less {
+                rest.sub(&part);
+                q |= 1 << i;
+            }
+        }
+        Some(Extended::round(false, q, -shift, !rest.is_zero()))
+    }
+
+    // n * 2^e
+    fn scaled(n: &Big, e: i64) -> Extended {
+        let e = e.clamp(-(1 << 20), 1 << 20) as i32;
+        let shift = (n.bits() - 128).max(0);
+        let (m, sticky) = n.shr(shift);
+        Extended::round(false, m, e + shift, sticky)
+    }
+}
+
+impl Neg for Extended {
+    type Output = Extended;
+
+    fn neg(self) -> Extended {
+        Extended {
+            sign_exp: self.sign_exp ^ 0x8000,
+            ..self
+        }
+    }
+}
+
+impl Add for Extended {
+    type Output = Extended;
+
+    fn add(self, other: Extended) -> Extended {
+        let ((sa, ea, ma), (sb, eb, mb)) = match (self.class(), other.class()) {
+            (Class::Nan, _) | (_, Class::Nan) => return Extended::NAN,
+            (Class::Inf(a), Class::Inf(b)) if a != b => return Extended::NAN,
+            (Class::Inf(_), _) => return self,
+            (_, Class::Inf(_)) => return other,
+            // -0 + -0 is -0, any other sum of zeros +0
+            (Class::Zero(a), Class::Zero(b)) => return Extended::zero(a && b),
+            (Class::Zero(_), _) => return other,
+            (_, Class::Zero(_)) => return self,
+            (Class::Finite(sa, ea, ma), Class::Finite(sb, eb, mb)) => ((sa, ea, ma), (sb, eb, mb)),
+        };
+        // the one of the higher exponent first, with 62 bits below it for
+        // the other, whose bits below those only count as a sticky one
+        let ((sa, ea, ma), (sb, eb, mb)) = if ea >= eb {
+            ((sa, ea, ma), (sb, eb, mb))
+        } else {
+            ((sb, eb, mb), (sa, ea, ma))
+        };
+        let a = u128::from(ma) << 62;
+        let d = ea - eb;
+        let b = if d <= 62 {
+            u128::from(mb) << (62 - d)
+        } else if d < 62 + 64 {
+            let shift = d - 62;
+            u128::from(mb >> shift) | u128::from(mb & ((1 << shift) - 1) != 0)
+        } else {
+            1
+        };
+        let e = ea - 62;
+        if sa == sb {
+            return Extended::round(sa, a + b, e, false);
+        }
+        match a.cmp(&b) {
+            Ordering::Equal => Extended::ZERO,
+            Ordering::Greater => Extended::round(sa, a - b, e, false),
+            Ordering::Less => Extended::round(sb, b - a, e, false),
+        }
+    }
+}
+
+impl Sub for Extended {
+    type Output = Extended;
+
+    fn sub(self, other: Extended) -> Extended {
+        self + -other
+    }
+}
+
+impl Mul for Extended {
+    type Output = Extended;
+
+    fn mul(self, other: Extended) -> Extended {
+        let sign = self.sign() != other.sign();
+        match (self.class(), other.class()) {
+            (Class::Nan, _) | (_, Class::Nan) => Extended::NAN,
+            (Class::Inf(_), Class::Zero(_)) | (Class::Zero(_), Class::Inf(_)) => Extended::NAN,
+            (Class::Inf(_), _) | (_, Class::Inf(_)) => Extended::inf(sign),
+            (Class::Zero(_), _) | (_, Class::Zero(_)) => Extended::zero(sign),
+            (Class::Finite(_, ea, ma), Class::Finite(_, eb, mb)) => {
+                Extended::round(sign, u128::from(ma) * u128::from(mb), ea + eb, false)
+            }
+        }
+    }
+}
+
+impl Div for Extended {
+    type Output = Extended;
+
+    fn div(self, other: Extended) -> Extended {
+        let sign = self.sign() != other.sign();
+        match (self.class(), other.class()) {
+            (Class::Nan, _) | (_, Class::Nan) => Extended::NAN,
+            (Class::Inf(_), Class::Inf(_)) | (Class::Zero(_), Class::Zero(_)) => Extended::NAN,
+            (Class::Inf(_), _) | (_, Class::Zero(_)) => Extended::inf(sign),
+            (Class::Zero(_), _) | (_, Class::Inf(_)) => Extended::zero(sign),
+            (Class::Finite(_, ea, ma), Class::Finite(_, eb, mb)) => {
+                // 64 or 65 bits of quotient, and 8 more for the rounding
+                let (ma, mb) = (u128::from(ma) << 64, u128::from(mb));
+                let (q, r) = (ma / mb, ma % mb);
+                let (q2, r2) = ((r << 8) / mb, (r << 8) % mb);
+                Extended::round(sign, q << 8 | q2, ea - eb - 72, r2 != 0)
+            }
+        }
+    }
+}
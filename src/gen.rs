@@ -1,29 +1,172 @@
-#![allow(dead_code)]
-use crate::lexer::TokType;
-use crate::parser::{DataType, NodeType, ParseNode, StmtType};
-use std::collections::{HashMap, HashSet};
+use crate::ir::{
+    Arg, BinOp, BlockId, CallRet, Callee, Cond, Datum, Function, Inst, Module, Operand, Param,
+    RetKind, Slot, StructVal, Terminator, Ty, UnOp, VReg,
+};
+use crate::regalloc::{allocate, Allocation, Loc};
+use crate::types::{
+    align_to, REG_SAVE_GP_SIZE, REG_SAVE_SIZE, VA_FP_OFFSET, VA_GP_OFFSET, VA_OVERFLOW_ARG_AREA,
+    VA_REG_SAVE_AREA,
+};
+use std::collections::HashMap;
 
-use std::sync::atomic;
+// The x86-64 assembly for the IR.  The virtual registers live where the
+// register allocation puts them, a machine register or an eightbyte of the
+// frame, and each instruction is computed right in the register of its
+// result when it can, in %rax otherwise, %xmm15 for a floating one.  Only
+// the low bytes of the type of a register count, the rest is whatever the
+// instruction that wrote it left there.
+//
+// A long double lives in 16 bytes of the frame, never in a register, and
+// the x87 computes on it: each instruction pushes its operands on the x87
+// stack, a constant through the red zone under %rsp, and pops its result,
+// so the stack is empty between them as the System V convention wants.
 
-// generate a std::String contains the assembly language code
-static LABEL_COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
-fn gen_labels(prefix: &str) -> String {
-    let label_counter = LABEL_COUNTER.fetch_add(1, atomic::Ordering::SeqCst);
-    let label = format!(".L{}{}", prefix, label_counter);
+// What gen keeps while it goes through one translation unit.  Each
+// translation unit gets a new one, so two compilations, one after the other
+// or at the same time, share nothing.
+pub struct Context {
+    labels: usize, // how many labels were made so far
+}
+
+impl Context {
+    // a new label, unique in the translation unit
+    fn label(&mut self, prefix: &str) -> String {
+        let label = format!(".L{}{}", prefix, self.labels);
+        self.labels += 1;
+        label
+    }
+}
+
+const ARG_REGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+const XMM_ARG_REGS: [&str; 8] = [
+    "%xmm0", "%xmm1", "%xmm2", "%xmm3", "%xmm4", "%xmm5", "%xmm6", "%xmm7",
+];
+// where a struct returned in registers is, by the class of its eightbytes
+const RET_REGS: [&str; 2] = ["%rax", "%rdx"];
+const XMM_RET_REGS: [&str; 2] = ["%xmm0", "%xmm1"];
+
+// the bytes of a string literal in a form `.string` takes them
+fn escape_string(data: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in data {
+        match b {
+            b'"' => s.push_str("\\\""),
+            b'\\' => s.push_str("\\\\"),
+            b' '..=b'~' => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    s
+}
+
+pub fn gen_prog(module: &Module) -> String {
+    let p = "        ";
+    let cx = &mut Context { labels: 0 };
+    let mut prog_body = String::new();
+    for g in module.globals.iter() {
+        let global = if g.global {
+            format!("{}.globl	{}\n", p, g.name)
+        } else {
+            String::new()
+        };
+        let init = match &g.init {
+            Some(init) => init,
+            // uninitialized global variable, just put it in .comm
+            None => {
+                prog_body.push_str(&gen_comm(&g.name, g.size, g.align, g.global));
+                continue;
+            }
+        };
+        // all zero, it takes no room in the file
+        let zero = init.iter().all(|d| d.symbol.is_none() && d.value == 0);
+        let section = if zero { ".bss" } else { ".data" };
+        // the bytes no scalar is at are 0
+        let mut data = String::new();
+        let mut offset = 0;
+        for d in init.iter().filter(|_| !zero) {
+            if d.offset > offset {
+                data.push_str(&format!("{}.zero	{}\n", p, d.offset - offset));
+            }
+            data.push_str(&gen_datum(d));
+            offset = d.offset + d.ty.size();
+        }
+        if g.size > offset {
+            data.push_str(&format!("{}.zero	{}\n", p, g.size - offset));
+        }
+        prog_body.push_str(&format!(
+            "{}\
+             {}{}\n\
+             {}.align {}\n\
+             {}.type	{}, @object\n\
+             {}.size	{}, {}\n\
+             {}:\n\
+             {}",
+            global, p, section, p, g.align, p, g.name, p, g.name, g.size, g.name, data
+        ));
+    }
+    if !module.strings.is_empty() {
+        prog_body.push_str(&format!("{}.section .rodata\n", p));
+        for (tag, data) in module.strings.iter() {
+            prog_body.push_str(&format!(
+                "{}:\n\
+                 {}.string \"{}\"\n",
+                tag,
+                p,
+                escape_string(data)
+            ));
+        }
+    }
+    for (i, func) in module.functions.iter().enumerate() {
+        prog_body.push_str(&Frame::new(cx, func, i).gen_fn());
+    }
+    format!(
+        "{}.file \"{}\"\n\
+         {}\
+         {}.ident	\"crust: 0.1 (By Haoran Wang)\"\n\
+         {}.section	.note.GNU-stack,\"\",@progbits\n",
+        p, module.name, prog_body, p, p
+    )
+}
 
-    label
+// the directives for a scalar in the initializer of a global
+fn gen_datum(d: &Datum) -> String {
+    let p = "        ";
+    let directive = match d.ty {
+        Ty::I8 => ".byte",
+        Ty::I16 => ".value",
+        Ty::I32 | Ty::F32 => ".long",
+        // lower gives a long double as its two parts
+        Ty::I64 | Ty::F64 | Ty::F80 => ".quad",
+    };
+    match &d.symbol {
+        Some(symbol) if d.value == 0 => format!("{}{}	{}\n", p, directive, symbol),
+        Some(symbol) => format!("{}{}	{}{:+}\n", p, directive, symbol, d.value),
+        None => format!("{}{}	{}\n", p, directive, d.value),
+    }
 }
 
-static FLAG_FOR_MAIN_HAS_RET: atomic::AtomicBool = atomic::AtomicBool::new(false);
-fn fn_main_has_ret() {
-    FLAG_FOR_MAIN_HAS_RET.swap(true, atomic::Ordering::SeqCst);
+// an uninitialized global, which the linker merges with the ones of the same
+// name in the other translation units unless it is `static`
+fn gen_comm(name: &str, size: i64, align: i64, global: bool) -> String {
+    let p = "        ";
+    let local = if global {
+        String::new()
+    } else {
+        format!("{}.local {}\n", p, name)
+    };
+    format!("{}{}.comm {}, {}, {}\n", local, p, name, size, align)
 }
 
-fn gen_fn_prologue(fn_name: &str) -> String {
+fn gen_fn_prologue(cx: &mut Context, fn_name: &str, global: bool) -> String {
     let p = "        ";
+    let global = if global {
+        format!("{}.global {}\n", p, fn_name)
+    } else {
+        String::new()
+    };
     format!(
         "{}.text\n\
-         {}.global {}\n\
+         {}\
          {}.type {}, @function\n\
          {}:\n\
          {}:\n\
@@ -35,12 +178,11 @@ fn gen_fn_prologue(fn_name: &str) -> String {
          {}.cfi_def_cfa_register 6\n\
          ",
         p,
-        p,
-        fn_name,
+        global,
         p,
         fn_name,
         fn_name,
-        gen_labels("FB"),
+        cx.label("FB"),
         p,
         p,
         p,
@@ -60,1774 +202,1802 @@ fn gen_fn_epilogue() -> String {
     )
 }
 
-fn compute_const(tree: &ParseNode) -> i64 {
-    match &tree.entry {
-        NodeType::BinExp(op) => {
-            let lhs = compute_const(tree.child.get(0).unwrap());
-            let rhs = compute_const(tree.child.get(1).unwrap());
-            match op {
-                TokType::Plus => {
-                    return lhs + rhs;
-                }
-                TokType::Multi => {
-                    return lhs * rhs;
-                }
-                TokType::Splash => {
-                    return lhs / rhs;
-                }
-                TokType::And => {
-                    if lhs != 0 && rhs != 0 {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::Or => {
-                    if lhs != 0 || rhs != 0 {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::Equal => {
-                    if lhs == rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::NotEqual => {
-                    if lhs != rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::LessEqual => {
-                    if lhs <= rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::GreaterEqual => {
-                    if lhs >= rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::Lt => {
-                    if lhs < rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                TokType::Gt => {
-                    if lhs > rhs {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                _ => panic!("{:?} should not occur in global variable initialization"),
-            }
-        }
-        NodeType::UnExp(op) => {
-            let child_val = compute_const(tree.child.get(0).unwrap());
-            match op {
-                TokType::Minus => {
-                    return -child_val;
-                }
-                TokType::Tilde => {
-                    return !child_val;
-                }
-                TokType::Exclamation => {
-                    if child_val == 0 {
-                        return 1;
-                    } else {
-                        return 0;
-                    }
-                }
-                _ => panic!("Expected Unary Operator, found {:?}", op),
-            }
-        }
-        NodeType::Const(val) => {
-            return val * 1;
+// copy `size` bytes from where %rax points to where %rcx points, through %rdx
+fn gen_copy(size: i64) -> String {
+    let p = "        ";
+    let mut s = String::new();
+    let mut off = 0;
+    for (chunk, mov, reg) in [
+        (8, "movq", "%rdx"),
+        (4, "movl", "%edx"),
+        (2, "movw", "%dx"),
+        (1, "movb", "%dl"),
+    ] {
+        while size - off >= chunk {
+            s.push_str(&format!(
+                "{}{} {}(%rax), {}\n\
+                 {}{} {}, {}(%rcx)\n",
+                p, mov, off, reg, p, mov, reg, off
+            ));
+            off += chunk;
         }
-        _ => return compute_const(tree.child.get(0).unwrap()),
     }
+    s
 }
-pub fn gen_prog(tree: &ParseNode) -> String {
-    let p = "        ".to_string();
-
-    // iter every function node
-    let mut prog_body = String::new();
-    let index_map: HashMap<String, isize> = HashMap::new();
-    let mut global_variable_scope: HashSet<String> = HashSet::new();
-    let idx: isize = 0;
-    for it in tree.child.iter() {
-        match &it.entry {
-            NodeType::Declare(var_name, DataType::I64) => {
-                // record it in the scope, index_map,
-                global_variable_scope.insert(var_name.to_string());
-                if it.child.is_empty() {
-                    // uninitialized global variable
-                    // just put them in .comm
-                    // now we use value has 8 bytes by default.
-                    // XXX: should be vary-length based on the data type.
-                    prog_body.push_str(&format!("{}.comm {}, 8, 8\n", p, var_name,))
-                } else {
-                    let val = compute_const(&it.child.get(0).unwrap());
-                    prog_body.push_str(&format!(
-                        "{}.globl	{}\n\
-                         {}.data\n\
-                         {}.align 8\n\
-                         {}.type	{}, @object\n\
-                         {}.size	{}, 8\n\
-                         {}:\n\
-                         {}.long	{}\n",
-                        p, var_name, p, p, p, var_name, p, var_name, var_name, p, val
-                    ));
-                }
-            }
-            NodeType::Declare(var_name, DataType::Arr64(len)) => {
-                global_variable_scope.insert(var_name.to_string());
-                prog_body.push_str(&format!("{}.comm {}, {}, 32\n", p, var_name, len * 8));
-            }
-            NodeType::Fn(fn_name, var_list_opt) => {
-                let fn_prologue = gen_fn_prologue(fn_name);
-                let fn_epilogue = gen_fn_epilogue();
-                // cause in function, we have to pass the offset of argument and scope contains argument
-                // to function body
-                let call_by_function = true;
-                let mut index_map: HashMap<String, isize> = HashMap::new();
-                let mut scope: HashMap<String, bool> = HashMap::new();
-                match var_list_opt {
-                    Some(var_list) => {
-                        let mut param_offset = 16 + (var_list.len() as isize - 6 - 1) * 8; // EBP + 16 (old EBP at 0, return address at 8)
-                        for i in 0..var_list.len() {
-                            scope.insert(var_list[i].to_string(), true);
-                            if i >= 6 {
-                                // this is stored in stack, starting from EBP + 16
-                                index_map.insert(var_list[i].to_string(), param_offset);
-                                param_offset -= 8;
-                            } else {
-                                // stored in regs, we use offset from 0-5 as index to regs.
-                                // and use (i+1)*-8 as their index, cause we will push them one by one at the new frame stack
-                                index_map.insert(var_list[i].to_string(), -(i as isize + 1) * 8);
-                            }
-                        }
-                    }
-                    None => {}
-                }
-                let fn_body = &gen_block(
-                    it,
-                    &index_map,
-                    &scope,
-                    idx,
-                    None,
-                    None,
-                    true,
-                    call_by_function,
-                    &global_variable_scope,
-                );
 
-                let tmp = if FLAG_FOR_MAIN_HAS_RET.load(atomic::Ordering::SeqCst) == false {
-                    format!(
-                        "{}movq $0, %rax\n\
-                         {}\
-                         {}ret\n",
-                        p,
-                        gen_fn_epilogue(),
-                        p
-                    )
-                } else {
-                    "".to_string()
-                };
-                let fn_tot = format!(
-                    "{}\
-                     {}\
-                     {}\
-                     {}\
-                     {}.cfi_endproc\n\
-                     {}:\n\
-                     {}.size   {}, .-{}\n",
-                    fn_prologue,
-                    fn_body,
-                    tmp,
-                    fn_epilogue,
+// load the `size` bytes, at most 8, at `off(%rax)` into `reg`, any register
+// but %rax, without reading past them: the last piece of a struct may end a
+// page
+fn gen_load_bytes(size: i64, off: i64, reg: &str) -> String {
+    let p = "        ";
+    // an eightbyte of floating members, of two floats or a double, or one
+    // float at the end
+    if is_xmm(reg) {
+        let mov = if size == 4 { "movss" } else { "movq" };
+        return format!("{}{} {}(%rax), {}\n", p, mov, off, reg);
+    }
+    let (reg32, reg8) = (sized(reg, Ty::I32), sized(reg, Ty::I8));
+    match size {
+        8 => format!("{}movq {}(%rax), {}\n", p, off, reg),
+        4 => format!("{}movl {}(%rax), {}\n", p, off, reg32),
+        2 => format!("{}movzwl {}(%rax), {}\n", p, off, reg32),
+        _ => {
+            // byte by byte, from the highest one down
+            let mut s = format!("{}movzbl {}(%rax), {}\n", p, off + size - 1, reg32);
+            for b in (0..size - 1).rev() {
+                s.push_str(&format!(
+                    "{}shlq $8, {}\n\
+                     {}movb {}(%rax), {}\n",
                     p,
-                    gen_labels("FE"),
+                    reg,
                     p,
-                    fn_name,
-                    fn_name
-                );
-                prog_body.push_str(&fn_tot);
+                    off + b,
+                    reg8
+                ));
             }
-            _ => panic!("`{:?}` type should not be here", it.entry),
+            s
         }
     }
-
-    match &tree.entry {
-        NodeType::Prog(prog_name) => format!(
-            "{}.file \"{}\"\n\
-             {}\
-             {}.ident	\"crust: 0.1 (By Haoran Wang)\"\n\
-             {}.section	.note.GNU-stack,\"\",@progbits\n",
-            p, prog_name, prog_body, p, p
-        ),
-        _ => panic!("Something went wrong in gen_prog"),
-    }
 }
 
-pub fn gen_declare(
-    tree: &ParseNode,
-    index_map: &HashMap<String, isize>,
-    scope: &HashMap<String, bool>, // 1 -> function argument, 0 -> local variables
-    idx: isize,
-    lbb: &str,
-    leb: &str,
-    loop_in_label: Option<&str>,
-    loop_out_label: Option<&str>,
-    global_variable_scope: &HashSet<String>,
-) -> (HashMap<String, isize>, HashMap<String, bool>, isize, String) {
-    // println!("in gen_declare with {:?}", tree.entry);
+// `op` with the constant `v` and `reg`, an immediate only has 32 bits
+fn gen_const_op(op: &str, v: i64, reg: &str) -> String {
     let p = "        ";
-    let mut index_map = index_map.clone();
-    let mut scope = scope.clone();
-    let mut idx = idx;
-    match &tree.entry {
-        NodeType::Declare(var_name, data_type) => {
-            let get_opt = scope.get(var_name);
-            match get_opt {
-                Some(flag) => {
-                    match flag {
-                        true => {
-                            // this variable is in scope, but was passed by function argument, so just shallow it
-                            scope.insert(var_name.to_string(), false);
-                            // println!("scope after insert: {:?}", scope);
-                            index_map.insert(var_name.to_string(), idx - 8);
-                            idx -= 8;
-                        }
-                        false => {
-                            panic!(
-                                "Error: redeclaration of variable `{}` in the same scope",
-                                var_name
-                            );
-                        }
-                    }
-                }
-                None => {
-                    // not declared
-                    scope.insert(var_name.to_string(), false);
-                    // println!("scope after insert: {:?}", scope);
-                    index_map.insert(var_name.to_string(), idx - 8);
-                    idx -= 8;
-                }
-            }
+    if fits_i32(v) {
+        format!("{}{} ${}, {}\n", p, op, v, reg)
+    } else {
+        format!("{}movabsq ${}, %r11\n{}{} %r11, {}\n", p, v, p, op, reg)
+    }
+}
 
-            // judge whether it's initialized
-            let mut e1 = String::new();
+fn fits_i32(v: i64) -> bool {
+    v >= i64::from(i32::MIN) && v <= i64::from(i32::MAX)
+}
 
-            if tree.child.is_empty() {
-                // just declare, we initialized it with 0
-                e1 = format!("        movq $0, %rax\n");
-            } else {
-                e1 = gen_stmt(
-                    tree.child
-                        .get(0)
-                        .expect("Statement::Declare Node has no child"),
-                    &index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-            }
-            let s = format!(
-                "{}\
-                 {}pushq %rax # gen_declare\n",
-                e1, p
-            );
-            (index_map, scope, idx, s)
-        }
-        _ => panic!("Type `{:?}` should not occur here", tree.entry),
+// the constant `v` as the immediate of an instruction on `ty`, of whose bits
+// only the low ones count; an i64 has to fit in the 32 bits it is extended from
+fn imm(v: i64, ty: Ty) -> Option<String> {
+    let v = match ty {
+        Ty::I8 => i64::from(v as i8),
+        Ty::I16 => i64::from(v as i16),
+        Ty::I32 | Ty::F32 => i64::from(v as i32),
+        Ty::I64 | Ty::F64 | Ty::F80 => v,
+    };
+    if fits_i32(v) {
+        Some(format!("${}", v))
+    } else {
+        None
     }
 }
 
-pub fn gen_for(
-    tree: &ParseNode,
-    index_map: &HashMap<String, isize>,
-    idx: isize,
-    global_variable_scope: &HashSet<String>,
-) -> String {
-    let p = "        ".to_string();
-    let label_begin_loop = gen_labels("BFOR");
-    let label_end_loop = gen_labels("EFOR");
-
-    let mut index_map = index_map.clone();
-    let mut idx: isize = idx;
-    // now in a new block now
-    let mut scope: HashMap<String, bool> = HashMap::new();
-    match tree.entry {
-        NodeType::Stmt(StmtType::ForDecl) => {
-            let (index_map_new, scope_new, idx_new, init) = gen_declare(
-                tree.child.get(0).unwrap(),
-                &index_map,
-                &scope,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            index_map = index_map_new.clone();
-            idx = idx_new;
-            scope = scope_new.clone();
-            let condition = gen_stmt(
-                tree.child.get(1).unwrap(),
-                &index_map,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            let post_exp = gen_stmt(
-                tree.child.get(2).unwrap(),
-                &index_map,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            let stmt = gen_block(
-                tree.child.get(3).unwrap(),
-                &index_map,
-                &scope,
-                idx,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                true,
-                false,
-                &global_variable_scope,
-            );
-            //           generate init (declare)
-            // BEGN_LOOP:
-            //           generate condition
-            //           cmpq $0, %rax
-            //           je  END_LOOP
-            //           generate statement
-            //           pos-expression
-            //           jmp BEGIN_LOOP
-            // END_LOOP:
-            //let b_deallocate = 8 * scope.len();
-            let mut b_deallocate = 0;
-            for (_, val) in scope.iter() {
-                if *val == false {
-                    b_deallocate += 8;
-                }
+// the 64-bit register `reg` in the width of `ty`; an xmm register has one
+// name for all, and a floating value in a general register is the integer of
+// its bits
+fn sized(reg: &str, ty: Ty) -> String {
+    let name = &reg[1..];
+    let ty = match ty {
+        Ty::F32 => Ty::I32,
+        Ty::F64 | Ty::F80 => Ty::I64,
+        t => t,
+    };
+    match (name, ty) {
+        (_, Ty::I64) => reg.to_string(),
+        _ if is_xmm(reg) => reg.to_string(),
+        ("rax", _) | ("rbx", _) | ("rcx", _) | ("rdx", _) => {
+            let c = &name[1..2];
+            match ty {
+                Ty::I32 => format!("%e{}x", c),
+                Ty::I16 => format!("%{}x", c),
+                _ => format!("%{}l", c),
             }
-            format!(
-                "{}\
-                 {}:\n\
-                 {}\
-                 {}cmpq $0, %rax\n\
-                 {}je {}\n\
-                 {}\
-                 {}\
-                 {}jmp {}\n\
-                 {}:\n\
-                 {}addq ${}, %rsp # for out clear block\n",
-                init,
-                label_begin_loop,
-                condition,
-                p,
-                p,
-                label_end_loop,
-                stmt,
-                post_exp,
-                p,
-                label_begin_loop,
-                label_end_loop,
-                p,
-                b_deallocate
-            )
         }
-        NodeType::Stmt(StmtType::For) => {
-            let init = gen_stmt(
-                tree.child.get(0).unwrap(),
-                &index_map,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            let condition = gen_stmt(
-                tree.child.get(1).unwrap(),
-                &index_map,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            let post_exp = gen_stmt(
-                tree.child.get(2).unwrap(),
-                &index_map,
-                idx,
-                &label_begin_loop,
-                &label_end_loop,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                &global_variable_scope,
-            );
-            let stmt = gen_block(
-                tree.child.get(3).unwrap(),
-                &index_map,
-                &scope,
-                idx,
-                Some(&label_begin_loop),
-                Some(&label_end_loop),
-                true,
-                false,
-                &global_variable_scope,
-            );
-            //           generate init
-            // BEGN_LOOP:
-            //           generate condition
-            //           cmpq $0, %rax
-            //           je  END_LOOP
-            //           generate statement
-            //           pos-expression
-            //           jmp BEGIN_LOOP
-            // END_LOOP:
-            // let b_deallocate = 8 * scope.len();
-            let mut b_deallocate = 0;
-            for (_, val) in scope.iter() {
-                if *val == false {
-                    b_deallocate += 8;
-                }
+        ("rsi", _) | ("rdi", _) => {
+            let c = &name[1..];
+            match ty {
+                Ty::I32 => format!("%e{}", c),
+                Ty::I16 => format!("%{}", c),
+                _ => format!("%{}l", c),
             }
-            format!(
-                "{}\
-                 {}:\n\
-                 {}\
-                 {}cmpq $0, %rax\n\
-                 {}je {}\n\
-                 {}\
-                 {}\
-                 {}jmp {}\n\
-                 {}:\n\
-                 {}addq ${}, %rsp # for out clear stack\n",
-                init,
-                label_begin_loop,
-                condition,
-                p,
-                p,
-                label_end_loop,
-                stmt,
-                post_exp,
-                p,
-                label_begin_loop,
-                label_end_loop,
-                p,
-                b_deallocate
-            )
         }
-        _ => panic!("Something wrong in gen_for"),
+        // %r8 to %r15
+        _ => {
+            let c = match ty {
+                Ty::I32 => 'd',
+                Ty::I16 => 'w',
+                _ => 'b',
+            };
+            format!("{}{}", reg, c)
+        }
     }
 }
-// gen_block() - into a new block, will have empty scope
-pub fn gen_block(
-    tree: &ParseNode,
-    index_map: &HashMap<String, isize>,
-    scope: &HashMap<String, bool>,
-    idx: isize,
-    loop_in_label: Option<&str>,
-    loop_out_label: Option<&str>,
-    flag: bool,
-    fn_def: bool,
-    global_variable_scope: &HashSet<String>,
-) -> String {
-    let p = "        ".to_string(); // 8 white spaces
-    let label_begin_block = gen_labels("BB");
-    let label_end_block = gen_labels("EB");
-    // iter every block
-    let mut stmts = String::new();
-    let mut index_map = index_map.clone();
-    let mut idx: isize = idx;
-    let mut current_scope: HashMap<String, bool> = scope.clone();
-    if fn_def == false {
-        current_scope = HashMap::new();
-    } else {
-        // this is a function definition block
-        // we need to store the input argument in the stack
-        // first push them in stack
-        let regs: Vec<&'static str> = vec!["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
-        if current_scope.len() > 6 {
-            for i in 0..6 {
-                stmts.push_str(&format!("{}pushq {}\n", p, regs[i]));
-            }
-        } else {
-            for i in 0..current_scope.len() {
-                stmts.push_str(&format!("{}pushq {}\n", p, regs[i]));
-            }
+
+fn is_xmm(reg: &str) -> bool {
+    reg.starts_with("%xmm")
+}
+
+// the operand size suffix of an instruction on `ty`, a floating one moved
+// as an integer of its size; only the x87 moves a long double, its 'q' is
+// never used
+fn suffix(ty: Ty) -> char {
+    match ty {
+        Ty::I8 => 'b',
+        Ty::I16 => 'w',
+        Ty::I32 | Ty::F32 => 'l',
+        Ty::I64 | Ty::F64 | Ty::F80 => 'q',
+    }
+}
+
+// the suffix of an SSE instruction on the floating `ty`, scalar single or
+// double
+fn sse(ty: Ty) -> &'static str {
+    match ty {
+        Ty::F32 => "ss",
+        _ => "sd",
+    }
+}
+
+// the condition code of `cond` and the one of its negation
+fn cc(cond: Cond) -> (&'static str, &'static str) {
+    match cond {
+        Cond::Eq => ("e", "ne"),
+        Cond::Ne => ("ne", "e"),
+        Cond::Lt => ("l", "ge"),
+        Cond::Le => ("le", "g"),
+        Cond::Gt => ("g", "le"),
+        Cond::Ge => ("ge", "l"),
+        Cond::ULt => ("b", "ae"),
+        Cond::ULe => ("be", "a"),
+        Cond::UGt => ("a", "be"),
+        Cond::UGe => ("ae", "b"),
+    }
+}
+
+// the condition code of the floating comparison `cond` and the one of its
+// negation, after ucomis with the operands swapped for Lt and Le: a NaN sets
+// CF, ZF and PF, so neither the one of Gt nor the one of Ge holds then; Eq
+// and Ne need PF too
+fn float_cc(cond: Cond) -> (&'static str, &'static str) {
+    match cond {
+        Cond::Gt | Cond::Lt => ("a", "be"),
+        Cond::Ge | Cond::Le => ("ae", "b"),
+        _ => cc(cond),
+    }
+}
+
+// whether a struct is passed in memory: one of more than 16 bytes, or one
+// with a long double in it
+fn in_memory(s: &StructVal) -> bool {
+    s.size > 16 || s.x87
+}
+
+// whether a struct is returned in memory, the same but for one of only a
+// long double, which comes back on the x87 like a long double does
+fn returned_in_memory(s: &StructVal) -> bool {
+    in_memory(s) && !on_x87(s)
+}
+
+fn on_x87(s: &StructVal) -> bool {
+    s.x87 && s.size == 16
+}
+
+// what an argument is, which says where it goes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Class {
+    Int,
+    Sse,
+    X87,
+    Struct(StructVal),
+}
+
+impl Class {
+    fn of(ty: Ty) -> Class {
+        match ty {
+            Ty::F80 => Class::X87,
+            Ty::F32 | Ty::F64 => Class::Sse,
+            _ => Class::Int,
         }
-        // XXX: cause right now the generated will use small amout of registers,
-        // but in the future will need to save callee-saved registers in the function stack
     }
+}
 
-    for it in &tree.child {
-        // iter through every block-item
-        match &it.entry {
-            NodeType::Declare(_var_name, DataType::I64) => {
-                let (index_map_new, scope_new, idx_new, s) = gen_declare(
-                    it,
-                    &index_map,
-                    &current_scope,
-                    idx,
-                    &label_begin_block,
-                    &label_end_block,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                index_map = index_map_new.clone();
-                idx = idx_new;
-                current_scope = scope_new.clone();
-                stmts.push_str(&s);
+// the register an argument of type `ty` is in, for the first general and
+// xmm registers arg_places gives it
+fn arg_reg(ty: Ty, (reg, xmm): (usize, usize)) -> &'static str {
+    match Class::of(ty) {
+        Class::Sse => XMM_ARG_REGS[xmm],
+        _ => ARG_REGS[reg],
+    }
+}
+
+// for each eightbyte of a struct passed in registers, whether it goes in an
+// xmm register
+fn eightbytes(s: &StructVal) -> &[bool] {
+    &s.sse[..((s.size + 7) / 8) as usize]
+}
+
+// the register of each eightbyte of a struct passed in registers: the
+// general ones take `regs` in order from `first.0`, the others `xmms` from
+// `first.1`
+fn eightbyte_regs(
+    s: &StructVal,
+    regs: &[&'static str],
+    xmms: &[&'static str],
+    first: (usize, usize),
+) -> Vec<&'static str> {
+    let (mut reg, mut xmm) = first;
+    eightbytes(s)
+        .iter()
+        .map(|sse| {
+            if *sse {
+                xmm += 1;
+                xmms[xmm - 1]
+            } else {
+                reg += 1;
+                regs[reg - 1]
             }
-            NodeType::Stmt(StmtType::Compound) => {
-                stmts.push_str(&gen_block(
-                    it,
-                    &index_map,
-                    &current_scope,
-                    idx,
-                    loop_in_label,
-                    loop_out_label,
-                    true,
-                    false, // call by  function not true
-                    &global_variable_scope,
-                ));
+        })
+        .collect()
+}
+
+// where arg_places puts an argument
+type ArgPlace = Result<(usize, usize), i64>;
+
+// System V calling convention: the arguments come in %rdi, %rsi, %rdx, %rcx,
+// %r8 and %r9 in order, after the address a struct returned in memory goes
+// to, and a float or a double in %xmm0 to %xmm7 in order.  A struct of up to
+// 16 bytes takes one register per eightbyte, an xmm one for an eightbyte of
+// only floating members, if enough of both are left.  Everything else is on
+// the stack, the first one at the lowest address.  Each argument gets
+// Ok(the first general and xmm registers it would take) or Err(its offset
+// among the ones on the stack), the second is how many bytes those take and
+// the third the first general and xmm registers left, where the arguments
+// after the named ones of a variadic function start.  A long double is
+// always on the stack, and it and a struct aligned to 16 start at an offset
+// aligned so.
+fn arg_places(ret_in_memory: bool, classes: &[Class]) -> (Vec<ArgPlace>, i64, (usize, usize)) {
+    let mut next_reg = usize::from(ret_in_memory);
+    let mut next_xmm = 0;
+    let mut stack_size = 0;
+    let mut places = Vec::new();
+    for arg in classes.iter() {
+        // how many general and xmm registers it takes
+        let n = match arg {
+            Class::Struct(s) if in_memory(s) => None,
+            Class::Struct(s) => {
+                let sse = eightbytes(s).iter().filter(|sse| **sse).count();
+                Some((eightbytes(s).len() - sse, sse))
+            }
+            Class::Int => Some((1, 0)),
+            Class::Sse => Some((0, 1)),
+            Class::X87 => None,
+        };
+        match n {
+            Some((n, m))
+                if next_reg + n <= ARG_REGS.len() && next_xmm + m <= XMM_ARG_REGS.len() =>
+            {
+                places.push(Ok((next_reg, next_xmm)));
+                next_reg += n;
+                next_xmm += m;
             }
             _ => {
-                let s = gen_stmt(
-                    it,
-                    &index_map,
-                    idx,
-                    &label_begin_block,
-                    &label_end_block,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                stmts.push_str(&s);
+                let (size, align) = match arg {
+                    Class::Struct(s) => (align_to(s.size.max(1), 8), s.align),
+                    Class::X87 => (16, 16),
+                    _ => (8, 8),
+                };
+                stack_size = align_to(stack_size, align.max(8));
+                places.push(Err(stack_size));
+                stack_size += size;
             }
         }
     }
-    let mut b_deallocate = 0;
-    for (_, val) in current_scope.iter() {
-        if *val == false {
-            b_deallocate += 8;
-        }
-    }
+    (places, stack_size, (next_reg, next_xmm))
+}
 
-    format!(
-        "{}:\n\
-         {}\
-         {}:\n\
-         {}addq ${}, %rsp # block out\n",
-        label_begin_block, stmts, label_end_block, p, b_deallocate
-    )
+fn param_places(func: &Function) -> (Vec<ArgPlace>, i64, (usize, usize)) {
+    let classes: Vec<Class> = func
+        .params
+        .iter()
+        .map(|param| match param {
+            Param::Value(r) => Class::of(func.ty(*r)),
+            Param::Struct(_, s) => Class::Struct(*s),
+        })
+        .collect();
+    let ret_in_memory = matches!(func.ret, RetKind::Struct(s) if returned_in_memory(&s));
+    arg_places(ret_in_memory, &classes)
 }
 
-fn gen_addr(
-    tree: &ParseNode,
-    index_map: &HashMap<String, isize>,
-    idx: isize,
-    lbb: &str, // label_begin_block
-    leb: &str, // label_end_block
-    loop_in_label: Option<&str>,
-    loop_out_label: Option<&str>,
-    global_variable_scope: &HashSet<String>,
-) -> String {
-    let p = "        ".to_string();
-    // first judge whether it is a global variable or local variable
-    match &tree.entry {
-        NodeType::ArrayRef(var_name) => {
-            match index_map.get(var_name) {
-                Some(c) => {
-                    // local array
-                    panic!(format!("Error: address to local array not implemented"));
-                }
-                None => {
-                    // not local but should check in global variable scope
-                    match global_variable_scope.contains(var_name) {
-                        true => {
-                            // address of array[exp]
-                            // should generate exp -> rax
-                            let get_index = gen_stmt(
-                                tree.child
-                                    .get(0)
-                                    .expect("Statement::Declare Node has no child"),
-                                index_map,
-                                idx,
-                                lbb,
-                                leb,
-                                loop_in_label,
-                                loop_out_label,
-                                &global_variable_scope,
-                            );
-                            //                get index => rax
-                            //        leaq    0(,%rax,8), %rdx
-                            //        movq    a@GOTPCREL(%rip), %rax
-                            //        addq    %rdx, %rax
-                            format!(
-                                "{}\
-                                 {}pushq %rdx\n\
-                                 {}leaq  0(,%rax,8), %rdx\n\
-                                 {}movq {}@GOTPCREL(%rip), %rax\n\
-                                 {}addq %rdx, %rax\n\
-                                 {}popq %rdx\n",
-                                get_index, p, p, p, var_name,p, p,
-                            )
-                        }
-                        false => {
-                            panic!(format!("Error: Using address operator against an undeclared variable `{}`", var_name));
-                        }
+fn call_places(ret: &CallRet, args: &[Arg]) -> (Vec<ArgPlace>, i64) {
+    let classes: Vec<Class> = args
+        .iter()
+        .map(|arg| match arg {
+            Arg::Value(ty, _) => Class::of(*ty),
+            Arg::Struct(_, s) => Class::Struct(*s),
+        })
+        .collect();
+    let ret_in_memory = matches!(ret, CallRet::Struct(_, s) if returned_in_memory(s));
+    let (places, stack_size, _) = arg_places(ret_in_memory, &classes);
+    (places, stack_size)
+}
+
+// the argument registers the values passed in them come in or go to, for the
+// register allocation to give them if it can
+fn arg_hints(func: &Function) -> HashMap<VReg, &'static str> {
+    let mut hints = HashMap::new();
+    for block in func.blocks.iter() {
+        for inst in block.insts.iter() {
+            if let Inst::Call { ret, args, .. } = inst {
+                for (arg, place) in args.iter().zip(call_places(ret, args).0) {
+                    if let (Arg::Value(ty, Operand::Reg(r)), Ok(first)) = (arg, place) {
+                        hints.insert(*r, arg_reg(*ty, first));
                     }
                 }
             }
         }
-        NodeType::Var(name) => {
-            match index_map.get(name) {
-                Some(c) => {
-                    // local variable
-                    format!("{}leaq {}(%rbp), %rax\n", p, c) // put address in rax
-                }
-                None => {
-                    // not local but should check in global
-                    if global_variable_scope.contains(name) {
-                        // ok
-                        format!("{}movq {}@GOTPCREL(%rip), %rax\n", p, name)
-                    } else {
-                        panic!(format!("Using address operator against an undeclared variable `{}`", name));
-                    }
-                }
-            }
+    }
+    // a parameter rather stays where it comes in
+    for (param, place) in func.params.iter().zip(param_places(func).0) {
+        if let (Param::Value(r), Ok(first)) = (param, place) {
+            hints.insert(*r, arg_reg(func.ty(*r), first));
         }
-        _ => {
-            if tree.child.is_empty() {
-                panic!(format!("Can not use address(&) operator to rhs({:?})", tree.entry));
-            } else {
-                gen_addr(
-                    tree.child.get(0).expect("In gen address no child node now"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    global_variable_scope)
+    }
+    hints
+}
+
+// what a move of gen_moves reads
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Src {
+    Reg(&'static str),
+    Mem(String),
+    Imm(i64),
+    // the `size` bytes at `off` from the address in the register or memory
+    Bytes(Box<Src>, i64, i64),
+}
+
+impl Src {
+    // the register it reads, if any
+    fn reads(&self) -> Option<&'static str> {
+        match self {
+            Src::Reg(reg) => Some(reg),
+            Src::Bytes(base, _, _) => base.reads(),
+            Src::Mem(_) | Src::Imm(_) => None,
+        }
+    }
+
+    fn rename(&mut self, from: &str, to: &'static str) {
+        match self {
+            Src::Reg(reg) if *reg == from => *self = Src::Reg(to),
+            Src::Bytes(base, _, _) => base.rename(from, to),
+            _ => {}
+        }
+    }
+}
+
+// The moves as if all were done at once, each destination, a register or
+// memory, getting what its source held before any of them was written.  A
+// move waits while another one still reads its destination.  When all of
+// them wait they are in cycles, each register being written by one move at
+// most, and saving the destination of one to %r11 breaks its cycle.
+fn gen_moves(mut moves: Vec<(String, Src)>) -> String {
+    let p = "        ";
+    let mut s = String::new();
+    while !moves.is_empty() {
+        let ready = (0..moves.len()).find(|&i| {
+            moves
+                .iter()
+                .enumerate()
+                .all(|(j, (_, src))| j == i || src.reads() != Some(moves[i].0.as_str()))
+        });
+        match ready {
+            Some(i) => {
+                let (dst, src) = moves.remove(i);
+                s.push_str(&gen_move(&dst, &src));
+            }
+            None => {
+                let dst = moves[0].0.clone();
+                s.push_str(&format!("{}movq {}, %r11\n", p, dst));
+                for (_, src) in moves.iter_mut() {
+                    src.rename(&dst, "%r11");
+                }
             }
         }
     }
+    s
 }
-pub fn gen_stmt(
-    tree: &ParseNode,
-    index_map: &HashMap<String, isize>,
-    idx: isize,
-    lbb: &str, // label_begin_block
-    leb: &str, // label_end_block
-    loop_in_label: Option<&str>,
-    loop_out_label: Option<&str>,
-    global_variable_scope: &HashSet<String>,
-) -> String {
-    let p = "        ".to_string(); // 8 white spaces
-    match &tree.entry {
-        NodeType::StringLiteral(data, tag) => format!(
-            "{}.section .rodata\n\
-             {}:\n\
-             {}.string \"{}\"\n\
-             {}.text\n\
-             {}leaq {}(%rip), %rax\n",
-            p, tag, p, data, p, p, tag,
+
+// one move of gen_moves, through %rax where it has to be; movq moves
+// between xmm registers, and to and from them, too
+fn gen_move(dst: &str, src: &Src) -> String {
+    let p = "        ";
+    let to_reg = dst.starts_with('%');
+    match src {
+        Src::Reg(reg) if *reg == dst => String::new(),
+        Src::Reg(reg) => format!("{}movq {}, {}\n", p, reg, dst),
+        Src::Mem(mem) if to_reg => format!("{}movq {}, {}\n", p, mem, dst),
+        // there is no immediate to an xmm register
+        Src::Imm(v) if fits_i32(*v) && !is_xmm(dst) => format!("{}movq ${}, {}\n", p, v, dst),
+        Src::Imm(v) if to_reg && !is_xmm(dst) => format!("{}movabsq ${}, {}\n", p, v, dst),
+        Src::Bytes(base, off, size) if to_reg => format!(
+            "{}{}",
+            gen_move("%rax", base),
+            gen_load_bytes(*size, *off, dst)
         ),
-        NodeType::ConditionalExp => {
-            if tree.child.len() == 1 {
-                // just one <logical-or-exp>
-                gen_stmt(
-                    tree.child
-                        .get(0)
-                        .expect("Conditional Expression has no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                )
-            } else if tree.child.len() == 3 {
-                // <logical-or-exp> "?" <exp> ":" <conditional-exp>
-                let e1_as = gen_stmt(
-                    tree.child.get(0).expect("Conditional expression no e1"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                let e2_as = gen_stmt(
-                    tree.child.get(1).expect("conditional expression no e2"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                let e3_as = gen_stmt(
-                    tree.child.get(2).expect("conditional expression no e3"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
+        _ => format!("{}{}movq %rax, {}\n", gen_move("%rax", src), p, dst),
+    }
+}
 
-                let label_e3 = gen_labels("E3");
-                let label_end = gen_labels("ENDCOND");
-                format!(
-                    "{}\
-                     {}cmpq $0, %rax\n\
-                     {}je {}\n\
-                     {}\
-                     {}jmp {}\n\
-                     {}:\n\
-                     {}\
-                     {}:\n",
-                    e1_as, p, p, label_e3, e2_as, p, label_end, label_e3, e3_as, label_end,
-                )
-            } else {
-                panic!("Error: something wrong in conditional expression")
+// The frame of a function: %rbp, then the address a struct is returned to,
+// the register save area of a variadic function, the stack slots, the
+// virtual registers that are spilled and the callee-saved registers the
+// function uses.
+struct Frame<'a> {
+    cx: &'a mut Context,
+    func: &'a Function,
+    index: usize, // of the function in the module, for its labels
+    alloc: Allocation,
+    uses: Vec<usize>, // how many times each virtual register is read
+    ret_addr: i64,    // where the address a struct is returned to is kept
+    save_area: i64,   // where va_arg finds the arguments that came in registers
+    slots: Vec<i64>,  // offset of each slot from %rbp
+    spills: i64,      // offset of the first spilled register
+    saves: i64,       // offset of the first saved register
+    size: i64,
+}
+
+impl<'a> Frame<'a> {
+    fn new(cx: &'a mut Context, func: &'a Function, index: usize) -> Frame<'a> {
+        let alloc = allocate(func, &arg_hints(func));
+        let mut uses = vec![0; func.vregs.len()];
+        for block in func.blocks.iter() {
+            let insts = block.insts.iter().flat_map(|inst| inst.uses());
+            for r in insts.chain(block.term.uses()) {
+                uses[r.0] += 1;
             }
         }
-        NodeType::FnCall(fn_name) => {
-            // now change to x64 calling convetion
-            // arguments: 1st 2nd 3rd 4th 5th 6th ...
-            //            rdi rsi rdx rcx r8  r9  stack
-            // iter every expression in reverse direction
-            // and then push them in stack
-            let mut s: String = String::new();
-            // should follow AMD System V ABI,
-            // The begin of main function stack is aligned 8,
-            // And end of the input argument area shall be aligned on a 16 (32, if __m256 is passed on stack) byte boundary.
-            // so if we have n local variables, we pushed them into the stack.
-            // and we need to store r10 and r11, and we should put argument with index bigger than 6 into stack
-            // so the total element pushed into stack should be (n + 2 + (arg_list.len() - 6 > 0 ? arg_list.len() - 6 : 0))
-            // if this value % 2 == 1, then we should push one element into stack.
-            // Now I only handled this in main function, and I should also track the stack align for every function that we defined,
-            // so we can make sure every function follows the ABI
-
-            // first judge whether we need to push one extra element into stack
-            let tmp = match tree.child.len() {
-                0...6 => 0,
-                _ => tree.child.len() - 6,
-            };
-            if cfg!(feature="debug") {
-                println!("index_map.len() = {}", index_map.len());
-                println!("tmp = {}", tmp);
+        let mut size = 0;
+        let ret_addr = match func.ret {
+            RetKind::Struct(s) if returned_in_memory(&s) => {
+                size += 8;
+                -size
             }
-            let extra: bool = match (index_map.len() + tmp + 2) % 2 {
-                0 => false,
-                _ => true,
-            };
+            _ => 0,
+        };
+        let save_area = if func.variadic {
+            size = align_to(size + REG_SAVE_SIZE, 16);
+            -size
+        } else {
+            0
+        };
+        let mut slots = Vec::new();
+        for (slot_size, align) in func.slots.iter() {
+            size = align_to(size + slot_size, *align);
+            slots.push(-size);
+        }
+        let spills = -(size + 8);
+        size += 8 * alloc.spills as i64;
+        let saves = -(size + 8);
+        size += 8 * alloc.saved.len() as i64;
+        Frame {
+            cx,
+            func,
+            index,
+            alloc,
+            uses,
+            ret_addr,
+            save_area,
+            slots,
+            spills,
+            saves,
+            size: align_to(size, 16),
+        }
+    }
 
-            if extra == true {
-                // then we need to add one element to stack to make sure follow the abi
-                s.push_str(&format!("{}pushq $0\n", p));
-            }
+    fn loc(&self, r: VReg) -> Loc {
+        self.alloc.locs[r.0].expect("a register the function uses has a location")
+    }
 
-            //then save the caller saves regs: r10, r11
-            s.push_str(&format!("{}pushq %r10\n", p));
-            s.push_str(&format!("{}pushq %r11\n", p));
-
-            // mov argument into registers or stack if it's 7th element or later argument
-            let regs: Vec<&'static str> = vec!["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
-            for i in 0..tree.child.len() {
-                s.push_str(&gen_stmt(
-                    tree.child.get(i).unwrap(),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                ));
-                if i >= 6 {
-                    // store in stack
-                    s.push_str(&format!("{}pushq %rax\n", p));
-                } else {
-                    // store into regs.
-                    s.push_str(&format!(
-                        "{}movq %rax, {}\n{}movq $0, %rax\n",
-                        p, regs[i], p
-                    ));
-                }
-            }
-            // call the function
-            s.push_str(&format!("{}call {}@PLT\n", p, fn_name));
-            // after the callee function returns, remove the arguments from stack
-            if tree.child.len() > 6 {
-                s.push_str(&format!(
-                    "{}addq ${}, %rsp # remove the arguments\n",
-                    p,
-                    8 * (tree.child.len() - 6)
-                ));
+    // the machine register `r` is in, if it is not spilled
+    fn machine_reg(&self, r: VReg) -> Option<&'static str> {
+        match self.loc(r) {
+            Loc::Reg(reg) => Some(reg),
+            Loc::Spill(_) => None,
+        }
+    }
+
+    // register `r` as an operand of an instruction on `ty`
+    fn at(&self, r: VReg, ty: Ty) -> String {
+        match self.loc(r) {
+            Loc::Reg(reg) => sized(reg, ty),
+            Loc::Spill(i) => format!("{}(%rbp)", self.spills - 8 * i as i64),
+        }
+    }
+
+    fn slot(&self, s: Slot) -> i64 {
+        self.slots[s.0]
+    }
+
+    fn block(&self, b: BlockId) -> String {
+        format!(".LBB{}_{}", self.index, b.0)
+    }
+
+    // the operand in all of `reg`, an xmm register getting a constant
+    // through %r11
+    fn load(&self, op: Operand, reg: &str) -> String {
+        let p = "        ";
+        match op {
+            Operand::Reg(r) if self.machine_reg(r) == Some(reg) => String::new(),
+            Operand::Reg(r) => format!("{}movq {}, {}\n", p, self.at(r, Ty::I64), reg),
+            Operand::Imm(_) if is_xmm(reg) => {
+                format!("{}{}movq %r11, {}\n", self.load(op, "%r11"), p, reg)
             }
-            s.push_str(&format!("{}popq %r11\n", p));
-            s.push_str(&format!("{}popq %r10\n", p));
+            Operand::Imm(v) if fits_i32(v) => format!("{}movq ${}, {}\n", p, v, reg),
+            Operand::Imm(v) => format!("{}movabsq ${}, {}\n", p, v, reg),
+        }
+    }
 
-            if extra == true {
-                s.push_str(&format!("{}addq $8, %rsp\n", p));
+    // `reg` to the register `r`
+    fn store(&self, r: VReg, reg: &str) -> String {
+        if self.machine_reg(r) == Some(reg) {
+            return String::new();
+        }
+        format!("        movq {}, {}\n", reg, self.at(r, Ty::I64))
+    }
+
+    // where to compute the value of `dst`: its own machine register, unless
+    // it is spilled or one of `avoid`, read after the first operand is
+    // there, is in it
+    fn target(&self, dst: VReg, avoid: &[Operand]) -> &'static str {
+        match self.machine_reg(dst) {
+            Some(reg)
+                if avoid.iter().all(|op| match op {
+                    Operand::Reg(r) => self.machine_reg(*r) != Some(reg),
+                    Operand::Imm(_) => true,
+                }) =>
+            {
+                reg
             }
-            s
+            _ if self.func.ty(dst).is_float() => "%xmm15",
+            _ => "%rax",
+        }
+    }
+
+    // the floating `op` as the source operand of an SSE instruction, a
+    // constant loaded into %xmm14
+    fn float_source(&self, op: Operand) -> (String, String) {
+        match op {
+            Operand::Reg(r) => (String::new(), self.at(r, Ty::I64)),
+            Operand::Imm(_) => (self.load(op, "%xmm14"), "%xmm14".to_string()),
         }
-        NodeType::Stmt(stmt) => match stmt {
-            StmtType::Return => format!(
-                "{}\
-                 {}\
-                 {}ret\n",
-                gen_stmt(
-                    tree.child.get(0).expect("Statement node no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                ),
-                gen_fn_epilogue(),
+    }
+
+    // `op` as the source operand of an instruction on `ty`, loaded into
+    // `scratch` first if it does not fit there
+    fn source(&self, op: Operand, ty: Ty, scratch: &str) -> (String, String) {
+        match op {
+            Operand::Reg(r) => (String::new(), self.at(r, ty)),
+            Operand::Imm(v) => match imm(v, ty) {
+                Some(imm) => (String::new(), imm),
+                None => (self.load(op, scratch), sized(scratch, ty)),
+            },
+        }
+    }
+
+    // the long double `op` pushed on the x87 stack, a constant, which is a
+    // double, through the red zone
+    fn fld(&self, op: Operand) -> String {
+        let p = "        ";
+        match op {
+            Operand::Reg(r) => format!("{}fldt {}\n", p, self.at(r, Ty::F80)),
+            Operand::Imm(_) => format!(
+                "{}{}movq %r11, -8(%rsp)\n{}fldl -8(%rsp)\n",
+                self.load(op, "%r11"),
+                p,
                 p
             ),
-            StmtType::Conditional(_) => {
-                let e1_as = gen_stmt(
-                    tree.child.get(0).expect("Conditional node no e1"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                let s1_as = gen_stmt(
-                    tree.child.get(1).expect("conditional node no s1"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                );
-                let s2_as: String = if tree.child.len() == 2 {
-                    "".to_string()
-                } else {
-                    gen_stmt(
-                        tree.child.get(2).expect("conditional node no s2"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    )
+        }
+    }
+
+    // the top of the x87 stack popped into the long double `r`
+    fn fstp(&self, r: VReg) -> String {
+        format!("        fstpt {}\n", self.at(r, Ty::F80))
+    }
+
+    // what gen_moves takes the operand from
+    fn src(&self, op: Operand) -> Src {
+        match op {
+            Operand::Reg(r) => match self.loc(r) {
+                Loc::Reg(reg) => Src::Reg(reg),
+                Loc::Spill(_) => Src::Mem(self.at(r, Ty::I64)),
+            },
+            Operand::Imm(v) => Src::Imm(v),
+        }
+    }
+
+    fn gen_fn(&mut self) -> String {
+        let p = "        ";
+        let func = self.func;
+        let mut s = gen_fn_prologue(self.cx, &func.name, func.global);
+        if self.size > 0 {
+            s.push_str(&format!("{}subq ${}, %rsp\n", p, self.size));
+        }
+        for (i, reg) in self.alloc.saved.iter().enumerate() {
+            s.push_str(&format!(
+                "{}movq {}, {}(%rbp)\n",
+                p,
+                reg,
+                self.saves - 8 * i as i64
+            ));
+        }
+        s.push_str(&self.gen_params());
+        for (i, block) in func.blocks.iter().enumerate() {
+            s.push_str(&format!("{}:\n", self.block(BlockId(i))));
+            // a comparison only the branch after it reads goes right into the
+            // jump, but for the floating == and !=, which take two flags
+            let fused = match (block.insts.last(), &block.term) {
+                (
+                    Some(Inst::Cmp { dst, cond, ty, .. }),
+                    Terminator::Branch {
+                        cond: Operand::Reg(r),
+                        ..
+                    },
+                ) => {
+                    dst == r
+                        && self.uses[r.0] == 1
+                        && !(ty.is_float() && matches!(cond, Cond::Eq | Cond::Ne))
+                }
+                _ => false,
+            };
+            let n = block.insts.len() - usize::from(fused);
+            for inst in block.insts[..n].iter() {
+                s.push_str(&self.gen_inst(inst));
+            }
+            match (&block.insts[n..], &block.term) {
+                (
+                    [Inst::Cmp {
+                        cond, ty, lhs, rhs, ..
+                    }],
+                    Terminator::Branch { then, els, .. },
+                ) => {
+                    let (cmp, cc) = self.gen_compare(*cond, *ty, *lhs, *rhs);
+                    s.push_str(&cmp);
+                    s.push_str(&self.gen_cond_jump(cc, *then, *els, BlockId(i)));
+                }
+                _ => s.push_str(&self.gen_term(&block.term, BlockId(i))),
+            }
+        }
+        // every `return` comes here
+        s.push_str(&format!(".LRET{}:\n", self.index));
+        for (i, reg) in self.alloc.saved.iter().enumerate() {
+            s.push_str(&format!(
+                "{}movq {}(%rbp), {}\n",
+                p,
+                self.saves - 8 * i as i64,
+                reg
+            ));
+        }
+        s.push_str(&gen_fn_epilogue());
+        s.push_str(&format!(
+            "{}ret\n\
+             {}.cfi_endproc\n\
+             {}:\n\
+             {}.size   {}, .-{}\n",
+            p,
+            p,
+            self.cx.label("FE"),
+            p,
+            func.name,
+            func.name
+        ));
+        s
+    }
+
+    // the parameters from where arg_places says the caller put them; the
+    // ones on the stack are above the return address
+    fn gen_params(&self) -> String {
+        let p = "        ";
+        let mut s = String::new();
+        if self.ret_addr != 0 {
+            s.push_str(&format!("{}movq %rdi, {}(%rbp)\n", p, self.ret_addr));
+        }
+        // all of the argument registers, whichever the unnamed ones are in
+        if self.func.variadic {
+            for (i, reg) in ARG_REGS.iter().enumerate() {
+                let at = self.save_area + 8 * i as i64;
+                s.push_str(&format!("{}movq {}, {}(%rbp)\n", p, reg, at));
+            }
+            for (i, reg) in XMM_ARG_REGS.iter().enumerate() {
+                let at = self.save_area + REG_SAVE_GP_SIZE + 16 * i as i64;
+                s.push_str(&format!("{}movq {}, {}(%rbp)\n", p, reg, at));
+            }
+        }
+        // %rbp + 16 (old %rbp at 0, return address at 8)
+        let stack = |offset: i64| format!("{}(%rbp)", 16 + offset);
+
+        // the scalars go where they are allocated all at once, a parameter
+        // may be allocated the register another one comes in
+        let mut moves = Vec::new();
+        // the structs on the stack after that, copying them takes %rcx and %rdx
+        let mut copies = String::new();
+        for (param, place) in self.func.params.iter().zip(param_places(self.func).0) {
+            match (param, place) {
+                // one that is never read is not moved anywhere
+                (Param::Value(r), _) if !self.alloc.entry.contains(r) => {}
+                (Param::Value(r), Ok(first)) => moves.push((
+                    self.at(*r, Ty::I64),
+                    Src::Reg(arg_reg(self.func.ty(*r), first)),
+                )),
+                // a long double is copied to its place with the structs
+                (Param::Value(r), Err(offset)) if self.func.ty(*r) == Ty::F80 => {
+                    copies.push_str(&format!("{}fldt {}\n{}", p, stack(offset), self.fstp(*r)));
+                }
+                (Param::Value(r), Err(offset)) => {
+                    moves.push((self.at(*r, Ty::I64), Src::Mem(stack(offset))))
+                }
+                (Param::Struct(slot, st), Ok(first)) => {
+                    let regs = eightbyte_regs(st, &ARG_REGS, &XMM_ARG_REGS, first);
+                    for (w, reg) in regs.iter().enumerate() {
+                        s.push_str(&format!(
+                            "{}movq {}, {}(%rbp)\n",
+                            p,
+                            reg,
+                            self.slot(*slot) + 8 * w as i64
+                        ));
+                    }
+                }
+                (Param::Struct(slot, st), Err(offset)) => {
+                    copies.push_str(&format!(
+                        "{}leaq {}, %rax\n\
+                         {}leaq {}(%rbp), %rcx\n\
+                         {}",
+                        p,
+                        stack(offset),
+                        p,
+                        self.slot(*slot),
+                        gen_copy(st.size)
+                    ));
+                }
+            }
+        }
+        s.push_str(&gen_moves(moves));
+        s.push_str(&copies);
+        s
+    }
+
+    fn gen_inst(&mut self, inst: &Inst) -> String {
+        let p = "        ";
+        let func = self.func;
+        match inst {
+            Inst::Copy { dst, src } if func.ty(*dst) == Ty::F80 => {
+                format!("{}{}", self.fld(*src), self.fstp(*dst))
+            }
+            Inst::Copy { dst, src } => self.gen_copy_reg(*dst, *src),
+            Inst::Trunc { dst, src } => self.gen_copy_reg(*dst, Operand::Reg(*src)),
+            // a register of type i8 or i16 is computed on as an i32, the low
+            // bytes come out right for everything but division and right
+            // shifts, which C only does on promoted operands anyway
+            Inst::Unary {
+                op: UnOp::Neg,
+                dst,
+                src,
+            } if func.ty(*dst) == Ty::F80 => {
+                format!("{}{}fchs\n{}", self.fld(*src), p, self.fstp(*dst))
+            }
+            // flip the sign bit
+            Inst::Unary {
+                op: UnOp::Neg,
+                dst,
+                src,
+            } if func.ty(*dst).is_float() => {
+                let t = self.target(*dst, &[]);
+                let sign = match func.ty(*dst) {
+                    Ty::F32 => 1 << 31,
+                    _ => i64::MIN,
                 };
-                let label_s2 = gen_labels("S2");
-                let label_end = gen_labels("ENDIF");
                 format!(
-                    "{}\
-                     {}cmpq $0, %rax\n\
-                     {}je {}\n\
-                     {}\
-                     {}jmp {}\n\
-                     {}:\n\
-                     {}\
-                     {}:\n",
-                    e1_as, p, p, label_s2, s1_as, p, label_end, label_s2, s2_as, label_end,
+                    "{}{}{}xorps %xmm14, {}\n{}",
+                    self.load(*src, t),
+                    self.load(Operand::Imm(sign), "%xmm14"),
+                    p,
+                    t,
+                    self.store(*dst, t)
                 )
             }
-            StmtType::Exp => gen_stmt(
-                tree.child.get(0).expect("Statement Node no child"),
-                index_map,
-                idx,
-                lbb,
-                leb,
-                loop_in_label,
-                loop_out_label,
-                &global_variable_scope,
-            ),
-            StmtType::Continue => match loop_in_label {
-                Some(l) => format!("{}jmp {} # Continue\n", p, l),
-                None => panic!("Continue should be in the loop scope"),
-            },
-            StmtType::Break => match loop_out_label {
-                Some(l) => format!("{}jmp {} # Break\n", p, l),
-                None => panic!("Break shoule be in the loop scope"),
-            },
-            StmtType::For | StmtType::ForDecl => {
-                gen_for(tree, index_map, idx, &global_variable_scope)
-            }
-            StmtType::Do => {
-                // LBB.
-                // stmt
-                // exp
-                // cmpq $1, %rax
-                // je  LBB
-                // LEB
-                let lbb = gen_labels("BDO");
-                let leb = gen_labels("EDO");
-                let scope: HashMap<String, bool> = HashMap::new();
-                let stmts = gen_block(
-                    tree.child.get(0).unwrap(),
-                    index_map,
-                    &scope,
-                    idx,
-                    loop_in_label,
-                    loop_out_label,
-                    true,
-                    false,
-                    &global_variable_scope,
-                ); // should enter a new scope
-                let exp = gen_stmt(
-                    tree.child.get(1).unwrap(),
-                    index_map,
-                    idx,
-                    &lbb,
-                    &leb,
-                    Some(&lbb),
-                    Some(&leb),
-                    &global_variable_scope,
-                );
+            Inst::Unary { op, dst, src } => {
+                let ty = max32(func.ty(*dst));
+                let t = self.target(*dst, &[]);
+                let op = match op {
+                    UnOp::Neg => "neg",
+                    UnOp::Not => "not",
+                };
                 format!(
-                    "{}:\n\
-                     {}\
-                     {}\
-                     {}cmpq $1, %rax\n\
-                     {}je   {}\n\
-                     {}:\n",
-                    lbb, stmts, exp, p, p, lbb, leb
+                    "{}{}{}{} {}\n{}",
+                    self.load(*src, t),
+                    p,
+                    op,
+                    suffix(ty),
+                    sized(t, ty),
+                    self.store(*dst, t)
                 )
             }
-            StmtType::While => {
-                // LBB.
-                // exp
-                // cmpq $1, %rax
-                // jne LEB
-                // stmt
-                // jmp LBB
-                // LEB.
-                let lbb = gen_labels("BWHILE");
-                let leb = gen_labels("EWHILE");
-                let scope: HashMap<String, bool> = HashMap::new();
-                let exp = gen_stmt(
-                    tree.child.get(0).unwrap(),
-                    index_map,
-                    idx,
-                    &lbb,
-                    &leb,
-                    Some(&lbb),
-                    Some(&leb),
-                    &global_variable_scope,
-                );
-                let stmts = gen_block(
-                    tree.child.get(1).unwrap(),
-                    index_map,
-                    &scope,
-                    idx,
-                    Some(&lbb),
-                    Some(&leb),
-                    true,
-                    false,
-                    &global_variable_scope,
-                ); // should enter a new scope
+            // the `r` forms take the operand pushed last from the other
+            Inst::Binary { op, dst, lhs, rhs } if func.ty(*dst) == Ty::F80 => {
+                let name = match op {
+                    BinOp::Add => "faddp",
+                    BinOp::Sub => "fsubrp",
+                    BinOp::Mul => "fmulp",
+                    _ => "fdivrp",
+                };
                 format!(
-                    "{}:\n\
-                     {}\
-                     {}cmpq $1, %rax\n\
-                     {}jne {}\n\
-                     {}\
-                     {}jmp {}\n\
-                     {}:\n",
-                    lbb, exp, p, p, leb, stmts, p, lbb, leb
+                    "{}{}{}{} %st, %st(1)\n{}",
+                    self.fld(*lhs),
+                    self.fld(*rhs),
+                    p,
+                    name,
+                    self.fstp(*dst)
                 )
             }
-            StmtType::Compound => {
-                let scope: HashMap<String, bool> = HashMap::new();
-                gen_block(
-                    tree,
-                    index_map,
-                    &scope,
-                    idx,
-                    loop_in_label,
-                    loop_out_label,
-                    true,
-                    false,
-                    &global_variable_scope,
+            Inst::Binary { op, dst, lhs, rhs } if func.ty(*dst).is_float() => {
+                let ty = func.ty(*dst);
+                let (lhs, rhs) = match (op, rhs) {
+                    (BinOp::Add, Operand::Reg(r)) | (BinOp::Mul, Operand::Reg(r))
+                        if self.machine_reg(*r).is_some()
+                            && self.machine_reg(*r) == self.machine_reg(*dst) =>
+                    {
+                        (rhs, lhs)
+                    }
+                    _ => (lhs, rhs),
+                };
+                let t = self.target(*dst, &[*rhs]);
+                let name = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    _ => "div",
+                };
+                let (load_rhs, rhs) = self.float_source(*rhs);
+                format!(
+                    "{}{}{}{}{} {}, {}\n{}",
+                    self.load(*lhs, t),
+                    load_rhs,
+                    p,
+                    name,
+                    sse(ty),
+                    rhs,
+                    t,
+                    self.store(*dst, t)
                 )
             }
-        },
-        NodeType::ArrayRef(var_name) => {
-            let get_index = gen_stmt(
-                tree.child
-                    .get(0)
-                    .expect("Statement::Declare Node has no child"),
-                index_map,
-                idx,
-                lbb,
-                leb,
-                loop_in_label,
-                loop_out_label,
-                &global_variable_scope,
-            );
-            // get index => rdx,
-            // movq array_index var@GOTPCREL(%rip) => %rbx
-            // movq (%rbx, rdx, data size), %rax
-            format!(
-                "{}\
-                 {}pushq %rdx\n\
-                 {}pushq %rbx\n\
-                 {}movq %rax, %rdx\n\
-                 {}movq {}@GOTPCREL(%rip), %rbx\n\
-                 {}movq (%rbx, %rdx, 8), %rax\n\
-                 {}popq %rbx\n\
-                 {}popq %rdx\n",
-                get_index, p, p, p, p, var_name, p, p, p,
-            )
-        }
-        NodeType::AssignNode(var_name, true) => {
-            match index_map.get(var_name) {
-                None => {
-                    // not in current scope, try to search global scope
-                    match global_variable_scope.contains(var_name) {
-                        true => {
-                            // declared in global scope, that's ok
-                            let get_index = gen_stmt(
-                                tree.child
-                                    .get(0)
-                                    .expect("Statement::Declare Node has no child"),
-                                index_map,
-                                idx,
-                                lbb,
-                                leb,
-                                loop_in_label,
-                                loop_out_label,
-                                &global_variable_scope,
-                            );
-                            let get_res = gen_stmt(
-                                tree.child.get(1).unwrap(),
-                                index_map,
-                                idx,
-                                lbb,
-                                leb,
-                                loop_in_label,
-                                loop_out_label,
-                                &global_variable_scope,
-                            );
-                            // movq array_index var@GOTPCREL(%rip) => %rbx
-                            // get index => rdx,
-                            // get res => rax
-                            // movq %rax, (%rbx, rdx, data size)
-                            format!(
-                                "{}\
-                                 {}movq %rax, %rdx\n\
-                                 {}\
-                                 {}movq {}@GOTPCREL(%rip), %rbx\n\
-                                 {}movq %rax, (%rbx, %rdx, 8)\n",
-                                get_index, p, get_res, p, var_name, p,
-                            )
-                        }
-                        false => {
-                            // Not declared before, that's not ok
-                            panic!("Error: Use un-declared variable `{}`", var_name)
-                        }
+            Inst::Binary { op, dst, lhs, rhs } => {
+                let ty = max32(func.ty(*dst));
+                match op {
+                    BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem => {
+                        // the divisor can not be a constant
+                        let (load_rhs, rhs) = match rhs {
+                            Operand::Reg(r) => (String::new(), self.at(*r, ty)),
+                            Operand::Imm(_) => (self.load(*rhs, "%rcx"), sized("%rcx", ty)),
+                        };
+                        let body = match op {
+                            BinOp::SDiv | BinOp::SRem => format!(
+                                "{}{}\n{}idiv{} {}\n",
+                                p,
+                                if ty == Ty::I64 { "cqto" } else { "cltd" },
+                                p,
+                                suffix(ty),
+                                rhs
+                            ),
+                            _ => format!("{}xorl %edx, %edx\n{}div{} {}\n", p, p, suffix(ty), rhs),
+                        };
+                        // the remainder is in %rdx
+                        let result = match op {
+                            BinOp::SRem | BinOp::URem => "%rdx",
+                            _ => "%rax",
+                        };
+                        format!(
+                            "{}{}{}{}",
+                            self.load(*lhs, "%rax"),
+                            load_rhs,
+                            body,
+                            self.store(*dst, result)
+                        )
                     }
-                }
-                Some(t) => {
-                    // declared before, that's ok
-                    let e1 = gen_stmt(
-                        tree.child
-                            .get(0)
-                            .expect("Statement::Declare Node has no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    );
-                    let get_result = index_map.get(var_name);
-                    let mut va_offset: isize = -8;
-                    match get_result {
-                        Some(t) => {
-                            va_offset = *t;
-                        }
-                        None => panic!("Something went wrong in gen::gen_stmt()"),
+                    BinOp::Shl | BinOp::Shr | BinOp::Sar => {
+                        let t = self.target(*dst, &[*rhs]);
+                        let name = match op {
+                            BinOp::Shl => "shl",
+                            BinOp::Shr => "shr",
+                            _ => "sar",
+                        };
+                        let (load_rhs, count) = match rhs {
+                            Operand::Imm(v) if (0..64).contains(v) => {
+                                (String::new(), format!("${}", v))
+                            }
+                            _ => (self.load(*rhs, "%rcx"), "%cl".to_string()),
+                        };
+                        format!(
+                            "{}{}{}{}{} {}, {}\n{}",
+                            self.load(*lhs, t),
+                            load_rhs,
+                            p,
+                            name,
+                            suffix(ty),
+                            count,
+                            sized(t, ty),
+                            self.store(*dst, t)
+                        )
+                    }
+                    _ => {
+                        // the operands of these can be swapped, so one already in the
+                        // register of the result need not be moved out of the way
+                        let (lhs, rhs) = match (op, rhs) {
+                            (BinOp::Add, Operand::Reg(r))
+                            | (BinOp::Mul, Operand::Reg(r))
+                            | (BinOp::And, Operand::Reg(r))
+                            | (BinOp::Or, Operand::Reg(r))
+                            | (BinOp::Xor, Operand::Reg(r))
+                                if self.machine_reg(*r).is_some()
+                                    && self.machine_reg(*r) == self.machine_reg(*dst) =>
+                            {
+                                (rhs, lhs)
+                            }
+                            _ => (lhs, rhs),
+                        };
+                        let t = self.target(*dst, &[*rhs]);
+                        let name = match op {
+                            BinOp::Add => "add",
+                            BinOp::Sub => "sub",
+                            BinOp::Mul => "imul",
+                            BinOp::And => "and",
+                            BinOp::Or => "or",
+                            _ => "xor",
+                        };
+                        let (load_rhs, rhs) = self.source(*rhs, ty, "%rcx");
+                        format!(
+                            "{}{}{}{}{} {}, {}\n{}",
+                            self.load(*lhs, t),
+                            load_rhs,
+                            p,
+                            name,
+                            suffix(ty),
+                            rhs,
+                            sized(t, ty),
+                            self.store(*dst, t)
+                        )
                     }
-                    format!(
-                        "{}\
-                         {}movq %rax, {}(%rbp)\n",
-                        e1, p, va_offset
-                    )
                 }
             }
-        }
-        NodeType::AssignNode(var_name, false) => {
-            // assign to int variable
-            match index_map.get(var_name) {
-                None => {
-                    // not in current scope, try to search global scope
-                    match global_variable_scope.contains(var_name) {
-                        true => {
-                            // declared in global scope, that's ok
-                            let e1 = gen_stmt(
-                                tree.child
-                                    .get(0)
-                                    .expect("Statement::Declare Node has no child"),
-                                index_map,
-                                idx,
-                                lbb,
-                                leb,
-                                loop_in_label,
-                                loop_out_label,
-                                &global_variable_scope,
-                            );
-                            format!(
-                                "{}\
-                                 {}movq %rax, {}(%rip)\n",
-                                e1, p, var_name
-                            )
-                        }
-                        false => {
-                            // Not declared before, that's not ok
-                            panic!("Error: Use un-declared variable `{}`", var_name)
-                        }
+            Inst::Cmp {
+                cond,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => {
+                let t = self.target(*dst, &[]);
+                let (t8, t32) = (sized(t, Ty::I8), sized(t, Ty::I32));
+                let (cmp, cc) = self.gen_compare(*cond, *ty, *lhs, *rhs);
+                // unordered, with a NaN, is not equal
+                let unordered = match cond {
+                    Cond::Eq if ty.is_float() => {
+                        format!("{}setnp %r11b\n{}andb %r11b, {}\n", p, p, t8)
                     }
-                }
-                Some(t) => {
-                    // declared before, that's ok
-                    let e1 = gen_stmt(
-                        tree.child
-                            .get(0)
-                            .expect("Statement::Declare Node has no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    );
-                    let get_result = index_map.get(var_name);
-                    let mut va_offset: isize = -8;
-                    match get_result {
-                        Some(t) => {
-                            va_offset = *t;
-                        }
-                        None => panic!("Something went wrong in gen::gen_stmt()"),
+                    Cond::Ne if ty.is_float() => {
+                        format!("{}setp %r11b\n{}orb %r11b, {}\n", p, p, t8)
                     }
-                    format!(
-                        "{}\
-                         {}movq %rax, {}(%rbp)\n",
-                        e1, p, va_offset
-                    )
-                }
+                    _ => String::new(),
+                };
+                format!(
+                    "{}{}set{} {}\n\
+                     {}\
+                     {}movzbl {}, {}\n\
+                     {}",
+                    cmp,
+                    p,
+                    cc.0,
+                    t8,
+                    unordered,
+                    p,
+                    t8,
+                    t32,
+                    self.store(*dst, t)
+                )
             }
-        }
-        NodeType::UnExp(op) => match op {
-            TokType::Addr => format!(
-                // put address of the factor in %rax
-                "{}", gen_addr(
-                    tree.child.get(0).expect("Addressing node no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    global_variable_scope)
-            ),
-            TokType::Minus => format!(
-                "{}\
-                 {}neg %rax\n",
-                gen_stmt(
-                    tree.child.get(0).expect("UnExp<-> no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                ),
-                p
+            Inst::Ext { signed, dst, src } => {
+                let t = self.target(*dst, &[]);
+                let from_ty = func.ty(*src);
+                let from = self.at(*src, from_ty);
+                let ext = match (from_ty, signed) {
+                    (Ty::I8, true) => format!("{}movsbq {}, {}\n", p, from, t),
+                    (Ty::I8, false) => format!("{}movzbl {}, {}\n", p, from, sized(t, Ty::I32)),
+                    (Ty::I16, true) => format!("{}movswq {}, {}\n", p, from, t),
+                    (Ty::I16, false) => format!("{}movzwl {}, {}\n", p, from, sized(t, Ty::I32)),
+                    (Ty::I32, true) => format!("{}movslq {}, {}\n", p, from, t),
+                    (Ty::I32, false) => format!("{}movl {}, {}\n", p, from, sized(t, Ty::I32)),
+                    (Ty::I64, _) => self.load(Operand::Reg(*src), t),
+                    _ => unreachable!("a floating value is converted, not extended"),
+                };
+                format!("{}{}", ext, self.store(*dst, t))
+            }
+            Inst::Load { dst, addr } if func.ty(*dst) == Ty::F80 => {
+                let (load_addr, base) = self.base(*addr);
+                format!("{}{}fldt ({})\n{}", load_addr, p, base, self.fstp(*dst))
+            }
+            Inst::Load { dst, addr } => {
+                let (load_addr, base) = self.base(*addr);
+                let t = self.target(*dst, &[]);
+                let load = match func.ty(*dst) {
+                    Ty::I8 => format!("movzbl ({}), {}", base, sized(t, Ty::I32)),
+                    Ty::I16 => format!("movzwl ({}), {}", base, sized(t, Ty::I32)),
+                    Ty::I32 => format!("movl ({}), {}", base, sized(t, Ty::I32)),
+                    Ty::I64 => format!("movq ({}), {}", base, t),
+                    Ty::F32 => format!("movss ({}), {}", base, t),
+                    Ty::F64 | Ty::F80 => format!("movsd ({}), {}", base, t),
+                };
+                format!("{}{}{}\n{}", load_addr, p, load, self.store(*dst, t))
+            }
+            Inst::Store {
+                ty: Ty::F80,
+                addr,
+                src,
+            } => {
+                let (load_addr, base) = self.base(*addr);
+                format!("{}{}{}fstpt ({})\n", self.fld(*src), load_addr, p, base)
+            }
+            // a floating constant is stored as the integer of its bits
+            Inst::Store { ty, addr, src } if ty.is_float() && matches!(src, Operand::Reg(_)) => {
+                let (load_addr, base) = self.base(*addr);
+                let (load_src, x) = match src {
+                    Operand::Reg(r) if self.machine_reg(*r).is_some() => {
+                        (String::new(), self.at(*r, *ty))
+                    }
+                    _ => (self.load(*src, "%xmm15"), "%xmm15".to_string()),
+                };
+                let store = format!("{}mov{} {}, ({})\n", p, sse(*ty), x, base);
+                format!("{}{}{}", load_src, load_addr, store)
+            }
+            Inst::Store { ty, addr, src } => {
+                let (load_addr, base) = self.base(*addr);
+                let (load_src, src) = match src {
+                    Operand::Reg(r) => match self.machine_reg(*r) {
+                        Some(reg) => (String::new(), sized(reg, *ty)),
+                        None => (self.load(*src, "%rax"), sized("%rax", *ty)),
+                    },
+                    Operand::Imm(_) => self.source(*src, *ty, "%rax"),
+                };
+                format!(
+                    "{}{}{}mov{} {}, ({})\n",
+                    load_src,
+                    load_addr,
+                    p,
+                    suffix(*ty),
+                    src,
+                    base
+                )
+            }
+            Inst::SlotAddr { dst, slot } => {
+                let t = self.target(*dst, &[]);
+                format!(
+                    "{}leaq {}(%rbp), {}\n{}",
+                    p,
+                    self.slot(*slot),
+                    t,
+                    self.store(*dst, t)
+                )
+            }
+            Inst::GlobalAddr { dst, name } => {
+                let t = self.target(*dst, &[]);
+                format!("{}leaq {}(%rip), {}\n{}", p, name, t, self.store(*dst, t))
+            }
+            Inst::MemCopy { dst, src, size } => format!(
+                "{}{}{}",
+                self.load(Operand::Reg(*src), "%rax"),
+                self.load(Operand::Reg(*dst), "%rcx"),
+                gen_copy(*size)
             ),
-            TokType::Tilde => format!(
-                "{}\
-                 {}not %rax\n",
-                gen_stmt(
-                    tree.child.get(0).expect("UnExp<~> no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                ),
-                p
+            Inst::Call { ret, callee, args } => self.gen_call(ret, callee, args),
+            // the unnamed arguments start after the registers and the stack
+            // the named ones take
+            Inst::VaStart { ap } => {
+                let (_, stack_size, (reg, xmm)) = param_places(func);
+                let (load_ap, base) = self.base(*ap);
+                format!(
+                    "{}{}movl ${}, {}({})\n\
+                     {}movl ${}, {}({})\n\
+                     {}leaq {}(%rbp), %rax\n\
+                     {}movq %rax, {}({})\n\
+                     {}leaq {}(%rbp), %rax\n\
+                     {}movq %rax, {}({})\n",
+                    load_ap,
+                    p,
+                    8 * reg,
+                    VA_GP_OFFSET,
+                    base,
+                    p,
+                    REG_SAVE_GP_SIZE + 16 * xmm as i64,
+                    VA_FP_OFFSET,
+                    base,
+                    p,
+                    16 + stack_size,
+                    p,
+                    VA_OVERFLOW_ARG_AREA,
+                    base,
+                    p,
+                    self.save_area,
+                    p,
+                    VA_REG_SAVE_AREA,
+                    base
+                )
+            }
+            Inst::Convert { signed, dst, src } => self.gen_convert(*signed, *dst, *src),
+        }
+    }
+
+    // `src` converted to the type of `dst`, one of them floating.  There is
+    // no conversion of an unsigned integer: a u32 is converted as the i64 it
+    // is, and a u64 with its highest bit set is halved first, keeping the
+    // lowest bit so it still rounds right, and doubled after; a floating
+    // value of 2^63 or more has 2^63 taken off first, and put back in the
+    // highest bit after.
+    fn gen_convert(&mut self, signed: bool, dst: VReg, src: VReg) -> String {
+        let p = "        ";
+        let (from, to) = (self.func.ty(src), self.func.ty(dst));
+        if from == Ty::F80 || to == Ty::F80 {
+            return self.gen_convert_x87(signed, dst, src);
+        }
+        let t = self.target(dst, &[]);
+        let x = self.at(src, Ty::I64);
+        let convert = match (from, to) {
+            (Ty::F32, Ty::F64) => format!("{}cvtss2sd {}, {}\n", p, x, t),
+            (Ty::F64, Ty::F32) => format!("{}cvtsd2ss {}, {}\n", p, x, t),
+            (_, _) if to.is_float() && signed => format!(
+                "{}cvtsi2{}{} {}, {}\n",
+                p,
+                sse(to),
+                suffix(from),
+                self.at(src, from),
+                t
             ),
-            TokType::Exclamation => format!(
-                "{}\
-                 {}cmp  $0, %rax\n\
-                 {}movq $0, %rax\n\
-                 {}sete %al\n",
-                gen_stmt(
-                    tree.child.get(0).expect("UnExp<!> node no child"),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                ),
+            (Ty::I32, _) => format!(
+                "{}movl {}, %eax\n{}cvtsi2{}q %rax, {}\n",
                 p,
+                self.at(src, Ty::I32),
                 p,
-                p
+                sse(to),
+                t
             ),
-            _ => panic!(format!(
-                "Unary Operator `{:?}` not implemented in gen::gen_unexp()\n",
-                op
-            )),
-        },
-        NodeType::BinExp(op) => {
-            match op {
-                TokType::Plus => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}addq %rcx, %rax\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp has no lhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp has no rhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    p
-                ),
-                TokType::Minus => format!(
+            (Ty::I64, _) => {
+                let (big, done) = (self.cx.label("CVT"), self.cx.label("CVT"));
+                format!(
                     "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}subq %rcx, %rax\n", // subl src, dst : dst - src -> dst
-                    //   let %rax = dst = e1, %rcx = src = e2
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp has no rhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp has no lhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                     {}testq %rax, %rax\n\
+                     {}js {}\n\
+                     {}cvtsi2{}q %rax, {}\n\
+                     {}jmp {}\n\
+                     {}:\n\
+                     {}movq %rax, %rcx\n\
+                     {}shrq $1, %rcx\n\
+                     {}andl $1, %eax\n\
+                     {}orq %rax, %rcx\n\
+                     {}cvtsi2{}q %rcx, {}\n\
+                     {}add{} {}, {}\n\
+                     {}:\n",
+                    self.load(Operand::Reg(src), "%rax"),
                     p,
-                    p
-                ),
-                TokType::Multi => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}imul %rcx, %rax\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp has no lhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
                     p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp has no rhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    big,
                     p,
-                    p
-                ),
-                TokType::Splash => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}xorq %rdx, %rdx\n\
-                     {}idivq %rcx\n",
-                    // let eax = e1, edx = 0, ecx = e2
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp has no rhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    sse(to),
+                    t,
                     p,
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp has no lhs"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    done,
+                    big,
                     p,
                     p,
-                    p
-                ),
-                TokType::Equal => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}sete %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
                     p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
                     p,
                     p,
+                    sse(to),
+                    t,
                     p,
-                    p
-                ),
-                TokType::NotEqual => format!(
+                    sse(to),
+                    t,
+                    t,
+                    done
+                )
+            }
+            (_, Ty::I32) if signed => {
+                format!("{}cvtt{}2sil {}, {}\n", p, sse(from), x, sized(t, Ty::I32))
+            }
+            (_, Ty::I32) => format!("{}cvtt{}2siq {}, {}\n", p, sse(from), x, t),
+            _ if signed => format!("{}cvtt{}2siq {}, {}\n", p, sse(from), x, t),
+            _ => {
+                let (big, done) = (self.cx.label("CVT"), self.cx.label("CVT"));
+                format!(
                     "{}\
-                     {}pushq %rax\n\
                      {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}setne %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                     {}ucomi{} %xmm14, %xmm15\n\
+                     {}jae {}\n\
+                     {}cvtt{}2siq %xmm15, {}\n\
+                     {}jmp {}\n\
+                     {}:\n\
+                     {}sub{} %xmm14, %xmm15\n\
+                     {}cvtt{}2siq %xmm15, {}\n\
+                     {}movabsq ${}, %r11\n\
+                     {}xorq %r11, {}\n\
+                     {}:\n",
+                    self.load(Operand::Reg(src), "%xmm15"),
+                    self.load(Operand::Imm(from.float_bits(2f64.powi(63))), "%xmm14"),
                     p,
+                    sse(from),
                     p,
+                    big,
                     p,
-                    p
-                ),
-                TokType::LessEqual => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}setle %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    sse(from),
+                    t,
                     p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    done,
+                    big,
                     p,
+                    sse(from),
                     p,
+                    sse(from),
+                    t,
                     p,
-                    p
-                ),
-                TokType::GreaterEqual => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}setge %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
+                    i64::MIN,
                     p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
+                    t,
+                    done
+                )
+            }
+        };
+        format!("{}{}", convert, self.store(dst, t))
+    }
+
+    // the same with a long double, which the x87 loads from and stores to
+    // memory, the red zone for a value in a register.  An integer is loaded
+    // as an i64, a u64 with its highest bit set getting 2^64 added after; a
+    // long double is truncated to an i64 with the rounding control of the
+    // x87 set to chop for it, one of 2^63 or more having 2^63 taken off
+    // first and put back in the highest bit after.
+    fn gen_convert_x87(&mut self, signed: bool, dst: VReg, src: VReg) -> String {
+        let p = "        ";
+        let (from, to) = (self.func.ty(src), self.func.ty(dst));
+        // the letter of the x87 instructions on a float or a double
+        let width = |ty: Ty| if ty == Ty::F32 { 's' } else { 'l' };
+        match (from, to) {
+            (Ty::F32, _) | (Ty::F64, _) => {
+                let load = match self.machine_reg(src) {
+                    Some(reg) => format!(
+                        "{}mov{} {}, -8(%rsp)\n{}fld{} -8(%rsp)\n",
+                        p,
+                        sse(from),
+                        reg,
+                        p,
+                        width(from)
                     ),
+                    None => format!("{}fld{} {}\n", p, width(from), self.at(src, from)),
+                };
+                format!("{}{}", load, self.fstp(dst))
+            }
+            (_, Ty::F32) | (_, Ty::F64) => {
+                let t = self.target(dst, &[]);
+                format!(
+                    "{}{}fstp{} -8(%rsp)\n{}mov{} -8(%rsp), {}\n{}",
+                    self.fld(Operand::Reg(src)),
                     p,
+                    width(to),
                     p,
-                    p,
-                    p
-                ),
-                TokType::Or => {
-                    let clause2_label = gen_labels("CLAUSE");
-                    let end_label = gen_labels("END");
+                    sse(to),
+                    t,
+                    self.store(dst, t)
+                )
+            }
+            (Ty::F80, _) => {
+                let chop = format!(
+                    "{}fnstcw -10(%rsp)\n\
+                     {}movzwl -10(%rsp), %eax\n\
+                     {}orl $3072, %eax\n\
+                     {}movw %ax, -12(%rsp)\n\
+                     {}fldcw -12(%rsp)\n\
+                     {}fistpq -8(%rsp)\n\
+                     {}fldcw -10(%rsp)\n\
+                     {}movq -8(%rsp), %rax\n",
+                    p, p, p, p, p, p, p, p
+                );
+                let convert = if signed || to != Ty::I64 {
+                    chop
+                } else {
+                    let (big, done) = (self.cx.label("CVT"), self.cx.label("CVT"));
                     format!(
-                        "{}\
-                         {}cmpq $0, %rax\n\
-                         {}je {}\n\
-                         {}movq $1, %rax\n\
+                        "{}movl $0x5f000000, -16(%rsp)\n\
+                         {}flds -16(%rsp)\n\
+                         {}fucomip %st(1), %st\n\
+                         {}jbe {}\n\
+                         {}\
                          {}jmp {}\n\
                          {}:\n\
+                         {}fsubs -16(%rsp)\n\
                          {}\
-                         {}cmpq $0, %rax\n\
-                         {}movq $0, %rax\n\
-                         {}setne %al\n\
-                         {}: # end of clause here\n",
-                        gen_stmt(
-                            tree.child.get(0).expect("BinExp<||> node no child"),
-                            index_map,
-                            idx,
-                            lbb,
-                            leb,
-                            loop_in_label,
-                            loop_out_label,
-                            &global_variable_scope,
-                        ),
+                         {}movabsq ${}, %r11\n\
+                         {}xorq %r11, %rax\n\
+                         {}:\n",
                         p,
                         p,
-                        clause2_label,
                         p,
                         p,
-                        end_label,
-                        clause2_label,
-                        gen_stmt(
-                            tree.child.get(1).expect("BinExp<||> node no child"),
-                            index_map,
-                            idx,
-                            lbb,
-                            leb,
-                            loop_in_label,
-                            loop_out_label,
-                            &global_variable_scope,
-                        ),
+                        big,
+                        chop,
                         p,
+                        done,
+                        big,
                         p,
+                        chop,
                         p,
-                        end_label
+                        i64::MIN,
+                        p,
+                        done
                     )
-                }
-                TokType::And => {
-                    let clause2_label = gen_labels("clause");
-                    let end_label = gen_labels("end");
+                };
+                format!(
+                    "{}{}{}",
+                    self.fld(Operand::Reg(src)),
+                    convert,
+                    self.store(dst, "%rax")
+                )
+            }
+            _ => {
+                let load = match (from, signed) {
+                    (Ty::I32, true) => format!("{}movslq {}, %rax\n", p, self.at(src, Ty::I32)),
+                    (Ty::I32, false) => format!("{}movl {}, %eax\n", p, self.at(src, Ty::I32)),
+                    _ => self.load(Operand::Reg(src), "%rax"),
+                };
+                let wrap = if from == Ty::I64 && !signed {
+                    let done = self.cx.label("CVT");
                     format!(
-                        "{}\
-                         {}cmpq $0, %rax\n\
-                         {}jne {}\n\
-                         {}jmp {}\n\
-                         {}:\n\
-                         {}\
-                         {}cmpq $0, %rax\n\
-                         {}movq $0, %rax\n\
-                         {}setne %al\n\
-                         {}: # end of clause here\n",
-                        gen_stmt(
-                            tree.child.get(0).expect("BinExp<||> node no child"),
-                            index_map,
-                            idx,
-                            lbb,
-                            leb,
-                            loop_in_label,
-                            loop_out_label,
-                            &global_variable_scope,
-                        ),
-                        p,
-                        p,
-                        clause2_label,
-                        p,
-                        end_label,
-                        clause2_label,
-                        gen_stmt(
-                            tree.child.get(1).expect("BinExp<||> node no child"),
-                            index_map,
-                            idx,
-                            lbb,
-                            leb,
-                            loop_in_label,
-                            loop_out_label,
-                            &global_variable_scope,
-                        ),
-                        p,
-                        p,
-                        p,
-                        end_label
+                        "{}testq %rax, %rax\n\
+                         {}jns {}\n\
+                         {}movl $0x5f800000, -8(%rsp)\n\
+                         {}fadds -8(%rsp)\n\
+                         {}:\n",
+                        p, p, done, p, p, done
                     )
-                }
-                TokType::Lt => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}setl %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    p,
-                    p,
-                    p
-                ),
-                TokType::Gt => format!(
-                    "{}\
-                     {}pushq %rax\n\
-                     {}\
-                     {}popq %rcx\n\
-                     {}cmpq %rax, %rcx # set ZF on if %rax == %rcx, set it off otherwise\n\
-                     {}movq $0, %rax   # zero out EAX, does not change flag\n\
-                     {}setg %al\n",
-                    gen_stmt(
-                        tree.child.get(0).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
-                    gen_stmt(
-                        tree.child.get(1).expect("BinExp<==> node no child"),
-                        index_map,
-                        idx,
-                        lbb,
-                        leb,
-                        loop_in_label,
-                        loop_out_label,
-                        &global_variable_scope,
-                    ),
-                    p,
+                } else {
+                    String::new()
+                };
+                format!(
+                    "{}{}movq %rax, -8(%rsp)\n{}fildq -8(%rsp)\n{}{}",
+                    load,
                     p,
                     p,
-                    p
-                ),
-                _ => panic!(format!(
-                    "Error: Binary Operator `{:?}` not implemented in gen::gen_binexp()\n",
-                    op
-                )),
+                    wrap,
+                    self.fstp(dst)
+                )
             }
         }
-        NodeType::Const(n) => format!("{}movq ${}, %rax\n", p, n),
-        NodeType::Var(var_name) => {
-            let var_offset = index_map.get(var_name);
-            match var_offset {
-                Some(t) => {
-                    let var_offset = t;
-                    format!("{}movq {}(%rbp), %rax\n", p, var_offset)
+    }
+
+    // `dst` gets the 64 bits of `src`
+    fn gen_copy_reg(&self, dst: VReg, src: Operand) -> String {
+        let t = match (self.machine_reg(dst), src) {
+            (Some(reg), _) => reg,
+            (None, Operand::Reg(r)) => self.machine_reg(r).unwrap_or("%rax"),
+            (None, Operand::Imm(_)) => "%rax",
+        };
+        format!("{}{}", self.load(src, t), self.store(dst, t))
+    }
+
+    // the register holding the address in `addr`, %rcx if it is spilled
+    fn base(&self, addr: VReg) -> (String, &'static str) {
+        match self.machine_reg(addr) {
+            Some(reg) => (String::new(), reg),
+            None => (self.load(Operand::Reg(addr), "%rcx"), "%rcx"),
+        }
+    }
+
+    // set the flags by comparing `lhs` with `rhs` of type `ty`, and the
+    // condition codes of `cond` and its negation after that
+    fn gen_compare(
+        &self,
+        cond: Cond,
+        ty: Ty,
+        lhs: Operand,
+        rhs: Operand,
+    ) -> (String, (&'static str, &'static str)) {
+        if !ty.is_float() {
+            return (self.gen_cmp(ty, lhs, rhs), cc(cond));
+        }
+        let (lhs, rhs) = match cond {
+            Cond::Lt | Cond::Le => (rhs, lhs),
+            _ => (lhs, rhs),
+        };
+        // fucomip sets the flags the way ucomis does, and the other
+        // operand is popped after it
+        if ty == Ty::F80 {
+            let cmp = format!(
+                "{}{}        fucomip %st(1), %st\n        fstp %st(0)\n",
+                self.fld(rhs),
+                self.fld(lhs)
+            );
+            return (cmp, float_cc(cond));
+        }
+        let (load_rhs, rhs) = self.float_source(rhs);
+        let (load_lhs, lhs) = match lhs {
+            Operand::Reg(r) if self.machine_reg(r).is_some() => (String::new(), self.at(r, ty)),
+            _ => (self.load(lhs, "%xmm15"), "%xmm15".to_string()),
+        };
+        let cmp = format!(
+            "{}{}        ucomi{} {}, {}\n",
+            load_rhs,
+            load_lhs,
+            sse(ty),
+            rhs,
+            lhs
+        );
+        (cmp, float_cc(cond))
+    }
+
+    // set the flags by comparing `lhs` with `rhs`, the first one in a register
+    fn gen_cmp(&self, ty: Ty, lhs: Operand, rhs: Operand) -> String {
+        let (load_rhs, rhs) = self.source(rhs, ty, "%rcx");
+        let (load_lhs, lhs) = match lhs {
+            Operand::Reg(r) if self.machine_reg(r).is_some() => (String::new(), self.at(r, ty)),
+            _ => (self.load(lhs, "%rax"), sized("%rax", ty)),
+        };
+        format!(
+            "{}{}        cmp{} {}, {}\n",
+            load_rhs,
+            load_lhs,
+            suffix(ty),
+            rhs,
+            lhs
+        )
+    }
+
+    // to `then` if the flags say `cc.0`, to `els` otherwise, leaving out the
+    // jump to the block right below
+    fn gen_cond_jump(
+        &self,
+        cc: (&str, &str),
+        then: BlockId,
+        els: BlockId,
+        current: BlockId,
+    ) -> String {
+        let p = "        ";
+        if els.0 == current.0 + 1 {
+            return format!("{}j{} {}\n", p, cc.0, self.block(then));
+        }
+        let mut s = format!("{}j{} {}\n", p, cc.1, self.block(els));
+        if then.0 != current.0 + 1 {
+            s.push_str(&format!("{}jmp {}\n", p, self.block(then)));
+        }
+        s
+    }
+
+    // The arguments go where arg_places says.  A struct of more than 16
+    // bytes, or with a long double in it, is returned in memory the caller
+    // provides, its address is passed in %rdi before all arguments; one of
+    // only a long double comes back on the x87.  %rsp stays 16 byte aligned
+    // between the instructions, the arguments on the stack keep it so.
+    fn gen_call(&mut self, ret: &CallRet, callee: &Callee, args: &[Arg]) -> String {
+        let p = "        ";
+        // where each argument goes: Ok(first registers) or Err(offset on the stack)
+        let (places, stack_size) = call_places(ret, args);
+        let stack_size = align_to(stack_size, 16);
+
+        let mut s = String::new();
+        if stack_size > 0 {
+            s.push_str(&format!("{}subq ${}, %rsp\n", p, stack_size));
+        }
+        // the ones on the stack first, copying them takes %rax, %rcx and %rdx
+        for (arg, place) in args.iter().zip(places.iter()) {
+            match (arg, place) {
+                (Arg::Struct(addr, st), Err(offset)) => {
+                    s.push_str(&self.load(Operand::Reg(*addr), "%rax"));
+                    s.push_str(&format!("{}leaq {}(%rsp), %rcx\n", p, offset));
+                    s.push_str(&gen_copy(st.size));
                 }
-                None => {
-                    // try to search global scope
-                    match global_variable_scope.contains(var_name) {
-                        true => {
-                            // in global scope
-                            let var_offset = var_name;
-                            format!("{}movq {}(%rip), %rax\n", p, var_offset)
+                (Arg::Value(Ty::F80, op), Err(offset)) => {
+                    s.push_str(&self.fld(*op));
+                    s.push_str(&format!("{}fstpt {}(%rsp)\n", p, offset));
+                }
+                (Arg::Value(_, op), Err(offset)) => {
+                    s.push_str(&self.load(*op, "%rax"));
+                    s.push_str(&format!("{}movq %rax, {}(%rsp)\n", p, offset));
+                }
+                _ => {}
+            }
+        }
+        // then the registers, all at once: an argument may be in the
+        // register another one goes to
+        let mut moves = Vec::new();
+        for (arg, place) in args.iter().zip(places.iter()) {
+            match (arg, place) {
+                (Arg::Struct(addr, st), Ok(first)) => {
+                    let regs = eightbyte_regs(st, &ARG_REGS, &XMM_ARG_REGS, *first);
+                    for (w, reg) in regs.iter().enumerate() {
+                        let w = w as i64;
+                        let bytes = Src::Bytes(
+                            Box::new(self.src(Operand::Reg(*addr))),
+                            8 * w,
+                            (st.size - 8 * w).min(8),
+                        );
+                        moves.push((reg.to_string(), bytes));
+                    }
+                }
+                (Arg::Value(ty, op), Ok(first)) => {
+                    moves.push((arg_reg(*ty, *first).to_string(), self.src(*op)))
+                }
+                _ => {}
+            }
+        }
+        if let CallRet::Struct(addr, st) = ret {
+            if returned_in_memory(st) {
+                moves.push(("%rdi".to_string(), self.src(Operand::Reg(*addr))));
+            }
+        }
+        // a function pointer goes to %r10 with them, no argument takes that
+        if let Callee::Ptr(r) = callee {
+            moves.push(("%r10".to_string(), self.src(Operand::Reg(*r))));
+        }
+        s.push_str(&gen_moves(moves));
+        // %al tells a variadic function how many vector registers are used
+        let sse: usize = args
+            .iter()
+            .zip(places.iter())
+            .filter(|(_, place)| place.is_ok())
+            .map(|(arg, _)| match arg {
+                Arg::Value(ty, _) => usize::from(Class::of(*ty) == Class::Sse),
+                Arg::Struct(_, st) => eightbytes(st).iter().filter(|sse| **sse).count(),
+            })
+            .sum();
+        s.push_str(&format!("{}movq ${}, %rax\n", p, sse));
+        match callee {
+            Callee::Named(name) => s.push_str(&format!("{}call {}@PLT\n", p, name)),
+            Callee::Ptr(_) => s.push_str(&format!("{}call *%r10\n", p)),
+        }
+        if stack_size > 0 {
+            s.push_str(&format!("{}addq ${}, %rsp\n", p, stack_size));
+        }
+        match ret {
+            CallRet::Void => {}
+            // a long double comes on the top of the x87 stack, which has to
+            // be left empty
+            CallRet::Value(dst) if self.func.ty(*dst) == Ty::F80 => s.push_str(&self.fstp(*dst)),
+            CallRet::Value(dst) if self.func.ty(*dst).is_float() => {
+                s.push_str(&self.store(*dst, "%xmm0"))
+            }
+            CallRet::Value(dst) => s.push_str(&self.store(*dst, "%rax")),
+            // the callee gives back the address we passed
+            CallRet::Struct(_, st) if returned_in_memory(st) => {}
+            CallRet::Struct(addr, st) if on_x87(st) => {
+                s.push_str(&self.load(Operand::Reg(*addr), "%rcx"));
+                s.push_str(&format!("{}fstpt (%rcx)\n", p));
+            }
+            // a struct returned in registers goes to its slot, whole eightbytes
+            CallRet::Struct(addr, st) => {
+                s.push_str(&self.load(Operand::Reg(*addr), "%rcx"));
+                let regs = eightbyte_regs(st, &RET_REGS, &XMM_RET_REGS, (0, 0));
+                for (w, reg) in regs.iter().enumerate() {
+                    s.push_str(&format!("{}movq {}, {}(%rcx)\n", p, reg, 8 * w));
+                }
+            }
+        }
+        s
+    }
+
+    fn gen_term(&mut self, term: &Terminator, current: BlockId) -> String {
+        let p = "        ";
+        match term {
+            // the next block is right below
+            Terminator::Jump(b) if b.0 == current.0 + 1 => String::new(),
+            Terminator::Jump(b) => format!("{}jmp {}\n", p, self.block(*b)),
+            Terminator::Branch { cond, then, els } => match cond {
+                Operand::Imm(v) => {
+                    let b = if *v != 0 { then } else { els };
+                    if b.0 == current.0 + 1 {
+                        return String::new();
+                    }
+                    format!("{}jmp {}\n", p, self.block(*b))
+                }
+                Operand::Reg(r) => {
+                    let ty = self.func.ty(*r);
+                    format!(
+                        "{}cmp{} $0, {}\n{}",
+                        p,
+                        suffix(ty),
+                        self.at(*r, ty),
+                        self.gen_cond_jump(("ne", "e"), *then, *els, current)
+                    )
+                }
+            },
+            Terminator::Switch {
+                value,
+                unsigned,
+                cases,
+                default,
+            } => {
+                let key = |v: i64| {
+                    if *unsigned {
+                        i128::from(v as u64)
+                    } else {
+                        i128::from(v)
+                    }
+                };
+                let cases: Vec<(i64, String)> =
+                    cases.iter().map(|(v, b)| (*v, self.block(*b))).collect();
+                let default = self.block(*default);
+                let dispatch = match (cases.first(), cases.last()) {
+                    // dense enough that a table of all values between is at most a third holes
+                    (Some((min, _)), Some((max, _)))
+                        if cases.len() >= 4 && key(*max) - key(*min) < 3 * cases.len() as i128 =>
+                    {
+                        gen_jump_table(self.cx, &cases, &default)
+                    }
+                    _ => gen_case_search(self.cx, &cases, &default, *unsigned),
+                };
+                format!("{}{}", self.load(Operand::Reg(*value), "%rax"), dispatch)
+            }
+            Terminator::Return(value) => {
+                let mut s = String::new();
+                match (self.func.ret, value) {
+                    // a struct is copied to the memory of the caller, or
+                    // loaded into %rax and %rdx, or %xmm0 and %xmm1, or
+                    // the x87
+                    (RetKind::Struct(st), Some(addr)) if returned_in_memory(&st) => {
+                        s.push_str(&self.load(*addr, "%rax"));
+                        s.push_str(&format!("{}movq {}(%rbp), %rcx\n", p, self.ret_addr));
+                        s.push_str(&gen_copy(st.size));
+                        s.push_str(&format!("{}movq %rcx, %rax\n", p));
+                    }
+                    (RetKind::Struct(st), Some(addr)) if on_x87(&st) => {
+                        s.push_str(&self.load(*addr, "%rax"));
+                        s.push_str(&format!("{}fldt (%rax)\n", p));
+                    }
+                    (RetKind::Struct(st), Some(addr)) => {
+                        s.push_str(&self.load(*addr, "%rax"));
+                        // the address is in %rax until the last eightbyte
+                        // is loaded, the one for %rax goes through %rcx
+                        let regs = eightbyte_regs(&st, &RET_REGS, &XMM_RET_REGS, (0, 0));
+                        for (w, reg) in regs.iter().enumerate().rev() {
+                            let w = w as i64;
+                            let reg = if *reg == "%rax" { "%rcx" } else { reg };
+                            s.push_str(&gen_load_bytes((st.size - 8 * w).min(8), 8 * w, reg));
                         }
-                        false => panic!(format!("Use of undeclared variable `{}`", var_name)),
+                        if regs.contains(&"%rax") {
+                            s.push_str(&format!("{}movq %rcx, %rax\n", p));
+                        }
+                    }
+                    (RetKind::Value(Ty::F80), Some(value)) => s.push_str(&self.fld(*value)),
+                    (RetKind::Value(ty), Some(value)) if ty.is_float() => {
+                        s.push_str(&self.load(*value, "%xmm0"))
                     }
+                    (_, Some(value)) => s.push_str(&self.load(*value, "%rax")),
+                    (_, None) => {}
+                }
+                if current.0 + 1 != self.func.blocks.len() {
+                    s.push_str(&format!("{}jmp .LRET{}\n", p, self.index));
                 }
+                s
             }
         }
-        NodeType::ExpOption => {
-            if tree.child.len() == 1 {
-                gen_stmt(
-                    tree.child
-                        .get(0)
-                        .expect(&format!("{:?} node no child", &tree.entry)),
-                    index_map,
-                    idx,
-                    lbb,
-                    leb,
-                    loop_in_label,
-                    loop_out_label,
-                    &global_variable_scope,
-                )
-            } else {
-                // null exp
-                // movq 1, %rax
-                format!("{}movq $1, %rax\n", p)
+    }
+}
+
+// the type an instruction on a register of type `ty` computes in
+fn max32(ty: Ty) -> Ty {
+    match ty {
+        Ty::I8 | Ty::I16 => Ty::I32,
+        t => t,
+    }
+}
+
+// jump to the case of the value in %rax through a table indexed by the value
+// minus the smallest case; it holds the distances of the labels from the
+// table, so it needs no relocations
+fn gen_jump_table(cx: &mut Context, cases: &[(i64, String)], default: &str) -> String {
+    let p = "        ";
+    let table = cx.label("JTABLE");
+    let min = cases[0].0;
+    let len = cases[cases.len() - 1].0.wrapping_sub(min) as u64 + 1;
+    let mut entries = String::new();
+    let mut next = cases.iter().peekable();
+    for i in 0..len {
+        let label = match next.peek() {
+            Some((v, label)) if *v == min.wrapping_add(i as i64) => {
+                next.next();
+                label
             }
+            _ => default,
+        };
+        entries.push_str(&format!("{}.long {}-{}\n", p, label, table));
+    }
+    format!(
+        "{}\
+         {}cmpq ${}, %rax\n\
+         {}ja {}\n\
+         {}leaq {}(%rip), %rcx\n\
+         {}movslq (%rcx,%rax,4), %rax\n\
+         {}addq %rcx, %rax\n\
+         {}jmp *%rax\n\
+         {}.section .rodata\n\
+         {}.align 4\n\
+         {}:\n\
+         {}\
+         {}.text\n",
+        gen_const_op("subq", min, "%rax"),
+        p,
+        len - 1,
+        p,
+        default,
+        p,
+        table,
+        p,
+        p,
+        p,
+        p,
+        p,
+        table,
+        entries,
+        p
+    )
+}
+
+// find the case of the value in %rax by a binary search over the sorted cases
+fn gen_case_search(
+    cx: &mut Context,
+    cases: &[(i64, String)],
+    default: &str,
+    unsigned: bool,
+) -> String {
+    let p = "        ";
+    if cases.len() <= 3 {
+        let mut s = String::new();
+        for (v, label) in cases {
+            s.push_str(&format!(
+                "{}{}je {}\n",
+                gen_const_op("cmpq", *v, "%rax"),
+                p,
+                label
+            ));
         }
-        NodeType::EqualityExp
-        | NodeType::RelationalExp
-        | NodeType::Term
-        | NodeType::Exp
-        | NodeType::Factor
-        | NodeType::AdditiveExp
-        | NodeType::LogicalOrExp
-        | NodeType::Block
-        | NodeType::LogicalAndExp => gen_stmt(
-            tree.child
-                .get(0)
-                .expect(&format!("{:?} node no child", &tree.entry)),
-            index_map,
-            idx,
-            lbb,
-            leb,
-            loop_in_label,
-            loop_out_label,
-            &global_variable_scope,
-        ),
-        _ => panic!(format!(
-            "Node `{:?}` not implemented in gen::gen_stmt()\n",
-            &tree.entry
-        )),
+        return format!("{}{}jmp {}\n", s, p, default);
     }
+    let mid = cases.len() / 2;
+    let (v, label) = &cases[mid];
+    let label_lower = cx.label("CASELT");
+    format!(
+        "{}\
+         {}je {}\n\
+         {}{} {}\n\
+         {}\
+         {}:\n\
+         {}",
+        gen_const_op("cmpq", *v, "%rax"),
+        p,
+        label,
+        p,
+        if unsigned { "jb" } else { "jl" },
+        label_lower,
+        gen_case_search(cx, &cases[mid + 1..], default, unsigned),
+        label_lower,
+        gen_case_search(cx, &cases[..mid], default, unsigned),
+    )
 }
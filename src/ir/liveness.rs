@@ -0,0 +1,57 @@
+use crate::ir::{Function, VReg};
+use std::collections::HashSet;
+
+// Which registers are live where: read later on some path before they are
+// written again.  The usual backwards dataflow, run to a fixed point over
+// the blocks; a register read before it is written anywhere on the way in,
+// an uninitialized local, is live at the entry.
+pub struct Liveness {
+    pub live_in: Vec<HashSet<VReg>>,  // at the start of each block
+    pub live_out: Vec<HashSet<VReg>>, // at its end, after the terminator
+}
+
+pub fn liveness(func: &Function) -> Liveness {
+    let n = func.blocks.len();
+    // what each block reads before writing it, and what it writes
+    let mut gen = vec![HashSet::new(); n];
+    let mut kill = vec![HashSet::new(); n];
+    for (b, block) in func.blocks.iter().enumerate() {
+        for inst in block.insts.iter() {
+            for r in inst.uses() {
+                if !kill[b].contains(&r) {
+                    gen[b].insert(r);
+                }
+            }
+            if let Some(r) = inst.def() {
+                kill[b].insert(r);
+            }
+        }
+        for r in block.term.uses() {
+            if !kill[b].contains(&r) {
+                gen[b].insert(r);
+            }
+        }
+    }
+
+    let mut live_in: Vec<HashSet<VReg>> = vec![HashSet::new(); n];
+    let mut live_out: Vec<HashSet<VReg>> = vec![HashSet::new(); n];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // backwards, so most of it is settled in one round
+        for b in (0..n).rev() {
+            let mut out = HashSet::new();
+            for s in func.blocks[b].term.successors() {
+                out.extend(live_in[s.0].iter().copied());
+            }
+            let mut inn = gen[b].clone();
+            inn.extend(out.difference(&kill[b]).copied());
+            if inn.len() != live_in[b].len() || out.len() != live_out[b].len() {
+                changed = true;
+                live_in[b] = inn;
+                live_out[b] = out;
+            }
+        }
+    }
+    Liveness { live_in, live_out }
+}
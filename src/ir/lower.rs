@@ -0,0 +1,2196 @@
+use super::{
+    Arg, BinOp, Block, BlockId, CallRet, Callee, Cond, Datum, Function, Global, Inst, Module,
+    Operand, Param, RetKind, Slot, StructVal, Terminator, Ty, UnOp, VReg,
+};
+use crate::diagnostic::{code, Diagnostic};
+use crate::extended::Extended;
+use crate::lexer::TokType;
+use crate::parser::{NodeType, ParseNode, StmtType, Storage};
+use crate::types::{
+    align_to, Type, REG_SAVE_GP_SIZE, REG_SAVE_SIZE, VA_FP_OFFSET, VA_GP_OFFSET,
+    VA_OVERFLOW_ARG_AREA, VA_REG_SAVE_AREA,
+};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+// The lowering of a checked translation unit to the IR.  sema has given
+// every expression its type and rejected what C does not allow; what is left
+// to find here is what only the shape of the code tells: a `break` outside
+// of a loop, a duplicate `case`, an initializer that is not a constant, and
+// what crust can not compile yet.
+
+// an error in the source program at `tree`
+fn error(tree: &ParseNode, code: &'static str, msg: &str) -> Diagnostic {
+    Diagnostic::error(code, &tree.loc, msg)
+}
+
+// a bug in crust: sema gave us a tree it should not have
+fn ice(tree: &ParseNode, msg: &str) -> Diagnostic {
+    Diagnostic::ice(&tree.loc, msg)
+}
+
+// the child `i` of `tree`, a missing one is a bug in the parser
+fn child(tree: &ParseNode, i: usize) -> Result<&ParseNode, Diagnostic> {
+    tree.child
+        .get(i)
+        .ok_or_else(|| ice(tree, &format!("{:?} node has no child {}", tree.entry, i)))
+}
+
+// the type of the value an expression computes, sema has worked it out
+fn type_of(tree: &ParseNode) -> Result<Type, Diagnostic> {
+    tree.ty
+        .clone()
+        .ok_or_else(|| ice(tree, &format!("{:?} node has no type", tree.entry)))
+}
+
+// whether `tree` only wraps the expression of its one child
+fn is_wrapper(tree: &ParseNode) -> bool {
+    let wraps = matches!(
+        tree.entry,
+        NodeType::Exp
+            | NodeType::ExpOption
+            | NodeType::Factor
+            | NodeType::Term
+            | NodeType::AdditiveExp
+            | NodeType::RelationalExp
+            | NodeType::EqualityExp
+            | NodeType::LogicalAndExp
+            | NodeType::LogicalOrExp
+            | NodeType::ConditionalExp
+            | NodeType::Block
+    );
+    wraps && tree.child.len() == 1
+}
+
+pub fn lower(tree: &ParseNode) -> Result<Module, Diagnostic> {
+    let name = match &tree.entry {
+        NodeType::Prog(name) => name.clone(),
+        _ => return Err(ice(tree, "the tree is not a translation unit")),
+    };
+    let mut module = Module {
+        name,
+        ..Module::default()
+    };
+    let mut globals: HashMap<String, Type> = HashMap::new();
+    // whether each global name has internal linkage, `static`, or external
+    let mut linkage: HashMap<String, bool> = HashMap::new();
+    for it in tree.child.iter() {
+        let storage = match &it.entry {
+            NodeType::Declare(var_name, _, storage) => {
+                declare_linkage(it, &mut linkage, var_name, *storage, !it.child.is_empty())?
+            }
+            // a function with no storage class is like an `extern` one
+            NodeType::Fn(fn_name, _, _, _, Storage::Static) => {
+                declare_linkage(it, &mut linkage, fn_name, Storage::Static, true)?
+            }
+            NodeType::Fn(fn_name, _, _, _, _) => {
+                declare_linkage(it, &mut linkage, fn_name, Storage::Extern, true)?
+            }
+            NodeType::FnDecl(fn_name, _, _, _, Storage::Static) => {
+                declare_linkage(it, &mut linkage, fn_name, Storage::Static, false)?
+            }
+            NodeType::FnDecl(fn_name, _, _, _, _) => {
+                declare_linkage(it, &mut linkage, fn_name, Storage::Extern, false)?
+            }
+            _ => Storage::None,
+        };
+        match &it.entry {
+            // the parser has taken care of the tag, sema of the prototype
+            NodeType::TagDecl(_) | NodeType::FnDecl(_, _, _, _, _) => {}
+            // defined in some other place, only its type is needed here
+            NodeType::Declare(var_name, data_type, _) if storage == Storage::Extern => {
+                globals.insert(var_name.to_string(), data_type.clone());
+            }
+            NodeType::Declare(var_name, data_type, _) => {
+                globals.insert(var_name.to_string(), data_type.clone());
+                let init = match it.child.first() {
+                    Some(init) => {
+                        let symbols =
+                            |name: &str| globals.get(name).map(|t| (name.to_string(), t.clone()));
+                        Some(global_init(init, data_type, &symbols, &mut module.strings)?)
+                    }
+                    None => None,
+                };
+                let global = global_object(var_name, storage != Storage::Static, data_type, init);
+                // a tentative definition again adds nothing, an initialized one
+                // takes the place of the ones before it
+                match module.globals.iter_mut().find(|g| g.name == *var_name) {
+                    Some(g) if global.init.is_some() => *g = global,
+                    Some(_) => {}
+                    None => module.globals.push(global),
+                }
+            }
+            NodeType::Fn(fn_name, ret_type, params, variadic, _) => {
+                let mut lower =
+                    Lower::new(&globals, &mut module.strings, &mut module.globals, ret_type);
+                let mut func = lower.function(it, fn_name, params, storage != Storage::Static)?;
+                func.variadic = *variadic;
+                module.functions.push(func);
+            }
+            _ => {
+                return Err(ice(
+                    it,
+                    &format!("`{:?}` type should not be here", it.entry),
+                ))
+            }
+        }
+    }
+    Ok(module)
+}
+
+// the linkage a global declaration of `name` gives it, with the storage
+// class it ends up with: an `extern` one after a `static` one is `static`
+// too, and only a definition is anything other than `extern`
+fn declare_linkage(
+    tree: &ParseNode,
+    linkage: &mut HashMap<String, bool>,
+    name: &str,
+    storage: Storage,
+    defines: bool,
+) -> Result<Storage, Diagnostic> {
+    let internal = match (linkage.get(name), storage) {
+        (Some(true), Storage::Extern) => true,
+        (Some(prev), _) if *prev != (storage == Storage::Static) => {
+            let msg = if *prev {
+                format!(
+                    "non-static declaration of `{}` follows static declaration",
+                    name
+                )
+            } else {
+                format!(
+                    "static declaration of `{}` follows non-static declaration",
+                    name
+                )
+            };
+            return Err(error(tree, code::REDECLARED, &msg));
+        }
+        _ => storage == Storage::Static,
+    };
+    linkage.insert(name.to_string(), internal);
+    Ok(match storage {
+        Storage::Extern if !defines => Storage::Extern,
+        _ if internal => Storage::Static,
+        _ => Storage::None,
+    })
+}
+
+// the type `lhs op rhs` is computed in for the operands of types `lhs` and
+// `rhs`, which `lhs op= rhs` converts back to the type of `lhs`
+fn op_type(op: &TokType, lhs: &Type, rhs: &Type) -> Type {
+    match op {
+        _ if lhs.pointee().is_some() => lhs.clone(),
+        TokType::Shl | TokType::Shr => lhs.promote(),
+        _ => Type::common(lhs, rhs),
+    }
+}
+
+// the value of a constant expression, for a global initializer, a `case` or
+// the length of an array
+pub(crate) fn compute_const(tree: &ParseNode) -> Result<i64, Diagnostic> {
+    let truth = |b: bool| Ok(b as i64);
+    match &tree.entry {
+        // floating operands give an int only by comparing them, which a long
+        // double holds any of exactly
+        NodeType::BinExp(op)
+            if type_of(child(tree, 0)?)?.is_floating()
+                || type_of(child(tree, 1)?)?.is_floating() =>
+        {
+            let lhs = compute_long(child(tree, 0)?)?;
+            let rhs = compute_long(child(tree, 1)?)?;
+            let order = lhs.compare(rhs);
+            match op {
+                TokType::And => truth(is_nonzero(lhs) && is_nonzero(rhs)),
+                TokType::Or => truth(is_nonzero(lhs) || is_nonzero(rhs)),
+                TokType::Equal => truth(order == Some(Ordering::Equal)),
+                TokType::NotEqual => truth(order != Some(Ordering::Equal)),
+                TokType::LessEqual => {
+                    truth(matches!(order, Some(Ordering::Less | Ordering::Equal)))
+                }
+                TokType::GreaterEqual => {
+                    truth(matches!(order, Some(Ordering::Greater | Ordering::Equal)))
+                }
+                TokType::Lt => truth(order == Some(Ordering::Less)),
+                TokType::Gt => truth(order == Some(Ordering::Greater)),
+                _ => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    "expression is not an integer constant expression",
+                )),
+            }
+        }
+        NodeType::BinExp(op) => {
+            let lhs = compute_const(child(tree, 0)?)?;
+            let rhs = compute_const(child(tree, 1)?)?;
+            match op {
+                TokType::Plus => Ok(lhs.wrapping_add(rhs)),
+                TokType::Minus => Ok(lhs.wrapping_sub(rhs)),
+                TokType::Multi => Ok(lhs.wrapping_mul(rhs)),
+                TokType::Splash if rhs == 0 => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    "division by zero in a constant expression",
+                )),
+                TokType::Splash => Ok(lhs.wrapping_div(rhs)),
+                TokType::Percent if rhs == 0 => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    "division by zero in a constant expression",
+                )),
+                TokType::Percent => Ok(lhs.wrapping_rem(rhs)),
+                TokType::Addr => Ok(lhs & rhs),
+                TokType::BitOr => Ok(lhs | rhs),
+                TokType::BitXor => Ok(lhs ^ rhs),
+                // in the type of the left operand, which a shift keeps
+                TokType::Shl => Ok(type_of(tree)?.wrap(lhs.wrapping_shl(rhs as u32))),
+                TokType::Shr if type_of(tree)?.is_signed() => Ok(lhs.wrapping_shr(rhs as u32)),
+                TokType::Shr => {
+                    let lhs = type_of(tree)?.wrap(lhs) as u64;
+                    Ok(lhs.wrapping_shr(rhs as u32) as i64)
+                }
+                TokType::And => truth(lhs != 0 && rhs != 0),
+                TokType::Or => truth(lhs != 0 || rhs != 0),
+                TokType::Equal => truth(lhs == rhs),
+                TokType::NotEqual => truth(lhs != rhs),
+                TokType::LessEqual => truth(lhs <= rhs),
+                TokType::GreaterEqual => truth(lhs >= rhs),
+                TokType::Lt => truth(lhs < rhs),
+                TokType::Gt => truth(lhs > rhs),
+                _ => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    &format!("{:?} should not occur in a constant expression", op),
+                )),
+            }
+        }
+        NodeType::UnExp(TokType::Exclamation) if type_of(child(tree, 0)?)?.is_floating() => {
+            truth(!compute_truth(child(tree, 0)?)?)
+        }
+        NodeType::UnExp(op) => {
+            let child_val = compute_const(child(tree, 0)?)?;
+            match op {
+                TokType::Minus => Ok(child_val.wrapping_neg()),
+                TokType::Tilde => Ok(!child_val),
+                TokType::Exclamation => truth(child_val == 0),
+                _ => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    "expression is not a constant expression",
+                )),
+            }
+        }
+        NodeType::Const(val, _) => Ok(*val),
+        NodeType::ConditionalExp if tree.child.len() == 3 => {
+            if compute_truth(child(tree, 0)?)? {
+                compute_const(child(tree, 1)?)
+            } else {
+                compute_const(child(tree, 2)?)
+            }
+        }
+        // what sema adds keeps the value, a constant converted
+        NodeType::Cast(t) if t.is_integer() => compute_init(child(tree, 0)?, t),
+        NodeType::FloatConst(_, _) => Err(error(
+            tree,
+            code::NOT_CONSTANT,
+            "expression is not an integer constant expression",
+        )),
+        _ => match tree.child.first() {
+            Some(c) if is_wrapper(tree) => compute_const(c),
+            _ => Err(error(
+                tree,
+                code::NOT_CONSTANT,
+                "expression is not a constant expression",
+            )),
+        },
+    }
+}
+
+// the value of a constant expression of any arithmetic type, as a double
+fn compute_float(tree: &ParseNode) -> Result<f64, Diagnostic> {
+    let t = type_of(tree)?;
+    if t == Type::LongDouble {
+        return Ok(compute_long(tree)?.to_f64());
+    }
+    if !t.is_floating() {
+        let v = compute_const(tree)?;
+        return Ok(if t.is_signed() {
+            v as f64
+        } else {
+            v as u64 as f64
+        });
+    }
+    // what a float computes is rounded to one
+    let round = |x: f64| Ty::of(&t).float_value(Ty::of(&t).float_bits(x));
+    match &tree.entry {
+        NodeType::FloatConst(c, _) => Ok(round(c.value)),
+        NodeType::BinExp(op) => {
+            let lhs = compute_float(child(tree, 0)?)?;
+            let rhs = compute_float(child(tree, 1)?)?;
+            match op {
+                TokType::Plus => Ok(round(lhs + rhs)),
+                TokType::Minus => Ok(round(lhs - rhs)),
+                TokType::Multi => Ok(round(lhs * rhs)),
+                TokType::Splash => Ok(round(lhs / rhs)),
+                _ => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    &format!("{:?} should not occur in a constant expression", op),
+                )),
+            }
+        }
+        NodeType::UnExp(TokType::Minus) => Ok(-compute_float(child(tree, 0)?)?),
+        NodeType::ConditionalExp if tree.child.len() == 3 => {
+            if compute_truth(child(tree, 0)?)? {
+                compute_float(child(tree, 1)?)
+            } else {
+                compute_float(child(tree, 2)?)
+            }
+        }
+        NodeType::Cast(_) => Ok(round(compute_float(child(tree, 0)?)?)),
+        _ => match tree.child.first() {
+            Some(c) if is_wrapper(tree) => compute_float(c),
+            _ => Err(error(
+                tree,
+                code::NOT_CONSTANT,
+                "expression is not a constant expression",
+            )),
+        },
+    }
+}
+
+// the value of a constant expression of any arithmetic type, as a long
+// double; one of another type is computed in its own precision
+fn compute_long(tree: &ParseNode) -> Result<Extended, Diagnostic> {
+    let t = type_of(tree)?;
+    if t != Type::LongDouble {
+        if t.is_floating() {
+            return Ok(Extended::from_f64(compute_float(tree)?));
+        }
+        let v = compute_const(tree)?;
+        return Ok(if t.is_signed() {
+            Extended::from_i64(v)
+        } else {
+            Extended::from_u64(v as u64)
+        });
+    }
+    match &tree.entry {
+        NodeType::FloatConst(c, _) => Ok(c.extended),
+        NodeType::BinExp(op) => {
+            let lhs = compute_long(child(tree, 0)?)?;
+            let rhs = compute_long(child(tree, 1)?)?;
+            match op {
+                TokType::Plus => Ok(lhs + rhs),
+                TokType::Minus => Ok(lhs - rhs),
+                TokType::Multi => Ok(lhs * rhs),
+                TokType::Splash => Ok(lhs / rhs),
+                _ => Err(error(
+                    tree,
+                    code::NOT_CONSTANT,
+                    &format!("{:?} should not occur in a constant expression", op),
+                )),
+            }
+        }
+        NodeType::UnExp(TokType::Minus) => Ok(-compute_long(child(tree, 0)?)?),
+        NodeType::ConditionalExp if tree.child.len() == 3 => {
+            if compute_truth(child(tree, 0)?)? {
+                compute_long(child(tree, 1)?)
+            } else {
+                compute_long(child(tree, 2)?)
+            }
+        }
+        NodeType::Cast(_) => compute_long(child(tree, 0)?),
+        _ => match tree.child.first() {
+            Some(c) if is_wrapper(tree) => compute_long(c),
+            _ => Err(error(
+                tree,
+                code::NOT_CONSTANT,
+                "expression is not a constant expression",
+            )),
+        },
+    }
+}
+
+fn is_nonzero(x: Extended) -> bool {
+    x.compare(Extended::ZERO) != Some(Ordering::Equal)
+}
+
+// whether the constant expression `tree`, a condition, is true
+fn compute_truth(tree: &ParseNode) -> Result<bool, Diagnostic> {
+    if type_of(tree)?.is_floating() {
+        Ok(is_nonzero(compute_long(tree)?))
+    } else {
+        Ok(compute_const(tree)? != 0)
+    }
+}
+
+// the global `name` of type `t`, with the data it starts with if any
+fn global_object(name: &str, global: bool, t: &Type, init: Option<Vec<Datum>>) -> Global {
+    let size = t.size();
+    let align = match t {
+        // the ABI wants arrays of 16 bytes or more aligned to 16
+        Type::Array(elem, _) if size >= 16 => elem.align().max(16),
+        t => t.align(),
+    };
+    Global {
+        name: name.to_string(),
+        global,
+        size,
+        align,
+        init,
+    }
+}
+
+// the symbol and the type of the object a variable in an address constant
+// names, none for one on the stack, which has no address until it runs
+type Symbols<'a> = dyn Fn(&str) -> Option<(String, Type)> + 'a;
+
+// the data a global of type `t` starts with, from its initializer `tree`;
+// the string literals in it are added to `strings`
+fn global_init(
+    tree: &ParseNode,
+    t: &Type,
+    symbols: &Symbols,
+    strings: &mut Vec<(String, Vec<u8>)>,
+) -> Result<Vec<Datum>, Diagnostic> {
+    if tree.entry != NodeType::InitList {
+        return datum(tree, 0, t, symbols, strings);
+    }
+    let mut data = Vec::new();
+    for it in tree.child.iter() {
+        let (offset, t) = match (&it.entry, &it.ty) {
+            (NodeType::InitAt(offset), Some(t)) => (*offset, t),
+            _ => return Err(ice(it, "an initializer list item is not an InitAt")),
+        };
+        let init = it
+            .child
+            .first()
+            .ok_or_else(|| ice(it, "InitAt node has no child"))?;
+        data.extend(datum(init, offset, t, symbols, strings)?);
+    }
+    Ok(data)
+}
+
+// the scalar of type `t` at `offset` in a global, which the constant
+// expression `tree` initializes: an arithmetic one, or an address constant
+fn datum(
+    tree: &ParseNode,
+    offset: i64,
+    t: &Type,
+    symbols: &Symbols,
+    strings: &mut Vec<(String, Vec<u8>)>,
+) -> Result<Vec<Datum>, Diagnostic> {
+    // a struct could only be copied from another object
+    if let Type::Struct(_) = t {
+        let mut init = tree;
+        while is_wrapper(init) {
+            init = child(init, 0)?;
+        }
+        return Err(match init.entry {
+            NodeType::CompoundLiteral(_) => error(
+                init,
+                code::UNSUPPORTED,
+                "compound literal at file scope is not supported yet",
+            ),
+            _ => error(
+                tree,
+                code::NOT_CONSTANT,
+                "initializer element is not constant",
+            ),
+        });
+    }
+    if *t == Type::LongDouble {
+        return Ok(long_double_data(offset, compute_long(tree)?));
+    }
+    let (symbol, value) = match type_of(tree)?.decay() {
+        Type::Ptr(_) => compute_addr(tree, symbols, strings)?,
+        _ => (None, compute_init(tree, t)?),
+    };
+    // the linker can only fill in an address of 8 bytes
+    if symbol.is_some() && t.size() != 8 {
+        return Err(error(
+            tree,
+            code::NOT_CONSTANT,
+            "initializer element is not computable at load time",
+        ));
+    }
+    Ok(vec![Datum {
+        offset,
+        ty: Ty::of(t),
+        symbol,
+        value,
+    }])
+}
+
+// the long double `x` at `offset`, its mantissa and above it its sign and
+// exponent, the padding after them 0
+fn long_double_data(offset: i64, x: Extended) -> Vec<Datum> {
+    let datum = |offset, ty, value| Datum {
+        offset,
+        ty,
+        symbol: None,
+        value,
+    };
+    vec![
+        datum(offset, Ty::I64, x.mantissa as i64),
+        datum(offset + 8, Ty::I16, i64::from(x.sign_exp as i16)),
+    ]
+}
+
+// the address constant `tree`: a symbol and an offset from its address, or
+// only the offset for an integer converted to a pointer
+fn compute_addr(
+    tree: &ParseNode,
+    symbols: &Symbols,
+    strings: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(Option<String>, i64), Diagnostic> {
+    match &tree.entry {
+        // an array is the address of its first element, a function its own
+        _ if matches!(type_of(tree)?, Type::Array(_, _) | Type::Func(..)) => {
+            compute_place(tree, symbols, strings)
+        }
+        _ if is_wrapper(tree) => compute_addr(child(tree, 0)?, symbols, strings),
+        NodeType::UnExp(TokType::Addr) => compute_place(child(tree, 0)?, symbols, strings),
+        NodeType::BinExp(op @ TokType::Plus) | NodeType::BinExp(op @ TokType::Minus) => {
+            let (lhs, rhs) = (child(tree, 0)?, child(tree, 1)?);
+            let (ptr, n) = match type_of(lhs)?.decay().pointee() {
+                Some(_) => (lhs, rhs),
+                None => (rhs, lhs),
+            };
+            let size = type_of(ptr)?.decay().pointee().map_or(1, |t| t.size());
+            let (symbol, offset) = compute_addr(ptr, symbols, strings)?;
+            let n = compute_const(n)?.wrapping_mul(size);
+            match op {
+                TokType::Plus => Ok((symbol, offset.wrapping_add(n))),
+                _ => Ok((symbol, offset.wrapping_sub(n))),
+            }
+        }
+        NodeType::Cast(_) => {
+            let operand = child(tree, 0)?;
+            match type_of(operand)?.decay() {
+                Type::Ptr(_) => compute_addr(operand, symbols, strings),
+                _ => Ok((None, compute_const(operand)?)),
+            }
+        }
+        NodeType::Const(v, _) => Ok((None, *v)),
+        _ => Err(error(
+            tree,
+            code::NOT_CONSTANT,
+            "initializer element is not constant",
+        )),
+    }
+}
+
+// the address of the global object the lvalue `tree` designates, as an
+// address constant
+fn compute_place(
+    tree: &ParseNode,
+    symbols: &Symbols,
+    strings: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(Option<String>, i64), Diagnostic> {
+    match &tree.entry {
+        _ if is_wrapper(tree) => compute_place(child(tree, 0)?, symbols, strings),
+        NodeType::Var(name) => match symbols(name) {
+            Some((symbol, _)) => Ok((Some(symbol), 0)),
+            None if matches!(type_of(tree)?, Type::Func(..)) => Ok((Some(name.clone()), 0)),
+            None => Err(error(
+                tree,
+                code::NOT_CONSTANT,
+                "initializer element is not constant",
+            )),
+        },
+        NodeType::StringLiteral(data, tag) => {
+            strings.push((tag.clone(), data.clone()));
+            Ok((Some(tag.clone()), 0))
+        }
+        NodeType::CompoundLiteral(_) => Err(error(
+            tree,
+            code::UNSUPPORTED,
+            "compound literal at file scope is not supported yet",
+        )),
+        // only the elements of an array are where the variable is
+        NodeType::ArrayRef(name) => match symbols(name) {
+            Some((symbol, Type::Array(elem, _))) => {
+                let i = compute_const(child(tree, 0)?)?;
+                Ok((Some(symbol), i.wrapping_mul(elem.size())))
+            }
+            _ => Err(error(
+                tree,
+                code::NOT_CONSTANT,
+                "initializer element is not constant",
+            )),
+        },
+        NodeType::Member(name) => {
+            let base = child(tree, 0)?;
+            let offset = match type_of(base)? {
+                Type::Struct(s) => s.member(name).map(|m| m.offset),
+                _ => None,
+            }
+            .ok_or_else(|| ice(tree, &format!("member `{}` of a non-struct", name)))?;
+            let (symbol, base_offset) = compute_place(base, symbols, strings)?;
+            Ok((symbol, base_offset + offset))
+        }
+        // &*p is p
+        NodeType::UnExp(TokType::Multi) => compute_addr(child(tree, 0)?, symbols, strings),
+        _ => Err(error(
+            tree,
+            code::NOT_CONSTANT,
+            "initializer element is not constant",
+        )),
+    }
+}
+
+// the value of the constant expression `tree` converted to the scalar type
+// `t`, the bits of a floating one
+fn compute_init(tree: &ParseNode, t: &Type) -> Result<i64, Diagnostic> {
+    if t.is_floating() {
+        return Ok(Ty::of(t).float_bits(compute_float(tree)?));
+    }
+    if type_of(tree)? == Type::LongDouble {
+        let x = compute_long(tree)?;
+        return Ok(match t {
+            Type::Bool => is_nonzero(x) as i64,
+            t => t.wrap(x.to_i128() as i64),
+        });
+    }
+    if type_of(tree)?.is_floating() {
+        return Ok(int_of_float(compute_float(tree)?, t));
+    }
+    Ok(t.wrap(compute_const(tree)?))
+}
+
+// the floating value `x` converted to the integer type `t`, rounded towards
+// 0; C leaves what a value out of its range gives undefined
+fn int_of_float(x: f64, t: &Type) -> i64 {
+    match t {
+        Type::Bool => (x != 0.0) as i64,
+        t if !t.is_signed() && t.size() == 8 => x as u64 as i64,
+        t => t.wrap(x as i64),
+    }
+}
+
+// the integer constant `v` of type `from` as a constant of the floating `ty`,
+// rounded to it once; none for a long double a double can not hold
+fn float_of_int(v: i64, from: &Type, ty: Ty) -> Option<i64> {
+    let bits = match (ty, from.is_signed()) {
+        (Ty::F32, true) => i64::from((v as f32).to_bits()),
+        (Ty::F32, false) => i64::from((v as u64 as f32).to_bits()),
+        (_, true) => (v as f64).to_bits() as i64,
+        (_, false) => (v as u64 as f64).to_bits() as i64,
+    };
+    let exact = if from.is_signed() {
+        Extended::from_i64(v)
+    } else {
+        Extended::from_u64(v as u64)
+    };
+    match ty {
+        Ty::F80 if Extended::from_f64(f64::from_bits(bits as u64)) != exact => None,
+        _ => Some(bits),
+    }
+}
+
+// the struct `t` passed or returned by value: an eightbyte of one of up to
+// 16 bytes with only floating members in it goes in an xmm register
+fn struct_val(t: &Type) -> StructVal {
+    let size = t.size();
+    let sse = |w: i64| size <= 16 && 8 * w < size && !t.has_integer_in(8 * w, 8 * w + 8);
+    StructVal {
+        size,
+        align: t.align(),
+        sse: [sse(0), sse(1)],
+        x87: t.has_long_double(),
+    }
+}
+
+// the `case` and `default` statements in `tree` that belong to its switch,
+// those of a nested switch belong to that one
+fn switch_cases(tree: &ParseNode) -> Vec<&ParseNode> {
+    let mut cases = Vec::new();
+    for it in tree.child.iter() {
+        match it.entry {
+            NodeType::Stmt(StmtType::Switch) => {}
+            NodeType::Stmt(StmtType::Case) | NodeType::Stmt(StmtType::Default) => {
+                cases.push(it);
+                cases.append(&mut switch_cases(it));
+            }
+            _ => cases.append(&mut switch_cases(it)),
+        }
+    }
+    cases
+}
+
+// the labeled statements in `tree`, by where they start
+fn labels<'t>(tree: &'t ParseNode, found: &mut Vec<&'t ParseNode>) {
+    if let NodeType::Stmt(StmtType::Label(_)) = tree.entry {
+        found.push(tree);
+    }
+    for it in tree.child.iter() {
+        labels(it, found);
+    }
+}
+
+// the names of the variables whose address `tree` takes, they live in
+// memory; a name in one scope keeps all variables of it in any scope there
+fn addressed(tree: &ParseNode, found: &mut HashSet<String>) {
+    if let NodeType::UnExp(TokType::Addr) = tree.entry {
+        let mut operand = tree.child.first();
+        while let Some(c) = operand.filter(|c| is_wrapper(c)) {
+            operand = c.child.first();
+        }
+        if let Some(ParseNode {
+            entry: NodeType::Var(name),
+            ..
+        }) = operand
+        {
+            found.insert(name.clone());
+        }
+    }
+    for it in tree.child.iter() {
+        addressed(it, found);
+    }
+}
+
+// a local variable: kept in a register, or in a slot when it is a struct or
+// its address is taken
+#[derive(Clone)]
+enum Local {
+    Reg(VReg),
+    Slot(Slot),
+    Array(Slot),          // where its elements are, not a pointer to them
+    Global(String, Type), // a `static` or `extern` one, by its symbol
+}
+
+// where an lvalue is: a register, or the memory at the address in one
+#[derive(Clone, Copy)]
+enum Place {
+    Reg(VReg),
+    Mem(VReg),
+}
+
+// What the lowering keeps while it goes through one function.
+struct Lower<'a> {
+    globals: &'a HashMap<String, Type>,
+    strings: &'a mut Vec<(String, Vec<u8>)>,
+    statics: &'a mut Vec<Global>, // where a `static` in a block goes
+    ret: Type,
+    vregs: Vec<Ty>,
+    slots: Vec<(i64, i64)>,
+    // the instructions of each block, and its terminator once it has one
+    blocks: Vec<(Vec<Inst>, Option<Terminator>)>,
+    current: BlockId,
+    scopes: Vec<HashMap<String, Local>>, // the blocks we are in, the innermost last
+    addressed: HashSet<String>,
+    breaks: Vec<BlockId>,    // where a `break` goes, the innermost last
+    continues: Vec<BlockId>, // where a `continue` goes
+    // the blocks of the `case` and `default` labels of the switches we are
+    // in, by where they start
+    cases: Vec<HashMap<usize, BlockId>>,
+    labels: HashMap<usize, BlockId>, // of the labeled statements, the same way
+}
+
+impl<'a> Lower<'a> {
+    fn new(
+        globals: &'a HashMap<String, Type>,
+        strings: &'a mut Vec<(String, Vec<u8>)>,
+        statics: &'a mut Vec<Global>,
+        ret: &Type,
+    ) -> Lower<'a> {
+        Lower {
+            globals,
+            strings,
+            statics,
+            ret: ret.clone(),
+            vregs: Vec::new(),
+            slots: Vec::new(),
+            blocks: vec![(Vec::new(), None)],
+            current: BlockId(0),
+            scopes: Vec::new(),
+            addressed: HashSet::new(),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            cases: Vec::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    fn reg(&mut self, ty: Ty) -> VReg {
+        self.vregs.push(ty);
+        VReg(self.vregs.len() - 1)
+    }
+
+    // a stack slot for a `t`, in whole eightbytes so a struct can be moved
+    // in and out of it a register at a time
+    fn slot(&mut self, t: &Type) -> Slot {
+        self.slots
+            .push((align_to(t.size().max(1), 8), t.align().max(8)));
+        Slot(self.slots.len() - 1)
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push((Vec::new(), None));
+        BlockId(self.blocks.len() - 1)
+    }
+
+    // code after a `return` or a jump goes to a block nothing jumps to
+    fn reachable(&mut self) {
+        if self.blocks[self.current.0].1.is_some() {
+            self.current = self.block();
+        }
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        self.reachable();
+        self.blocks[self.current.0].0.push(inst);
+    }
+
+    fn terminate(&mut self, term: Terminator) {
+        self.reachable();
+        self.blocks[self.current.0].1 = Some(term);
+    }
+
+    // jump to `b`, unless a jump or a `return` has left the block already
+    fn fall(&mut self, b: BlockId) {
+        if self.blocks[self.current.0].1.is_none() {
+            self.terminate(Terminator::Jump(b));
+        }
+    }
+
+    // go on in `b`, the block before falls through to it
+    fn start(&mut self, b: BlockId) {
+        self.fall(b);
+        self.current = b;
+    }
+
+    // `op` in a register of the type `ty`
+    fn in_reg(&mut self, op: Operand, ty: Ty) -> VReg {
+        match op {
+            Operand::Reg(r) => r,
+            Operand::Imm(_) => {
+                let dst = self.reg(ty);
+                self.emit(Inst::Copy { dst, src: op });
+                dst
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinOp, ty: Ty, lhs: Operand, rhs: Operand) -> Operand {
+        let dst = self.reg(ty);
+        self.emit(Inst::Binary { op, dst, lhs, rhs });
+        Operand::Reg(dst)
+    }
+
+    fn cmp(&mut self, cond: Cond, ty: Ty, lhs: Operand, rhs: Operand) -> Operand {
+        self.cmp_to(cond, ty, lhs, rhs, Ty::I32)
+    }
+
+    // the comparison in a register of type `dst_ty`
+    fn cmp_to(&mut self, cond: Cond, ty: Ty, lhs: Operand, rhs: Operand, dst_ty: Ty) -> Operand {
+        let dst = self.reg(dst_ty);
+        self.emit(Inst::Cmp {
+            cond,
+            ty,
+            dst,
+            lhs,
+            rhs,
+        });
+        Operand::Reg(dst)
+    }
+
+    // the address `offset` bytes after `base`
+    fn offset(&mut self, base: VReg, offset: i64) -> VReg {
+        if offset == 0 {
+            return base;
+        }
+        let dst = self.reg(Ty::I64);
+        self.emit(Inst::Binary {
+            op: BinOp::Add,
+            dst,
+            lhs: Operand::Reg(base),
+            rhs: Operand::Imm(offset),
+        });
+        dst
+    }
+
+    // the integer `op` times the size of a pointee, for pointer arithmetic
+    fn scale(&mut self, op: Operand, size: i64) -> Operand {
+        match op {
+            Operand::Imm(v) => Operand::Imm(v.wrapping_mul(size)),
+            _ if size == 1 => op,
+            _ => self.binary(BinOp::Mul, Ty::I64, op, Operand::Imm(size)),
+        }
+    }
+
+    // the value `op` of type `from` converted to `to`; in a register only the
+    // bytes of its type count, so a narrower type is extended as it is widened
+    fn convert(&mut self, op: Operand, from: &Type, to: &Type) -> Operand {
+        if !to.is_arithmetic() && to.pointee().is_none() {
+            return op;
+        }
+        if from.is_floating() || to.is_floating() {
+            return self.convert_float(op, from, to);
+        }
+        let (ft, tt) = (Ty::of(from), Ty::of(to));
+        if let Operand::Imm(v) = op {
+            return Operand::Imm(if to.is_integer() { to.wrap(v) } else { v });
+        }
+        let src = self.in_reg(op, ft);
+        if *to == Type::Bool && *from != Type::Bool {
+            return self.cmp_to(Cond::Ne, ft, op, Operand::Imm(0), tt);
+        }
+        if ft == tt {
+            return op;
+        }
+        let dst = self.reg(tt);
+        if ft.size() < tt.size() {
+            self.emit(Inst::Ext {
+                signed: from.is_signed(),
+                dst,
+                src,
+            });
+        } else {
+            self.emit(Inst::Trunc { dst, src });
+        }
+        Operand::Reg(dst)
+    }
+
+    // convert for a floating `from` or `to`: Convert only takes an integer
+    // of 32 or 64 bits, a narrower one is extended before or truncated after
+    fn convert_float(&mut self, op: Operand, from: &Type, to: &Type) -> Operand {
+        let (ft, tt) = (Ty::of(from), Ty::of(to));
+        if let Operand::Imm(v) = op {
+            let folded = match (from.is_floating(), to.is_floating()) {
+                (true, true) => Some(tt.float_bits(ft.float_value(v))),
+                (true, false) => Some(int_of_float(ft.float_value(v), to)),
+                _ => float_of_int(v, from, tt),
+            };
+            if let Some(v) = folded {
+                return Operand::Imm(v);
+            }
+        }
+        let src = self.in_reg(op, ft);
+        if *to == Type::Bool {
+            return self.cmp_to(Cond::Ne, ft, op, Operand::Imm(0), tt);
+        }
+        if ft == tt {
+            return op;
+        }
+        let (signed, src) = match from {
+            _ if from.is_floating() => (to.is_signed(), src),
+            _ if ft.size() < 4 => {
+                let wide = self.reg(Ty::I32);
+                self.emit(Inst::Ext {
+                    signed: from.is_signed(),
+                    dst: wide,
+                    src,
+                });
+                (true, wide)
+            }
+            _ => (from.is_signed(), src),
+        };
+        let dst = self.reg(if tt.size() < 4 { Ty::I32 } else { tt });
+        self.emit(Inst::Convert { signed, dst, src });
+        if tt.size() >= 4 {
+            return Operand::Reg(dst);
+        }
+        let narrow = self.reg(tt);
+        self.emit(Inst::Trunc {
+            dst: narrow,
+            src: dst,
+        });
+        Operand::Reg(narrow)
+    }
+
+    fn load(&mut self, place: Place, t: &Type) -> Operand {
+        match (place, t) {
+            (Place::Reg(r), _) => Operand::Reg(r),
+            // an array or a struct is the address of its first byte, a
+            // function is its address
+            (Place::Mem(addr), Type::Array(_, _))
+            | (Place::Mem(addr), Type::Struct(_))
+            | (Place::Mem(addr), Type::Func(..)) => Operand::Reg(addr),
+            (Place::Mem(addr), t) => {
+                let dst = self.reg(Ty::of(t));
+                self.emit(Inst::Load { dst, addr });
+                Operand::Reg(dst)
+            }
+        }
+    }
+
+    // store the `value` of type `t`, a struct is copied from where it points
+    fn store(&mut self, place: Place, t: &Type, value: Operand) {
+        match (place, t) {
+            (Place::Reg(dst), _) => self.emit(Inst::Copy { dst, src: value }),
+            (Place::Mem(dst), Type::Struct(_)) => {
+                let src = self.in_reg(value, Ty::I64);
+                self.emit(Inst::MemCopy {
+                    dst,
+                    src,
+                    size: t.size(),
+                })
+            }
+            (Place::Mem(addr), t) => self.emit(Inst::Store {
+                ty: Ty::of(t),
+                addr,
+                src: value,
+            }),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn function(
+        &mut self,
+        tree: &ParseNode,
+        name: &str,
+        params: &Option<Vec<(String, Type)>>,
+        global: bool,
+    ) -> Result<Function, Diagnostic> {
+        addressed(tree, &mut self.addressed);
+        let mut found = Vec::new();
+        labels(tree, &mut found);
+        for label in found {
+            let b = self.block();
+            self.labels.insert(label.loc.span.0, b);
+        }
+        // the switches claim their labels, any left are outside of them
+        if let Some(c) = switch_cases(tree).first() {
+            return Err(error(
+                c,
+                code::NOT_IN_LOOP,
+                "case label not within a switch statement",
+            ));
+        }
+
+        let mut scope = HashMap::new();
+        let mut ir_params = Vec::new();
+        for (var, t) in params.iter().flatten() {
+            let local = match t {
+                Type::Struct(_) => {
+                    let slot = self.slot(t);
+                    ir_params.push(Param::Struct(slot, struct_val(t)));
+                    Local::Slot(slot)
+                }
+                t if self.addressed.contains(var) => {
+                    let r = self.reg(Ty::of(t));
+                    ir_params.push(Param::Value(r));
+                    let slot = self.slot(t);
+                    let addr = self.reg(Ty::I64);
+                    self.emit(Inst::SlotAddr { dst: addr, slot });
+                    self.store(Place::Mem(addr), t, Operand::Reg(r));
+                    Local::Slot(slot)
+                }
+                t => {
+                    let r = self.reg(Ty::of(t));
+                    ir_params.push(Param::Value(r));
+                    Local::Reg(r)
+                }
+            };
+            scope.insert(var.to_string(), local);
+        }
+        // the body may declare a parameter again, it shadows it
+        self.scopes = vec![scope, HashMap::new()];
+        for it in tree.child.iter() {
+            self.item(it)?;
+        }
+
+        let ret = match &self.ret {
+            Type::Void => RetKind::Void,
+            Type::Struct(_) => RetKind::Struct(struct_val(&self.ret)),
+            t => RetKind::Value(Ty::of(t)),
+        };
+        // running off the end of a function returns 0, even when there is a
+        // `return` somewhere before
+        if self.blocks[self.current.0].1.is_none() {
+            let value = match ret {
+                RetKind::Value(_) => Some(Operand::Imm(0)),
+                _ => None,
+            };
+            self.terminate(Terminator::Return(value));
+        }
+        let mut blocks = Vec::new();
+        for (insts, term) in std::mem::take(&mut self.blocks) {
+            let term = term.ok_or_else(|| ice(tree, "a block of the function has no end"))?;
+            blocks.push(Block { insts, term });
+        }
+        Ok(Function {
+            name: name.to_string(),
+            global,
+            params: ir_params,
+            variadic: false,
+            ret,
+            vregs: std::mem::take(&mut self.vregs),
+            slots: std::mem::take(&mut self.slots),
+            blocks,
+        })
+    }
+
+    fn item(&mut self, tree: &ParseNode) -> Result<(), Diagnostic> {
+        match &tree.entry {
+            NodeType::Declare(name, t, Storage::Static) => self.declare_static(tree, name, t),
+            NodeType::Declare(name, t, Storage::Extern) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.to_string(), Local::Global(name.to_string(), t.clone()));
+                }
+                Ok(())
+            }
+            NodeType::Declare(name, t, _) => self.declare(tree, name, t),
+            // the parser has taken care of the tag
+            NodeType::TagDecl(_) => Ok(()),
+            _ => self.stmt(tree),
+        }
+    }
+
+    fn declare(&mut self, tree: &ParseNode, name: &str, t: &Type) -> Result<(), Diagnostic> {
+        let (local, place) = match t {
+            Type::Array(_, _) | Type::Struct(_) => self.local_slot(t),
+            t if self.addressed.contains(name) => self.local_slot(t),
+            t => {
+                let r = self.reg(Ty::of(t));
+                (Local::Reg(r), Place::Reg(r))
+            }
+        };
+        // the variable is in scope in its own initializer
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), local);
+        }
+        match (tree.child.first(), place) {
+            (Some(init), Place::Mem(addr)) if init.entry == NodeType::InitList => {
+                self.init_list(init, addr, t)?
+            }
+            (Some(init), _) => {
+                let value = self.value(init)?;
+                let value = self.convert(value, &type_of(init)?.decay(), t);
+                self.store(place, t, value);
+            }
+            // not initialized, crust gives it 0 anyway
+            (None, Place::Reg(dst)) => self.emit(Inst::Copy {
+                dst,
+                src: Operand::Imm(0),
+            }),
+            (None, Place::Mem(addr)) => self.zero(addr, 0, t.size()),
+        }
+        Ok(())
+    }
+
+    // a `static` in a block is a global of its own, under a name no other
+    // one has, and starts with its initializer before the program runs
+    fn declare_static(&mut self, tree: &ParseNode, name: &str, t: &Type) -> Result<(), Diagnostic> {
+        let symbol = format!("{}.{}", name, self.statics.len());
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Local::Global(symbol.clone(), t.clone()));
+        }
+        let init = match tree.child.first() {
+            Some(init) => {
+                let (scopes, globals) = (&self.scopes, self.globals);
+                let symbols = |name: &str| match scopes.iter().rev().find_map(|s| s.get(name)) {
+                    Some(Local::Global(symbol, t)) => Some((symbol.clone(), t.clone())),
+                    Some(_) => None,
+                    None => globals.get(name).map(|t| (name.to_string(), t.clone())),
+                };
+                Some(global_init(init, t, &symbols, self.strings)?)
+            }
+            None => None,
+        };
+        self.statics.push(global_object(&symbol, false, t, init));
+        Ok(())
+    }
+
+    // the initializer list `init` of the object of type `t` at `addr`: sema
+    // left an InitAt node for every scalar it gives a value, the bytes
+    // between them are 0
+    fn init_list(&mut self, init: &ParseNode, addr: VReg, t: &Type) -> Result<(), Diagnostic> {
+        let mut done = 0;
+        for it in init.child.iter() {
+            let (offset, ty) = match (&it.entry, &it.ty) {
+                (NodeType::InitAt(offset), Some(ty)) => (*offset, ty),
+                _ => return Err(ice(it, "an initializer list item is not an InitAt")),
+            };
+            self.zero(addr, done, offset);
+            let init = child(it, 0)?;
+            let value = self.value(init)?;
+            let value = self.convert(value, &type_of(init)?.decay(), ty);
+            let at = self.offset(addr, offset);
+            self.store(Place::Mem(at), ty, value);
+            done = offset + ty.size();
+        }
+        self.zero(addr, done, t.size());
+        Ok(())
+    }
+
+    // store 0 over the bytes `from..to` of the object at `addr`, in a loop
+    // when there are many of them
+    fn zero(&mut self, addr: VReg, from: i64, to: i64) {
+        let mut from = from;
+        let words = (to - from) / 8;
+        if words > 8 {
+            // for (p = addr + from; p != end; p += 8) *p = 0;
+            let start = self.offset(addr, from);
+            let end = self.offset(addr, from + 8 * words);
+            let p = self.reg(Ty::I64);
+            self.emit(Inst::Copy {
+                dst: p,
+                src: Operand::Reg(start),
+            });
+            let (body, done) = (self.block(), self.block());
+            self.start(body);
+            self.emit(Inst::Store {
+                ty: Ty::I64,
+                addr: p,
+                src: Operand::Imm(0),
+            });
+            self.emit(Inst::Binary {
+                op: BinOp::Add,
+                dst: p,
+                lhs: Operand::Reg(p),
+                rhs: Operand::Imm(8),
+            });
+            let more = self.cmp_to(
+                Cond::Ne,
+                Ty::I64,
+                Operand::Reg(p),
+                Operand::Reg(end),
+                Ty::I32,
+            );
+            self.terminate(Terminator::Branch {
+                cond: more,
+                then: body,
+                els: done,
+            });
+            self.start(done);
+            from += 8 * words;
+        }
+        while from < to {
+            let ty = [Ty::I64, Ty::I32, Ty::I16, Ty::I8]
+                .iter()
+                .copied()
+                .find(|ty| ty.size() <= to - from)
+                .unwrap_or(Ty::I8);
+            let at = self.offset(addr, from);
+            self.emit(Inst::Store {
+                ty,
+                addr: at,
+                src: Operand::Imm(0),
+            });
+            from += ty.size();
+        }
+    }
+
+    fn local_slot(&mut self, t: &Type) -> (Local, Place) {
+        let slot = self.slot(t);
+        let addr = self.reg(Ty::I64);
+        self.emit(Inst::SlotAddr { dst: addr, slot });
+        let local = match t {
+            Type::Array(_, _) => Local::Array(slot),
+            _ => Local::Slot(slot),
+        };
+        (local, Place::Mem(addr))
+    }
+
+    // a statement, or the expression of one in a `for`
+    fn stmt(&mut self, tree: &ParseNode) -> Result<(), Diagnostic> {
+        let stmt = match &tree.entry {
+            NodeType::Stmt(stmt) => stmt,
+            _ => {
+                self.value(tree)?;
+                return Ok(());
+            }
+        };
+        match stmt {
+            StmtType::Return => {
+                let res = child(tree, 0)?;
+                let value = self.value(res)?;
+                let value = match self.ret.clone() {
+                    Type::Void => None,
+                    t => Some(self.convert(value, &type_of(res)?.decay(), &t)),
+                };
+                self.terminate(Terminator::Return(value));
+            }
+            StmtType::Exp => {
+                for it in tree.child.iter() {
+                    self.value(it)?;
+                }
+            }
+            StmtType::Conditional(_) => {
+                let cond = self.condition(child(tree, 0)?)?;
+                let (then, end) = (self.block(), self.block());
+                let els = if tree.child.len() == 3 {
+                    self.block()
+                } else {
+                    end
+                };
+                self.terminate(Terminator::Branch { cond, then, els });
+                self.current = then;
+                self.stmt(child(tree, 1)?)?;
+                if tree.child.len() == 3 {
+                    self.start(end);
+                    self.current = els;
+                    self.stmt(child(tree, 2)?)?;
+                }
+                self.start(end);
+            }
+            StmtType::Compound => {
+                self.scopes.push(HashMap::new());
+                for it in tree.child.iter() {
+                    self.item(it)?;
+                }
+                self.scopes.pop();
+            }
+            StmtType::For | StmtType::ForDecl => {
+                self.scopes.push(HashMap::new());
+                self.item(child(tree, 0)?)?;
+                let (cond, body, post, end) =
+                    (self.block(), self.block(), self.block(), self.block());
+                self.start(cond);
+                let c = self.condition(child(tree, 1)?)?;
+                self.terminate(Terminator::Branch {
+                    cond: c,
+                    then: body,
+                    els: end,
+                });
+                self.current = body;
+                self.in_loop(post, end, child(tree, 3)?)?;
+                // `continue` still runs the post expression
+                self.start(post);
+                self.value(child(tree, 2)?)?;
+                self.fall(cond);
+                self.current = end;
+                self.scopes.pop();
+            }
+            StmtType::While => {
+                let (cond, body, end) = (self.block(), self.block(), self.block());
+                self.start(cond);
+                let c = self.condition(child(tree, 0)?)?;
+                self.terminate(Terminator::Branch {
+                    cond: c,
+                    then: body,
+                    els: end,
+                });
+                self.current = body;
+                self.in_loop(cond, end, child(tree, 1)?)?;
+                self.fall(cond);
+                self.current = end;
+            }
+            StmtType::Do => {
+                let (body, cond, end) = (self.block(), self.block(), self.block());
+                self.start(body);
+                self.in_loop(cond, end, child(tree, 0)?)?;
+                self.start(cond);
+                let c = self.condition(child(tree, 1)?)?;
+                self.terminate(Terminator::Branch {
+                    cond: c,
+                    then: body,
+                    els: end,
+                });
+                self.current = end;
+            }
+            StmtType::Switch => self.switch(tree)?,
+            StmtType::Case | StmtType::Default => {
+                let b = self
+                    .cases
+                    .last()
+                    .and_then(|cases| cases.get(&tree.loc.span.0))
+                    .copied()
+                    .ok_or_else(|| ice(tree, "the case label has no switch"))?;
+                self.start(b);
+                self.stmt(tree.child.last().ok_or_else(|| ice(tree, "no statement"))?)?;
+            }
+            StmtType::Label(_) => {
+                let b = self.labels[&tree.loc.span.0];
+                self.start(b);
+                self.stmt(child(tree, 0)?)?;
+            }
+            StmtType::Goto(_, target) => match self.labels.get(target) {
+                Some(b) => self.terminate(Terminator::Jump(*b)),
+                None => return Err(ice(tree, "the label of the goto is not in the function")),
+            },
+            StmtType::Continue => match self.continues.last() {
+                Some(b) => self.terminate(Terminator::Jump(*b)),
+                None => {
+                    return Err(error(
+                        tree,
+                        code::NOT_IN_LOOP,
+                        "Continue should be in the loop scope",
+                    ))
+                }
+            },
+            StmtType::Break => match self.breaks.last() {
+                Some(b) => self.terminate(Terminator::Jump(*b)),
+                None => {
+                    return Err(error(
+                        tree,
+                        code::NOT_IN_LOOP,
+                        "Break should be in a loop or switch scope",
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // the body of a loop, where `continue` goes to `next` and `break` to `end`
+    fn in_loop(&mut self, next: BlockId, end: BlockId, body: &ParseNode) -> Result<(), Diagnostic> {
+        self.continues.push(next);
+        self.breaks.push(end);
+        let res = self.stmt(body);
+        self.continues.pop();
+        self.breaks.pop();
+        res
+    }
+
+    fn switch(&mut self, tree: &ParseNode) -> Result<(), Diagnostic> {
+        let exp = child(tree, 0)?;
+        let exp_type = type_of(exp)?;
+        // the case values are converted to the promoted type of the expression
+        let t = exp_type.promote();
+        let body = child(tree, 1)?;
+        let mut cases: Vec<(i64, &ParseNode)> = Vec::new();
+        let mut default: Option<&ParseNode> = None;
+        for c in switch_cases(body) {
+            if c.entry == NodeType::Stmt(StmtType::Default) {
+                if let Some(first) = default {
+                    return Err(error(
+                        c,
+                        code::REDECLARED,
+                        "multiple default labels in one switch",
+                    )
+                    .note(Some(&first.loc), "this is the first default label"));
+                }
+                default = Some(c);
+                continue;
+            }
+            let v = t.wrap(compute_const(child(c, 0)?)?);
+            if let Some((_, first)) = cases.iter().find(|(w, _)| *w == v) {
+                return Err(error(c, code::REDECLARED, "duplicate case value")
+                    .note(Some(&first.loc), "previously used here"));
+            }
+            cases.push((v, c));
+        }
+        // in the order of the type, an unsigned one has its large values negative
+        let unsigned = !t.is_signed();
+        cases.sort_by_key(|(v, _)| {
+            if unsigned {
+                i128::from(*v as u64)
+            } else {
+                i128::from(*v)
+            }
+        });
+
+        let value = self.value(exp)?;
+        let value = self.convert(value, &exp_type, &t);
+        let value = self.convert(value, &t, &Type::Long);
+        let value = self.in_reg(value, Ty::I64);
+        let end = self.block();
+        let mut blocks = HashMap::new();
+        let mut targets = Vec::new();
+        for (v, c) in cases {
+            let b = self.block();
+            blocks.insert(c.loc.span.0, b);
+            targets.push((v, b));
+        }
+        let default = match default {
+            Some(d) => {
+                let b = self.block();
+                blocks.insert(d.loc.span.0, b);
+                b
+            }
+            None => end,
+        };
+        self.terminate(Terminator::Switch {
+            value,
+            unsigned,
+            cases: targets,
+            default,
+        });
+        self.cases.push(blocks);
+        self.breaks.push(end);
+        let res = self.stmt(body);
+        self.breaks.pop();
+        self.cases.pop();
+        res?;
+        self.start(end);
+        Ok(())
+    }
+
+    // the value of the expression `tree`: a scalar, or the address of an
+    // array or a struct
+    fn value(&mut self, tree: &ParseNode) -> Result<Operand, Diagnostic> {
+        let value = match &tree.entry {
+            _ if is_wrapper(tree) => self.value(child(tree, 0)?)?,
+            // an empty <exp-option>, true where a `for` tests it
+            NodeType::ExpOption if tree.child.is_empty() => Operand::Imm(1),
+            NodeType::Const(v, _) => Operand::Imm(*v),
+            // a long double a double can not hold is loaded from memory
+            NodeType::FloatConst(c, Type::LongDouble)
+                if Extended::from_f64(c.value) != c.extended =>
+            {
+                let tag = format!(".LLD{}", self.strings.len());
+                let mut data = c.extended.mantissa.to_le_bytes().to_vec();
+                data.extend(c.extended.sign_exp.to_le_bytes());
+                self.strings.push((tag.clone(), data));
+                let addr = self.reg(Ty::I64);
+                self.emit(Inst::GlobalAddr {
+                    dst: addr,
+                    name: tag,
+                });
+                let dst = self.reg(Ty::F80);
+                self.emit(Inst::Load { dst, addr });
+                Operand::Reg(dst)
+            }
+            NodeType::FloatConst(c, t) => Operand::Imm(Ty::of(t).float_bits(c.value)),
+            NodeType::StringLiteral(data, tag) => {
+                self.strings.push((tag.clone(), data.clone()));
+                let dst = self.reg(Ty::I64);
+                self.emit(Inst::GlobalAddr {
+                    dst,
+                    name: tag.clone(),
+                });
+                Operand::Reg(dst)
+            }
+            NodeType::Var(_)
+            | NodeType::ArrayRef(_)
+            | NodeType::Member(_)
+            | NodeType::UnExp(TokType::Multi)
+            | NodeType::CompoundLiteral(_) => {
+                let place = self.place(tree)?;
+                self.load(place, &type_of(tree)?)
+            }
+            NodeType::AssignNode(_, elem) => {
+                let res = child(tree, usize::from(*elem))?;
+                self.assign(tree, tree, res)?
+            }
+            NodeType::Assign => self.assign(tree, child(tree, 0)?, child(tree, 1)?)?,
+            NodeType::AssignOp(op) => {
+                let (lhs, res) = (child(tree, 0)?, child(tree, 1)?);
+                let (t, rhs_type) = (type_of(lhs)?, type_of(res)?.decay());
+                let place = self.place(lhs)?;
+                let b = self.value(res)?;
+                let a = self.load(place, &t);
+                let value = self.arith(tree, op, (a, &t), (b, &rhs_type))?;
+                let value = self.convert(value, &op_type(op, &t, &rhs_type), &t);
+                self.store(place, &t, value);
+                value
+            }
+            // `++a` is `a += 1`, `a++` gives what `a` was before
+            NodeType::IncDec(op, postfix) => {
+                let operand = child(tree, 0)?;
+                let t = type_of(operand)?;
+                let place = self.place(operand)?;
+                let old = self.load(place, &t);
+                // the store overwrites a variable in a register, keep what it was
+                let old = match place {
+                    Place::Reg(_) if *postfix => {
+                        let dst = self.reg(Ty::of(&t));
+                        self.emit(Inst::Copy { dst, src: old });
+                        Operand::Reg(dst)
+                    }
+                    _ => old,
+                };
+                let bin = match op {
+                    TokType::Inc => TokType::Plus,
+                    _ => TokType::Minus,
+                };
+                let one = (Operand::Imm(1), &Type::Int);
+                let value = self.arith(tree, &bin, (old, &t), one)?;
+                let value = self.convert(value, &op_type(&bin, &t, &Type::Int), &t);
+                self.store(place, &t, value);
+                if *postfix {
+                    old
+                } else {
+                    value
+                }
+            }
+            NodeType::FnCall(name) => self.call(tree, name)?,
+            NodeType::CallPtr => {
+                let callee = self.value(child(tree, 0)?)?;
+                let callee = self.in_reg(callee, Ty::I64);
+                self.emit_call(tree, Callee::Ptr(callee), 1)?
+            }
+            NodeType::VaArg(t) => self.va_arg(tree, t)?,
+            NodeType::Cast(t) => {
+                let value = child(tree, 0)?;
+                let op = self.value(value)?;
+                self.convert(op, &type_of(value)?.decay(), t)
+            }
+            NodeType::UnExp(TokType::Addr) => match self.place(child(tree, 0)?)? {
+                Place::Mem(addr) => Operand::Reg(addr),
+                Place::Reg(_) => return Err(ice(tree, "the variable is not in memory")),
+            },
+            NodeType::UnExp(op @ TokType::Minus) | NodeType::UnExp(op @ TokType::Tilde) => {
+                // computed in the promoted type of the operand
+                let operand = child(tree, 0)?;
+                let t = type_of(tree)?;
+                let src = self.value(operand)?;
+                let src = self.convert(src, &type_of(operand)?, &t);
+                let dst = self.reg(Ty::of(&t));
+                let op = if *op == TokType::Minus {
+                    UnOp::Neg
+                } else {
+                    UnOp::Not
+                };
+                self.emit(Inst::Unary { op, dst, src });
+                Operand::Reg(dst)
+            }
+            NodeType::UnExp(TokType::Exclamation) => {
+                let operand = child(tree, 0)?;
+                let src = self.value(operand)?;
+                let ty = Ty::of(&type_of(operand)?.decay());
+                self.cmp(Cond::Eq, ty, src, Operand::Imm(0))
+            }
+            NodeType::BinExp(TokType::And) | NodeType::BinExp(TokType::Or) => {
+                // the right operand only runs when the left one does not decide
+                let is_and = tree.entry == NodeType::BinExp(TokType::And);
+                let dst = self.reg(Ty::I32);
+                let (rhs, end) = (self.block(), self.block());
+                let lhs = self.condition(child(tree, 0)?)?;
+                self.emit(Inst::Copy {
+                    dst,
+                    src: Operand::Imm(if is_and { 0 } else { 1 }),
+                });
+                let (then, els) = if is_and { (rhs, end) } else { (end, rhs) };
+                self.terminate(Terminator::Branch {
+                    cond: lhs,
+                    then,
+                    els,
+                });
+                self.current = rhs;
+                let operand = child(tree, 1)?;
+                let src = self.value(operand)?;
+                let ty = Ty::of(&type_of(operand)?.decay());
+                self.emit(Inst::Cmp {
+                    cond: Cond::Ne,
+                    ty,
+                    dst,
+                    lhs: src,
+                    rhs: Operand::Imm(0),
+                });
+                self.start(end);
+                Operand::Reg(dst)
+            }
+            NodeType::BinExp(TokType::Comma) => {
+                self.value(child(tree, 0)?)?;
+                self.value(child(tree, 1)?)?
+            }
+            NodeType::BinExp(op) => self.binary_exp(tree, op)?,
+            NodeType::ConditionalExp if tree.child.len() == 3 => {
+                // both branches give a value of the type of the whole expression
+                let t = type_of(tree)?;
+                let dst = match t {
+                    Type::Void => None,
+                    _ => Some(self.reg(Ty::of(&t))),
+                };
+                let cond = self.condition(child(tree, 0)?)?;
+                let (then, els, end) = (self.block(), self.block(), self.block());
+                self.terminate(Terminator::Branch { cond, then, els });
+                for (i, b) in [(1, then), (2, els)] {
+                    self.current = b;
+                    let e = child(tree, i)?;
+                    let value = self.value(e)?;
+                    let value = self.convert(value, &type_of(e)?.decay(), &t);
+                    if let Some(dst) = dst {
+                        self.emit(Inst::Copy { dst, src: value });
+                    }
+                    self.start(end);
+                }
+                match dst {
+                    Some(dst) => Operand::Reg(dst),
+                    None => Operand::Imm(0),
+                }
+            }
+            _ => {
+                return Err(ice(
+                    tree,
+                    &format!("`{:?}` is not an expression", tree.entry),
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    // the value of the controlling expression `tree` for a branch, which
+    // tests an integer; a floating one is compared with 0
+    fn condition(&mut self, tree: &ParseNode) -> Result<Operand, Diagnostic> {
+        let value = self.value(tree)?;
+        let t = type_of(tree)?;
+        if !t.is_floating() {
+            return Ok(value);
+        }
+        Ok(self.cmp(Cond::Ne, Ty::of(&t), value, Operand::Imm(0)))
+    }
+
+    // `lhs = res` for the assignment `tree`, the value is the one stored
+    fn assign(
+        &mut self,
+        tree: &ParseNode,
+        lhs: &ParseNode,
+        res: &ParseNode,
+    ) -> Result<Operand, Diagnostic> {
+        let t = type_of(tree)?;
+        let value = self.value(res)?;
+        let value = self.convert(value, &type_of(res)?.decay(), &t);
+        let place = self.place(lhs)?;
+        self.store(place, &t, value);
+        Ok(value)
+    }
+
+    // where the lvalue `tree` is
+    fn place(&mut self, tree: &ParseNode) -> Result<Place, Diagnostic> {
+        let place = match &tree.entry {
+            _ if is_wrapper(tree) => self.place(child(tree, 0)?)?,
+            NodeType::Var(name) | NodeType::AssignNode(name, false) => self.var(tree, name)?,
+            NodeType::ArrayRef(name) | NodeType::AssignNode(name, true) => {
+                Place::Mem(self.elem(tree, name)?)
+            }
+            // the struct is its address already, the member is at an offset in it
+            NodeType::Member(name) => {
+                let base = child(tree, 0)?;
+                let offset = match type_of(base)? {
+                    Type::Struct(s) => match s.member(name) {
+                        Some(m) => m.offset,
+                        None => {
+                            return Err(ice(
+                                tree,
+                                &format!("`{}` has no member named `{}`", s.name, name),
+                            ))
+                        }
+                    },
+                    t => return Err(ice(tree, &format!("member `{}` of `{}`", name, t))),
+                };
+                let addr = self.value(base)?;
+                let addr = self.in_reg(addr, Ty::I64);
+                Place::Mem(self.offset(addr, offset))
+            }
+            // an object of its own, which lives until the end of the block
+            NodeType::CompoundLiteral(t) => {
+                let (_, place) = self.local_slot(t);
+                if let Place::Mem(addr) = place {
+                    let init = child(tree, 0)?;
+                    if init.entry == NodeType::InitList {
+                        self.init_list(init, addr, t)?;
+                    } else {
+                        let value = self.value(init)?;
+                        let value = self.convert(value, &type_of(init)?.decay(), t);
+                        self.store(place, t, value);
+                    }
+                }
+                place
+            }
+            // the address *p refers to is the value of p
+            NodeType::UnExp(TokType::Multi) => {
+                let addr = self.value(child(tree, 0)?)?;
+                Place::Mem(self.in_reg(addr, Ty::I64))
+            }
+            _ => return Err(ice(tree, "the expression has no address")),
+        };
+        Ok(place)
+    }
+
+    // the variable `name`, local ones shadow the globals
+    fn var(&mut self, tree: &ParseNode, name: &str) -> Result<Place, Diagnostic> {
+        let place = match self.lookup(name) {
+            Some(Local::Reg(r)) => Place::Reg(r),
+            Some(Local::Slot(slot)) | Some(Local::Array(slot)) => {
+                let dst = self.reg(Ty::I64);
+                self.emit(Inst::SlotAddr { dst, slot });
+                Place::Mem(dst)
+            }
+            Some(Local::Global(name, _)) => {
+                let dst = self.reg(Ty::I64);
+                self.emit(Inst::GlobalAddr { dst, name });
+                Place::Mem(dst)
+            }
+            None if self.globals.contains_key(name) || matches!(type_of(tree)?, Type::Func(..)) => {
+                let dst = self.reg(Ty::I64);
+                self.emit(Inst::GlobalAddr {
+                    dst,
+                    name: name.to_string(),
+                });
+                Place::Mem(dst)
+            }
+            None => return Err(ice(tree, &format!("`{}` was not resolved", name))),
+        };
+        Ok(place)
+    }
+
+    // the address of the element `name[...]` of the array or pointer `name`,
+    // the index is the first child of `tree`
+    fn elem(&mut self, tree: &ParseNode, name: &str) -> Result<VReg, Diagnostic> {
+        let is_array = match self.lookup(name) {
+            Some(local) => matches!(local, Local::Array(_) | Local::Global(_, Type::Array(_, _))),
+            None => matches!(self.globals.get(name), Some(Type::Array(_, _))),
+        };
+        let place = self.var(tree, name)?;
+        // an array is where its elements are, a pointer has their address
+        let base = match place {
+            Place::Mem(addr) if is_array => Operand::Reg(addr),
+            place => self.load(place, &Type::ptr_to(Type::Void)),
+        };
+        let index = child(tree, 0)?;
+        let i = self.value(index)?;
+        let i = self.convert(i, &type_of(index)?.decay(), &Type::Long);
+        let i = self.scale(i, type_of(tree)?.size());
+        let addr = self.binary(BinOp::Add, Ty::I64, base, i);
+        Ok(self.in_reg(addr, Ty::I64))
+    }
+
+    fn binary_exp(&mut self, tree: &ParseNode, op: &TokType) -> Result<Operand, Diagnostic> {
+        let (lhs, rhs) = (child(tree, 0)?, child(tree, 1)?);
+        let lhs_type = type_of(lhs)?.decay();
+        let rhs_type = type_of(rhs)?.decay();
+        let a = self.value(lhs)?;
+        let b = self.value(rhs)?;
+        self.arith(tree, op, (a, &lhs_type), (b, &rhs_type))
+    }
+
+    // `a op b` for the values `a` and `b` of the types paired with them
+    fn arith(
+        &mut self,
+        tree: &ParseNode,
+        op: &TokType,
+        (a, lhs_type): (Operand, &Type),
+        (b, rhs_type): (Operand, &Type),
+    ) -> Result<Operand, Diagnostic> {
+        let value = match op {
+            TokType::Plus | TokType::Minus => {
+                let bin = if *op == TokType::Plus {
+                    BinOp::Add
+                } else {
+                    BinOp::Sub
+                };
+                // pointer arithmetic counts in elements: scale the integer side by the
+                // pointee size, and divide the difference of two pointers by it.
+                // integers are converted to their common type first.
+                match (lhs_type.pointee(), rhs_type.pointee()) {
+                    (Some(l), Some(_)) => {
+                        let size = l.size();
+                        let diff = self.binary(BinOp::Sub, Ty::I64, a, b);
+                        if size == 1 {
+                            diff
+                        } else {
+                            self.binary(BinOp::SDiv, Ty::I64, diff, Operand::Imm(size))
+                        }
+                    }
+                    (Some(l), None) => {
+                        let size = l.size();
+                        let b = self.convert(b, rhs_type, &Type::Long);
+                        let b = self.scale(b, size);
+                        self.binary(bin, Ty::I64, a, b)
+                    }
+                    (None, Some(r)) => {
+                        let size = r.size();
+                        let a = self.convert(a, lhs_type, &Type::Long);
+                        let a = self.scale(a, size);
+                        self.binary(bin, Ty::I64, a, b)
+                    }
+                    (None, None) => {
+                        let common = Type::common(lhs_type, rhs_type);
+                        let a = self.convert(a, lhs_type, &common);
+                        let b = self.convert(b, rhs_type, &common);
+                        self.binary(bin, Ty::of(&common), a, b)
+                    }
+                }
+            }
+            TokType::Multi
+            | TokType::Splash
+            | TokType::Percent
+            | TokType::Addr
+            | TokType::BitOr
+            | TokType::BitXor => {
+                let common = Type::common(lhs_type, rhs_type);
+                let a = self.convert(a, lhs_type, &common);
+                let b = self.convert(b, rhs_type, &common);
+                let bin = match (op, common.is_signed()) {
+                    (TokType::Multi, _) => BinOp::Mul,
+                    (TokType::Addr, _) => BinOp::And,
+                    (TokType::BitOr, _) => BinOp::Or,
+                    (TokType::BitXor, _) => BinOp::Xor,
+                    (TokType::Splash, _) if common.is_floating() => BinOp::FDiv,
+                    (TokType::Splash, true) => BinOp::SDiv,
+                    (TokType::Splash, false) => BinOp::UDiv,
+                    (_, true) => BinOp::SRem,
+                    (_, false) => BinOp::URem,
+                };
+                self.binary(bin, Ty::of(&common), a, b)
+            }
+            // the count is converted to the type of what it shifts, `>>`
+            // keeps the sign of a signed one
+            TokType::Shl | TokType::Shr => {
+                let t = lhs_type.promote();
+                let a = self.convert(a, lhs_type, &t);
+                let b = self.convert(b, rhs_type, &t);
+                let bin = match (op, t.is_signed()) {
+                    (TokType::Shl, _) => BinOp::Shl,
+                    (_, true) => BinOp::Sar,
+                    (_, false) => BinOp::Shr,
+                };
+                self.binary(bin, Ty::of(&t), a, b)
+            }
+            TokType::Equal
+            | TokType::NotEqual
+            | TokType::Lt
+            | TokType::Gt
+            | TokType::LessEqual
+            | TokType::GreaterEqual => {
+                // numbers are compared in their common type, pointers as unsigned addresses
+                let common = if lhs_type.is_arithmetic() && rhs_type.is_arithmetic() {
+                    Type::common(lhs_type, rhs_type)
+                } else {
+                    Type::ULong
+                };
+                let a = self.convert(a, lhs_type, &common);
+                let b = self.convert(b, rhs_type, &common);
+                let cond = match (op, common.is_signed() || common.is_floating()) {
+                    (TokType::Equal, _) => Cond::Eq,
+                    (TokType::NotEqual, _) => Cond::Ne,
+                    (TokType::Lt, true) => Cond::Lt,
+                    (TokType::Lt, false) => Cond::ULt,
+                    (TokType::Gt, true) => Cond::Gt,
+                    (TokType::Gt, false) => Cond::UGt,
+                    (TokType::LessEqual, true) => Cond::Le,
+                    (TokType::LessEqual, false) => Cond::ULe,
+                    (TokType::GreaterEqual, true) => Cond::Ge,
+                    (_, _) => Cond::UGe,
+                };
+                self.cmp(cond, Ty::of(&common), a, b)
+            }
+            _ => {
+                return Err(error(
+                    tree,
+                    code::UNSUPPORTED,
+                    &format!("Binary Operator `{:?}` not implemented", op),
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    fn call(&mut self, tree: &ParseNode, name: &str) -> Result<Operand, Diagnostic> {
+        match name {
+            "__builtin_va_start" => {
+                let ap = self.value(child(tree, 0)?)?;
+                let ap = self.in_reg(ap, Ty::I64);
+                self.emit(Inst::VaStart { ap });
+                return Ok(Operand::Imm(0));
+            }
+            // nothing to clean up
+            "__builtin_va_end" => {
+                self.value(child(tree, 0)?)?;
+                return Ok(Operand::Imm(0));
+            }
+            "__builtin_va_copy" => {
+                let (dst, src) = (self.value(child(tree, 0)?)?, self.value(child(tree, 1)?)?);
+                let (dst, src) = (self.in_reg(dst, Ty::I64), self.in_reg(src, Ty::I64));
+                let size = type_of(child(tree, 0)?)?
+                    .decay()
+                    .pointee()
+                    .map_or(0, Type::size);
+                self.emit(Inst::MemCopy { dst, src, size });
+                return Ok(Operand::Imm(0));
+            }
+            _ => {}
+        }
+        self.emit_call(tree, Callee::Named(name.to_string()), 0)
+    }
+
+    // the call of `callee` with the arguments in the kids of `tree` from
+    // `first` on
+    fn emit_call(
+        &mut self,
+        tree: &ParseNode,
+        callee: Callee,
+        first: usize,
+    ) -> Result<Operand, Diagnostic> {
+        let mut args = Vec::new();
+        for arg in tree.child[first..].iter() {
+            let t = type_of(arg)?.decay();
+            let value = self.value(arg)?;
+            match t {
+                Type::Struct(_) => {
+                    let addr = self.in_reg(value, Ty::I64);
+                    args.push(Arg::Struct(addr, struct_val(&t)));
+                }
+                // what is narrower than int is passed as an int
+                t => {
+                    let promoted = t.promote();
+                    let value = self.convert(value, &t, &promoted);
+                    args.push(Arg::Value(Ty::of(&promoted), value));
+                }
+            }
+        }
+        let ret_type = type_of(tree)?;
+        let (ret, value) = match ret_type {
+            Type::Void => (CallRet::Void, Operand::Imm(0)),
+            // a struct goes to a slot of its own, its value is the address there
+            Type::Struct(_) => {
+                let slot = self.slot(&ret_type);
+                let addr = self.reg(Ty::I64);
+                self.emit(Inst::SlotAddr { dst: addr, slot });
+                (
+                    CallRet::Struct(addr, struct_val(&ret_type)),
+                    Operand::Reg(addr),
+                )
+            }
+            t => {
+                let dst = self.reg(Ty::of(&t));
+                (CallRet::Value(dst), Operand::Reg(dst))
+            }
+        };
+        self.emit(Inst::Call { ret, callee, args });
+        Ok(value)
+    }
+
+    // `va_arg(ap, t)`: the next argument comes from the register save area
+    // while there are registers left for all of it, the way the caller
+    // passes it, and from the arguments on the stack after that
+    fn va_arg(&mut self, tree: &ParseNode, t: &Type) -> Result<Operand, Diagnostic> {
+        let ap = self.value(child(tree, 0)?)?;
+        let ap = self.in_reg(ap, Ty::I64);
+        // how many general and xmm registers it takes
+        let regs = match t {
+            // a long double and a struct with one are always on the stack
+            _ if t.has_long_double() => None,
+            Type::Struct(_) if t.size() > 16 => None,
+            Type::Struct(_) => {
+                let s = struct_val(t);
+                let words = ((s.size + 7) / 8) as usize;
+                let sse = s.sse[..words].iter().filter(|sse| **sse).count();
+                Some((words - sse, sse))
+            }
+            t if t.is_floating() => Some((0, 1)),
+            _ => Some((1, 0)),
+        };
+        let addr = self.reg(Ty::I64);
+        let (on_stack, done) = (self.block(), self.block());
+        if let Some((n, m)) = regs {
+            let (gp_addr, fp_addr) = (self.offset(ap, VA_GP_OFFSET), self.offset(ap, VA_FP_OFFSET));
+            let (gp, fp) = (self.reg(Ty::I32), self.reg(Ty::I32));
+            self.emit(Inst::Load {
+                dst: gp,
+                addr: gp_addr,
+            });
+            self.emit(Inst::Load {
+                dst: fp,
+                addr: fp_addr,
+            });
+            let gp_end = Operand::Imm(REG_SAVE_GP_SIZE - 8 * n as i64);
+            let fp_end = Operand::Imm(REG_SAVE_SIZE - 16 * m as i64);
+            let gp_left = self.cmp(Cond::Le, Ty::I32, Operand::Reg(gp), gp_end);
+            let fp_left = self.cmp(Cond::Le, Ty::I32, Operand::Reg(fp), fp_end);
+            let left = self.binary(BinOp::And, Ty::I32, gp_left, fp_left);
+            let in_regs = self.block();
+            self.terminate(Terminator::Branch {
+                cond: left,
+                then: in_regs,
+                els: on_stack,
+            });
+            self.start(in_regs);
+            let save_addr = self.offset(ap, VA_REG_SAVE_AREA);
+            let save = self.reg(Ty::I64);
+            self.emit(Inst::Load {
+                dst: save,
+                addr: save_addr,
+            });
+            let (gp64, fp64) = (self.reg(Ty::I64), self.reg(Ty::I64));
+            self.emit(Inst::Ext {
+                signed: false,
+                dst: gp64,
+                src: gp,
+            });
+            self.emit(Inst::Ext {
+                signed: false,
+                dst: fp64,
+                src: fp,
+            });
+            let gp_at = self.binary(BinOp::Add, Ty::I64, Operand::Reg(save), Operand::Reg(gp64));
+            let fp_at = self.binary(BinOp::Add, Ty::I64, Operand::Reg(save), Operand::Reg(fp64));
+            let (gp_at, fp_at) = (self.in_reg(gp_at, Ty::I64), self.in_reg(fp_at, Ty::I64));
+            match t {
+                // the eightbytes are apart in the save area, they go together
+                // in a slot
+                Type::Struct(_) => {
+                    let s = struct_val(t);
+                    let slot = self.slot(t);
+                    self.emit(Inst::SlotAddr { dst: addr, slot });
+                    let (mut reg, mut xmm) = (0, 0);
+                    for w in 0..(s.size + 7) / 8 {
+                        let src = if s.sse[w as usize] {
+                            xmm += 1;
+                            self.offset(fp_at, 16 * (xmm - 1))
+                        } else {
+                            reg += 1;
+                            self.offset(gp_at, 8 * (reg - 1))
+                        };
+                        let dst = self.offset(addr, 8 * w);
+                        let size = (s.size - 8 * w).min(8);
+                        self.emit(Inst::MemCopy { dst, src, size });
+                    }
+                }
+                t if t.is_floating() => self.emit(Inst::Copy {
+                    dst: addr,
+                    src: Operand::Reg(fp_at),
+                }),
+                _ => self.emit(Inst::Copy {
+                    dst: addr,
+                    src: Operand::Reg(gp_at),
+                }),
+            }
+            if n > 0 {
+                let gp = self.binary(
+                    BinOp::Add,
+                    Ty::I32,
+                    Operand::Reg(gp),
+                    Operand::Imm(8 * n as i64),
+                );
+                self.store(Place::Mem(gp_addr), &Type::UInt, gp);
+            }
+            if m > 0 {
+                let fp = self.binary(
+                    BinOp::Add,
+                    Ty::I32,
+                    Operand::Reg(fp),
+                    Operand::Imm(16 * m as i64),
+                );
+                self.store(Place::Mem(fp_addr), &Type::UInt, fp);
+            }
+            self.terminate(Terminator::Jump(done));
+        }
+        // on the stack each argument takes whole eightbytes, one aligned to
+        // 16 starting at an address aligned so
+        self.start(on_stack);
+        let overflow_addr = self.offset(ap, VA_OVERFLOW_ARG_AREA);
+        if t.align() > 8 {
+            let area = self.reg(Ty::I64);
+            self.emit(Inst::Load {
+                dst: area,
+                addr: overflow_addr,
+            });
+            let up = self.binary(BinOp::Add, Ty::I64, Operand::Reg(area), Operand::Imm(15));
+            let src = self.binary(BinOp::And, Ty::I64, up, Operand::Imm(-16));
+            self.emit(Inst::Copy { dst: addr, src });
+        } else {
+            self.emit(Inst::Load {
+                dst: addr,
+                addr: overflow_addr,
+            });
+        }
+        let next = self.binary(
+            BinOp::Add,
+            Ty::I64,
+            Operand::Reg(addr),
+            Operand::Imm(align_to(t.size().max(1), 8)),
+        );
+        self.store(Place::Mem(overflow_addr), &Type::ptr_to(Type::Void), next);
+        self.start(done);
+        // a float is passed as a double
+        Ok(match t {
+            Type::Float => {
+                let value = self.load(Place::Mem(addr), &Type::Double);
+                self.convert(value, &Type::Double, t)
+            }
+            t => self.load(Place::Mem(addr), t),
+        })
+    }
+}
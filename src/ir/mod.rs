@@ -0,0 +1,824 @@
+use crate::types::Type;
+use std::fmt;
+
+pub mod liveness;
+pub mod lower;
+
+// The intermediate representation between the checked tree and the
+// assembly.  A function is a list of basic blocks, each a straight run of
+// three-address instructions ending in the one terminator that says where
+// control goes next.  Values live in virtual registers, as many as the
+// function needs, each with the integer or floating type it holds; a pointer
+// is an i64.
+// What has to be in memory, a struct or a variable whose address is taken,
+// gets a stack slot instead and is reached through loads and stores.
+//
+// A virtual register may be assigned more than once, a local variable kept
+// in one is assigned wherever the C source assigns it.
+
+// the type of a value in a register: an integer by its size, or a float, a
+// double or a long double, an f80, which only the x87 computes on and which
+// never gets a machine register but 16 bytes of the frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Ty {
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    F80,
+}
+
+impl Ty {
+    // the register type for a value of the C type `t`: an array, a struct and
+    // a union are the address of their first byte
+    pub fn of(t: &Type) -> Ty {
+        match t {
+            Type::Array(_, _) | Type::Struct(_) | Type::Func(..) => Ty::I64,
+            Type::Float => Ty::F32,
+            Type::Double => Ty::F64,
+            Type::LongDouble => Ty::F80,
+            t => match t.size() {
+                1 => Ty::I8,
+                2 => Ty::I16,
+                4 => Ty::I32,
+                _ => Ty::I64,
+            },
+        }
+    }
+
+    pub fn size(self) -> i64 {
+        match self {
+            Ty::I8 => 1,
+            Ty::I16 => 2,
+            Ty::I32 | Ty::F32 => 4,
+            Ty::I64 | Ty::F64 => 8,
+            Ty::F80 => 16,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Ty::F32 | Ty::F64 | Ty::F80)
+    }
+
+    // the value of the constant `bits` of this floating type
+    pub fn float_value(self, bits: i64) -> f64 {
+        match self {
+            Ty::F32 => f64::from(f32::from_bits(bits as u32)),
+            _ => f64::from_bits(bits as u64),
+        }
+    }
+
+    // the constant of this floating type closest to `x`
+    pub fn float_bits(self, x: f64) -> i64 {
+        match self {
+            Ty::F32 => i64::from((x as f32).to_bits()),
+            _ => x.to_bits() as i64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct VReg(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct BlockId(pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub struct Slot(pub usize);
+
+// what an instruction reads: a register, or a constant of the type the
+// instruction works in, the bits of a floating one; an f80 constant is a
+// double, the bits of one whose value the f80 has exactly
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Operand {
+    Reg(VReg),
+    Imm(i64),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+    FDiv, // the division of floating values, which only take it, add, sub and mul
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr, // logical
+    Sar, // arithmetic
+}
+
+// a comparison, the ones with a `U` take the operands as unsigned; floating
+// operands take the others, and when either is a NaN only `Ne` holds
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+}
+
+// a struct passed or returned by value: its size and alignment, for each
+// eightbyte of one of up to 16 bytes whether only floating members are in
+// it, which puts it in an xmm register rather than a general one, and
+// whether a long double is in it, which puts it in memory however small
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct StructVal {
+    pub size: i64,
+    pub align: i64,
+    pub sse: [bool; 2],
+    pub x87: bool,
+}
+
+// an argument of a call: a scalar of the type, or the address of a struct
+// passed by value
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Arg {
+    Value(Ty, Operand),
+    Struct(VReg, StructVal),
+}
+
+// where a call puts what the function gives back: nothing, a register, or
+// the memory at an address for a struct
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CallRet {
+    Void,
+    Value(VReg),
+    Struct(VReg, StructVal),
+}
+
+// the function a call goes to: one by its name, or the one at the address
+// in a register
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Callee {
+    Named(String),
+    Ptr(VReg),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Inst {
+    Copy {
+        dst: VReg,
+        src: Operand,
+    },
+    Unary {
+        op: UnOp,
+        dst: VReg,
+        src: Operand,
+    },
+    // in the type of `dst`
+    Binary {
+        op: BinOp,
+        dst: VReg,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    // 1 if the operands of type `ty` compare so, 0 otherwise
+    Cmp {
+        cond: Cond,
+        ty: Ty,
+        dst: VReg,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    // `src` sign or zero extended to the wider `dst`
+    Ext {
+        signed: bool,
+        dst: VReg,
+        src: VReg,
+    },
+    // the low bytes of `src` in the narrower `dst`
+    Trunc {
+        dst: VReg,
+        src: VReg,
+    },
+    // `src` converted to the type of `dst`, of which at least one is
+    // floating and an integer is an i32 or an i64, signed or not; a floating
+    // value becomes an integer rounded towards 0
+    Convert {
+        signed: bool,
+        dst: VReg,
+        src: VReg,
+    },
+    // the value of the type of `dst` at `addr`
+    Load {
+        dst: VReg,
+        addr: VReg,
+    },
+    Store {
+        ty: Ty,
+        addr: VReg,
+        src: Operand,
+    },
+    SlotAddr {
+        dst: VReg,
+        slot: Slot,
+    },
+    // the address of a global variable or a string literal
+    GlobalAddr {
+        dst: VReg,
+        name: String,
+    },
+    MemCopy {
+        dst: VReg,
+        src: VReg,
+        size: i64,
+    },
+    Call {
+        ret: CallRet,
+        callee: Callee,
+        args: Vec<Arg>,
+    },
+    // fill in the va_list at `ap` for the arguments after the named ones of
+    // a variadic function
+    VaStart {
+        ap: VReg,
+    },
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Terminator {
+    Jump(BlockId),
+    // to `then` if `cond` is not 0
+    Branch {
+        cond: Operand,
+        then: BlockId,
+        els: BlockId,
+    },
+    // to the block of the case equal to the i64 `value`, `default` if none
+    // is; the cases are sorted in the order of the type of the C expression
+    Switch {
+        value: VReg,
+        unsigned: bool,
+        cases: Vec<(i64, BlockId)>,
+        default: BlockId,
+    },
+    // the value of a scalar function, the address of the struct one returns
+    Return(Option<Operand>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub term: Terminator,
+}
+
+// how a parameter arrives: in a register, or copied into a slot for a struct
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Param {
+    Value(VReg),
+    Struct(Slot, StructVal),
+}
+
+// what a function gives back
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RetKind {
+    Void,
+    Value(Ty),
+    Struct(StructVal),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Function {
+    pub name: String,
+    pub global: bool, // seen by the other translation units
+    pub params: Vec<Param>,
+    pub variadic: bool, // takes arguments after `params`
+    pub ret: RetKind,
+    pub vregs: Vec<Ty>,         // the type of each virtual register
+    pub slots: Vec<(i64, i64)>, // the size and alignment of each stack slot
+    pub blocks: Vec<Block>,     // the first one is the entry
+}
+
+impl Function {
+    pub fn ty(&self, r: VReg) -> Ty {
+        self.vregs[r.0]
+    }
+
+    pub fn new_reg(&mut self, ty: Ty) -> VReg {
+        self.vregs.push(ty);
+        VReg(self.vregs.len() - 1)
+    }
+
+    // the blocks each block is a successor of, once for every edge
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (b, block) in self.blocks.iter().enumerate() {
+            for s in block.term.successors() {
+                preds[s.0].push(BlockId(b));
+            }
+        }
+        preds
+    }
+}
+
+// a global variable; an uninitialized one is common, merged by the linker
+// with those of the same name unless it is static
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Global {
+    pub name: String,
+    pub global: bool,
+    pub size: i64,
+    pub align: i64,
+    pub init: Option<Vec<Datum>>, // in the order of the offsets, 0 between them
+}
+
+// a scalar in the initializer of a global
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Datum {
+    pub offset: i64,
+    pub ty: Ty,
+    pub symbol: Option<String>, // an address, the value is the offset from it
+    pub value: i64,             // the bits of a floating one (see Operand)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Module {
+    pub name: String, // of the source file
+    pub globals: Vec<Global>,
+    // the label and bytes of each string literal, and of each long double
+    // constant a double can not hold
+    pub strings: Vec<(String, Vec<u8>)>,
+    pub functions: Vec<Function>,
+}
+
+impl Inst {
+    // the register the instruction writes
+    pub fn def(&self) -> Option<VReg> {
+        match self {
+            Inst::Copy { dst, .. }
+            | Inst::Unary { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Ext { dst, .. }
+            | Inst::Trunc { dst, .. }
+            | Inst::Convert { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::SlotAddr { dst, .. }
+            | Inst::GlobalAddr { dst, .. } => Some(*dst),
+            Inst::Call {
+                ret: CallRet::Value(dst),
+                ..
+            } => Some(*dst),
+            Inst::Store { .. }
+            | Inst::MemCopy { .. }
+            | Inst::Call { .. }
+            | Inst::VaStart { .. } => None,
+        }
+    }
+
+    // the registers the instruction reads
+    pub fn uses(&self) -> Vec<VReg> {
+        let ops: Vec<Operand> = match self {
+            Inst::Copy { src, .. } | Inst::Unary { src, .. } => vec![*src],
+            Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Inst::Ext { src, .. } | Inst::Trunc { src, .. } | Inst::Convert { src, .. } => {
+                vec![Operand::Reg(*src)]
+            }
+            Inst::Load { addr, .. } | Inst::VaStart { ap: addr } => vec![Operand::Reg(*addr)],
+            Inst::Store { addr, src, .. } => vec![Operand::Reg(*addr), *src],
+            Inst::SlotAddr { .. } | Inst::GlobalAddr { .. } => vec![],
+            Inst::MemCopy { dst, src, .. } => vec![Operand::Reg(*dst), Operand::Reg(*src)],
+            Inst::Call { ret, callee, args } => {
+                let mut ops: Vec<Operand> = args
+                    .iter()
+                    .map(|a| match a {
+                        Arg::Value(_, op) => *op,
+                        Arg::Struct(addr, _) => Operand::Reg(*addr),
+                    })
+                    .collect();
+                if let CallRet::Struct(addr, _) = ret {
+                    ops.push(Operand::Reg(*addr));
+                }
+                if let Callee::Ptr(r) = callee {
+                    ops.push(Operand::Reg(*r));
+                }
+                ops
+            }
+        };
+        regs(&ops)
+    }
+
+    // the operands it reads, which a constant can take the place of
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Inst::Copy { src, .. } | Inst::Unary { src, .. } => vec![src],
+            Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Store { src, .. } => vec![src],
+            Inst::Call { args, .. } => args
+                .iter_mut()
+                .filter_map(|a| match a {
+                    Arg::Value(_, op) => Some(op),
+                    Arg::Struct(_, _) => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // every register it reads, where another one can take its place
+    pub fn uses_mut(&mut self) -> Vec<&mut VReg> {
+        let (mut regs, ops): (Vec<&mut VReg>, Vec<&mut Operand>) = match self {
+            Inst::Copy { src, .. } | Inst::Unary { src, .. } => (vec![], vec![src]),
+            Inst::Binary { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => (vec![], vec![lhs, rhs]),
+            Inst::Ext { src, .. } | Inst::Trunc { src, .. } | Inst::Convert { src, .. } => {
+                (vec![src], vec![])
+            }
+            Inst::Load { addr, .. } | Inst::VaStart { ap: addr } => (vec![addr], vec![]),
+            Inst::Store { addr, src, .. } => (vec![addr], vec![src]),
+            Inst::SlotAddr { .. } | Inst::GlobalAddr { .. } => (vec![], vec![]),
+            Inst::MemCopy { dst, src, .. } => (vec![dst, src], vec![]),
+            Inst::Call { ret, callee, args } => {
+                let mut regs = Vec::new();
+                let mut ops = Vec::new();
+                for a in args.iter_mut() {
+                    match a {
+                        Arg::Value(_, op) => ops.push(op),
+                        Arg::Struct(addr, _) => regs.push(addr),
+                    }
+                }
+                if let CallRet::Struct(addr, _) = ret {
+                    regs.push(addr);
+                }
+                if let Callee::Ptr(r) = callee {
+                    regs.push(r);
+                }
+                (regs, ops)
+            }
+        };
+        regs.extend(ops.into_iter().filter_map(|op| match op {
+            Operand::Reg(r) => Some(r),
+            Operand::Imm(_) => None,
+        }));
+        regs
+    }
+
+    // whether it does anything but write its register, so it has to stay
+    // even if nothing reads that
+    pub fn has_effect(&self) -> bool {
+        matches!(
+            self,
+            Inst::Store { .. } | Inst::MemCopy { .. } | Inst::Call { .. } | Inst::VaStart { .. }
+        )
+    }
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(b) => vec![*b],
+            Terminator::Branch { then, els, .. } => vec![*then, *els],
+            Terminator::Switch { cases, default, .. } => {
+                let mut succ: Vec<BlockId> = cases.iter().map(|(_, b)| *b).collect();
+                succ.push(*default);
+                succ
+            }
+            Terminator::Return(_) => vec![],
+        }
+    }
+
+    pub fn uses(&self) -> Vec<VReg> {
+        match self {
+            Terminator::Branch { cond, .. } => regs(&[*cond]),
+            Terminator::Switch { value, .. } => vec![*value],
+            Terminator::Return(Some(op)) => regs(&[*op]),
+            Terminator::Jump(_) | Terminator::Return(None) => vec![],
+        }
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut VReg> {
+        match self {
+            Terminator::Branch {
+                cond: Operand::Reg(r),
+                ..
+            }
+            | Terminator::Switch { value: r, .. }
+            | Terminator::Return(Some(Operand::Reg(r))) => vec![r],
+            _ => vec![],
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(b) => vec![b],
+            Terminator::Branch { then, els, .. } => vec![then, els],
+            Terminator::Switch { cases, default, .. } => {
+                let mut succ: Vec<&mut BlockId> = cases.iter_mut().map(|(_, b)| b).collect();
+                succ.push(default);
+                succ
+            }
+            Terminator::Return(_) => vec![],
+        }
+    }
+}
+
+fn regs(ops: &[Operand]) -> Vec<VReg> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Operand::Reg(r) => Some(*r),
+            Operand::Imm(_) => None,
+        })
+        .collect()
+}
+
+// The printer, the form `--crust-print-ir` shows:
+//
+//     function main(%0: i32) -> i32 {
+//       slot0: 16 bytes, align 8
+//     bb0:
+//       %1: i32 = add %0, 1
+//       br %1, bb1, bb2
+//     ...
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ty::F32 => write!(f, "f32"),
+            Ty::F64 => write!(f, "f64"),
+            Ty::F80 => write!(f, "f80"),
+            _ => write!(f, "i{}", self.size() * 8),
+        }
+    }
+}
+
+impl fmt::Display for VReg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "slot{}", self.0)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(r) => write!(f, "{}", r),
+            Operand::Imm(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            UnOp::Neg => "neg",
+            UnOp::Not => "not",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::SDiv => "sdiv",
+            BinOp::UDiv => "udiv",
+            BinOp::SRem => "srem",
+            BinOp::URem => "urem",
+            BinOp::FDiv => "fdiv",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Xor => "xor",
+            BinOp::Shl => "shl",
+            BinOp::Shr => "shr",
+            BinOp::Sar => "sar",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Cond::Eq => "eq",
+            Cond::Ne => "ne",
+            Cond::Lt => "lt",
+            Cond::Le => "le",
+            Cond::Gt => "gt",
+            Cond::Ge => "ge",
+            Cond::ULt => "ult",
+            Cond::ULe => "ule",
+            Cond::UGt => "ugt",
+            Cond::UGe => "uge",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// `struct 16`, with the eightbytes that go in xmm registers: `struct 16 (sse, int)`,
+// or `struct 16 (x87)` with a long double in it
+impl fmt::Display for StructVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "struct {}", self.size)?;
+        if self.x87 {
+            write!(f, " (x87)")?;
+        } else if self.sse.contains(&true) {
+            let classes: Vec<&str> = self
+                .sse
+                .iter()
+                .take(((self.size + 7) / 8) as usize)
+                .map(|sse| if *sse { "sse" } else { "int" })
+                .collect();
+            write!(f, " ({})", classes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Value(ty, op) => write!(f, "{} {}", ty, op),
+            Arg::Struct(addr, s) => write!(f, "{} [{}]", s, addr),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Terminator::Jump(b) => write!(f, "jmp {}", b),
+            Terminator::Branch { cond, then, els } => write!(f, "br {}, {}, {}", cond, then, els),
+            Terminator::Switch {
+                value,
+                unsigned,
+                cases,
+                default,
+            } => {
+                let cases: Vec<String> =
+                    cases.iter().map(|(v, b)| format!("{}: {}", v, b)).collect();
+                let kind = if *unsigned { "unsigned " } else { "" };
+                write!(
+                    f,
+                    "switch {}{} [{}], default {}",
+                    kind,
+                    value,
+                    cases.join(", "),
+                    default
+                )
+            }
+            Terminator::Return(Some(op)) => write!(f, "ret {}", op),
+            Terminator::Return(None) => write!(f, "ret"),
+        }
+    }
+}
+
+// the instruction, with the types of the registers it writes from `func`
+fn fmt_inst(f: &mut fmt::Formatter, func: &Function, inst: &Inst) -> fmt::Result {
+    if let Some(dst) = inst.def() {
+        write!(f, "{}: {} = ", dst, func.ty(dst))?;
+    }
+    match inst {
+        Inst::Copy { src, .. } => write!(f, "copy {}", src),
+        Inst::Unary { op, src, .. } => write!(f, "{} {}", op, src),
+        Inst::Binary { op, lhs, rhs, .. } => write!(f, "{} {}, {}", op, lhs, rhs),
+        Inst::Cmp {
+            cond, ty, lhs, rhs, ..
+        } => write!(f, "cmp {} {} {}, {}", cond, ty, lhs, rhs),
+        Inst::Ext { signed, src, .. } => {
+            let op = if *signed { "sext" } else { "zext" };
+            write!(f, "{} {}", op, src)
+        }
+        Inst::Trunc { src, .. } => write!(f, "trunc {}", src),
+        Inst::Convert { signed, dst, src } => {
+            let op = match (func.ty(*src).is_float(), func.ty(*dst).is_float(), signed) {
+                (false, _, true) => "sitofp",
+                (false, _, false) => "uitofp",
+                (true, false, true) => "fptosi",
+                (true, false, false) => "fptoui",
+                _ if func.ty(*src).size() < func.ty(*dst).size() => "fpext",
+                _ => "fptrunc",
+            };
+            write!(f, "{} {}", op, src)
+        }
+        Inst::Load { addr, .. } => write!(f, "load [{}]", addr),
+        Inst::Store { ty, addr, src } => write!(f, "store {} {}, [{}]", ty, src, addr),
+        Inst::SlotAddr { slot, .. } => write!(f, "addr {}", slot),
+        Inst::GlobalAddr { name, .. } => write!(f, "addr @{}", name),
+        Inst::MemCopy { dst, src, size } => write!(f, "memcpy [{}], [{}], {}", dst, src, size),
+        Inst::VaStart { ap } => write!(f, "va_start [{}]", ap),
+        Inst::Call { ret, callee, args } => {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            match callee {
+                Callee::Named(name) => write!(f, "call @{}", name)?,
+                Callee::Ptr(r) => write!(f, "call {}", r)?,
+            }
+            write!(f, "({})", args.join(", "))?;
+            match ret {
+                CallRet::Struct(addr, s) => write!(f, " -> {} [{}]", s, addr),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| match p {
+                Param::Value(r) => format!("{}: {}", r, self.ty(*r)),
+                Param::Struct(slot, s) => format!("{}: {}", slot, s),
+            })
+            .collect();
+        let ret = match self.ret {
+            RetKind::Void => "void".to_string(),
+            RetKind::Value(ty) => ty.to_string(),
+            RetKind::Struct(s) => s.to_string(),
+        };
+        if self.variadic {
+            params.push("...".to_string());
+        }
+        let linkage = if self.global { "" } else { "static " };
+        writeln!(
+            f,
+            "{}function {}({}) -> {} {{",
+            linkage,
+            self.name,
+            params.join(", "),
+            ret
+        )?;
+        for (i, (size, align)) in self.slots.iter().enumerate() {
+            writeln!(f, "  {}: {} bytes, align {}", Slot(i), size, align)?;
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i))?;
+            for inst in block.insts.iter() {
+                write!(f, "  ")?;
+                fmt_inst(f, self, inst)?;
+                writeln!(f)?;
+            }
+            writeln!(f, "  {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for g in self.globals.iter() {
+            let linkage = if g.global { "" } else { "static " };
+            write!(
+                f,
+                "{}global @{}: {} bytes, align {}",
+                linkage, g.name, g.size, g.align
+            )?;
+            match &g.init {
+                Some(data) => {
+                    let data: Vec<String> = data
+                        .iter()
+                        .map(|d| match &d.symbol {
+                            Some(symbol) => {
+                                format!("{}: {} @{}{:+}", d.offset, d.ty, symbol, d.value)
+                            }
+                            None => format!("{}: {} {}", d.offset, d.ty, d.value),
+                        })
+                        .collect();
+                    writeln!(f, " = {{ {} }}", data.join(", "))?
+                }
+                None => writeln!(f)?,
+            }
+        }
+        for (label, data) in self.strings.iter() {
+            writeln!(f, "string @{} = {:?}", label, String::from_utf8_lossy(data))?;
+        }
+        for func in self.functions.iter() {
+            writeln!(f)?;
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
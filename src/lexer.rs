@@ -1,354 +1,806 @@
-#[derive(Eq, PartialEq, Clone, Debug)]
-pub enum KwdType {
-    Int,      // int
-    Void,     // void
-    Ret,      // return
-    If,       // if
-    Else,     // else
-    While,    // while
-    For,      // for
-    Do,       // do
-    Break,    // break
-    Continue, // continue
-}
-
-#[derive(Eq, PartialEq, Clone, Debug)]
-pub enum TokType {
-    Kwd(KwdType),
-    LBrace,             // {
-    RBrace,             // }
-    LParen,             // (
-    RParen,             // )
-    LBracket,           // [
-    RBracket,           // ]
-    Semicolon,          // ;
-    Assign,             // =
-    Lt,                 // <
-    Gt,                 // >
-    Minus,              // -
-    Tilde,              // ~
-    Exclamation,        // !
-    Plus,               // +
-    Multi,              // *
-    Splash,             // /
-    Literal(i64),       // [0-9]+
-    Identifier(String), // identifier
-    And,                // &&
-    Or,                 // ||
-    Equal,              // ==
-    NotEqual,           // !=
-    LessEqual,          // <=
-    GreaterEqual,       // >=
-    Colon,              // :
-    QuestionMark,       // ?
-    Comma,              // ,
-    String(String, String),
-    Addr,               // &var
-}
-
-static mut LABEL_COUNTER: i64 = -1;
-fn gen_string_tag() -> String {
-    unsafe {
-        LABEL_COUNTER = LABEL_COUNTER + 1;
-        return format!(".LSTR{}", LABEL_COUNTER);
-    }
-}
-pub fn lex(input: &str) -> Result<Vec<TokType>, String> {
-    let mut result = Vec::new();
-
-    let mut it = input.chars().peekable();
-
-    while let Some(&c) = it.peek() {
-        match c {
-            '"' => {
-                it.next();
-                let mut s = "".to_string();
-                loop {
-                    let &c = it.peek().unwrap();
-                    if c == '"' {
-                        break;
-                    }
-                    s.push(c);
-                    it.next();
-                }
-                result.push(TokType::String(s, gen_string_tag()));
-                it.next();
-            }
-            '\'' => {
-                // try parse a char
-                // now just use int to represent char
-                // transform it to int
-                it.next(); // skip '
-                let &c = it.peek().unwrap();
-                if c == '\'' {
-                    return Err(format!("Error: empty character constant"));
-                }
-                if c == '\\' {
-                    it.next();
-                    let &c = it.peek().unwrap();
-                    match c {
-                        'a' => {
-                            result.push(TokType::Literal(0x07));
-                        } // Alert (Beep, Bell) (added in C89)
-                        'b' => {
-                            result.push(TokType::Literal(0x08));
-                        } // Backspace
-                        'e' => {
-                            result.push(TokType::Literal(0x1B));
-                        } // escape character
-                        'f' => {
-                            result.push(TokType::Literal(0x0C));
-                        } // Formfeed Page Break
-                        'n' => {
-                            result.push(TokType::Literal(0x0A));
-                        } // Newline (Line Feed)
-                        'r' => {
-                            result.push(TokType::Literal(0x0D));
-                        } // Carriage Return
-                        't' => {
-                            result.push(TokType::Literal(0x09));
-                        } // Horizontal Tab
-                        'v' => {
-                            result.push(TokType::Literal(0x0B));
-                        } // Vertical Tab
-                        '\\' => {
-                            result.push(TokType::Literal(0x5C));
-                        } // Backslash
-                        '\'' => {
-                            result.push(TokType::Literal(0x27));
-                        } // Apostrophe or single quotation mark
-                        '\"' => {
-                            result.push(TokType::Literal(0x22));
-                        } // Double quotation mark
-                        '?' => {
-                            result.push(TokType::Literal(0x3F));
-                        } // question mark
-                        _ => {
-                            return Err(format!("unrecongnized character"));
-                        }
-                    }
-                    it.next();
-                    if it.peek().unwrap() != &'\'' {
-                        return Err(format!("Error: unmatched '"));
-                    }
-                    it.next();
-                } else {
-                    result.push(TokType::Literal(c as i64));
-                    it.next(); // skip char
-                    it.next(); // skip '
-                }
-            }
-            '0'...'9' => {
-                it.next();
-                let mut number = c
-                    .to_string()
-                    .parse::<i64>()
-                    .expect("The caller should have passed a digit.");
-
-                while let Some(Ok(digit)) = it.peek().map(|c| c.to_string().parse::<i64>()) {
-                    number = number * 10 + digit;
-                    it.next();
-                }
-                result.push(TokType::Literal(number));
-            }
-            'a'...'z' | 'A'...'Z' | '_' => {
-                it.next();
-                let mut s = String::new();
-                s.push(c);
-                while let Some(&tmp) = it.peek() {
-                    match tmp {
-                        'a'...'z' | 'A'...'Z' | '_' => {
-                            s.push(tmp);
-                            it.next();
-                        }
-                        _ => {
-                            break;
-                        }
-                    }
-                }
-                match s.as_ref() {
-                    "int" => result.push(TokType::Kwd(KwdType::Int)),
-                    "char" => result.push(TokType::Kwd(KwdType::Int)),
-                    "return" => result.push(TokType::Kwd(KwdType::Ret)),
-                    "void" => result.push(TokType::Kwd(KwdType::Void)),
-                    "if" => result.push(TokType::Kwd(KwdType::If)),
-                    "else" => result.push(TokType::Kwd(KwdType::Else)),
-                    "while" => result.push(TokType::Kwd(KwdType::While)),
-                    "for" => result.push(TokType::Kwd(KwdType::For)),
-                    "do" => result.push(TokType::Kwd(KwdType::Do)),
-                    "continue" => result.push(TokType::Kwd(KwdType::Continue)),
-                    "break" => result.push(TokType::Kwd(KwdType::Break)),
-                    _ => result.push(TokType::Identifier(s)),
-                }
-            }
-            '(' => {
-                result.push(TokType::LParen);
-                it.next();
-            }
-            ')' => {
-                result.push(TokType::RParen);
-                it.next();
-            }
-            '{' => {
-                result.push(TokType::LBrace);
-                it.next();
-            }
-            '}' => {
-                result.push(TokType::RBrace);
-                it.next();
-            }
-            '[' => {
-                result.push(TokType::LBracket);
-                it.next();
-            }
-            ']' => {
-                result.push(TokType::RBracket);
-                it.next();
-            }
-            ';' => {
-                result.push(TokType::Semicolon);
-                it.next();
-            }
-            '=' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::Equal);
-                            it.next();
-                        }
-                        '>' => {
-                            result.push(TokType::GreaterEqual);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Assign);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '<' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            it.next();
-                            result.push(TokType::LessEqual);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Lt);
-                            it.next();
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '>' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::GreaterEqual);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Gt);
-                            it.next();
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '-' => {
-                result.push(TokType::Minus);
-                it.next();
-            }
-            '~' => {
-                result.push(TokType::Tilde);
-                it.next();
-            }
-            '!' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::NotEqual);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Exclamation);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '+' => {
-                result.push(TokType::Plus);
-                it.next();
-            }
-            '*' => {
-                result.push(TokType::Multi);
-                it.next();
-            }
-            '/' => {
-                result.push(TokType::Splash);
-                it.next();
-            }
-            '&' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '&' => {
-                            result.push(TokType::And);
-                            it.next();
-                        }
-                        _ => {
-                            // now don't support bitwise and, so just return Err
-                            // & operator to get the address of a variable
-                            result.push(TokType::Addr);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '|' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '|' => {
-                            result.push(TokType::Or);
-                            it.next();
-                        }
-                        _ => {
-                            // now don't support bitwise or, so just return Err
-                            return Err(format!("unexpected token {}", c));
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '?' => {
-                result.push(TokType::QuestionMark);
-                it.next();
-            }
-            ':' => {
-                result.push(TokType::Colon);
-                it.next();
-            }
-            ',' => {
-                result.push(TokType::Comma);
-                it.next();
-            }
-            ' ' | '\n' | '\t' | '\r' => {
-                // skip
-                it.next();
-            }
-            _ => {
-                return Err(format!("unexpected character {}", c));
-            }
-        }
-    }
-    Ok(result)
-}
+use crate::diagnostic::{code, Diagnostic};
+use crate::extended::Extended;
+use crate::loc::Loc;
+use std::fmt;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum KwdType {
+    Int,      // int
+    Char,     // char
+    Void,     // void
+    Ret,      // return
+    If,       // if
+    Else,     // else
+    While,    // while
+    For,      // for
+    Do,       // do
+    Break,    // break
+    Continue, // continue
+    // the rest of the C11 keywords, so they are never taken for identifiers
+    Auto,         // auto
+    Case,         // case
+    Const,        // const
+    Default,      // default
+    Double,       // double
+    Enum,         // enum
+    Extern,       // extern
+    Float,        // float
+    Goto,         // goto
+    Inline,       // inline
+    Long,         // long
+    Register,     // register
+    Restrict,     // restrict
+    Short,        // short
+    Signed,       // signed
+    Sizeof,       // sizeof
+    Static,       // static
+    Struct,       // struct
+    Switch,       // switch
+    Typedef,      // typedef
+    Union,        // union
+    Unsigned,     // unsigned
+    Volatile,     // volatile
+    Alignas,      // _Alignas
+    Alignof,      // _Alignof
+    Atomic,       // _Atomic
+    Bool,         // _Bool
+    Complex,      // _Complex
+    Generic,      // _Generic
+    Imaginary,    // _Imaginary
+    Noreturn,     // _Noreturn
+    StaticAssert, // _Static_assert
+    ThreadLocal,  // _Thread_local
+}
+
+// how an integer constant was written, its type depends on it (C11 6.4.4.1)
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default)]
+pub struct IntSuffix {
+    pub unsigned: bool, // u or U
+    pub long: u8,       // 0, 1 for l or L, 2 for ll or LL
+    pub decimal: bool,  // written in base 10, only those never become unsigned by themselves
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum FloatSuffix {
+    None, // double
+    F,    // float
+    L,    // long double
+}
+
+// a floating constant, compared bit by bit so tokens can still be `Eq`;
+// `extended` is its exact long double value, `value` the double nearest it
+#[derive(Clone, Copy, Debug)]
+pub struct FloatConst {
+    pub value: f64,
+    pub extended: Extended,
+    pub suffix: FloatSuffix,
+}
+
+impl PartialEq for FloatConst {
+    fn eq(&self, other: &FloatConst) -> bool {
+        self.value.to_bits() == other.value.to_bits()
+            && self.extended == other.extended
+            && self.suffix == other.suffix
+    }
+}
+
+impl Eq for FloatConst {}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum TokType {
+    Kwd(KwdType),
+    LBrace,                   // {
+    RBrace,                   // }
+    LParen,                   // (
+    RParen,                   // )
+    LBracket,                 // [
+    RBracket,                 // ]
+    Semicolon,                // ;
+    Assign,                   // =
+    Lt,                       // <
+    Gt,                       // >
+    Minus,                    // -
+    Tilde,                    // ~
+    Exclamation,              // !
+    Plus,                     // +
+    Multi,                    // *
+    Splash,                   // /
+    Literal(i64, IntSuffix),  // integer and character constants
+    FloatLiteral(FloatConst), // 1.5, 1e10, 0x1p-3
+    Identifier(String),       // identifier
+    And,                      // &&
+    Or,                       // ||
+    Equal,                    // ==
+    NotEqual,                 // !=
+    LessEqual,                // <=
+    GreaterEqual,             // >=
+    Colon,                    // :
+    QuestionMark,             // ?
+    Comma,                    // ,
+    String(Vec<u8>, String),  // bytes without the terminating 0, tag
+    Addr,                     // &var, and bitwise and
+    Percent,                  // %
+    BitOr,                    // |
+    BitXor,                   // ^
+    Shl,                      // <<
+    Shr,                      // >>
+    Inc,                      // ++
+    Dec,                      // --
+    Dot,                      // .
+    Arrow,                    // ->
+    Ellipsis,                 // ...
+    MulAssign,                // *=
+    DivAssign,                // /=
+    ModAssign,                // %=
+    AddAssign,                // +=
+    SubAssign,                // -=
+    ShlAssign,                // <<=
+    ShrAssign,                // >>=
+    AndAssign,                // &=
+    XorAssign,                // ^=
+    OrAssign,                 // |=
+    Hash,                     // #, only meaningful to the preprocessor
+    HashHash,                 // ##, only meaningful to the preprocessor
+    Eof,                      // the end of the input, the last token `lex` gives
+}
+
+// the token as it would be written in the source, for diagnostics
+impl fmt::Display for TokType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokType::Kwd(kwd) => match KEYWORDS.iter().find(|(_, k)| k == kwd) {
+                Some((s, _)) => write!(f, "{}", s),
+                None => write!(f, "{:?}", kwd),
+            },
+            TokType::Literal(n, _) => write!(f, "{}", n),
+            TokType::FloatLiteral(c) => write!(f, "{}", c.value),
+            TokType::Identifier(name) => write!(f, "{}", name),
+            TokType::String(data, _) => {
+                write!(f, "\"{}\"", String::from_utf8_lossy(data).escape_debug())
+            }
+            TokType::Eof => write!(f, "end of input"),
+            // the plain spelling comes after the digraph
+            tok => match PUNCTUATORS.iter().rev().find(|(_, t)| t == tok) {
+                Some((s, _)) => write!(f, "{}", s),
+                None => write!(f, "{:?}", tok),
+            },
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Token {
+    pub tok: TokType,
+    pub loc: Loc,
+}
+
+// walks through the input and keeps track of where we are in the source file
+struct Cursor<'a> {
+    input: &'a str,
+    chars: Peekable<Chars<'a>>,
+    pos: usize,        // byte offset in the input
+    file: Rc<str>,     // file we are in, set by the line markers
+    line: usize,       // line we are at in `file`
+    line_start: usize, // byte offset where the current line starts
+    strings: usize,    // string literals so far, each one gets a tag of its own
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+        }
+        Some(c)
+    }
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    // the input we have not looked at yet
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    // skip `n` bytes, they must end on a char boundary
+    fn advance(&mut self, n: usize) {
+        let end = self.pos + n;
+        while self.pos < end && self.next().is_some() {}
+    }
+
+    // location of the text from `start` to the current position
+    fn loc(&self, start: usize) -> Loc {
+        let end = if self.pos > start {
+            self.pos
+        } else {
+            start + 1
+        };
+        // a token can run over a newline, like a string literal that ends
+        // in a `\` and a newline; it is where it starts
+        let (line, line_start) = if start < self.line_start {
+            let newlines = self.input[start..self.line_start].matches('\n').count();
+            let line_start = self.input[..start].rfind('\n').map_or(0, |i| i + 1);
+            (self.line - newlines, line_start)
+        } else {
+            (self.line, self.line_start)
+        };
+        Loc::new(
+            self.file.clone(),
+            line,
+            start - line_start + 1,
+            (start, end),
+        )
+    }
+
+    fn error(&self, code: &'static str, start: usize, msg: &str) -> Diagnostic {
+        Diagnostic::error(code, &self.loc(start), msg)
+    }
+
+    // the label gen puts the next string literal at
+    fn string_tag(&mut self) -> String {
+        let tag = format!(".LSTR{}", self.strings);
+        self.strings += 1;
+        tag
+    }
+
+    // # <line> "<file>" [flags], the rest of the line after `#` is in `marker`
+    fn line_marker(&mut self, marker: &str) {
+        let marker = marker.trim();
+        let (num, rest) = marker.split_at(
+            marker
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(marker.len()),
+        );
+        let line = match num.parse::<usize>() {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix('"') {
+            let mut name = String::new();
+            let mut chars = rest.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => name.extend(chars.next()),
+                    _ => name.push(c),
+                }
+            }
+            self.file = Rc::from(name);
+        }
+        // the line after the marker is `line`
+        self.line = line.saturating_sub(1);
+    }
+}
+
+// `char` is read as `int` for now
+const KEYWORDS: &[(&str, KwdType)] = &[
+    ("int", KwdType::Int),
+    ("char", KwdType::Char),
+    ("void", KwdType::Void),
+    ("return", KwdType::Ret),
+    ("if", KwdType::If),
+    ("else", KwdType::Else),
+    ("while", KwdType::While),
+    ("for", KwdType::For),
+    ("do", KwdType::Do),
+    ("break", KwdType::Break),
+    ("continue", KwdType::Continue),
+    ("auto", KwdType::Auto),
+    ("case", KwdType::Case),
+    ("const", KwdType::Const),
+    ("default", KwdType::Default),
+    ("double", KwdType::Double),
+    ("enum", KwdType::Enum),
+    ("extern", KwdType::Extern),
+    ("float", KwdType::Float),
+    ("goto", KwdType::Goto),
+    ("inline", KwdType::Inline),
+    ("long", KwdType::Long),
+    ("register", KwdType::Register),
+    ("restrict", KwdType::Restrict),
+    ("short", KwdType::Short),
+    ("signed", KwdType::Signed),
+    ("sizeof", KwdType::Sizeof),
+    ("static", KwdType::Static),
+    ("struct", KwdType::Struct),
+    ("switch", KwdType::Switch),
+    ("typedef", KwdType::Typedef),
+    ("union", KwdType::Union),
+    ("unsigned", KwdType::Unsigned),
+    ("volatile", KwdType::Volatile),
+    ("_Alignas", KwdType::Alignas),
+    ("_Alignof", KwdType::Alignof),
+    ("_Atomic", KwdType::Atomic),
+    ("_Bool", KwdType::Bool),
+    ("_Complex", KwdType::Complex),
+    ("_Generic", KwdType::Generic),
+    ("_Imaginary", KwdType::Imaginary),
+    ("_Noreturn", KwdType::Noreturn),
+    ("_Static_assert", KwdType::StaticAssert),
+    ("_Thread_local", KwdType::ThreadLocal),
+];
+
+// every punctuator of C11 6.4.6, longer ones first so `<<=` is not read as `<<` `=`
+const PUNCTUATORS: &[(&str, TokType)] = &[
+    ("%:%:", TokType::HashHash),
+    ("...", TokType::Ellipsis),
+    ("<<=", TokType::ShlAssign),
+    (">>=", TokType::ShrAssign),
+    ("->", TokType::Arrow),
+    ("++", TokType::Inc),
+    ("--", TokType::Dec),
+    ("<<", TokType::Shl),
+    (">>", TokType::Shr),
+    ("<=", TokType::LessEqual),
+    (">=", TokType::GreaterEqual),
+    ("==", TokType::Equal),
+    ("!=", TokType::NotEqual),
+    ("&&", TokType::And),
+    ("||", TokType::Or),
+    ("*=", TokType::MulAssign),
+    ("/=", TokType::DivAssign),
+    ("%=", TokType::ModAssign),
+    ("+=", TokType::AddAssign),
+    ("-=", TokType::SubAssign),
+    ("&=", TokType::AndAssign),
+    ("^=", TokType::XorAssign),
+    ("|=", TokType::OrAssign),
+    ("##", TokType::HashHash),
+    ("<:", TokType::LBracket),
+    (":>", TokType::RBracket),
+    ("<%", TokType::LBrace),
+    ("%>", TokType::RBrace),
+    ("%:", TokType::Hash),
+    ("[", TokType::LBracket),
+    ("]", TokType::RBracket),
+    ("(", TokType::LParen),
+    (")", TokType::RParen),
+    ("{", TokType::LBrace),
+    ("}", TokType::RBrace),
+    (".", TokType::Dot),
+    ("&", TokType::Addr),
+    ("*", TokType::Multi),
+    ("+", TokType::Plus),
+    ("-", TokType::Minus),
+    ("~", TokType::Tilde),
+    ("!", TokType::Exclamation),
+    ("/", TokType::Splash),
+    ("%", TokType::Percent),
+    ("<", TokType::Lt),
+    (">", TokType::Gt),
+    ("^", TokType::BitXor),
+    ("|", TokType::BitOr),
+    ("?", TokType::QuestionMark),
+    (":", TokType::Colon),
+    (";", TokType::Semicolon),
+    ("=", TokType::Assign),
+    (",", TokType::Comma),
+    ("#", TokType::Hash),
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// an integer or floating constant, `text` is the whole preprocessing number
+fn number(text: &str) -> Result<TokType, String> {
+    let hex = text.starts_with("0x") || text.starts_with("0X");
+    let float = if hex {
+        text.contains(['.', 'p', 'P'])
+    } else {
+        text.contains(['.', 'e', 'E'])
+    };
+    if float {
+        float_const(text, hex)
+    } else {
+        int_const(text)
+    }
+}
+
+fn int_const(text: &str) -> Result<TokType, String> {
+    let (digits, radix, decimal) =
+        if let Some(d) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            (d, 16, false)
+        } else if let Some(d) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+            (d, 2, false)
+        } else if text.starts_with('0') {
+            (text, 8, false)
+        } else {
+            (text, 10, true)
+        };
+    let end = digits
+        .find(|c: char| !c.is_ascii_hexdigit() || (radix != 16 && c.is_ascii_alphabetic()))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+    if digits.is_empty() {
+        return Err(format!("invalid integer constant `{}`", text));
+    }
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        let base = if radix == 8 { "octal" } else { "binary" };
+        return Err(format!("invalid digit \"{}\" in {} constant", c, base));
+    }
+    let value = u64::from_str_radix(digits, radix)
+        .map_err(|_| format!("integer constant `{}` is too large", text))?;
+
+    // u and l can come in either order, but ll must not be lL
+    let (unsigned, longs) = match suffix
+        .strip_prefix(['u', 'U'])
+        .or_else(|| suffix.strip_suffix(['u', 'U']))
+    {
+        Some(rest) => (true, rest),
+        None => (false, suffix),
+    };
+    let long = match longs {
+        "" => 0,
+        "l" | "L" => 1,
+        "ll" | "LL" => 2,
+        _ => return Err(format!("invalid suffix \"{}\" on integer constant", suffix)),
+    };
+    Ok(TokType::Literal(
+        value as i64,
+        IntSuffix {
+            unsigned,
+            long,
+            decimal,
+        },
+    ))
+}
+
+fn float_const(text: &str, hex: bool) -> Result<TokType, String> {
+    // a hex digit `f` is only a suffix after the exponent
+    let (body, suffix) = match text.char_indices().last() {
+        Some((i, 'f')) | Some((i, 'F')) if !hex || text[..i].contains(['p', 'P']) => {
+            (&text[..i], FloatSuffix::F)
+        }
+        Some((i, 'l')) | Some((i, 'L')) => (&text[..i], FloatSuffix::L),
+        _ => (text, FloatSuffix::None),
+    };
+    let invalid = || format!("invalid floating constant `{}`", text);
+    let value = if hex {
+        // 0x<hex digits>[.<hex digits>]p[+-]<decimal digits>
+        let (mantissa, exp) = body[2..]
+            .split_once(['p', 'P'])
+            .ok_or_else(|| "hexadecimal floating constants require an exponent".to_string())?;
+        let exp: i32 = exp.parse().map_err(|_| invalid())?;
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int.is_empty() && frac.is_empty() {
+            return Err(invalid());
+        }
+        let mut value = 0.0;
+        for c in int.chars().chain(frac.chars()) {
+            value = value * 16.0 + c.to_digit(16).ok_or_else(invalid)? as f64;
+        }
+        value * 2f64.powi(exp - 4 * frac.len() as i32)
+    } else {
+        body.parse::<f64>().map_err(|_| invalid())?
+    };
+    // a long double one is read again in its own precision, rounding it to
+    // a double first could round twice
+    let (value, extended) = if suffix == FloatSuffix::L {
+        let extended = Extended::parse(body).ok_or_else(invalid)?;
+        (extended.to_f64(), extended)
+    } else {
+        (value, Extended::from_f64(value))
+    };
+    Ok(TokType::FloatLiteral(FloatConst {
+        value,
+        extended,
+        suffix,
+    }))
+}
+
+// the value of an escape sequence, `it` is right after the backslash
+fn escape(it: &mut Cursor, start: usize) -> Result<u32, Diagnostic> {
+    let c = match it.next() {
+        Some(c) => c,
+        None => return Err(it.error(code::LEX_LITERAL, start, "missing terminating character")),
+    };
+    let value = match c {
+        'a' => 0x07, // Alert (Beep, Bell) (added in C89)
+        'b' => 0x08, // Backspace
+        'e' => 0x1B, // escape character
+        'f' => 0x0C, // Formfeed Page Break
+        'n' => 0x0A, // Newline (Line Feed)
+        'r' => 0x0D, // Carriage Return
+        't' => 0x09, // Horizontal Tab
+        'v' => 0x0B, // Vertical Tab
+        '\\' | '\'' | '"' | '?' => c as u32,
+        '0'..='7' => {
+            // up to three octal digits
+            let mut value = c as u32 - '0' as u32;
+            for _ in 0..2 {
+                match it.peek().and_then(|c| c.to_digit(8)) {
+                    Some(d) => {
+                        value = value * 8 + d;
+                        it.next();
+                    }
+                    None => break,
+                }
+            }
+            value
+        }
+        'x' => {
+            // as many hex digits as there are
+            let mut value: u32 = 0;
+            let mut digits = 0;
+            while let Some(d) = it.peek().and_then(|c| c.to_digit(16)) {
+                value = match value.checked_mul(16) {
+                    Some(v) => v + d,
+                    None => {
+                        return Err(it.error(
+                            code::LEX_LITERAL,
+                            start,
+                            "hex escape sequence out of range",
+                        ))
+                    }
+                };
+                digits += 1;
+                it.next();
+            }
+            if digits == 0 {
+                return Err(it.error(
+                    code::LEX_LITERAL,
+                    start,
+                    "\\x used with no following hex digits",
+                ));
+            }
+            value
+        }
+        'u' | 'U' => {
+            // universal character name, exactly 4 or 8 hex digits
+            let n = if c == 'u' { 4 } else { 8 };
+            let mut value: u32 = 0;
+            for _ in 0..n {
+                match it.peek().and_then(|c| c.to_digit(16)) {
+                    Some(d) => {
+                        value = value * 16 + d;
+                        it.next();
+                    }
+                    None => {
+                        return Err(it.error(
+                            code::LEX_LITERAL,
+                            start,
+                            &format!("incomplete universal character name \\{}", c),
+                        ))
+                    }
+                }
+            }
+            if char::from_u32(value).is_none() {
+                return Err(it.error(code::LEX_LITERAL, start, "invalid universal character"));
+            }
+            value
+        }
+        _ => {
+            return Err(it.error(
+                code::LEX_LITERAL,
+                start,
+                &format!("unknown escape sequence: '\\{}'", c),
+            ))
+        }
+    };
+    Ok(value)
+}
+
+// the contents of a character constant or string literal up to the closing
+// `quote`, each element is a char or the value of an escape sequence
+fn quoted(it: &mut Cursor, start: usize, quote: char) -> Result<Vec<(u32, bool)>, Diagnostic> {
+    it.next(); // opening quote
+    let mut chars = Vec::new();
+    loop {
+        match it.peek() {
+            Some(&c) if c == quote => {
+                it.next();
+                return Ok(chars);
+            }
+            Some('\\') => {
+                it.next();
+                chars.push((escape(it, start)?, true));
+            }
+            Some(&c) if c != '\n' => {
+                chars.push((c as u32, false));
+                it.next();
+            }
+            _ => {
+                return Err(it.error(
+                    code::LEX_LITERAL,
+                    start,
+                    &format!("missing terminating {} character", quote),
+                ))
+            }
+        }
+    }
+}
+
+// octal and hex escapes are bytes, everything else is a character stored as UTF-8
+fn to_bytes(it: &Cursor, start: usize, chars: &[(u32, bool)]) -> Result<Vec<u8>, Diagnostic> {
+    let mut bytes = Vec::new();
+    for &(c, escaped) in chars.iter() {
+        if escaped && c <= 0xff {
+            bytes.push(c as u8);
+        } else {
+            match char::from_u32(c) {
+                Some(c) if !escaped || c.len_utf8() > 1 => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => {
+                    return Err(it.error(code::LEX_LITERAL, start, "escape sequence out of range"))
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+// 'c', L'c', u'c' or U'c', `prefix` is the part before the quote
+fn char_const(it: &mut Cursor, start: usize, prefix: &str) -> Result<TokType, Diagnostic> {
+    let chars = quoted(it, start, '\'')?;
+    if chars.is_empty() {
+        return Err(it.error(code::LEX_LITERAL, start, "empty character constant"));
+    }
+    let value = if !prefix.is_empty() {
+        // wide character, gcc takes the last one if there are more
+        chars.last().map_or(0, |&(c, _)| c as i64)
+    } else {
+        let bytes = to_bytes(it, start, &chars)?;
+        if bytes.len() == 1 {
+            // plain char is signed on x86-64
+            bytes[0] as i8 as i64
+        } else {
+            // multi-character constant, gcc packs the bytes into an int
+            bytes
+                .iter()
+                .fold(0i32, |v, &b| v.wrapping_shl(8) | b as i32) as i64
+        }
+    };
+    Ok(TokType::Literal(value, IntSuffix::default()))
+}
+
+// "..." or u8"..."
+fn string_lit(it: &mut Cursor, start: usize, prefix: &str) -> Result<TokType, Diagnostic> {
+    if !prefix.is_empty() && prefix != "u8" {
+        return Err(it.error(
+            code::UNSUPPORTED,
+            start,
+            "wide string literals are not supported",
+        ));
+    }
+    let chars = quoted(it, start, '"')?;
+    let bytes = to_bytes(it, start, &chars)?;
+    Ok(TokType::String(bytes, it.string_tag()))
+}
+
+pub fn lex(input: &str) -> Result<Vec<Token>, Diagnostic> {
+    let mut result = Vec::new();
+    let mut locs = Vec::new();
+
+    let mut it = Cursor {
+        input,
+        chars: input.chars().peekable(),
+        pos: 0,
+        file: Rc::from("<input>"),
+        line: 1,
+        line_start: 0,
+        strings: 0,
+    };
+
+    while let Some(&c) = it.peek() {
+        let start = it.pos;
+        let rest = it.rest();
+        match c {
+            '"' => result.push(string_lit(&mut it, start, "")?),
+            '\'' => result.push(char_const(&mut it, start, "")?),
+            '0'..='9' | '.' if c != '.' || rest[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                // read a whole preprocessing number, then see what kind of constant it is
+                let bytes = rest.as_bytes();
+                let mut end = 1;
+                while end < bytes.len() {
+                    let c = bytes[end] as char;
+                    let prev = bytes[end - 1] as char;
+                    if is_ident_char(c)
+                        || c == '.'
+                        || ((c == '+' || c == '-') && "eEpP".contains(prev))
+                    {
+                        end += 1;
+                    } else {
+                        break;
+                    }
+                }
+                it.advance(end);
+                let tok =
+                    number(&rest[..end]).map_err(|msg| it.error(code::LEX_LITERAL, start, &msg))?;
+                result.push(tok);
+            }
+            c if is_ident_char(c) => {
+                let end = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+                let s = &rest[..end];
+                it.advance(end);
+                match (s, it.peek()) {
+                    ("L" | "u" | "U", Some('\'')) => result.push(char_const(&mut it, start, s)?),
+                    ("L" | "u" | "U" | "u8", Some('"')) => {
+                        result.push(string_lit(&mut it, start, s)?)
+                    }
+                    _ => match KEYWORDS.iter().find(|(k, _)| *k == s) {
+                        Some((_, kwd)) => result.push(TokType::Kwd(kwd.clone())),
+                        None => result.push(TokType::Identifier(s.to_string())),
+                    },
+                }
+            }
+            '/' if rest.starts_with("//") => {
+                while let Some(&tmp) = it.peek() {
+                    if tmp == '\n' {
+                        break;
+                    }
+                    it.next();
+                }
+            }
+            '/' if rest.starts_with("/*") => match rest[2..].find("*/") {
+                Some(end) => it.advance(end + 4),
+                None => {
+                    it.advance(2);
+                    return Err(it.error(code::LEX, start, "unterminated comment"));
+                }
+            },
+            '#' if input[it.line_start..start].trim().is_empty() => {
+                // line marker left by the preprocessor: # <line> "<file>" [flags]
+                it.next();
+                let mut marker = String::new();
+                while let Some(&tmp) = it.peek() {
+                    if tmp == '\n' {
+                        break;
+                    }
+                    marker.push(tmp);
+                    it.next();
+                }
+                it.line_marker(&marker);
+            }
+            ' ' | '\n' | '\t' | '\r' | '\x0b' | '\x0c' => {
+                // skip
+                it.next();
+            }
+            _ => match PUNCTUATORS.iter().find(|(p, _)| rest.starts_with(p)) {
+                Some((p, tok)) => {
+                    it.advance(p.len());
+                    result.push(tok.clone());
+                }
+                None => {
+                    it.next();
+                    return Err(it.error(code::LEX, start, &format!("unexpected character {}", c)));
+                }
+            },
+        }
+        if result.len() > locs.len() {
+            locs.push(it.loc(start));
+        }
+    }
+
+    // adjacent string literals are one string
+    let mut toks: Vec<Token> = Vec::with_capacity(result.len());
+    for (tok, loc) in result.into_iter().zip(locs) {
+        if let (TokType::String(data, _), Some(last)) = (&tok, toks.last_mut()) {
+            if let TokType::String(prev, _) = &mut last.tok {
+                prev.extend_from_slice(data);
+                last.loc = last.loc.to(&loc);
+                continue;
+            }
+        }
+        toks.push(Token { tok, loc });
+    }
+    // the parser looks at the next token without running off the end
+    toks.push(Token {
+        tok: TokType::Eof,
+        loc: it.loc(input.len()),
+    });
+    Ok(toks)
+}
@@ -0,0 +1,23 @@
+// crust as a library: the preprocessor, the lexer, the parser, the semantic
+// analysis, the IR and its optimizer, the register allocation and the code
+// generator, with the built-in assembler and linker, for tools that want to
+// compile C without running the `crust` binary.  A `Session` takes the
+// sources from memory and hands back what each stage makes of them.
+
+pub mod asm;
+pub mod diagnostic;
+pub mod extended;
+mod gen;
+pub mod ir;
+pub mod lexer;
+pub mod link;
+pub mod loc;
+pub mod opt;
+pub mod parser;
+mod preprocessor;
+mod regalloc;
+pub mod sema;
+mod session;
+pub mod types;
+
+pub use session::{Options, Session};
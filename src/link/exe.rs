@@ -0,0 +1,234 @@
+use crate::asm::elf;
+
+// The static ELF64 executable: the header, a program header for each
+// segment, the contents of the sections at the offsets the linker gave them
+// and, after them, the symbol table and the section headers.
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+const PT_LOAD: u32 = 1;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+pub struct OutSection {
+    pub name: &'static str,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub data: Vec<u8>, // empty for SHT_NOBITS
+    pub size: u64,
+    pub align: u64,
+    pub offset: u64, // in the file
+    pub addr: u64,
+}
+
+impl OutSection {
+    pub fn new(name: &'static str, sh_type: u32, flags: u64) -> OutSection {
+        OutSection {
+            name,
+            sh_type,
+            flags,
+            data: Vec::new(),
+            size: 0,
+            align: 1,
+            offset: 0,
+            addr: 0,
+        }
+    }
+
+    // room for `size` bytes aligned to `align`, filled with `data` unless the
+    // section takes no room in the file; the offset of it in the section
+    pub fn append(&mut self, align: u64, data: &[u8], size: u64) -> u64 {
+        self.align = self.align.max(align);
+        let offset = self.size.div_ceil(align) * align;
+        self.size = offset + size;
+        if self.sh_type != elf::SHT_NOBITS {
+            self.data.resize(offset as usize, 0);
+            self.data.extend_from_slice(data);
+        }
+        offset
+    }
+}
+
+// the sections are .text, .rodata, .data and .bss in this order, .bss right
+// after .data in memory
+pub fn write(
+    sections: &[OutSection],
+    symbols: &[elf::Symbol],
+    first_global: usize,
+    entry: u64,
+) -> Vec<u8> {
+    let (text, rodata, data, bss) = (&sections[0], &sections[1], &sections[2], &sections[3]);
+    // (flags, offset, address, size in the file, size in memory)
+    let mut segments = vec![(PF_R | PF_X, text.offset, text.addr, text.size, text.size)];
+    if rodata.size > 0 {
+        segments.push((PF_R, rodata.offset, rodata.addr, rodata.size, rodata.size));
+    }
+    if data.size > 0 || bss.size > 0 {
+        let memsz = bss.addr + bss.size - data.addr;
+        segments.push((PF_R | PF_W, data.offset, data.addr, data.size, memsz));
+    }
+
+    let mut out = vec![0; EHDR_SIZE];
+    for (flags, offset, addr, filesz, memsz) in segments.iter() {
+        push_phdr(
+            &mut out, PT_LOAD, *flags, *offset, *addr, *filesz, *memsz, 0x1000,
+        );
+    }
+    // the stack is not executable
+    push_phdr(&mut out, PT_GNU_STACK, PF_R | PF_W, 0, 0, 0, 0, 16);
+    let phnum = segments.len() + 1;
+
+    for s in sections.iter() {
+        if s.sh_type == elf::SHT_NOBITS {
+            continue;
+        }
+        out.resize(s.offset as usize, 0);
+        out.extend_from_slice(&s.data);
+    }
+
+    let mut strtab = vec![0];
+    let mut shstrtab = vec![0];
+
+    // (name, type, flags, address, offset, size, link, info, align, entsize)
+    let mut headers = vec![(0, 0, 0, 0, 0, 0, 0, 0, 0, 0)];
+    for s in sections.iter() {
+        let name = add(&mut shstrtab, s.name);
+        headers.push((
+            name, s.sh_type, s.flags, s.addr, s.offset, s.size, 0, 0, s.align, 0,
+        ));
+    }
+    align(&mut out, 8);
+    let symtab_offset = out.len() as u64;
+    for sym in symbols.iter() {
+        let name = if sym.name.is_empty() {
+            0
+        } else {
+            add(&mut strtab, &sym.name)
+        };
+        out.extend_from_slice(&name.to_le_bytes());
+        out.push(sym.bind << 4 | sym.kind);
+        out.push(0);
+        out.extend_from_slice(&sym.shndx.to_le_bytes());
+        out.extend_from_slice(&sym.value.to_le_bytes());
+        out.extend_from_slice(&sym.size.to_le_bytes());
+    }
+    let symtab_index = headers.len() as u32;
+    let name = add(&mut shstrtab, ".symtab");
+    let size = (symbols.len() * SYM_SIZE) as u64;
+    let info = first_global as u32;
+    headers.push((
+        name,
+        elf::SHT_SYMTAB,
+        0,
+        0,
+        symtab_offset,
+        size,
+        symtab_index + 1,
+        info,
+        8,
+        SYM_SIZE as u64,
+    ));
+    let name = add(&mut shstrtab, ".strtab");
+    headers.push((
+        name,
+        elf::SHT_STRTAB,
+        0,
+        0,
+        out.len() as u64,
+        strtab.len() as u64,
+        0,
+        0,
+        1,
+        0,
+    ));
+    out.extend_from_slice(&strtab);
+    let name = add(&mut shstrtab, ".shstrtab");
+    headers.push((
+        name,
+        elf::SHT_STRTAB,
+        0,
+        0,
+        out.len() as u64,
+        shstrtab.len() as u64,
+        0,
+        0,
+        1,
+        0,
+    ));
+    out.extend_from_slice(&shstrtab);
+
+    align(&mut out, 8);
+    let shoff = out.len() as u64;
+    for h in headers.iter() {
+        out.extend_from_slice(&h.0.to_le_bytes());
+        out.extend_from_slice(&h.1.to_le_bytes());
+        out.extend_from_slice(&h.2.to_le_bytes());
+        out.extend_from_slice(&h.3.to_le_bytes());
+        out.extend_from_slice(&h.4.to_le_bytes());
+        out.extend_from_slice(&h.5.to_le_bytes());
+        out.extend_from_slice(&h.6.to_le_bytes());
+        out.extend_from_slice(&h.7.to_le_bytes());
+        out.extend_from_slice(&h.8.to_le_bytes());
+        out.extend_from_slice(&h.9.to_le_bytes());
+    }
+
+    let mut ehdr = Vec::with_capacity(EHDR_SIZE);
+    ehdr.extend_from_slice(b"\x7fELF");
+    ehdr.extend_from_slice(&[2, 1, 1, 0]); // 64-bit, little endian, version 1, System V
+    ehdr.extend_from_slice(&[0; 8]);
+    ehdr.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    ehdr.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    ehdr.extend_from_slice(&1u32.to_le_bytes());
+    ehdr.extend_from_slice(&entry.to_le_bytes());
+    ehdr.extend_from_slice(&(EHDR_SIZE as u64).to_le_bytes()); // program headers
+    ehdr.extend_from_slice(&shoff.to_le_bytes());
+    ehdr.extend_from_slice(&0u32.to_le_bytes()); // flags
+    ehdr.extend_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(phnum as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16).to_le_bytes());
+    ehdr.extend_from_slice(&(headers.len() as u16 - 1).to_le_bytes()); // .shstrtab is last
+    out[..EHDR_SIZE].copy_from_slice(&ehdr);
+    out
+}
+
+#[allow(clippy::too_many_arguments)]
+fn push_phdr(
+    out: &mut Vec<u8>,
+    p_type: u32,
+    flags: u32,
+    offset: u64,
+    addr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+) {
+    out.extend_from_slice(&p_type.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&offset.to_le_bytes());
+    out.extend_from_slice(&addr.to_le_bytes()); // virtual
+    out.extend_from_slice(&addr.to_le_bytes()); // physical
+    out.extend_from_slice(&filesz.to_le_bytes());
+    out.extend_from_slice(&memsz.to_le_bytes());
+    out.extend_from_slice(&align.to_le_bytes());
+}
+
+// a name in a string table, the offset of it
+fn add(table: &mut Vec<u8>, name: &str) -> u32 {
+    let offset = table.len() as u32;
+    table.extend_from_slice(name.as_bytes());
+    table.push(0);
+    offset
+}
+
+fn align(out: &mut Vec<u8>, align: usize) {
+    while !out.len().is_multiple_of(align) {
+        out.push(0);
+    }
+}
@@ -0,0 +1,548 @@
+mod exe;
+mod object;
+
+use self::exe::OutSection;
+use self::object::Object;
+use crate::asm::{self, elf};
+use crate::diagnostic::{code, Diagnostic, Severity};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The static linker: it merges the sections of the objects into one text,
+// one read-only data, one data and one bss section, resolves the symbols
+// between them and applies the relocations.  The program starts in a small
+// `_start` of its own that calls `main` and exits with what it returns.
+// There is no C library here, a program that needs one is linked by `cc`.
+
+// where the executable is loaded, as ld does it for non-PIE programs
+const BASE: u64 = 0x40_0000;
+const PAGE: u64 = 0x1000;
+
+// the symbol at the start of the GOT, -fPIC code refers to it
+const GOT: &str = "_GLOBAL_OFFSET_TABLE_";
+
+const START: &str = "\
+        .text
+        .globl _start
+_start:
+        xorl %ebp, %ebp
+        movq (%rsp), %rdi
+        leaq 8(%rsp), %rsi
+        leaq 16(%rsp,%rdi,8), %rdx
+        andq $-16, %rsp
+        call main
+        movl %eax, %edi
+        movl $231, %eax
+        syscall
+";
+
+// how a global symbol is defined, after all the objects are read
+enum Def {
+    Undefined { weak: bool },
+    Common { size: u64, align: u64 },
+    Defined { obj: usize, sym: usize, weak: bool },
+}
+
+// the four output sections, in the order they are laid out
+const TEXT: usize = 0;
+const RODATA: usize = 1;
+const DATA: usize = 2;
+const BSS: usize = 3;
+
+// link the objects and archives in `inputs` and the libraries `libs`, found
+// in `lib_dirs`, into the executable `output`; false when the program needs
+// something only the system linker can find, like the C library
+pub fn link(
+    inputs: &[PathBuf],
+    lib_dirs: &[PathBuf],
+    libs: &[String],
+    output: &Path,
+) -> Result<bool, Diagnostic> {
+    let start = asm::assemble(START, "_start").map_err(Diagnostic::into_ice)?;
+    let mut objects = vec![object::read_object("_start", &start).map_err(ice)?];
+    let mut archives = Vec::new();
+    let mut paths = inputs.to_vec();
+    for lib in libs {
+        let name = format!("lib{}.a", lib);
+        match lib_dirs.iter().map(|d| d.join(&name)).find(|p| p.is_file()) {
+            Some(path) => paths.push(path),
+            None => return Ok(false),
+        }
+    }
+    for path in paths.iter() {
+        let data = fs::read(path).map_err(|e| Diagnostic::io(path, &e))?;
+        let name = path.display().to_string();
+        if object::is_archive(&data) {
+            let mut members = Vec::new();
+            for (name, data) in object::read_archive(&name, &data).map_err(error)? {
+                members.push(Some(object::read_object(&name, &data).map_err(error)?));
+            }
+            archives.push(members);
+        } else {
+            objects.push(object::read_object(&name, &data).map_err(error)?);
+        }
+    }
+
+    let mut linker = Linker::default();
+    for obj in objects {
+        linker.add(obj).map_err(error)?;
+    }
+    // a member of an archive is linked when it defines a symbol that is still
+    // undefined, until there is none left to take
+    loop {
+        let mut added = false;
+        for members in archives.iter_mut() {
+            for member in members.iter_mut() {
+                let wanted = member.as_ref().is_some_and(|obj| linker.wants(obj));
+                if wanted {
+                    linker.add(member.take().unwrap()).map_err(error)?;
+                    added = true;
+                }
+            }
+        }
+        if !added {
+            break;
+        }
+    }
+    if linker
+        .globals
+        .iter()
+        .any(|(name, d)| name != GOT && matches!(d, Def::Undefined { weak: false }))
+    {
+        return Ok(false);
+    }
+
+    let exe = linker.link().map_err(error)?;
+    fs::write(output, exe).map_err(|e| Diagnostic::io(output, &e))?;
+    set_executable(output).map_err(|e| Diagnostic::io(output, &e))?;
+    Ok(true)
+}
+
+fn error(msg: String) -> Diagnostic {
+    Diagnostic::driver(code::LINK, &msg)
+}
+
+fn ice(msg: String) -> Diagnostic {
+    Diagnostic::new(Severity::Ice, None, None, &msg)
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+// a slot of the global offset table: a global symbol, or a local one of an object
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum GotKey {
+    Global(String),
+    Local(usize, usize),
+}
+
+#[derive(Default)]
+struct Linker {
+    objects: Vec<Object>,
+    globals: HashMap<String, Def>,
+    order: Vec<String>, // the global symbols in the order they are first seen
+    // for each section of each object: the output section and the offset in it
+    placement: Vec<Vec<Option<(usize, u64)>>>,
+    commons: HashMap<String, u64>, // offset in .bss
+    got: HashMap<GotKey, u64>,     // offset in .data
+    got_start: u64,                // offset of the GOT in .data
+    out: Vec<OutSection>,
+}
+
+impl Linker {
+    // whether `obj` defines a symbol that is undefined so far
+    fn wants(&self, obj: &Object) -> bool {
+        obj.symbols.iter().any(|s| {
+            s.bind != elf::STB_LOCAL
+                && s.shndx != elf::SHN_UNDEF
+                && matches!(self.globals.get(&s.name), Some(Def::Undefined { .. }))
+        })
+    }
+
+    fn add(&mut self, obj: Object) -> Result<(), String> {
+        let index = self.objects.len();
+        for (i, s) in obj.symbols.iter().enumerate() {
+            if s.bind == elf::STB_LOCAL || s.name.is_empty() {
+                continue;
+            }
+            let weak = s.bind == elf::STB_WEAK;
+            if !self.globals.contains_key(&s.name) {
+                self.order.push(s.name.clone());
+            }
+            let def = self
+                .globals
+                .entry(s.name.clone())
+                .or_insert(Def::Undefined { weak: true });
+            match (s.shndx, &*def) {
+                // a reference: it is weak only if all of them are
+                (elf::SHN_UNDEF, Def::Undefined { weak: w }) => {
+                    *def = Def::Undefined { weak: *w && weak }
+                }
+                (elf::SHN_UNDEF, _) => {}
+                (elf::SHN_COMMON, Def::Undefined { .. }) => {
+                    *def = Def::Common {
+                        size: s.size,
+                        align: s.value.max(1),
+                    }
+                }
+                (elf::SHN_COMMON, Def::Common { size, align }) => {
+                    *def = Def::Common {
+                        size: s.size.max(*size),
+                        align: s.value.max(*align),
+                    }
+                }
+                (elf::SHN_COMMON, Def::Defined { .. }) => {}
+                (
+                    _,
+                    Def::Defined {
+                        obj: first,
+                        weak: false,
+                        ..
+                    },
+                ) if !weak => {
+                    return Err(format!(
+                        "{}: multiple definition of `{}`, first defined in {}",
+                        obj.name, s.name, self.objects[*first].name
+                    ));
+                }
+                (_, Def::Defined { weak: false, .. }) => {}
+                (_, Def::Defined { weak: true, .. }) if weak => {}
+                _ => {
+                    *def = Def::Defined {
+                        obj: index,
+                        sym: i,
+                        weak,
+                    }
+                }
+            }
+        }
+        self.objects.push(obj);
+        Ok(())
+    }
+
+    fn link(mut self) -> Result<Vec<u8>, String> {
+        self.out = vec![
+            OutSection::new(
+                ".text",
+                elf::SHT_PROGBITS,
+                elf::SHF_ALLOC | elf::SHF_EXECINSTR,
+            ),
+            OutSection::new(".rodata", elf::SHT_PROGBITS, elf::SHF_ALLOC),
+            OutSection::new(".data", elf::SHT_PROGBITS, elf::SHF_ALLOC | elf::SHF_WRITE),
+            OutSection::new(".bss", elf::SHT_NOBITS, elf::SHF_ALLOC | elf::SHF_WRITE),
+        ];
+        self.place_sections()?;
+        self.place_commons();
+        self.place_got()?;
+        self.assign_addresses();
+        self.relocate()?;
+        for (key, offset) in self.got.clone() {
+            let addr = match key {
+                GotKey::Global(name) => self.global_addr(&name)?,
+                GotKey::Local(obj, sym) => self.symbol_addr(obj, sym)?,
+            };
+            let data = &mut self.out[DATA].data;
+            data[offset as usize..offset as usize + 8].copy_from_slice(&addr.to_le_bytes());
+        }
+        let entry = self.global_addr("_start")?;
+        let (symbols, first_global) = self.symbol_table()?;
+        Ok(exe::write(&self.out, &symbols, first_global, entry))
+    }
+
+    fn place_sections(&mut self) -> Result<(), String> {
+        for obj in self.objects.iter() {
+            let mut placement = Vec::new();
+            for s in obj.sections.iter() {
+                if !s.is_alloc() {
+                    placement.push(None);
+                    continue;
+                }
+                if s.flags & 0x400 != 0 {
+                    return Err(format!(
+                        "{}: thread-local storage in `{}` is not supported",
+                        obj.name, s.name
+                    ));
+                }
+                let out = if s.sh_type == elf::SHT_NOBITS {
+                    BSS
+                } else if s.flags & elf::SHF_EXECINSTR != 0 {
+                    TEXT
+                } else if s.flags & elf::SHF_WRITE != 0 {
+                    DATA
+                } else {
+                    RODATA
+                };
+                let offset = self.out[out].append(s.align, &s.data, s.size);
+                placement.push(Some((out, offset)));
+            }
+            self.placement.push(placement);
+        }
+        Ok(())
+    }
+
+    // the common symbols nobody defined go to the end of .bss
+    fn place_commons(&mut self) {
+        for name in self.order.iter() {
+            if let Some(Def::Common { size, align }) = self.globals.get(name) {
+                let offset = self.out[BSS].append(*align, &[], *size);
+                self.commons.insert(name.clone(), offset);
+            }
+        }
+    }
+
+    // a slot in the GOT for every symbol a GOTPCREL or GOT64 relocation refers to
+    fn place_got(&mut self) -> Result<(), String> {
+        let mut keys = Vec::new();
+        for (i, obj) in self.objects.iter().enumerate() {
+            for (j, s) in obj.sections.iter().enumerate() {
+                if self.placement[i][j].is_none() {
+                    continue;
+                }
+                for r in s.relocs.iter() {
+                    if !is_got(r.kind) {
+                        continue;
+                    }
+                    let sym = &obj.symbols[r.sym];
+                    let key = if sym.bind == elf::STB_LOCAL {
+                        GotKey::Local(i, r.sym)
+                    } else {
+                        GotKey::Global(sym.name.clone())
+                    };
+                    if !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+            }
+        }
+        self.got_start = self.out[DATA].append(8, &[], 0);
+        for key in keys {
+            let offset = self.out[DATA].append(8, &[0; 8], 8);
+            self.got.insert(key, offset);
+        }
+        Ok(())
+    }
+
+    // each section on pages of its own, at the address of its place in the file
+    fn assign_addresses(&mut self) {
+        let mut offset = PAGE;
+        for (i, out) in self.out.iter_mut().enumerate() {
+            if i == BSS {
+                // right after .data in memory, it takes no room in the file
+                offset = align_up(offset, out.align);
+            } else if i != TEXT {
+                offset = align_up(offset, PAGE.max(out.align));
+            }
+            out.offset = offset;
+            out.addr = BASE + offset;
+            offset += out.size;
+        }
+    }
+
+    fn section_addr(&self, obj: usize, shndx: u16) -> Result<u64, String> {
+        match self.placement[obj].get(shndx as usize) {
+            Some(Some((out, offset))) => Ok(self.out[*out].addr + offset),
+            _ => Err(format!(
+                "{}: a symbol refers to a section that is not linked",
+                self.objects[obj].name
+            )),
+        }
+    }
+
+    fn global_addr(&self, name: &str) -> Result<u64, String> {
+        match self.globals.get(name) {
+            Some(Def::Defined { obj, sym, .. }) => {
+                let s = &self.objects[*obj].symbols[*sym];
+                match s.shndx {
+                    elf::SHN_ABS => Ok(s.value),
+                    shndx => Ok(self.section_addr(*obj, shndx)? + s.value),
+                }
+            }
+            Some(Def::Common { .. }) => Ok(self.out[BSS].addr + self.commons[name]),
+            Some(Def::Undefined { .. }) if name == GOT => Ok(self.got_addr()),
+            // an undefined weak symbol is 0
+            Some(Def::Undefined { .. }) => Ok(0),
+            None => Err(format!("undefined reference to `{}`", name)),
+        }
+    }
+
+    fn got_addr(&self) -> u64 {
+        self.out[DATA].addr + self.got_start
+    }
+
+    fn symbol_addr(&self, obj: usize, sym: usize) -> Result<u64, String> {
+        let s = &self.objects[obj].symbols[sym];
+        if s.bind != elf::STB_LOCAL {
+            return self.global_addr(&s.name);
+        }
+        match s.shndx {
+            elf::SHN_ABS => Ok(s.value),
+            elf::SHN_UNDEF => Ok(0),
+            shndx => Ok(self.section_addr(obj, shndx)? + s.value),
+        }
+    }
+
+    fn relocate(&mut self) -> Result<(), String> {
+        for i in 0..self.objects.len() {
+            for j in 0..self.objects[i].sections.len() {
+                let (out, base) = match self.placement[i][j] {
+                    Some(p) => p,
+                    None => continue,
+                };
+                for k in 0..self.objects[i].sections[j].relocs.len() {
+                    let r = &self.objects[i].sections[j].relocs[k];
+                    let (kind, sym, addend) = (r.kind, r.sym, r.addend);
+                    let offset = base + r.offset;
+                    let p = self.out[out].addr + offset;
+                    let s = self.symbol_addr(i, sym)?;
+                    let value = match kind {
+                        elf::R_X86_64_64 => s.wrapping_add(addend as u64),
+                        elf::R_X86_64_PC32 | elf::R_X86_64_PLT32 | elf::R_X86_64_PC64 => {
+                            s.wrapping_add(addend as u64).wrapping_sub(p)
+                        }
+                        elf::R_X86_64_32 | elf::R_X86_64_32S => s.wrapping_add(addend as u64),
+                        elf::R_X86_64_GOTPC32 | elf::R_X86_64_GOTPC64 => {
+                            self.got_addr().wrapping_add(addend as u64).wrapping_sub(p)
+                        }
+                        // there is no PLT, a function is called where it is
+                        elf::R_X86_64_GOTOFF64 | elf::R_X86_64_PLTOFF64 => {
+                            s.wrapping_add(addend as u64).wrapping_sub(self.got_addr())
+                        }
+                        kind if is_got(kind) => {
+                            let obj = &self.objects[i];
+                            let key = if obj.symbols[sym].bind == elf::STB_LOCAL {
+                                GotKey::Local(i, sym)
+                            } else {
+                                GotKey::Global(obj.symbols[sym].name.clone())
+                            };
+                            // GOT64 is the offset of the slot in the GOT
+                            if kind == elf::R_X86_64_GOT64 {
+                                (self.got[&key] - self.got_start).wrapping_add(addend as u64)
+                            } else {
+                                let g = self.out[DATA].addr + self.got[&key];
+                                g.wrapping_add(addend as u64).wrapping_sub(p)
+                            }
+                        }
+                        kind => {
+                            return Err(format!(
+                                "{}: relocation type {} is not supported",
+                                self.objects[i].name, kind
+                            ))
+                        }
+                    };
+                    let v = value as i64;
+                    let (size, fits) = match kind {
+                        elf::R_X86_64_64
+                        | elf::R_X86_64_PC64
+                        | elf::R_X86_64_GOTPC64
+                        | elf::R_X86_64_GOTOFF64
+                        | elf::R_X86_64_GOT64
+                        | elf::R_X86_64_PLTOFF64 => (8, true),
+                        elf::R_X86_64_32 => (4, value <= u64::from(u32::MAX)),
+                        _ => (4, v >= i64::from(i32::MIN) && v <= i64::from(i32::MAX)),
+                    };
+                    if !fits {
+                        let obj = &self.objects[i];
+                        return Err(format!(
+                            "{}: relocation against `{}` in `{}` is out of range",
+                            obj.name, obj.symbols[sym].name, obj.sections[j].name
+                        ));
+                    }
+                    let data = &mut self.out[out].data;
+                    let at = offset as usize;
+                    match data.get_mut(at..at + size) {
+                        Some(field) => field.copy_from_slice(&value.to_le_bytes()[..size]),
+                        None => {
+                            return Err(format!(
+                                "{}: relocation outside of `{}`",
+                                self.objects[i].name, self.objects[i].sections[j].name
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the functions and variables by name, for debuggers and objdump
+    fn symbol_table(&self) -> Result<(Vec<elf::Symbol>, usize), String> {
+        let shndx = |obj: usize, s: &object::Symbol| match s.shndx {
+            elf::SHN_ABS => Some(elf::SHN_ABS),
+            shndx => match self.placement[obj].get(shndx as usize) {
+                Some(Some((out, _))) => Some(*out as u16 + 1),
+                _ => None,
+            },
+        };
+        let mut symbols = vec![elf::Symbol {
+            name: String::new(),
+            bind: elf::STB_LOCAL,
+            kind: elf::STT_NOTYPE,
+            shndx: elf::SHN_UNDEF,
+            value: 0,
+            size: 0,
+        }];
+        for (i, obj) in self.objects.iter().enumerate() {
+            for (j, s) in obj.symbols.iter().enumerate() {
+                let named =
+                    !s.name.is_empty() && s.kind != elf::STT_SECTION && s.kind != elf::STT_FILE;
+                if s.bind != elf::STB_LOCAL || !named {
+                    continue;
+                }
+                if let Some(shndx) = shndx(i, s) {
+                    symbols.push(elf::Symbol {
+                        name: s.name.clone(),
+                        bind: elf::STB_LOCAL,
+                        kind: s.kind,
+                        shndx,
+                        value: self.symbol_addr(i, j)?,
+                        size: s.size,
+                    });
+                }
+            }
+        }
+        let first_global = symbols.len();
+        for name in self.order.iter() {
+            let (kind, shndx, size) = match &self.globals[name] {
+                Def::Defined { obj, sym, .. } => {
+                    let s = &self.objects[*obj].symbols[*sym];
+                    match shndx(*obj, s) {
+                        Some(shndx) => (s.kind, shndx, s.size),
+                        None => continue,
+                    }
+                }
+                Def::Common { size, .. } => (elf::STT_OBJECT, BSS as u16 + 1, *size),
+                Def::Undefined { .. } if name == GOT => (elf::STT_OBJECT, DATA as u16 + 1, 0),
+                Def::Undefined { .. } => continue,
+            };
+            symbols.push(elf::Symbol {
+                name: name.clone(),
+                bind: elf::STB_GLOBAL,
+                kind,
+                shndx,
+                value: self.global_addr(name)?,
+                size,
+            });
+        }
+        Ok((symbols, first_global))
+    }
+}
+
+fn is_got(kind: u32) -> bool {
+    kind == elf::R_X86_64_GOTPCREL
+        || kind == elf::R_X86_64_GOTPCRELX
+        || kind == elf::R_X86_64_REX_GOTPCRELX
+        || kind == elf::R_X86_64_GOT64
+}
+
+fn align_up(v: u64, align: u64) -> u64 {
+    v.div_ceil(align) * align
+}
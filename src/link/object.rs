@@ -0,0 +1,227 @@
+use crate::asm::elf;
+
+// Reading ELF64 relocatable objects for x86-64, alone or as the members of
+// an `ar` archive.  Only what the linker needs is kept: the sections with
+// their relocations and the symbol table.
+
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub align: u64,
+    pub data: Vec<u8>, // empty for SHT_NOBITS, `size` is what counts
+    pub size: u64,
+    pub relocs: Vec<Rela>,
+}
+
+impl Section {
+    // whether the section ends up in the memory image of the program
+    pub fn is_alloc(&self) -> bool {
+        self.flags & elf::SHF_ALLOC != 0 && self.sh_type != elf::SHT_NOTE
+    }
+}
+
+pub struct Rela {
+    pub offset: u64,
+    pub sym: usize,
+    pub kind: u32,
+    pub addend: i64,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub bind: u8,
+    pub kind: u8,
+    pub shndx: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+pub struct Object {
+    pub name: String,           // `a.o` or `libx.a(a.o)`, for messages
+    pub sections: Vec<Section>, // indexed like the section headers
+    pub symbols: Vec<Symbol>,
+}
+
+// the members of an archive, with their names and contents
+pub fn read_archive(name: &str, data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    if data.starts_with(b"!<thin>\n") {
+        return Err(format!("{}: thin archives are not supported", name));
+    }
+    let bad = || format!("{}: malformed archive", name);
+    let mut members = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut pos = 8;
+    while pos < data.len() {
+        let header = data.get(pos..pos + 60).ok_or_else(bad)?;
+        if &header[58..60] != b"`\n" {
+            return Err(bad());
+        }
+        let field = |range: std::ops::Range<usize>| {
+            String::from_utf8_lossy(&header[range]).trim().to_string()
+        };
+        let size: usize = field(48..58).parse().map_err(|_| bad())?;
+        let body = data.get(pos + 60..pos + 60 + size).ok_or_else(bad)?;
+        pos += 60 + size + size % 2;
+        let member = field(0..16);
+        match member.as_str() {
+            // the symbol index, the linker looks at the members themselves
+            "/" | "/SYM64/" => {}
+            "//" => long_names = body,
+            _ => {
+                let member = match member.strip_prefix('/') {
+                    Some(offset) => {
+                        let offset: usize = offset.parse().map_err(|_| bad())?;
+                        let rest = long_names.get(offset..).ok_or_else(bad)?;
+                        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+                        String::from_utf8_lossy(&rest[..end])
+                            .trim_end_matches('/')
+                            .to_string()
+                    }
+                    None => member.trim_end_matches('/').to_string(),
+                };
+                members.push((format!("{}({})", name, member), body.to_vec()));
+            }
+        }
+    }
+    Ok(members)
+}
+
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(b"!<arch>\n") || data.starts_with(b"!<thin>\n")
+}
+
+// the fields of the file, with the error for reading past its end
+struct Reader<'a> {
+    name: &'a str,
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, off: u64, len: u64) -> Result<&'a [u8], String> {
+        let start = off as usize;
+        self.data
+            .get(start..start.wrapping_add(len as usize))
+            .ok_or_else(|| format!("{}: malformed ELF file", self.name))
+    }
+
+    fn u16(&self, off: u64) -> Result<u16, String> {
+        let b = self.bytes(off, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, off: u64) -> Result<u32, String> {
+        let mut b = [0; 4];
+        b.copy_from_slice(self.bytes(off, 4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+
+    fn u64(&self, off: u64) -> Result<u64, String> {
+        let mut b = [0; 8];
+        b.copy_from_slice(self.bytes(off, 8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+
+    // a NUL terminated string of the string table at `table`
+    fn name(&self, table: &[u8], off: u32) -> Result<String, String> {
+        let rest = table
+            .get(off as usize..)
+            .ok_or_else(|| format!("{}: malformed ELF file", self.name))?;
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    }
+}
+
+pub fn read_object(name: &str, data: &[u8]) -> Result<Object, String> {
+    let r = Reader { name, data };
+    if !data.starts_with(b"\x7fELF") {
+        return Err(format!("{}: not an object file or archive", name));
+    }
+    // 64-bit, little endian, relocatable, x86-64
+    if r.bytes(4, 2)? != [2, 1] || r.u16(16)? != 1 || r.u16(18)? != 62 {
+        return Err(format!("{}: not an x86-64 relocatable object", name));
+    }
+    let shoff = r.u64(40)?;
+    let shnum = u64::from(r.u16(60)?);
+    let shstrndx = u64::from(r.u16(62)?);
+    let header = |i: u64, field: u64| shoff + i * 64 + field;
+
+    let section_data = |i: u64| -> Result<&[u8], String> {
+        if r.u32(header(i, 4))? == elf::SHT_NOBITS {
+            return Ok(&[]);
+        }
+        r.bytes(r.u64(header(i, 24))?, r.u64(header(i, 32))?)
+    };
+    let shstrtab = section_data(shstrndx)?;
+
+    let mut sections = Vec::new();
+    let mut symtab = None;
+    for i in 0..shnum {
+        let sh_type = r.u32(header(i, 4))?;
+        if sh_type == elf::SHT_SYMTAB {
+            symtab = Some(i);
+        }
+        sections.push(Section {
+            name: r.name(shstrtab, r.u32(header(i, 0))?)?,
+            sh_type,
+            flags: r.u64(header(i, 8))?,
+            align: r.u64(header(i, 48))?.max(1),
+            data: section_data(i)?.to_vec(),
+            size: r.u64(header(i, 32))?,
+            relocs: Vec::new(),
+        });
+    }
+
+    let mut symbols = Vec::new();
+    if let Some(i) = symtab {
+        let strtab = section_data(u64::from(r.u32(header(i, 40))?))?;
+        let table = section_data(i)?;
+        for entry in table.chunks_exact(24) {
+            let sym = Reader { name, data: entry };
+            let info = entry[4];
+            symbols.push(Symbol {
+                name: r.name(strtab, sym.u32(0)?)?,
+                bind: info >> 4,
+                kind: info & 0xf,
+                shndx: sym.u16(6)?,
+                value: sym.u64(8)?,
+                size: sym.u64(16)?,
+            });
+        }
+    }
+
+    for i in 0..shnum {
+        if r.u32(header(i, 4))? != elf::SHT_RELA {
+            continue;
+        }
+        let target = r.u32(header(i, 44))? as usize;
+        let mut relocs = Vec::new();
+        for entry in section_data(i)?.chunks_exact(24) {
+            let rel = Reader { name, data: entry };
+            let info = rel.u64(8)?;
+            let sym = (info >> 32) as usize;
+            if sym >= symbols.len() {
+                return Err(format!(
+                    "{}: relocation against a symbol that does not exist",
+                    name
+                ));
+            }
+            relocs.push(Rela {
+                offset: rel.u64(0)?,
+                sym,
+                kind: info as u32,
+                addend: rel.u64(16)? as i64,
+            });
+        }
+        match sections.get_mut(target) {
+            Some(s) => s.relocs.extend(relocs),
+            None => return Err(format!("{}: malformed ELF file", name)),
+        }
+    }
+
+    Ok(Object {
+        name: name.to_string(),
+        sections,
+        symbols,
+    })
+}
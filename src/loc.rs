@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::rc::Rc;
+
+// A position in the source code.
+//
+// `file`, `line` and `col` are where the code came from, after following the
+// line markers left by the preprocessor, so they point into the file the user
+// wrote (or the header that was included).  `span` is the byte range in the
+// translation unit the lexer was given; it is only used to tell how far a
+// token or a node reaches.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Loc {
+    pub file: Rc<str>,
+    pub line: usize,          // 1-based
+    pub col: usize,           // 1-based, counted in bytes like gcc does, 0 if not known
+    pub span: (usize, usize), // [start, end)
+}
+
+impl Loc {
+    pub fn new(file: Rc<str>, line: usize, col: usize, span: (usize, usize)) -> Loc {
+        Loc {
+            file,
+            line,
+            col,
+            span,
+        }
+    }
+
+    // the location of something that starts at `self` and ends with `other`
+    pub fn to(&self, other: &Loc) -> Loc {
+        let mut loc = self.clone();
+        if other.file == self.file && other.span.1 > loc.span.1 {
+            loc.span.1 = other.span.1;
+        }
+        loc
+    }
+}
+
+impl fmt::Display for Loc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.col == 0 {
+            write!(f, "{}:{}", self.file, self.line)
+        } else {
+            write!(f, "{}:{}:{}", self.file, self.line, self.col)
+        }
+    }
+}
+
+// The text of the files diagnostics point into, read when first needed.
+#[derive(Default)]
+pub struct SourceMap {
+    files: HashMap<String, Option<String>>,
+}
+
+impl SourceMap {
+    // register a file we already have in memory
+    pub fn add(&mut self, name: &str, contents: &str) {
+        self.files
+            .insert(name.to_string(), Some(contents.to_string()));
+    }
+
+    // line `line` of `file`, None for files that can not be read, like `<command-line>`
+    pub fn line(&mut self, file: &str, line: usize) -> Option<&str> {
+        let contents = self
+            .files
+            .entry(file.to_string())
+            .or_insert_with(|| fs::read_to_string(file).ok());
+        contents.as_ref()?.lines().nth(line.checked_sub(1)?)
+    }
+}
+
+// `line` followed by a `^~~~` line underlining `loc` in it
+pub fn caret(line: &str, loc: &Loc) -> String {
+    let line = line.trim_end_matches('\r');
+    let start = (loc.col - 1).min(line.len());
+    let end = (start + (loc.span.1 - loc.span.0).max(1))
+        .min(line.len())
+        .max(start + 1);
+    // keep the tabs, so the caret lines up in every terminal
+    let indent: String = line
+        .bytes()
+        .take(start)
+        .map(|b| if b == b'\t' { '\t' } else { ' ' })
+        .collect();
+    format!("{}\n{}^{}", line, indent, "~".repeat(end - start - 1))
+}
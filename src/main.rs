@@ -1,48 +1,226 @@
-mod gen;
-mod lexer;
+mod driver;
 mod opts;
-mod parser;
 
-use std::{error, fs};
+use crust::diagnostic::{self, code, Diagnostic};
+use crust::{link, parser, Options, Session};
+use std::path::{Path, PathBuf};
+use std::{fs, panic, process, thread};
 
-fn main() -> Result<(), Box<dyn error::Error>> {
+// the parser, sema and the lowering take a frame for every level of an
+// expression, deep ones need more stack than a thread starts with
+const STACK_SIZE: usize = 256 << 20;
+
+fn main() {
+    // a panic is always a bug in crust, never in the program being compiled
+    panic::set_hook(Box::new(|info| {
+        eprintln!("crust: internal compiler error: {}", info);
+        eprintln!("note: this is a bug in crust, please report it");
+        process::exit(diagnostic::EXIT_ICE);
+    }));
+    // a panic in it has already exited through the hook
+    let _ = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(crust)
+        .expect("can not start the compiler thread")
+        .join();
+}
+
+fn crust() {
     let opts: opts::Opts = {
         use structopt::StructOpt;
 
-        opts::Opts::from_args()
+        match opts::args() {
+            Ok(args) => opts::Opts::from_iter(args),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(e.exit_code());
+            }
+        }
     };
+    let mut session = Session::new(Options {
+        include_dirs: opts.include_dirs().to_vec(),
+        defines: opts.defines().to_vec(),
+        opt_level: opts.opt_level(),
+    });
+    let res = run(&opts, &mut session);
+    for w in session.take_warnings() {
+        eprintln!("{}", session.render(&w));
+    }
+    if let Err(e) = res {
+        eprintln!("{}", session.render(&e));
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(opts: &opts::Opts, session: &mut Session) -> Result<(), Diagnostic> {
+    let stop_stage = opts.stop_stage();
+
+    if opts.output().is_some() && opts.input().len() > 1 && !stop_stage.link() {
+        return Err(Diagnostic::driver(
+            code::USAGE,
+            "can not specify `-o` with `-c`, `-S` or `-E` with multiple files",
+        ));
+    }
+
+    if let Some(pass) = opts.crust_debug_flags().print_after() {
+        if !crust::opt::PASSES.contains(&pass) {
+            return Err(Diagnostic::driver(
+                code::USAGE,
+                &format!(
+                    "no optimization pass `{}`, the passes are {}",
+                    pass,
+                    crust::opt::PASSES.join(", ")
+                ),
+            ));
+        }
+    }
+
+    let mut temps = driver::Temps::default();
+    let mut objects = Vec::new();
+    for input_file in opts.input() {
+        let (asm, from_c) = match input_file.extension().and_then(|e| e.to_str()) {
+            Some("c") => {
+                let asm = match compile(input_file, opts, session)? {
+                    Some(asm) => asm,
+                    None => continue, // -E, the preprocessed source is written
+                };
+                if stop_stage.compile() {
+                    let asm_file = output_for(opts, input_file, "s");
+                    if opts.crust_debug_flags().print_filenames() {
+                        println!("Output file: {}\n", asm_file.display());
+                    }
+                    fs::write(&asm_file, asm).map_err(|e| Diagnostic::io(&asm_file, &e))?;
+                    continue;
+                }
+                (asm, true)
+            }
+            Some("s") => {
+                if stop_stage.compile() || stop_stage.preprocess() {
+                    continue;
+                }
+                let asm =
+                    fs::read_to_string(input_file).map_err(|e| Diagnostic::io(input_file, &e))?;
+                (asm, false)
+            }
+            // object files and libraries are for the linker
+            _ => {
+                objects.push(input_file.clone());
+                continue;
+            }
+        };
+        let obj_file = if stop_stage.assemble() {
+            output_for(opts, input_file, "o")
+        } else {
+            temps.path(input_file, "o")
+        };
+        if opts.crust_debug_flags().system_as() {
+            let asm_file = if from_c {
+                let asm_file = temps.path(input_file, "s");
+                fs::write(&asm_file, asm).map_err(|e| Diagnostic::io(&asm_file, &e))?;
+                asm_file
+            } else {
+                input_file.clone()
+            };
+            driver::assemble(&asm_file, &obj_file)?;
+        } else {
+            let asm_name = input_file.with_extension("s").display().to_string();
+            let obj = session.assemble(&asm_name, &asm).map_err(|e| {
+                // gen wrote assembly that crust can not assemble, a bug in crust
+                if from_c {
+                    e.into_ice()
+                } else {
+                    e
+                }
+            })?;
+            fs::write(&obj_file, obj).map_err(|e| Diagnostic::io(&obj_file, &e))?;
+        }
+        objects.push(obj_file);
+    }
 
-    // TODO: allow support for multiple input files.
-    //       Currently it tries to get the first input file and thats all
-    let input_file = opts.input()[0].clone();
+    if stop_stage.link() {
+        let output = match opts.output() {
+            Some(output) => output.clone(),
+            None => PathBuf::from("a.out"),
+        };
+        // the built-in linker has no C library, nor any idea of what the
+        // options for the system linker mean
+        let linked = !opts.crust_debug_flags().system_ld()
+            && opts.linker_flags().is_empty()
+            && opts.link_flags().is_empty()
+            && link::link(&objects, opts.lib_dirs(), opts.libs(), &output)?;
+        if !linked {
+            driver::link(&objects, &output, opts)?;
+        }
+    }
+    Ok(())
+}
 
+// the file a stage stopped after writes for `input`: the one given with `-o`,
+// or the name of the input with the suffix of the stage in the current directory
+fn output_for(opts: &opts::Opts, input: &Path, ext: &str) -> PathBuf {
+    match opts.output() {
+        Some(output) => output.clone(),
+        None => Path::new(input.file_name().unwrap_or_default()).with_extension(ext),
+    }
+}
+
+// the assembly for the C source in `input_file`, or nothing when the driver
+// stops after preprocessing it
+fn compile(
+    input_file: &Path,
+    opts: &opts::Opts,
+    session: &mut Session,
+) -> Result<Option<String>, Diagnostic> {
     if opts.crust_debug_flags().print_filenames() {
         println!("Source file: {}\n", input_file.display())
     }
 
-    let input_file_contents = fs::read_to_string(input_file.clone())?;
+    let input_file_contents =
+        fs::read_to_string(input_file).map_err(|e| Diagnostic::io(input_file, &e))?;
+    let name = input_file.display().to_string();
 
     if opts.crust_debug_flags().print_file_contents() {
         println!("File contents:\n{}\n", input_file_contents)
     }
 
-    let tokens = lexer::lex(&input_file_contents)?;
-    let root_node = parser::parse_prog(&input_file_contents, &input_file.display().to_string())?;
+    let preprocessed = session.preprocess(&name, &input_file_contents)?;
+
+    if opts.stop_stage().preprocess() {
+        match opts.output() {
+            Some(output) => {
+                fs::write(output, preprocessed).map_err(|e| Diagnostic::io(output, &e))?
+            }
+            None => print!("{}", preprocessed),
+        }
+        return Ok(None);
+    }
+
+    let tokens = session.lex(&preprocessed)?;
+    let mut root_node = session.parse(&name, &tokens)?;
 
     if opts.crust_debug_flags().print_source_ast() {
-        println!("Source AST:\n{}\n", parser::print(&root_node, 0))
+        println!("Source AST:\n{}\n", parser::print(&root_node, 0)?)
     }
 
-    if opts.crust_debug_flags().print_filenames() {
-        println!("Output file: {}\n", opts.output().display());
+    session.check(&mut root_node)?;
+    let mut module = session.lower(&root_node)?;
+
+    if opts.crust_debug_flags().print_ir() {
+        println!("IR:\n{}", module)
     }
 
-    let output_file_contents = gen::gen_prog(&root_node);
+    let print_after = opts.crust_debug_flags().print_after();
+    session.optimize(&mut module, &mut |pass, module| {
+        if print_after == Some(pass) {
+            println!("IR after {}:\n{}", pass, module)
+        }
+    });
+
+    let output_file_contents = session.codegen(&module);
 
     if opts.crust_debug_flags().print_file_contents() {
         println!("File contents:\n{}\n", output_file_contents)
     }
 
-    fs::write(opts.output(), output_file_contents)?;
-    Ok(())
+    Ok(Some(output_file_contents))
 }
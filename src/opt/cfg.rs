@@ -0,0 +1,127 @@
+use crate::ir::{Block, BlockId, Function, Operand, Terminator};
+use std::collections::HashSet;
+
+// The passes on the blocks themselves.
+
+// A branch that goes one way whatever it decides becomes a jump, a jump to a
+// block that only jumps on goes straight to where that one does, and a block
+// only ever entered from the end of another is joined to it.
+pub fn simplify_branches(func: &mut Function) -> bool {
+    let mut changed = false;
+    for block in func.blocks.iter_mut() {
+        let to = match &block.term {
+            Terminator::Branch {
+                cond: Operand::Imm(v),
+                then,
+                els,
+            } => Some(if *v != 0 { *then } else { *els }),
+            Terminator::Branch { then, els, .. } if then == els => Some(*then),
+            Terminator::Switch { cases, default, .. }
+                if cases.iter().all(|(_, b)| b == default) =>
+            {
+                Some(*default)
+            }
+            _ => None,
+        };
+        if let Some(to) = to {
+            block.term = Terminator::Jump(to);
+            changed = true;
+        }
+    }
+
+    // where a jump to each block ends up
+    let through: Vec<BlockId> = (0..func.blocks.len())
+        .map(|b| {
+            let mut b = BlockId(b);
+            let mut seen = HashSet::new();
+            while let (true, Terminator::Jump(to)) =
+                (func.blocks[b.0].insts.is_empty(), &func.blocks[b.0].term)
+            {
+                // an empty loop jumps to itself for ever
+                if !seen.insert(b) {
+                    break;
+                }
+                b = *to;
+            }
+            b
+        })
+        .collect();
+    for block in func.blocks.iter_mut() {
+        for s in block.term.successors_mut() {
+            if through[s.0] != *s {
+                *s = through[s.0];
+                changed = true;
+            }
+        }
+    }
+
+    let mut preds = func.predecessors();
+    for b in 0..func.blocks.len() {
+        while let Terminator::Jump(s) = func.blocks[b].term {
+            if s.0 == 0 || s.0 == b || preds[s.0].len() != 1 {
+                break;
+            }
+            // what is left of the one joined is never reached, and removed
+            let next = std::mem::replace(
+                &mut func.blocks[s.0],
+                Block {
+                    insts: Vec::new(),
+                    term: Terminator::Return(None),
+                },
+            );
+            for t in next.term.successors() {
+                for p in preds[t.0].iter_mut() {
+                    if *p == s {
+                        *p = BlockId(b);
+                    }
+                }
+            }
+            preds[s.0].clear();
+            let block = &mut func.blocks[b];
+            block.insts.extend(next.insts);
+            block.term = next.term;
+            changed = true;
+        }
+    }
+    changed
+}
+
+// The blocks no path from the entry reaches are removed, those after them
+// numbered down.
+pub fn remove_unreachable(func: &mut Function) -> bool {
+    let mut reached = vec![false; func.blocks.len()];
+    let mut stack = vec![BlockId(0)];
+    reached[0] = true;
+    while let Some(b) = stack.pop() {
+        for s in func.blocks[b.0].term.successors() {
+            if !reached[s.0] {
+                reached[s.0] = true;
+                stack.push(s);
+            }
+        }
+    }
+    if reached.iter().all(|r| *r) {
+        return false;
+    }
+
+    let mut renumber = vec![None; func.blocks.len()];
+    let mut n = 0;
+    for (b, reached) in reached.iter().enumerate() {
+        if *reached {
+            renumber[b] = Some(BlockId(n));
+            n += 1;
+        }
+    }
+    let blocks = std::mem::take(&mut func.blocks);
+    func.blocks = blocks
+        .into_iter()
+        .zip(reached)
+        .filter_map(|(block, reached)| if reached { Some(block) } else { None })
+        .collect();
+    for block in func.blocks.iter_mut() {
+        for s in block.term.successors_mut() {
+            *s = renumber[s.0].unwrap();
+        }
+    }
+    true
+}
@@ -0,0 +1,236 @@
+use super::{fold_binary, fold_cmp, fold_convert, fold_ext, fold_unary, wrap};
+use crate::ir::{BlockId, Function, Inst, Operand, Param, Terminator, VReg};
+use std::collections::HashMap;
+
+// Constant propagation: which registers hold the same constant on every
+// path to an instruction, and which instructions compute one from them.
+// A forward dataflow over the blocks, reaching only the successors a branch
+// can take, so a condition known to be constant keeps the side it never goes
+// to from spoiling what is known after the two sides join (Wegman and
+// Zadeck).  Then the registers known to be constants are replaced by them,
+// and an instruction with a constant result becomes a copy of it.
+
+// what a register holds; one not in the map is not written yet on any path
+// there, which does not say anything about it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Val {
+    Const(i64),
+    Varying,
+}
+
+type State = HashMap<VReg, Val>;
+
+pub fn run(func: &mut Function) -> bool {
+    let states = analyze(func);
+    let mut changed = false;
+    for (b, state) in states.into_iter().enumerate() {
+        let mut state = match state {
+            Some(state) => state,
+            None => continue, // never reached, unreachable-blocks removes it
+        };
+        let mut insts = std::mem::take(&mut func.blocks[b].insts);
+        for inst in insts.iter_mut() {
+            let value = eval(func, &state, inst);
+            let new = match (inst.def(), value) {
+                (Some(dst), Some(Val::Const(v))) if !inst.has_effect() => Inst::Copy {
+                    dst,
+                    src: Operand::Imm(v),
+                },
+                _ => {
+                    let mut new = inst.clone();
+                    for op in new.operands_mut() {
+                        if let Some(v) = constant(&state, *op) {
+                            *op = Operand::Imm(v);
+                        }
+                    }
+                    new
+                }
+            };
+            if new != *inst {
+                *inst = new;
+                changed = true;
+            }
+            define(&mut state, inst.def(), value);
+        }
+        func.blocks[b].insts = insts;
+
+        let term = &func.blocks[b].term;
+        let new = match term {
+            Terminator::Branch {
+                cond: Operand::Reg(r),
+                then,
+                els,
+            } => match constant(&state, Operand::Reg(*r)) {
+                Some(v) => Terminator::Branch {
+                    cond: Operand::Imm(v),
+                    then: *then,
+                    els: *els,
+                },
+                None => continue,
+            },
+            Terminator::Switch { value, .. } => match constant(&state, Operand::Reg(*value)) {
+                Some(v) => Terminator::Jump(switch_target(term, v)),
+                None => continue,
+            },
+            Terminator::Return(Some(Operand::Reg(r))) => match constant(&state, Operand::Reg(*r)) {
+                Some(v) => Terminator::Return(Some(Operand::Imm(v))),
+                None => continue,
+            },
+            _ => continue,
+        };
+        func.blocks[b].term = new;
+        changed = true;
+    }
+    changed
+}
+
+// the state at the start of each block, None for one no path reaches
+fn analyze(func: &Function) -> Vec<Option<State>> {
+    let mut states: Vec<Option<State>> = vec![None; func.blocks.len()];
+    // what comes in can be anything
+    let entry = func
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            Param::Value(r) => Some((*r, Val::Varying)),
+            Param::Struct(_, _) => None,
+        })
+        .collect();
+    states[0] = Some(entry);
+    let mut worklist = vec![BlockId(0)];
+    while let Some(b) = worklist.pop() {
+        let mut state = states[b.0].clone().unwrap_or_default();
+        let block = &func.blocks[b.0];
+        for inst in block.insts.iter() {
+            let value = eval(func, &state, inst);
+            define(&mut state, inst.def(), value);
+        }
+        for s in taken(&state, &block.term) {
+            let merged = match &states[s.0] {
+                Some(old) => meet(old, &state),
+                None => state.clone(),
+            };
+            if states[s.0].as_ref() != Some(&merged) {
+                states[s.0] = Some(merged);
+                worklist.push(s);
+            }
+        }
+    }
+    states
+}
+
+fn define(state: &mut State, dst: Option<VReg>, value: Option<Val>) {
+    if let Some(dst) = dst {
+        match value {
+            Some(v) => state.insert(dst, v),
+            None => state.remove(&dst),
+        };
+    }
+}
+
+// what the register an instruction writes holds after it, None if that
+// depends on a register not written yet
+fn eval(func: &Function, state: &State, inst: &Inst) -> Option<Val> {
+    let dst = inst.def()?;
+    let ty = func.ty(dst);
+    let get = |op: &Operand| match op {
+        Operand::Reg(r) => state.get(r).copied(),
+        Operand::Imm(v) => Some(Val::Const(*v)),
+    };
+    let reg = |r: &VReg| get(&Operand::Reg(*r));
+    // the operands as constants, Varying if one of them varies
+    let consts = |ops: &[Option<Val>]| -> Result<Vec<i64>, Option<Val>> {
+        let mut vs = Vec::new();
+        for op in ops {
+            match op {
+                Some(Val::Const(v)) => vs.push(*v),
+                Some(Val::Varying) => return Err(Some(Val::Varying)),
+                None => return Err(None),
+            }
+        }
+        Ok(vs)
+    };
+    let fold = |ops: &[Option<Val>], f: &dyn Fn(&[i64]) -> Option<i64>| match consts(ops) {
+        Ok(vs) => Some(f(&vs).map_or(Val::Varying, Val::Const)),
+        Err(v) => v,
+    };
+    match inst {
+        // the 64 bits of the register, of which only the ones of its type are known
+        Inst::Copy { src, .. } => match src {
+            Operand::Reg(r) if func.ty(*r).size() < ty.size() => Some(Val::Varying),
+            _ => fold(&[get(src)], &|v| Some(wrap(v[0], ty))),
+        },
+        Inst::Unary { op, src, .. } => fold(&[get(src)], &|v| Some(fold_unary(*op, ty, v[0]))),
+        Inst::Binary { op, lhs, rhs, .. } => {
+            fold(&[get(lhs), get(rhs)], &|v| fold_binary(*op, ty, v[0], v[1]))
+        }
+        Inst::Cmp {
+            cond,
+            ty: cmp_ty,
+            lhs,
+            rhs,
+            ..
+        } => fold(&[get(lhs), get(rhs)], &|v| {
+            Some(fold_cmp(*cond, *cmp_ty, v[0], v[1]))
+        }),
+        Inst::Ext { signed, src, .. } => fold(&[reg(src)], &|v| {
+            Some(fold_ext(*signed, func.ty(*src), ty, v[0]))
+        }),
+        Inst::Trunc { src, .. } => fold(&[reg(src)], &|v| Some(wrap(v[0], ty))),
+        Inst::Convert { signed, src, .. } => fold(&[reg(src)], &|v| {
+            fold_convert(*signed, func.ty(*src), ty, v[0])
+        }),
+        _ => Some(Val::Varying),
+    }
+}
+
+// the constant `op` is, if it is one
+fn constant(state: &State, op: Operand) -> Option<i64> {
+    match op {
+        Operand::Reg(r) => match state.get(&r) {
+            Some(Val::Const(v)) => Some(*v),
+            _ => None,
+        },
+        Operand::Imm(v) => Some(v),
+    }
+}
+
+// the successors the terminator can go to, all of them unless what it
+// decides on is a constant
+fn taken(state: &State, term: &Terminator) -> Vec<BlockId> {
+    match term {
+        Terminator::Branch { cond, then, els } => match constant(state, *cond) {
+            Some(v) if v != 0 => vec![*then],
+            Some(_) => vec![*els],
+            None => vec![*then, *els],
+        },
+        Terminator::Switch { value, .. } => match constant(state, Operand::Reg(*value)) {
+            Some(v) => vec![switch_target(term, v)],
+            None => term.successors(),
+        },
+        _ => term.successors(),
+    }
+}
+
+fn switch_target(term: &Terminator, v: i64) -> BlockId {
+    match term {
+        Terminator::Switch { cases, default, .. } => cases
+            .iter()
+            .find(|(case, _)| *case == v)
+            .map_or(*default, |(_, b)| *b),
+        _ => unreachable!(),
+    }
+}
+
+// what holds on both of two paths coming together
+fn meet(a: &State, b: &State) -> State {
+    let mut state = a.clone();
+    for (r, v) in b.iter() {
+        let v = match a.get(r) {
+            Some(old) if old != v => Val::Varying,
+            _ => *v,
+        };
+        state.insert(*r, v);
+    }
+    state
+}
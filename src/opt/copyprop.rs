@@ -0,0 +1,100 @@
+use crate::ir::{Function, Inst, Operand, Ty, VReg};
+use std::collections::HashMap;
+
+// Copy propagation: after `%a = copy %b`, and as long as neither of them is
+// written again, a read of %a can read %b instead, which leaves the copy for
+// dce once nothing reads %a any more.  Which copies hold is a forward
+// dataflow, a copy holding at the start of a block if it holds at the end
+// of all the blocks before it.
+
+// the copies that hold, from the register written to the one copied
+type Copies = HashMap<VReg, VReg>;
+
+pub fn run(func: &mut Function) -> bool {
+    let states = analyze(func);
+    let tys = func.vregs.clone();
+    let mut changed = false;
+    for (block, state) in func.blocks.iter_mut().zip(states) {
+        let mut copies = match state {
+            Some(copies) => copies,
+            None => continue,
+        };
+        for inst in block.insts.iter_mut() {
+            for r in inst.uses_mut() {
+                if let Some(src) = copies.get(r) {
+                    *r = *src;
+                    changed = true;
+                }
+            }
+            transfer(&tys, &mut copies, inst);
+        }
+        for r in block.term.uses_mut() {
+            if let Some(src) = copies.get(r) {
+                *r = *src;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+// the copies that hold at the start of each block, None for one no path
+// from the entry reaches
+fn analyze(func: &Function) -> Vec<Option<Copies>> {
+    let n = func.blocks.len();
+    let preds = func.predecessors();
+    let mut ins: Vec<Option<Copies>> = vec![None; n];
+    let mut outs: Vec<Option<Copies>> = vec![None; n];
+    ins[0] = Some(Copies::new());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in 0..n {
+            // the ones on all the paths in, a block not reached yet says nothing
+            let mut copies = if b == 0 {
+                Some(Copies::new())
+            } else {
+                preds[b].iter().filter_map(|p| outs[p.0].as_ref()).fold(
+                    None,
+                    |acc: Option<Copies>, out| match acc {
+                        None => Some(out.clone()),
+                        Some(mut acc) => {
+                            acc.retain(|dst, src| out.get(dst) == Some(src));
+                            Some(acc)
+                        }
+                    },
+                )
+            };
+            if copies != ins[b] {
+                ins[b] = copies.clone();
+                changed = true;
+            }
+            if let Some(copies) = copies.as_mut() {
+                for inst in func.blocks[b].insts.iter() {
+                    transfer(&func.vregs, copies, inst);
+                }
+            }
+            if copies != outs[b] {
+                outs[b] = copies;
+                changed = true;
+            }
+        }
+    }
+    ins
+}
+
+fn transfer(tys: &[Ty], copies: &mut Copies, inst: &Inst) {
+    if let Some(dst) = inst.def() {
+        copies.retain(|d, s| *d != dst && *s != dst);
+        if let Inst::Copy {
+            src: Operand::Reg(src),
+            ..
+        } = inst
+        {
+            // of the same type, a wider one has bits the narrower one does not
+            if *src != dst && tys[src.0] == tys[dst.0] {
+                copies.insert(dst, *src);
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::ir::{BinOp, Cond, Function, Inst, Operand, Slot, Ty, UnOp, VReg};
+use std::collections::HashMap;
+
+// Common subexpression elimination within a block: an instruction computing
+// what an earlier one already has in a register, with the same operands not
+// written since, becomes a copy of that register.  A load counts as the same
+// only until something may have written memory, a store, a memcpy or a call.
+
+// what an instruction computes, in the type of its result
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Unary(UnOp, Ty, Operand),
+    Binary(BinOp, Ty, Operand, Operand),
+    Cmp(Cond, Ty, Ty, Operand, Operand),
+    Ext(bool, Ty, VReg),
+    Trunc(Ty, VReg),
+    Convert(bool, Ty, VReg),
+    Load(Ty, VReg),
+    SlotAddr(Slot),
+    GlobalAddr(String),
+}
+
+impl Key {
+    fn of(func: &Function, inst: &Inst) -> Option<Key> {
+        let ty = func.ty(inst.def()?);
+        // the operands of the ones that can be swapped in a fixed order
+        let sorted = |lhs: &Operand, rhs: &Operand| {
+            let key = |op: &Operand| match op {
+                Operand::Reg(r) => (0, r.0 as i64),
+                Operand::Imm(v) => (1, *v),
+            };
+            if key(lhs) <= key(rhs) {
+                (*lhs, *rhs)
+            } else {
+                (*rhs, *lhs)
+            }
+        };
+        Some(match inst {
+            Inst::Unary { op, src, .. } => Key::Unary(*op, ty, *src),
+            Inst::Binary { op, lhs, rhs, .. } => match op {
+                BinOp::Add | BinOp::Mul | BinOp::And | BinOp::Or | BinOp::Xor => {
+                    let (lhs, rhs) = sorted(lhs, rhs);
+                    Key::Binary(*op, ty, lhs, rhs)
+                }
+                _ => Key::Binary(*op, ty, *lhs, *rhs),
+            },
+            Inst::Cmp {
+                cond,
+                ty: cmp_ty,
+                lhs,
+                rhs,
+                ..
+            } => match cond {
+                Cond::Eq | Cond::Ne => {
+                    let (lhs, rhs) = sorted(lhs, rhs);
+                    Key::Cmp(*cond, *cmp_ty, ty, lhs, rhs)
+                }
+                _ => Key::Cmp(*cond, *cmp_ty, ty, *lhs, *rhs),
+            },
+            Inst::Ext { signed, src, .. } => Key::Ext(*signed, ty, *src),
+            Inst::Trunc { src, .. } => Key::Trunc(ty, *src),
+            Inst::Convert { signed, src, .. } => Key::Convert(*signed, ty, *src),
+            Inst::Load { addr, .. } => Key::Load(ty, *addr),
+            Inst::SlotAddr { slot, .. } => Key::SlotAddr(*slot),
+            Inst::GlobalAddr { name, .. } => Key::GlobalAddr(name.clone()),
+            _ => return None,
+        })
+    }
+
+    fn reads(&self, r: VReg) -> bool {
+        let op = |op: &Operand| *op == Operand::Reg(r);
+        match self {
+            Key::Unary(_, _, src) => op(src),
+            Key::Binary(_, _, lhs, rhs) | Key::Cmp(_, _, _, lhs, rhs) => op(lhs) || op(rhs),
+            Key::Ext(_, _, src)
+            | Key::Trunc(_, src)
+            | Key::Convert(_, _, src)
+            | Key::Load(_, src) => *src == r,
+            Key::SlotAddr(_) | Key::GlobalAddr(_) => false,
+        }
+    }
+}
+
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    for b in 0..func.blocks.len() {
+        // what is computed so far, and where it is
+        let mut available: HashMap<Key, VReg> = HashMap::new();
+        for i in 0..func.blocks[b].insts.len() {
+            let inst = &func.blocks[b].insts[i];
+            let key = Key::of(func, inst);
+            if let (Some(key), Some(dst)) = (&key, inst.def()) {
+                if let Some(reg) = available.get(key) {
+                    func.blocks[b].insts[i] = Inst::Copy {
+                        dst,
+                        src: Operand::Reg(*reg),
+                    };
+                    changed = true;
+                }
+            }
+            let inst = &func.blocks[b].insts[i];
+            if let Some(dst) = inst.def() {
+                available.retain(|k, reg| *reg != dst && !k.reads(dst));
+            }
+            if inst.has_effect() {
+                available.retain(|k, _| !matches!(k, Key::Load(_, _)));
+            }
+            if let (Some(key), Some(dst)) = (key, inst.def()) {
+                if !key.reads(dst) {
+                    available.insert(key, dst);
+                }
+            }
+        }
+    }
+    changed
+}
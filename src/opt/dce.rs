@@ -0,0 +1,59 @@
+use crate::ir::liveness::liveness;
+use crate::ir::{CallRet, Function, Inst, Operand, Ty};
+
+// Dead code elimination: an instruction whose register nothing reads before
+// it is written again is removed, unless it does something else too, when a
+// call only forgets what it returns, but for a long double, which it has to
+// take off the x87 stack all the same.  Removing one can leave what it read
+// dead in turn, so it goes on until there is nothing left to remove.
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let live = liveness(func);
+        let mut removed = false;
+        for (block, out) in func.blocks.iter_mut().zip(live.live_out) {
+            let mut live = out;
+            live.extend(block.term.uses());
+            let mut insts = Vec::with_capacity(block.insts.len());
+            for mut inst in std::mem::take(&mut block.insts).into_iter().rev() {
+                let dead = match inst.def() {
+                    Some(dst) => !live.contains(&dst),
+                    None => false,
+                };
+                if let Inst::Copy {
+                    dst,
+                    src: Operand::Reg(src),
+                } = inst
+                {
+                    if dst == src {
+                        removed = true;
+                        continue;
+                    }
+                }
+                if dead {
+                    if !inst.has_effect() {
+                        removed = true;
+                        continue;
+                    }
+                    if let Inst::Call { ret, .. } = &mut inst {
+                        if !matches!(ret, CallRet::Value(dst) if func.vregs[dst.0] == Ty::F80) {
+                            *ret = CallRet::Void;
+                            removed = true;
+                        }
+                    }
+                }
+                if let Some(dst) = inst.def() {
+                    live.remove(&dst);
+                }
+                live.extend(inst.uses());
+                insts.push(inst);
+            }
+            insts.reverse();
+            block.insts = insts;
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
@@ -0,0 +1,256 @@
+use crate::extended::Extended;
+use crate::ir::{BinOp, Cond, Function, Module, Ty, UnOp};
+
+mod cfg;
+mod constprop;
+mod copyprop;
+mod cse;
+mod dce;
+mod strength;
+
+// The optimizations `-O` runs over the IR.  Each pass works on one function
+// at a time and says whether it changed it.  They leave the cleaning up to
+// each other: constant propagation turns a branch on a known condition into
+// one on a constant, which simplify-branches makes a jump, which leaves the
+// other side for unreachable-blocks to remove, and a register the others
+// made nothing read any more is for dce to delete.
+//
+// None of them needs SSA.  A virtual register may be written in several
+// places, a local variable kept in one is, so what one pass knows about a
+// register holds only until the next write of it, and where the paths into
+// a block meet, only what holds on all of them.
+
+type Pass = (&'static str, fn(&mut Function) -> bool);
+
+const CONST_PROP: Pass = ("const-prop", constprop::run);
+const COPY_PROP: Pass = ("copy-prop", copyprop::run);
+const CSE: Pass = ("cse", cse::run);
+const STRENGTH_REDUCE: Pass = ("strength-reduce", strength::run);
+const SIMPLIFY_BRANCHES: Pass = ("simplify-branches", cfg::simplify_branches);
+const UNREACHABLE_BLOCKS: Pass = ("unreachable-blocks", cfg::remove_unreachable);
+const DCE: Pass = ("dce", dce::run);
+
+// the names of the passes, which `--crust-print-after` takes
+pub const PASSES: [&str; 7] = [
+    CONST_PROP.0,
+    COPY_PROP.0,
+    CSE.0,
+    STRENGTH_REDUCE.0,
+    SIMPLIFY_BRANCHES.0,
+    UNREACHABLE_BLOCKS.0,
+    DCE.0,
+];
+
+// -O1: one round of the cheap ones
+const O1: [Pass; 5] = [
+    CONST_PROP,
+    COPY_PROP,
+    SIMPLIFY_BRANCHES,
+    UNREACHABLE_BLOCKS,
+    DCE,
+];
+
+// -O2 and up: all of them, again as long as a round finds something to do,
+// what one pass does often opens something up for one before it
+const O2: [Pass; 7] = [
+    CONST_PROP,
+    COPY_PROP,
+    CSE,
+    STRENGTH_REDUCE,
+    SIMPLIFY_BRANCHES,
+    UNREACHABLE_BLOCKS,
+    DCE,
+];
+const O2_ROUNDS: usize = 4;
+
+// the module after the passes of the `-O` level; `after` is shown it after
+// each pass that runs, with the name of the pass
+pub fn optimize(module: &mut Module, level: u8, after: &mut dyn FnMut(&str, &Module)) {
+    let (passes, rounds): (&[Pass], usize) = match level {
+        0 => (&[], 0),
+        1 => (&O1, 1),
+        _ => (&O2, O2_ROUNDS),
+    };
+    for _ in 0..rounds {
+        let mut changed = false;
+        for (name, run) in passes.iter() {
+            for func in module.functions.iter_mut() {
+                changed |= run(func);
+            }
+            after(name, module);
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+// `v` as a value of `ty`: its low bytes, sign extended, or the bits of a
+// floating one
+fn wrap(v: i64, ty: Ty) -> i64 {
+    match ty {
+        Ty::I8 => i64::from(v as i8),
+        Ty::I16 => i64::from(v as i16),
+        Ty::I32 => i64::from(v as i32),
+        Ty::F32 => i64::from(v as u32),
+        Ty::I64 | Ty::F64 | Ty::F80 => v,
+    }
+}
+
+// `v` as an unsigned value of `ty`: its low bytes, zero extended
+fn unsigned(v: i64, ty: Ty) -> u64 {
+    match ty {
+        Ty::I8 => u64::from(v as u8),
+        Ty::I16 => u64::from(v as u16),
+        Ty::I32 | Ty::F32 => u64::from(v as u32),
+        Ty::I64 | Ty::F64 | Ty::F80 => v as u64,
+    }
+}
+
+// the bits an i8 or i16 is computed in, as gen does it
+fn max32(ty: Ty) -> Ty {
+    match ty {
+        Ty::I8 | Ty::I16 => Ty::I32,
+        t => t,
+    }
+}
+
+fn fold_unary(op: UnOp, ty: Ty, v: i64) -> i64 {
+    match op {
+        UnOp::Neg if ty.is_float() => ty.float_bits(-ty.float_value(v)),
+        UnOp::Neg => wrap(v.wrapping_neg(), ty),
+        UnOp::Not => wrap(!v, ty),
+    }
+}
+
+// the value of `lhs op rhs` in `ty`, None for a division the program would
+// trap on, which is left for it to do
+fn fold_binary(op: BinOp, ty: Ty, lhs: i64, rhs: i64) -> Option<i64> {
+    if ty.is_float() {
+        return fold_float(op, ty, lhs, rhs);
+    }
+    let wide = max32(ty);
+    let bits = wide.size() as u32 * 8;
+    let (l, r) = (wrap(lhs, wide), wrap(rhs, wide));
+    let (ul, ur) = (unsigned(lhs, wide), unsigned(rhs, wide));
+    let v = match op {
+        BinOp::Add => l.wrapping_add(r),
+        BinOp::Sub => l.wrapping_sub(r),
+        BinOp::Mul => l.wrapping_mul(r),
+        BinOp::SDiv | BinOp::SRem if r == 0 || (r == -1 && l == wrap(i64::MIN, wide)) => {
+            return None
+        }
+        BinOp::SDiv => l / r,
+        BinOp::SRem => l % r,
+        BinOp::UDiv | BinOp::URem if ur == 0 => return None,
+        BinOp::UDiv => (ul / ur) as i64,
+        BinOp::URem => (ul % ur) as i64,
+        BinOp::FDiv => return None,
+        BinOp::And => l & r,
+        BinOp::Or => l | r,
+        BinOp::Xor => l ^ r,
+        // the count is taken modulo the width, as the machine does
+        BinOp::Shl => l.wrapping_shl(r as u32 % bits),
+        BinOp::Shr => (ul >> (r as u32 % bits)) as i64,
+        BinOp::Sar => l >> (r as u32 % bits),
+    };
+    Some(wrap(v, ty))
+}
+
+// the same for floating values, computed in the precision of `ty` as the
+// machine does; an f80 is only folded when a double holds the result
+fn fold_float(op: BinOp, ty: Ty, lhs: i64, rhs: i64) -> Option<i64> {
+    if ty == Ty::F80 {
+        let (l, r) = (
+            Extended::from_f64(ty.float_value(lhs)),
+            Extended::from_f64(ty.float_value(rhs)),
+        );
+        return f80_bits(match op {
+            BinOp::Add => l + r,
+            BinOp::Sub => l - r,
+            BinOp::Mul => l * r,
+            BinOp::FDiv => l / r,
+            _ => return None,
+        });
+    }
+    let (l, r) = (ty.float_value(lhs), ty.float_value(rhs));
+    let v = match op {
+        BinOp::Add => l + r,
+        BinOp::Sub => l - r,
+        BinOp::Mul => l * r,
+        BinOp::FDiv => l / r,
+        _ => return None,
+    };
+    Some(ty.float_bits(v))
+}
+
+// the f80 constant `x`, if a double holds it
+fn f80_bits(x: Extended) -> Option<i64> {
+    let d = x.to_f64();
+    if Extended::from_f64(d) == x {
+        Some(d.to_bits() as i64)
+    } else {
+        None
+    }
+}
+
+fn fold_cmp(cond: Cond, ty: Ty, lhs: i64, rhs: i64) -> i64 {
+    if ty.is_float() {
+        let (l, r) = (ty.float_value(lhs), ty.float_value(rhs));
+        let v = match cond {
+            Cond::Eq => l == r,
+            Cond::Ne => l != r,
+            Cond::Lt => l < r,
+            Cond::Le => l <= r,
+            Cond::Gt => l > r,
+            _ => l >= r,
+        };
+        return i64::from(v);
+    }
+    let (l, r) = (wrap(lhs, ty), wrap(rhs, ty));
+    let (ul, ur) = (unsigned(lhs, ty), unsigned(rhs, ty));
+    let v = match cond {
+        Cond::Eq => l == r,
+        Cond::Ne => l != r,
+        Cond::Lt => l < r,
+        Cond::Le => l <= r,
+        Cond::Gt => l > r,
+        Cond::Ge => l >= r,
+        Cond::ULt => ul < ur,
+        Cond::ULe => ul <= ur,
+        Cond::UGt => ul > ur,
+        Cond::UGe => ul >= ur,
+    };
+    i64::from(v)
+}
+
+// `v` of type `from` extended to `to`
+fn fold_ext(signed: bool, from: Ty, to: Ty, v: i64) -> i64 {
+    if signed {
+        wrap(wrap(v, from), to)
+    } else {
+        wrap(unsigned(v, from) as i64, to)
+    }
+}
+
+// `v` of type `from` converted to `to`, see Inst::Convert; C leaves what a
+// floating value out of the range of the integer type gives undefined; none
+// for an f80 a double can not hold
+fn fold_convert(signed: bool, from: Ty, to: Ty, v: i64) -> Option<i64> {
+    Some(match (from.is_float(), to.is_float()) {
+        (true, true) => to.float_bits(from.float_value(v)),
+        (true, false) if signed => wrap(from.float_value(v) as i64, to),
+        (true, false) => wrap(from.float_value(v) as u64 as i64, to),
+        (false, _) if to == Ty::F80 && signed => {
+            return f80_bits(Extended::from_i64(wrap(v, from)))
+        }
+        (false, _) if to == Ty::F80 => return f80_bits(Extended::from_u64(unsigned(v, from))),
+        // rounded once, straight to the type
+        (false, _) => match (to, signed) {
+            (Ty::F32, true) => i64::from((wrap(v, from) as f32).to_bits()),
+            (Ty::F32, false) => i64::from((unsigned(v, from) as f32).to_bits()),
+            (_, true) => to.float_bits(wrap(v, from) as f64),
+            (_, false) => to.float_bits(unsigned(v, from) as f64),
+        },
+    })
+}
@@ -0,0 +1,102 @@
+use super::{unsigned, wrap};
+use crate::ir::{BinOp, Function, Inst, Operand, Ty};
+
+// Strength reduction: a multiplication or division by a power of two becomes
+// a shift, an unsigned remainder by one a mask.  A signed division rounds
+// towards 0, where the shift rounds down, so a negative dividend first gets
+// the divisor less one added, its sign bits shifted down to just that many
+// ones.
+//
+// gen computes an i8 or an i16 in 32 bits whose upper ones are not kept
+// right, which a shift right would bring down, so the divisions are left
+// alone in those.  Floating values are left alone altogether.
+
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    for b in 0..func.blocks.len() {
+        let insts = std::mem::take(&mut func.blocks[b].insts);
+        let mut out = Vec::with_capacity(insts.len());
+        for inst in insts {
+            match reduce(func, &inst) {
+                Some(new) => {
+                    out.extend(new);
+                    changed = true;
+                }
+                None => out.push(inst),
+            }
+        }
+        func.blocks[b].insts = out;
+    }
+    changed
+}
+
+// what takes the place of the instruction, if it can be done cheaper
+fn reduce(func: &mut Function, inst: &Inst) -> Option<Vec<Inst>> {
+    let (op, dst, lhs, rhs) = match inst {
+        Inst::Binary { op, dst, lhs, rhs } => (*op, *dst, *lhs, *rhs),
+        _ => return None,
+    };
+    let ty = func.ty(dst);
+    let binary = |op, dst, lhs, rhs| Inst::Binary { op, dst, lhs, rhs };
+    let copy = |src| Inst::Copy { dst, src };
+    match (op, lhs, rhs) {
+        _ if ty.is_float() => None,
+        (BinOp::Mul, x, Operand::Imm(c)) | (BinOp::Mul, Operand::Imm(c), x) => match wrap(c, ty) {
+            0 => Some(vec![copy(Operand::Imm(0))]),
+            1 => Some(vec![copy(x)]),
+            c if c > 0 && (c as u64).is_power_of_two() => Some(vec![binary(
+                BinOp::Shl,
+                dst,
+                x,
+                Operand::Imm(i64::from(c.trailing_zeros())),
+            )]),
+            _ => None,
+        },
+        (_, _, _) if ty != Ty::I32 && ty != Ty::I64 => None,
+        (BinOp::UDiv, x, Operand::Imm(c)) | (BinOp::URem, x, Operand::Imm(c)) => {
+            let c = unsigned(c, ty);
+            if !c.is_power_of_two() {
+                return None;
+            }
+            let k = i64::from(c.trailing_zeros());
+            Some(vec![match op {
+                BinOp::UDiv => binary(BinOp::Shr, dst, x, Operand::Imm(k)),
+                _ => binary(BinOp::And, dst, x, Operand::Imm(wrap(c as i64 - 1, ty))),
+            }])
+        }
+        (BinOp::SDiv, x, Operand::Imm(c)) | (BinOp::SRem, x, Operand::Imm(c)) => {
+            let c = wrap(c, ty);
+            if c == 1 {
+                let v = if op == BinOp::SDiv {
+                    x
+                } else {
+                    Operand::Imm(0)
+                };
+                return Some(vec![copy(v)]);
+            }
+            if c <= 0 || !(c as u64).is_power_of_two() {
+                return None;
+            }
+            let k = i64::from(c.trailing_zeros());
+            let bits = ty.size() * 8;
+            let (t1, t2, t3) = (func.new_reg(ty), func.new_reg(ty), func.new_reg(ty));
+            let mut insts = vec![
+                // c - 1 for a negative x, 0 otherwise
+                binary(BinOp::Sar, t1, x, Operand::Imm(bits - 1)),
+                binary(BinOp::Shr, t2, Operand::Reg(t1), Operand::Imm(bits - k)),
+                binary(BinOp::Add, t3, x, Operand::Reg(t2)),
+            ];
+            if op == BinOp::SDiv {
+                insts.push(binary(BinOp::Sar, dst, Operand::Reg(t3), Operand::Imm(k)));
+            } else {
+                // x less its quotient times c
+                let t4 = func.new_reg(ty);
+                let mask = Operand::Imm(wrap(-c, ty));
+                insts.push(binary(BinOp::And, t4, Operand::Reg(t3), mask));
+                insts.push(binary(BinOp::Sub, dst, x, Operand::Reg(t4)));
+            }
+            Some(insts)
+        }
+        _ => None,
+    }
+}
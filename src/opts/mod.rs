@@ -1,4 +1,5 @@
-use std::path;
+use crust::diagnostic::{code, Diagnostic};
+use std::{env, ffi, path};
 
 mod arg_groups;
 
@@ -9,14 +10,43 @@ pub struct Opts {
     /// The input files(s)
     #[structopt(parse(from_os_str), required = true)]
     input: Vec<path::PathBuf>,
-    /// The output file
+    /// Add the directory to the list of directories searched for header files
+    #[structopt(short = "-I", parse(from_os_str), number_of_values = 1)]
+    include_dirs: Vec<path::PathBuf>,
+    /// Predefine a macro, as `NAME` or `NAME=VALUE`
+    #[structopt(short = "-D", number_of_values = 1)]
+    defines: Vec<String>,
+    /// Add the directory to the list of directories searched for libraries
+    #[structopt(short = "-L", parse(from_os_str), number_of_values = 1)]
+    lib_dirs: Vec<path::PathBuf>,
+    /// Link with the library, as `-lNAME`
+    #[structopt(short = "-l", number_of_values = 1)]
+    libs: Vec<String>,
+    /// Warning options, and `-Wl,OPTIONS` to pass comma-separated options to the linker
+    #[structopt(short = "-W", number_of_values = 1)]
+    w_flags: Vec<String>,
+    /// The output file, `a.out` for an executable and the input file name
+    /// with the suffix of the stage otherwise
     #[structopt(short = "-o", parse(from_os_str))]
-    output: path::PathBuf,
+    output: Option<path::PathBuf>,
+    /// Optimize, `-O1` (or `-O`, `-Og`) for the cheap passes, `-O2` (or `-Os`,
+    /// `-Ofast`) for all of them
+    #[structopt(short = "-O", default_value = "0")]
+    opt_level: u8,
+    /// Link statically, given as `-static`
+    #[structopt(long = "--static")]
+    link_static: bool,
+    /// Make a shared object, given as `-shared`
+    #[structopt(long = "--shared")]
+    link_shared: bool,
+    /// Link with the threads library, given as `-pthread`
+    #[structopt(long = "--pthread")]
+    link_pthread: bool,
     #[structopt(flatten)]
     stop_stage: StopStage,
 }
 
-#[derive(structopt::StructOpt, Clone, Copy, Debug)]
+#[derive(structopt::StructOpt, Clone, Debug)]
 pub struct CrustDebugFlags {
     /// Print file contents
     #[structopt(long = "--crust-print-file-contents")]
@@ -27,6 +57,20 @@ pub struct CrustDebugFlags {
     /// Print the source file ast.
     #[structopt(long = "--crust-print-source-ast")]
     print_source_ast: bool,
+    /// Print the intermediate representation of each translation unit.
+    #[cfg(feature = "debug")]
+    #[structopt(long = "--crust-print-ir")]
+    print_ir: bool,
+    /// Print the intermediate representation after each run of the optimization pass.
+    #[cfg(feature = "debug")]
+    #[structopt(long = "--crust-print-after")]
+    print_after: Option<String>,
+    /// Run the system assembler instead of the built-in one.
+    #[structopt(long = "--crust-system-as")]
+    system_as: bool,
+    /// Link with the system linker through `cc` instead of the built-in one.
+    #[structopt(long = "--crust-system-ld")]
+    system_ld: bool,
 }
 
 #[derive(structopt::StructOpt, Clone, Copy, Debug)]
@@ -43,17 +87,106 @@ pub struct StopStage {
     preprocess: bool,
 }
 
+// the command line as structopt takes it, so crust can stand in for `cc` in
+// a Makefile: a bare `-O` is `-O1`, as a short option with a value can not do
+// without it, and the levels gcc names by what they are for are the nearest
+// of crust; the link options gcc spells with one dash get two; and the
+// options that only tell gcc how to compile are dropped, crust makes no debug
+// info nor dependency files, has one dialect and one code model and no
+// warnings to turn off; `-M` and `-MM`, which ask for the dependencies
+// instead of the output, are refused
+pub fn args() -> Result<Vec<ffi::OsString>, Diagnostic> {
+    let mut args = Vec::new();
+    let mut it = env::args_os();
+    while let Some(arg) = it.next() {
+        match arg.to_str() {
+            Some("-O") | Some("-Og") => args.push("-O1".into()),
+            Some("-Os") | Some("-Oz") | Some("-Ofast") => args.push("-O2".into()),
+            Some(flag @ "-M") | Some(flag @ "-MM") => {
+                return Err(Diagnostic::driver(
+                    code::USAGE,
+                    &format!(
+                        "`{}` asks for the dependencies of the sources, crust does not make them",
+                        flag
+                    ),
+                ))
+            }
+            Some(flag @ "-static") | Some(flag @ "-shared") | Some(flag @ "-pthread") => {
+                args.push(format!("-{}", flag).into())
+            }
+            // the file or target comes in the next argument
+            Some("-MF") | Some("-MT") | Some("-MQ") => {
+                it.next();
+            }
+            Some(flag) if is_compile_only(flag) => {}
+            _ => args.push(arg),
+        }
+    }
+    Ok(args)
+}
+
+// `-g`, `-std=c99`, `-fPIC`, `-MMD`, `-w`, `-pedantic` and the like
+fn is_compile_only(flag: &str) -> bool {
+    flag == "-w"
+        || ["-g", "-std=", "-f", "-M", "-pedantic"]
+            .iter()
+            .any(|prefix| flag.starts_with(prefix))
+}
+
 impl Opts {
-    pub fn crust_debug_flags(&self) -> CrustDebugFlags {
-        self.crust_debug_flags
+    pub fn crust_debug_flags(&self) -> &CrustDebugFlags {
+        &self.crust_debug_flags
     }
 
     pub fn input(&self) -> &[path::PathBuf] {
         &self.input
     }
 
-    pub fn output(&self) -> &path::PathBuf {
-        &self.output
+    pub fn include_dirs(&self) -> &[path::PathBuf] {
+        &self.include_dirs
+    }
+
+    pub fn defines(&self) -> &[String] {
+        &self.defines
+    }
+
+    pub fn lib_dirs(&self) -> &[path::PathBuf] {
+        &self.lib_dirs
+    }
+
+    pub fn libs(&self) -> &[String] {
+        &self.libs
+    }
+
+    // the `-Wl,` options, as the linker takes them; crust has no warnings to turn on or off yet
+    pub fn linker_flags(&self) -> Vec<&str> {
+        self.w_flags
+            .iter()
+            .filter_map(|w| w.strip_prefix("l,"))
+            .flat_map(|w| w.split(','))
+            .collect()
+    }
+
+    // `-static`, `-shared` and `-pthread`, which only the system linker knows
+    pub fn link_flags(&self) -> Vec<&str> {
+        let flags = [
+            (self.link_static, "-static"),
+            (self.link_shared, "-shared"),
+            (self.link_pthread, "-pthread"),
+        ];
+        flags
+            .iter()
+            .filter(|(given, _)| *given)
+            .map(|(_, flag)| *flag)
+            .collect()
+    }
+
+    pub fn output(&self) -> Option<&path::PathBuf> {
+        self.output.as_ref()
+    }
+
+    pub fn opt_level(&self) -> u8 {
+        self.opt_level
     }
 
     pub fn stop_stage(&self) -> StopStage {
@@ -73,6 +206,35 @@ impl CrustDebugFlags {
     pub fn print_source_ast(&self) -> bool {
         self.print_source_ast
     }
+
+    // only a build with the `debug` feature has the flag
+    #[cfg(feature = "debug")]
+    pub fn print_ir(&self) -> bool {
+        self.print_ir
+    }
+
+    #[cfg(not(feature = "debug"))]
+    pub fn print_ir(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "debug")]
+    pub fn print_after(&self) -> Option<&str> {
+        self.print_after.as_deref()
+    }
+
+    #[cfg(not(feature = "debug"))]
+    pub fn print_after(&self) -> Option<&str> {
+        None
+    }
+
+    pub fn system_as(&self) -> bool {
+        self.system_as
+    }
+
+    pub fn system_ld(&self) -> bool {
+        self.system_ld
+    }
 }
 
 impl StopStage {
@@ -87,4 +249,9 @@ impl StopStage {
     pub fn preprocess(&self) -> bool {
         self.preprocess
     }
+
+    // no stage to stop after was given, so the driver goes on to link
+    pub fn link(&self) -> bool {
+        !(self.assemble || self.compile || self.preprocess)
+    }
 }
@@ -1,4 +1,11 @@
+use crate::diagnostic::{code, Diagnostic, Severity};
 use crate::lexer;
+use crate::loc::Loc;
+use crate::sema;
+use crate::types::{self, Aggregate, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
 // TODO:
 // Trying to implement a better parser to support c11 full standard
@@ -536,9 +543,12 @@ use crate::lexer;
 #[derive(Eq, PartialEq, Clone, Debug)]
 pub enum NodeType {
     Prog(String),
-    // TODO: now only support int parameters
-    // <function> ::= "int" <id> "(" [ "int" <id> { "," "int" <id> } ] ")" "{" {<block-item>} "}"
-    Fn(String, Option<Vec<String>>),
+    // <function> ::= <type> <id> "(" [ <type> <id> { "," <type> <id> } ] ")" "{" {<block-item>} "}"
+    // name, return type, parameters: None for `()`, which says nothing about
+    // them, whether `...` follows them
+    Fn(String, Type, Option<Vec<(String, Type)>>, bool, Storage),
+    // <prototype> ::= <type> <id> "(" [ <type> [<id>] { "," <type> [<id>] } [ "," "..." ] ] ")" ";"
+    FnDecl(String, Type, Option<Vec<(String, Type)>>, bool, Storage), // like Fn
     Stmt(StmtType),
     // <statement> ::= "return" <exp> ";"
     //               | <exp-option> ";"
@@ -550,32 +560,86 @@ pub enum NodeType {
     //               | "do" <statement> "while" <exp> ";"
     //               | "break" ";"
     //               | "continue" ";"
+    #[allow(dead_code)]
     Block, // <block> ::= <statement> | <declaration>
-    Const(i64),
-    StringLiteral(String, String), // data, tag
+    Const(i64, Type),
+    FloatConst(lexer::FloatConst, Type),
+    StringLiteral(Vec<u8>, String), // data, tag
     Var(String),
-    ArrayRef(String),          // referencing to array
+    ArrayRef(String),             // referencing to array
     AssignNode(String, bool), // String -> variable name, bool -> true if this is a assign to array element
+    Assign,                   // any other assignment, like *p = 1, kids: lvalue, value
     UnExp(lexer::TokType),    // Unary Expression
     BinExp(lexer::TokType),   // Binary Operator
-    Exp,                      // <exp> ::= <id> ["[" <exp> "]"] "=" <exp> | <conditional-exp>
-    ExpOption,                // <exp-option> :: <exp> | ""
+    AssignOp(lexer::TokType), // `a op= b`, the token is the binary operator, kids: lvalue, value
+    IncDec(lexer::TokType, bool), // `++` or `--`, true if it follows the kid, an lvalue
+    Exp, // <exp> ::= <id> ["[" <exp> "]"] "=" <exp> | <unary-exp> ("=" | "+=" | ...) <exp> | <conditional-exp>
+    ExpOption, // <exp-option> :: <exp> | ""
     ConditionalExp, // <conditional-exp> ::= <logical-or-exp> [ "?" <exp> ":" <conditional-exp> ]
-    LogicalOrExp,   // <logical-or-exp> ::= <logical-and-exp> { "||" <logical-and-exp> }
-    LogicalAndExp,  // <logical-and-exp> ::= <equality-exp> { "&&" <equality-exp> }
-    EqualityExp,    // <EqualityExp> ::= <relational-exp> { ("!="|"==") <relational-exp> }
-    RelationalExp, // <relational-exp> ::= <additive-exp> { ("<" | ">" | "<=" | ">=") <additive-exp> }
-    AdditiveExp,   // <additive-exp> ::= <term> { ("+" | "-") <term> }
-    Term,          // <term> ::= <factor> { ("*" | "/") <factor> }
-    Factor, // <factor> ::= <function-call> | "(" <exp> ")" | <unary_op> <factor> | <int> | string | <id> "[" <exp> "]" | <id>
+    LogicalOrExp, // <logical-or-exp> ::= <logical-and-exp> { "||" <logical-and-exp> }
+    LogicalAndExp, // <logical-and-exp> ::= <bit-or-exp> { "&&" <bit-or-exp> }
+    EqualityExp, // <EqualityExp> ::= <relational-exp> { ("!="|"==") <relational-exp> }
+    RelationalExp, // <relational-exp> ::= <shift-exp> { ("<" | ">" | "<=" | ">=") <shift-exp> }
+    AdditiveExp, // <additive-exp> ::= <term> { ("+" | "-") <term> }
+    Term, // <term> ::= <factor> { ("*" | "/" | "%") <factor> }
+    Factor, // <factor> ::= <function-call> | "(" <exp> ")" | <unary_op> <factor> | "(" <type-name> ")" <factor> | <int> | <float> | string | <id> "[" <exp> "]" | <id>
     FnCall(String), // <function-call> ::= id "(" [ <exp> { "," <exp> } ] ")"
-    Declare(String, DataType), // <declaration> ::= "int" <id> "[" <int> "]" ";" | "int" <id> [ = <exp> ] ";"
+    // <factor> "(" [ <exp> { "," <exp> } ] ")", a call through a pointer to a
+    // function; kids: the pointer, then the arguments
+    CallPtr,
+    Declare(String, Type, Storage), // <declaration> ::= <type> <id> <array> [ = <initializer> ] ";"
+    // <initializer> ::= <exp> | "{" [ <designation> ] <initializer> { "," [ <designation> ] <initializer> } [ "," ] "}"
+    // sema makes the kids of one InitAt nodes, for the scalars in order
+    InitList,
+    Designated(Vec<Designator>), // <designation> ::= { "[" <int> "]" | "." <id> } "=", kid: the initializer
+    InitAt(i64),                 // the kid initializes what is at this offset in the object
+    TagDecl(Type), // a declaration of nothing but a struct or union, like `struct s { int a; };`
+    Member(String), // <exp> "." <id>, kid: the struct or union
+    Cast(Type),    // the kid converted to the type, sema adds them where C converts implicitly
+    // "sizeof" <factor> | "sizeof" "(" <type-name> ")", kid: the expression
+    // if there is no type; sema makes it a Const
+    Sizeof(Option<Type>),
+    Alignof(Option<Type>), // "_Alignof" "(" <type-name> ")", or like gcc "_Alignof" <factor>
+    CompoundLiteral(Type), // "(" <type-name> ")" <initializer>, kid: the initializer in braces
+    // "__builtin_va_arg" "(" <exp> "," <type-name> ")", what `va_arg` of
+    // <stdarg.h> is; kid: the va_list
+    VaArg(Type),
 }
 
+// what a designation in an initializer list names, in the object the list
+// initializes
 #[derive(Eq, PartialEq, Clone, Debug)]
-pub enum DataType {
-    I64,        // now int in c was translated in 64 bits int
-    Arr64(i64), // int array[len]
+pub enum Designator {
+    Index(i64),
+    Member(String),
+}
+
+impl fmt::Display for Designator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Designator::Index(i) => write!(f, "[{}]", i),
+            Designator::Member(name) => write!(f, ".{}", name),
+        }
+    }
+}
+
+// the storage class of a declaration at file scope, crust has none for
+// the ones in a block yet
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Storage {
+    None,   // external linkage, a definition
+    Static, // internal linkage, not seen by the other translation units
+    Extern, // external linkage, defined in some translation unit
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Storage::None => write!(f, ""),
+            Storage::Static => write!(f, "static "),
+            Storage::Extern => write!(f, "extern "),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -584,10 +648,15 @@ pub enum StmtType {
     Exp,
     Conditional(String),
     Compound,
-    For,     // kids: exp-opion, exp-option, exp-option
-    ForDecl, // kids: declaration, exp, exp-option, statement
-    While,   // kids: exp, stmt
-    Do,      // kids: stmt, exp
+    For,                 // kids: exp-opion, exp-option, exp-option
+    ForDecl,             // kids: declaration, exp, exp-option, statement
+    While,               // kids: exp, stmt
+    Do,                  // kids: stmt, exp
+    Switch,              // kids: exp, stmt
+    Case,                // kids: exp, stmt
+    Default,             // kids: stmt
+    Label(String),       // kids: stmt
+    Goto(String, usize), // the label and where it starts, known once the function is parsed
     Break,
     Continue,
 }
@@ -596,492 +665,1372 @@ pub enum StmtType {
 pub struct ParseNode {
     pub child: Vec<ParseNode>,
     pub entry: NodeType,
+    pub loc: Loc, // where the node starts, the span reaches to the end of its last child
+    pub ty: Option<Type>, // the type of an expression, filled in by sema
 }
 
 impl ParseNode {
-    pub fn new() -> ParseNode {
+    pub fn new(loc: Loc) -> ParseNode {
         ParseNode {
             child: Vec::new(),
             entry: NodeType::Prog("root".to_string()),
+            loc,
+            ty: None,
         }
     }
 }
 
-fn p_logical_or_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    let mut log_or_exp_node = ParseNode::new();
+// The struct and union tags in scope, the innermost scope last.  Tags have
+// a name space of their own: `struct s` and a variable `s` do not clash.
+// The types of the variables are kept along, `sizeof` in the length of an
+// array needs them before sema has run.
+struct Tags {
+    scopes: Vec<HashMap<String, Rc<Aggregate>>>,
+    vars: Vec<HashMap<String, Type>>,
+    va_list: Rc<Aggregate>, // what `__builtin_va_list` is an array of
+}
+
+impl Tags {
+    fn new() -> Tags {
+        Tags {
+            scopes: vec![HashMap::new()],
+            vars: vec![HashMap::new()],
+            va_list: Aggregate::va_list_tag(),
+        }
+    }
+
+    fn enter(&mut self) {
+        self.scopes.push(HashMap::new());
+        self.vars.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.scopes.pop();
+        self.vars.pop();
+    }
+
+    fn var(&self, name: &str) -> Option<Type> {
+        self.vars.iter().rev().find_map(|s| s.get(name).cloned())
+    }
+
+    fn declare_var(&mut self, name: &str, t: &Type) {
+        if let Some(scope) = self.vars.last_mut() {
+            scope.insert(name.to_string(), t.clone());
+        }
+    }
+
+    fn lookup(&self, tag: &str) -> Option<Rc<Aggregate>> {
+        self.scopes.iter().rev().find_map(|s| s.get(tag).cloned())
+    }
+
+    fn lookup_current(&self, tag: &str) -> Option<Rc<Aggregate>> {
+        self.scopes.last()?.get(tag).cloned()
+    }
+
+    fn declare(&mut self, tag: &str, aggregate: Rc<Aggregate>) -> Rc<Aggregate> {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(tag.to_string(), aggregate.clone());
+        }
+        aggregate
+    }
+}
+
+// location of the token at `pos`, or of the last token if we ran out of them
+fn loc_at(toks: &[lexer::Token], pos: usize) -> &Loc {
+    &toks[pos.min(toks.len() - 1)].loc
+}
+
+// the token at `pos`, or the end of input for a lookahead past it
+fn tok_at(toks: &[lexer::Token], pos: usize) -> &lexer::TokType {
+    &toks[pos.min(toks.len() - 1)].tok
+}
+
+// stretch the span of every node over the spans of its children
+fn fix_spans(tree: &mut ParseNode) {
+    for it in tree.child.iter_mut() {
+        fix_spans(it);
+        tree.loc = tree.loc.to(&it.loc);
+    }
+}
+
+fn p_logical_or_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let mut log_or_exp_node = ParseNode::new(loc.clone());
     log_or_exp_node.entry = NodeType::LogicalOrExp;
     // Parse <logical-and-exp> first
 
     // <LogicalOrExp> -> <LogicalAndExp>
     let mut pos = pos;
-    let (log_and_exp_node, tmp_pos) = r#try!(p_logical_and_exp(toks, pos));
+    let (log_and_exp_node, tmp_pos) = p_logical_and_exp(toks, pos, tags)?;
     pos = tmp_pos;
     // peek next node
-    let mut tok = &toks[pos];
-    pos = pos + 1;
+    let mut tok = &toks[pos].tok;
+    pos += 1;
     if *tok != lexer::TokType::Or {
         // only one child_node
         log_or_exp_node.child.push(log_and_exp_node);
-        pos = pos - 1;
+        pos -= 1;
         return Ok((log_or_exp_node, pos));
     }
 
     // log_or_exp -> BinExp -> (left: logAndExp, right logAndExp)
     let mut lhs = log_and_exp_node;
     while *tok == lexer::TokType::Or {
-        let mut binexp_node = ParseNode::new();
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(lexer::TokType::Or);
 
-        let (rhs, tmp_pos) = r#try!(p_logical_and_exp(toks, pos));
+        let (rhs, tmp_pos) = p_logical_and_exp(toks, pos, tags)?;
 
         binexp_node.child.push(lhs);
         binexp_node.child.push(rhs);
         lhs = binexp_node;
         pos = tmp_pos;
-        tok = &toks[pos];
-        pos = pos + 1;
+        tok = &toks[pos].tok;
+        pos += 1;
     }
     log_or_exp_node.child.push(lhs);
-    pos = pos - 1;
-    return Ok((log_or_exp_node, pos));
+    pos -= 1;
+    Ok((log_or_exp_node, pos))
 }
 
-fn p_conditional_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_conditional_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // <conditional-exp> ::= <logical-or-exp> [ "?" <exp> ":" <conditional-exp> ]
-    let mut conditional_exp_node = ParseNode::new();
+    let mut conditional_exp_node = ParseNode::new(loc.clone());
     conditional_exp_node.entry = NodeType::ConditionalExp;
     // parse <logical-or-exp> first
-    let (logical_or_exp_node, pos) = r#try!(p_logical_or_exp(toks, pos));
+    let (logical_or_exp_node, pos) = p_logical_or_exp(toks, pos, tags)?;
     conditional_exp_node.child.push(logical_or_exp_node);
 
     // it's optional if you got a "?"
-    if toks[pos] == lexer::TokType::QuestionMark {
+    if toks[pos].tok == lexer::TokType::QuestionMark {
         // parse <exp>
         let pos = pos + 1;
-        let (exp_node, pos) = r#try!(p_exp(toks, pos));
-
-        if toks[pos] != lexer::TokType::Colon {
-            return Err(format!(
-                "Expected `:` in conditional expression, but got {:?} at {}",
-                toks[pos], pos
+        let (exp_node, pos) = p_comma_exp(toks, pos, tags)?;
+
+        if toks[pos].tok != lexer::TokType::Colon {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!(
+                    "Expected `:` in conditional expression, but got `{}`",
+                    toks[pos].tok
+                ),
             ));
         }
         let pos = pos + 1;
         // parse next <conditonal-exp>
-        let (next_conditional_exp_node, pos) = r#try!(p_conditional_exp(toks, pos));
+        let (next_conditional_exp_node, pos) = p_conditional_exp(toks, pos, tags)?;
         conditional_exp_node.child.push(exp_node);
         conditional_exp_node.child.push(next_conditional_exp_node);
-        return Ok((conditional_exp_node, pos));
+        Ok((conditional_exp_node, pos))
     } else {
-        return Ok((conditional_exp_node, pos));
+        Ok((conditional_exp_node, pos))
     }
 }
 
-fn p_exp_opt(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_exp_opt(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // <exp-option> ::= <exp> | ""
-    let mut exp_opt_node = ParseNode::new();
+    let mut exp_opt_node = ParseNode::new(loc.clone());
     exp_opt_node.entry = NodeType::ExpOption;
-    let res = p_exp(toks, pos);
-    match res {
-        Ok((exp_node, pos)) => {
-            // <exp>
-            exp_opt_node.child.push(exp_node);
-            return Ok((exp_opt_node, pos));
-        }
-        Err(_) => {
+    match toks[pos].tok {
+        lexer::TokType::Semicolon | lexer::TokType::RParen => {
             // ""
             // no child, means null statement
-            return Ok((exp_opt_node, pos));
+            Ok((exp_opt_node, pos))
+        }
+        _ => {
+            // <exp>
+            let (exp_node, pos) = p_comma_exp(toks, pos, tags)?;
+            exp_opt_node.child.push(exp_node);
+            Ok((exp_opt_node, pos))
         }
     }
 }
 
-fn p_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // println!("in fn: p_exp, with pos:{}", pos);
-    // <exp> ::= <id> [ "[" <exp> "]" ] "=" <exp> | <conditional-exp>
-    let mut exp_node = ParseNode::new();
+    // <exp> ::= <id> [ "[" <exp> "]" ] "=" <exp> | <unary-exp> "=" <exp> | <conditional-exp>
+    let mut exp_node = ParseNode::new(loc.clone());
     exp_node.entry = NodeType::Exp;
 
-    let tok = &toks[pos];
-    match tok {
-        lexer::TokType::Identifier(var_name) => {
-            // check next token is Assign
-            let mut pos = pos + 1;
-            let tok = &toks[pos];
-            match tok {
-                lexer::TokType::Assign => {
-                    pos = pos + 1;
-                    // something like a = 1
-                    let mut assign_node = ParseNode::new();
-                    assign_node.entry = NodeType::AssignNode(var_name.to_string(), false); // assign a int variable
-                    let (next_exp_node, pos) = r#try!(p_exp(toks, pos));
-                    assign_node.child.push(next_exp_node);
-                    return Ok((assign_node, pos));
+    let tok = &toks[pos].tok;
+    if let lexer::TokType::Identifier(var_name) = tok {
+        // check next token is Assign
+        let mut pos = pos + 1;
+        let tok = &toks[pos].tok;
+        match tok {
+            lexer::TokType::Assign => {
+                pos += 1;
+                // something like a = 1
+                let mut assign_node = ParseNode::new(loc.clone());
+                assign_node.entry = NodeType::AssignNode(var_name.to_string(), false); // assign a int variable
+                let (next_exp_node, pos) = p_exp(toks, pos, tags)?;
+                assign_node.child.push(next_exp_node);
+                return Ok((assign_node, pos));
+            }
+            lexer::TokType::LBracket => {
+                // something like a[<exp>] = 1;
+                pos += 1;
+                // parse exp.
+                let (index_node, new_pos) = p_comma_exp(toks, pos, tags)?;
+                pos = new_pos;
+                // parse ']'
+                if toks[pos].tok != lexer::TokType::RBracket {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        &format!(
+                            "Expected ']' for bracket closing, found `{}`",
+                            toks[pos].tok
+                        ),
+                    ));
                 }
-                lexer::TokType::LBracket => {
-                    // something like a[<exp>] = 1;
-                    let back_pos = pos - 1;
-                    pos = pos + 1;
-                    // parse exp.
-                    let (index_node, new_pos) = r#try!(p_exp(toks, pos));
-                    pos = new_pos;
-                    // parse ']'
-                    if toks[pos] != lexer::TokType::RBracket {
-                        return Err(format!(
-                            "Expected ']' for bracket closing, found {:?} at {}",
-                            toks[pos], pos
-                        ));
-                    }
 
-                    // try '='
-                    pos = pos + 1;
-                    if toks[pos] != lexer::TokType::Assign {
-                        pos = back_pos;
-                        let (conditional_node, pos) = r#try!(p_conditional_exp(toks, pos));
-                        exp_node.child.push(conditional_node);
-                        return Ok((exp_node, pos));
-                    }
-                    pos = pos + 1;
+                // try '=', if it is not there parse it again as a <conditional-exp>
+                pos += 1;
+                if toks[pos].tok == lexer::TokType::Assign {
+                    pos += 1;
                     // try parse exp
-                    let mut assign_node = ParseNode::new();
+                    let mut assign_node = ParseNode::new(loc.clone());
                     assign_node.entry = NodeType::AssignNode(var_name.to_string(), true); // assign to a array element
-                    let (res_node, new_pos) = r#try!(p_exp(toks, pos));
+                    let (res_node, new_pos) = p_exp(toks, pos, tags)?;
                     pos = new_pos;
                     assign_node.child.push(index_node);
                     assign_node.child.push(res_node);
                     return Ok((assign_node, pos));
                 }
-                _ => {
-                    pos = pos - 1;
-                    let (conditional_node, pos) = r#try!(p_conditional_exp(toks, pos));
-                    exp_node.child.push(conditional_node);
-                    return Ok((exp_node, pos));
+            }
+            _ => {}
+        }
+    }
+    // try <conditional-exp>
+    let (cond_node, pos) = p_conditional_exp(toks, pos, tags)?;
+    if let Some(op) = assign_op(&toks[pos].tok) {
+        // something like a += 1, the lhs is only evaluated once
+        let mut assign_node = ParseNode::new(loc.clone());
+        assign_node.entry = NodeType::AssignOp(op);
+        let (res_node, pos) = p_exp(toks, pos + 1, tags)?;
+        assign_node.child.push(cond_node);
+        assign_node.child.push(res_node);
+        return Ok((assign_node, pos));
+    }
+    if toks[pos].tok == lexer::TokType::Assign {
+        // something like *p = 1, sema checks that the lhs is an lvalue
+        let mut assign_node = ParseNode::new(loc.clone());
+        assign_node.entry = NodeType::Assign;
+        let (res_node, pos) = p_exp(toks, pos + 1, tags)?;
+        assign_node.child.push(cond_node);
+        assign_node.child.push(res_node);
+        return Ok((assign_node, pos));
+    }
+    exp_node.child.push(cond_node);
+    Ok((exp_node, pos))
+}
+
+// the binary operator of the compound assignment `tok`
+fn assign_op(tok: &lexer::TokType) -> Option<lexer::TokType> {
+    let op = match tok {
+        lexer::TokType::AddAssign => lexer::TokType::Plus,
+        lexer::TokType::SubAssign => lexer::TokType::Minus,
+        lexer::TokType::MulAssign => lexer::TokType::Multi,
+        lexer::TokType::DivAssign => lexer::TokType::Splash,
+        lexer::TokType::ModAssign => lexer::TokType::Percent,
+        lexer::TokType::ShlAssign => lexer::TokType::Shl,
+        lexer::TokType::ShrAssign => lexer::TokType::Shr,
+        lexer::TokType::AndAssign => lexer::TokType::Addr,
+        lexer::TokType::XorAssign => lexer::TokType::BitXor,
+        lexer::TokType::OrAssign => lexer::TokType::BitOr,
+        _ => return None,
+    };
+    Some(op)
+}
+
+// <comma-exp> ::= <exp> { "," <exp> }, where an argument list or an
+// initializer takes the comma for a separator
+fn p_comma_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let (mut lhs, mut pos) = p_exp(toks, pos, tags)?;
+    while toks[pos].tok == lexer::TokType::Comma {
+        let mut binexp_node = ParseNode::new(loc.clone());
+        binexp_node.entry = NodeType::BinExp(lexer::TokType::Comma);
+        let (rhs, tmp_pos) = p_exp(toks, pos + 1, tags)?;
+        binexp_node.child.push(lhs);
+        binexp_node.child.push(rhs);
+        lhs = binexp_node;
+        pos = tmp_pos;
+    }
+    Ok((lhs, pos))
+}
+
+// the type <stdarg.h> defines `va_list` as
+const VA_LIST: &str = "__builtin_va_list";
+
+// can `tok` start a <type>
+fn is_type_start(tok: &lexer::TokType) -> bool {
+    match tok {
+        lexer::TokType::Identifier(name) => name == VA_LIST,
+        _ => matches!(
+            tok,
+            lexer::TokType::Kwd(
+                lexer::KwdType::Void
+                    | lexer::KwdType::Bool
+                    | lexer::KwdType::Char
+                    | lexer::KwdType::Short
+                    | lexer::KwdType::Int
+                    | lexer::KwdType::Long
+                    | lexer::KwdType::Float
+                    | lexer::KwdType::Double
+                    | lexer::KwdType::Signed
+                    | lexer::KwdType::Unsigned
+                    | lexer::KwdType::Const
+                    | lexer::KwdType::Volatile
+                    | lexer::KwdType::Struct
+                    | lexer::KwdType::Union
+            )
+        ),
+    }
+}
+
+// <type> ::= <type-specifier> { <type-specifier> } { "*" }
+fn p_type(toks: &[lexer::Token], pos: usize, tags: &mut Tags) -> Result<(Type, usize), Diagnostic> {
+    let (data_type, pos) = p_specifiers(toks, pos, tags)?;
+    Ok(p_pointers(toks, pos, data_type))
+}
+
+// <type-name> ::= <type> ( <array> | <paren-declarator> ), the
+// type of a declaration without the name, as in a cast
+fn p_type_name(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(Type, usize), Diagnostic> {
+    let (data_type, pos) = p_type(toks, pos, tags)?;
+    if is_paren_declarator(toks, pos) {
+        let (_, data_type, pos) = p_paren_declarator(toks, pos, data_type, tags)?;
+        return Ok((data_type, pos));
+    }
+    p_array(toks, pos, data_type, tags)
+}
+
+// the specifiers can come in any order, like `long unsigned int`;
+// `const` and `volatile` are accepted but crust does not check them
+fn p_specifiers(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(Type, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let (mut void, mut bool_, mut char_, mut short, mut int, mut long) = (0, 0, 0, 0, 0, 0);
+    let (mut signed, mut unsigned) = (0, 0);
+    let (mut float, mut double) = (0, 0);
+    let mut aggregate = None;
+    let mut pos = pos;
+    while pos < toks.len() {
+        match &toks[pos].tok {
+            lexer::TokType::Kwd(kwd) => match kwd {
+                lexer::KwdType::Struct | lexer::KwdType::Union if aggregate.is_none() => {
+                    let (t, new_pos) = p_struct(toks, pos, tags)?;
+                    aggregate = Some(t);
+                    pos = new_pos;
+                    continue;
                 }
+                lexer::KwdType::Void => void += 1,
+                lexer::KwdType::Bool => bool_ += 1,
+                lexer::KwdType::Char => char_ += 1,
+                lexer::KwdType::Short => short += 1,
+                lexer::KwdType::Int => int += 1,
+                lexer::KwdType::Long => long += 1,
+                lexer::KwdType::Float => float += 1,
+                lexer::KwdType::Double => double += 1,
+                lexer::KwdType::Signed => signed += 1,
+                lexer::KwdType::Unsigned => unsigned += 1,
+                lexer::KwdType::Const | lexer::KwdType::Volatile => {}
+                _ => break,
+            },
+            // the type <stdarg.h> defines `va_list` as, like gcc
+            lexer::TokType::Identifier(name) if name == VA_LIST && aggregate.is_none() => {
+                let tag = Type::Struct(tags.va_list.clone());
+                aggregate = Some(Type::Array(Box::new(tag), 1));
             }
+            _ => break,
         }
+        pos += 1;
+    }
+    let sign = signed + unsigned;
+    if let Some(t) = aggregate {
+        // `struct s` is the whole type, only qualifiers can go with it
+        if (void, bool_, char_, short, int, long, sign, float + double) != (0, 0, 0, 0, 0, 0, 0, 0)
+        {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &loc.to(loc_at(toks, pos - 1)),
+                "invalid combination of type specifiers",
+            ));
+        }
+        return Ok((t, pos));
+    }
+    // only `long` goes with `double`
+    if float + double > 0 {
+        let data_type = match (void, bool_, char_, short, int, long, sign, float, double) {
+            (0, 0, 0, 0, 0, 0, 0, 1, 0) => Type::Float,
+            (0, 0, 0, 0, 0, 0, 0, 0, 1) => Type::Double,
+            (0, 0, 0, 0, 0, 1, 0, 0, 1) => Type::LongDouble,
+            _ => {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &loc.to(loc_at(toks, pos - 1)),
+                    "invalid combination of type specifiers",
+                ));
+            }
+        };
+        return Ok((data_type, pos));
+    }
+    let data_type = match (void, bool_, char_, short, int, long) {
+        (0, 0, 0, 0, 0, 0) if sign == 0 => {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                loc_at(toks, pos),
+                &format!("Expected type, found `{}`", toks[pos].tok),
+            ));
+        }
+        (1, 0, 0, 0, 0, 0) if sign == 0 => Type::Void,
+        (0, 1, 0, 0, 0, 0) if sign == 0 => Type::Bool,
+        (0, 0, 1, 0, 0, 0) if signed == 1 && unsigned == 0 => Type::SChar,
+        (0, 0, 1, 0, 0, 0) if unsigned == 1 && signed == 0 => Type::UChar,
+        (0, 0, 1, 0, 0, 0) if sign == 0 => Type::Char,
+        (0, 0, 0, 1, 0..=1, 0) if unsigned == 1 && signed == 0 => Type::UShort,
+        (0, 0, 0, 1, 0..=1, 0) if sign <= 1 => Type::Short,
+        (0, 0, 0, 0, 0..=1, 0) if unsigned == 1 && signed == 0 => Type::UInt,
+        (0, 0, 0, 0, 0..=1, 0) if sign <= 1 => Type::Int,
+        (0, 0, 0, 0, 0..=1, 1) if unsigned == 1 && signed == 0 => Type::ULong,
+        (0, 0, 0, 0, 0..=1, 1) if sign <= 1 => Type::Long,
+        (0, 0, 0, 0, 0..=1, 2) if unsigned == 1 && signed == 0 => Type::ULongLong,
+        (0, 0, 0, 0, 0..=1, 2) if sign <= 1 => Type::LongLong,
         _ => {
-            // try <conditional-exp>
-            let (cond_node, pos) = r#try!(p_conditional_exp(toks, pos));
-            exp_node.child.push(cond_node);
-            return Ok((exp_node, pos));
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &loc.to(loc_at(toks, pos - 1)),
+                "invalid combination of type specifiers",
+            ));
+        }
+    };
+    Ok((data_type, pos))
+}
+
+// the `*`s after the specifiers, each one makes a pointer to what is before it
+fn p_pointers(toks: &[lexer::Token], pos: usize, data_type: Type) -> (Type, usize) {
+    let mut data_type = data_type;
+    let mut pos = pos;
+    while pos < toks.len() && toks[pos].tok == lexer::TokType::Multi {
+        data_type = Type::ptr_to(data_type);
+        pos += 1;
+        while let lexer::TokType::Kwd(
+            lexer::KwdType::Const | lexer::KwdType::Volatile | lexer::KwdType::Restrict,
+        ) = toks[pos].tok
+        {
+            pos += 1;
         }
     }
+    (data_type, pos)
 }
 
-fn p_fn(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    // println!("in p_fn with pos: {}", pos);
-    // <function> ::= "int" <id> "(" ")" "{" { <statement> } "}"
+// <struct-or-union> ::= ("struct" | "union") [ <id> ] [ "{" { <member-declaration> } "}" ]
+// <member-declaration> ::= <type> [ <member> { "," <member> } ] ";"
+// <member> ::= { "*" } ( <id> <array> | <paren-declarator> )
+// a member without a name is an anonymous struct or union, its members can be
+// used as if they were members of the enclosing one
+fn p_struct(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(Type, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let is_union = toks[pos].tok == lexer::TokType::Kwd(lexer::KwdType::Union);
+    let kind = if is_union { "union" } else { "struct" };
+    let mut pos = pos + 1;
+    let tag = match &toks[pos].tok {
+        lexer::TokType::Identifier(name) => {
+            pos += 1;
+            Some(name.to_string())
+        }
+        _ => None,
+    };
+    let defined = pos < toks.len() && toks[pos].tok == lexer::TokType::LBrace;
+    let aggregate = match &tag {
+        Some(tag) => {
+            // a definition always declares the tag in the current scope,
+            // a plain use refers to the one in scope or declares it
+            let known = if defined {
+                tags.lookup_current(tag)
+            } else {
+                tags.lookup(tag)
+            };
+            match known {
+                Some(aggregate) if aggregate.is_union != is_union => {
+                    return Err(Diagnostic::error(
+                        code::REDECLARED,
+                        &loc.to(loc_at(toks, pos - 1)),
+                        &format!("`{}` defined as wrong kind of tag", tag),
+                    ));
+                }
+                Some(aggregate) if defined && aggregate.is_complete() => {
+                    return Err(Diagnostic::error(
+                        code::REDECLARED,
+                        &loc.to(loc_at(toks, pos - 1)),
+                        &format!("redefinition of `{}`", aggregate.name),
+                    ));
+                }
+                Some(aggregate) => aggregate,
+                None => tags.declare(tag, Aggregate::new(format!("{} {}", kind, tag), is_union)),
+            }
+        }
+        None if defined => Aggregate::new(format!("{} (unnamed at {})", kind, loc), is_union),
+        None => {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                loc_at(toks, pos),
+                &format!(
+                    "Expected tag name or `{{` after `{}`, found `{}`",
+                    kind, toks[pos].tok
+                ),
+            ));
+        }
+    };
+    if !defined {
+        return Ok((Type::Struct(aggregate), pos));
+    }
+
+    pos += 1;
+    let mut members: Vec<(Option<String>, Type)> = Vec::new();
+    while pos < toks.len() && toks[pos].tok != lexer::TokType::RBrace {
+        let (base_type, new_pos) = p_specifiers(toks, pos, tags)?;
+        pos = new_pos;
+        if toks[pos].tok == lexer::TokType::Semicolon {
+            if let Type::Struct(_) = base_type {
+                members.push((None, base_type));
+                pos += 1;
+                continue;
+            }
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                "declaration does not declare anything",
+            ));
+        }
+        loop {
+            let (member_type, new_pos) = p_pointers(toks, pos, base_type.clone());
+            pos = new_pos;
+            let name_loc = toks[pos].loc.clone();
+            let (name, member_type, new_pos) = match &toks[pos].tok {
+                lexer::TokType::Identifier(name) => {
+                    let (member_type, new_pos) = p_array(toks, pos + 1, member_type, tags)?;
+                    (name.to_string(), member_type, new_pos)
+                }
+                _ if is_paren_declarator(toks, pos) => {
+                    p_paren_declarator(toks, pos, member_type, tags)?
+                }
+                _ => (String::new(), member_type, pos),
+            };
+            if name.is_empty() {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!("Expected member name, found `{}`", toks[pos].tok),
+                ));
+            }
+            pos = new_pos;
+            if member_type.is_unknown_array() {
+                return Err(Diagnostic::error(
+                    code::UNSUPPORTED,
+                    &name_loc,
+                    &format!("flexible array member `{}` is not supported yet", name),
+                ));
+            }
+            let incomplete = match member_type.innermost() {
+                Type::Void => true,
+                Type::Struct(s) => !s.is_complete(),
+                _ => false,
+            };
+            if incomplete {
+                return Err(Diagnostic::error(
+                    code::INCOMPLETE_TYPE,
+                    &name_loc,
+                    &format!("field `{}` has incomplete type `{}`", name, member_type),
+                ));
+            }
+            if members
+                .iter()
+                .any(|(n, _)| n.as_deref() == Some(name.as_str()))
+            {
+                return Err(Diagnostic::error(
+                    code::REDECLARED,
+                    &name_loc,
+                    &format!("duplicate member `{}`", name),
+                ));
+            }
+            members.push((Some(name), member_type));
+            match toks[pos].tok {
+                lexer::TokType::Comma => pos += 1,
+                lexer::TokType::Semicolon => {
+                    pos += 1;
+                    break;
+                }
+                _ => {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        &format!(
+                            "Expected `,` or `;` after member, found `{}`",
+                            toks[pos].tok
+                        ),
+                    ));
+                }
+            }
+        }
+    }
     if pos >= toks.len() {
-        return Err("Out of program length".to_string());
+        return Err(
+            Diagnostic::error(code::SYNTAX, loc_at(toks, pos), "Missing `}`")
+                .note(Some(&loc), &format!("to end this {}", kind)),
+        );
     }
-    let tok = &toks[pos];
-    if *tok != lexer::TokType::Kwd(lexer::KwdType::Int) {
-        return Err(format!("Expected `int`, found {:?} at {}", toks[pos], pos));
+    pos += 1;
+    aggregate.complete(members);
+    Ok((Type::Struct(aggregate), pos))
+}
+
+// the rest of a function definition or prototype, `pos` is after its return type
+fn p_fn(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+    ret_type: Type,
+    storage: Storage,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    // println!("in p_fn with pos: {}", pos);
+    // <function> ::= <type> <id> "(" ")" "{" { <statement> } "}"
+    if pos >= toks.len() {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            loc_at(toks, pos),
+            "Out of program length",
+        ));
     }
-    let mut pos = pos + 1;
+    let mut pos = pos;
 
-    let tok = &toks[pos];
-    let mut fn_name = String::new();
-    match tok {
-        lexer::TokType::Identifier(n) => {
-            fn_name = n.to_string();
-        }
+    let tok = &toks[pos].tok;
+    let fn_name = match tok {
+        lexer::TokType::Identifier(n) => n.to_string(),
         _ => {
-            return Err(format!("Expected function name, but not function name"));
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                loc_at(toks, pos),
+                "Expected function name, but not function name",
+            ));
         }
-    }
-    pos = pos + 1;
+    };
+    pos += 1;
 
-    let tok = &toks[pos];
+    let tok = &toks[pos].tok;
     if *tok != lexer::TokType::LParen {
-        return Err(format!("Expected `(`, found {:?} at {}", toks[pos], pos));
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `(`, found `{}`", toks[pos].tok),
+        ));
+    }
+    pos += 1;
+    // the parameters and the body share a scope
+    tags.enter();
+    let (params, variadic, new_pos) = p_params(toks, pos, tags)?;
+    pos = new_pos;
+
+    let mut fn_node = ParseNode::new(loc.clone());
+    if pos < toks.len() && toks[pos].tok == lexer::TokType::Semicolon {
+        tags.leave();
+        fn_node.entry = NodeType::FnDecl(fn_name, ret_type, params, variadic, storage);
+        return Ok((fn_node, pos + 1));
+    }
+    let tok = &toks[pos].tok;
+    if *tok != lexer::TokType::LBrace {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `{{` or `;`, found `{}`", toks[pos].tok),
+        ));
+    }
+    if let Some(i) = params
+        .iter()
+        .flatten()
+        .position(|(name, _)| name.is_empty())
+    {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &loc,
+            &format!(
+                "parameter name omitted for parameter {} of `{}`",
+                i + 1,
+                fn_name
+            ),
+        ));
+    }
+    pos += 1;
+
+    fn_node.entry = NodeType::Fn(fn_name, ret_type, params, variadic, storage);
+
+    while pos < toks.len() && toks[pos].tok != lexer::TokType::RBrace {
+        let (block_node, tmp_pos) = p_block(toks, pos, tags)?;
+        pos = tmp_pos;
+        fn_node.child.push(block_node);
+    }
+    tags.leave();
+    resolve_labels(&mut fn_node)?;
+
+    if pos >= toks.len() {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            loc_at(toks, pos),
+            "Missing `}`",
+        ));
     }
-    pos = pos + 1;
-    // XXX: add void support, now only support int arg list
-    let mut arg_list: Vec<String> = Vec::new();
+    if toks[pos].tok != lexer::TokType::RBrace {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `}}`, found `{}`", toks[pos].tok),
+        ));
+    }
+    pos += 1;
+
+    //println!("out p_fn with pos: {}", pos);
+    Ok((fn_node, pos))
+}
+
+// the parameters of a function, None for `()`, which says nothing about them
+type Params = Option<Vec<(String, Type)>>;
+
+// <parameters> ::= [ <type> [<id>] { "," <type> [<id>] } [ "," "..." ] ] ")",
+// `pos` is after the `(`; gives the parameters, whether `...` ends them and
+// the position after the `)`
+fn p_params(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(Params, bool, usize), Diagnostic> {
+    let mut pos = pos;
+    let mut arg_list: Vec<(String, Type)> = Vec::new();
     let mut arg_count = 0;
-    while pos < toks.len() && toks[pos] != lexer::TokType::RParen {
+    // `()` says nothing about the parameters, `(void)` that there are none
+    let mut prototyped = false;
+    let mut variadic = false;
+    while pos < toks.len() && toks[pos].tok != lexer::TokType::RParen {
+        prototyped = true;
         // try to parse argument list
-        // match int
-        match &toks[pos] {
-            lexer::TokType::Kwd(lexer::KwdType::Int) => {
-                pos = pos + 1;
-            }
-            lexer::TokType::Kwd(lexer::KwdType::Void) => {
+        // match the type
+        let arg_type = match &toks[pos].tok {
+            // `(void)` is an empty parameter list
+            lexer::TokType::Kwd(lexer::KwdType::Void)
+                if toks[pos + 1].tok == lexer::TokType::RParen =>
+            {
                 if arg_count > 0 {
-                    return Err(format!(
-                        "Error: void after other argument in one function definition"
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        "void after other argument in one function definition",
                     ));
                 }
-                pos = pos + 1;
+                pos += 1;
+                break;
+            }
+            // any number of arguments after the named ones
+            lexer::TokType::Ellipsis if arg_count > 0 => {
+                variadic = true;
+                pos += 1;
                 break;
             }
             _ => {
-                return Err(format!("Expected `int`, found {:?} at {}", toks[pos], pos));
+                let (arg_type, new_pos) = p_type(toks, pos, tags)?;
+                if arg_type == Type::Void {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        "parameter can not have type `void`",
+                    ));
+                }
+                pos = new_pos;
+                arg_type
             }
-        }
-        // match identifier
-        match &toks[pos] {
+        };
+        // match identifier, a prototype may leave it out
+        let (var_name, arg_type, new_pos) = match &toks[pos].tok {
             lexer::TokType::Identifier(var_name) => {
-                arg_list.push(var_name.to_string());
-                pos = pos + 1;
+                let (arg_type, new_pos) = p_array(toks, pos + 1, arg_type, tags)?;
+                (var_name.to_string(), arg_type, new_pos)
             }
+            _ if is_paren_declarator(toks, pos) => p_paren_declarator(toks, pos, arg_type, tags)?,
             _ => {
-                return Err(format!(
-                    "Expected identifier name, found {:?} at {}",
-                    toks[pos], pos
-                ));
+                let (arg_type, new_pos) = p_array(toks, pos, arg_type, tags)?;
+                (String::new(), arg_type, new_pos)
             }
-        }
-        arg_count = arg_count + 1;
+        };
+        pos = new_pos;
+        // a parameter declared an array is a pointer to its first element
+        let arg_type = match arg_type {
+            Type::Array(elem, _) => Type::Ptr(elem),
+            t => t,
+        };
+        tags.declare_var(&var_name, &arg_type);
+        arg_list.push((var_name, arg_type));
+        arg_count += 1;
         // match ,
-        match &toks[pos] {
+        match &toks[pos].tok {
             lexer::TokType::Comma => {
-                pos = pos + 1;
+                pos += 1;
             }
             lexer::TokType::RParen => {
                 continue;
             }
             _ => {
-                return Err(format!(
-                    "Expected `,` or `)` at the end of one var_name, found {:?} at {}",
-                    toks[pos], pos
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!(
+                        "Expected `,` or `)` at the end of one var_name, found `{}`",
+                        toks[pos].tok
+                    ),
                 ));
             }
         }
-        if toks[pos] == lexer::TokType::RParen {
+        if toks[pos].tok == lexer::TokType::RParen {
             break;
         }
     }
-    let tok = &toks[pos];
+    let tok = &toks[pos].tok;
     if *tok != lexer::TokType::RParen {
-        return Err(format!("Expected `)`, found {:?} at {}", toks[pos], pos));
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `)`, found `{}`", toks[pos].tok),
+        ));
     }
-    pos = pos + 1;
+    let params = if prototyped { Some(arg_list) } else { None };
+    Ok((params, variadic, pos + 1))
+}
 
-    let tok = &toks[pos];
-    if *tok != lexer::TokType::LBrace {
-        return Err(format!("Expected `{{`, found {:?} at {}", toks[pos], pos));
+fn p_declare(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    // println!("in p_declare with pos = {}", pos);
+    let tok = &toks[pos].tok;
+    if !is_type_start(tok) {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected type, found `{}`", toks[pos].tok),
+        ));
     }
-    pos = pos + 1;
+    let (data_type, pos) = p_type(toks, pos, tags)?;
+    p_declarator(toks, pos, data_type, Storage::None, tags)
+}
 
-    let mut fn_node = ParseNode::new();
-    if arg_list.is_empty() {
-        fn_node.entry = NodeType::Fn(fn_name, None);
-    } else {
-        fn_node.entry = NodeType::Fn(fn_name, Some(arg_list));
-    }
+// the rest of a declaration, `pos` is after its type
+// <type> ( <id> <array> | <paren-declarator> ) [ = <initializer> ] ";"
+fn p_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    data_type: Type,
+    storage: Storage,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let tok = &toks[pos].tok;
+    // the declaration is located at the variable name, like gcc does
+    let (var_name, loc, data_type, pos) = match tok {
+        // `struct s { ... };` declares the tag only
+        lexer::TokType::Semicolon if matches!(data_type, Type::Struct(_)) => {
+            let mut tag_node = ParseNode::new(toks[pos].loc.clone());
+            tag_node.entry = NodeType::TagDecl(data_type);
+            return Ok((tag_node, pos + 1));
+        }
+        lexer::TokType::Identifier(var_name) => {
+            let loc = &toks[pos].loc;
+            if data_type == Type::Void {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc,
+                    &format!("variable `{}` declared void", var_name),
+                ));
+            }
+            let (data_type, pos) = p_array(toks, pos + 1, data_type, tags)?;
+            (var_name.to_string(), loc, data_type, pos)
+        }
+        _ if is_paren_declarator(toks, pos) => {
+            let (var_name, data_type, new_pos) = p_paren_declarator(toks, pos, data_type, tags)?;
+            let name_tok = lexer::TokType::Identifier(var_name.clone());
+            let loc = match toks[pos..new_pos].iter().find(|t| t.tok == name_tok) {
+                Some(t) if !var_name.is_empty() => &t.loc,
+                _ => {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        "Expected identifier name in declarator",
+                    ));
+                }
+            };
+            (var_name, loc, data_type, new_pos)
+        }
+        _ => {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!("Expected identifier name, found `{}`", toks[pos].tok),
+            ));
+        }
+    };
+    tags.declare_var(&var_name, &data_type);
+    let mut stmt_node = ParseNode::new(loc.clone());
+    stmt_node.entry = NodeType::Declare(var_name.clone(), data_type.clone(), storage);
+    let tok = &toks[pos].tok;
+    match tok {
+        lexer::TokType::Assign => {
+            // e.g. int a = exp; or int a[] = { 1, 2 };
+            let (init_node, pos) = p_initializer(toks, pos + 1, tags)?;
 
-    while pos < toks.len() && toks[pos] != lexer::TokType::RBrace {
-        let (block_node, tmp_pos) = r#try!(p_block(toks, pos));
-        pos = tmp_pos;
-        fn_node.child.push(block_node);
+            let tok = &toks[pos].tok;
+            if *tok != lexer::TokType::Semicolon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!("Expected `;`, found `{}`", toks[pos].tok),
+                ));
+            }
+            let pos = pos + 1;
+            stmt_node.child.push(init_node);
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Semicolon => {
+            // if just declare, but no assignment, just record the var_name
+            // e.g. int var;
+            // only an initializer or another definition can say how
+            // long `int a[];` is
+            if data_type.is_unknown_array() && storage != Storage::Extern {
+                return Err(Diagnostic::error(
+                    code::INCOMPLETE_TYPE,
+                    loc,
+                    &format!("array size missing in `{}`", var_name),
+                ));
+            }
+            let pos = pos + 1;
+            Ok((stmt_node, pos))
+        }
+        _ => Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected Assignment `;` or `=`, found `{}`", toks[pos].tok),
+        )),
     }
+}
 
-    if pos >= toks.len() {
-        return Err(format!("Missing `}}`"));
-    }
-    if toks[pos] != lexer::TokType::RBrace {
-        return Err(format!("Expected `}}`, found {:?} at {}", toks[pos], pos));
+// <array> ::= { "[" <conditional-exp> "]" }
+// the lengths `[N]` after a declared name, if any, make `data_type` the type
+// of the elements of an array of arrays, the first length the outermost one;
+// that one alone can be left out, as in `int a[] = { 1, 2 };`; a length is
+// an integer constant expression like `N + 1` or `sizeof(int) * 2`
+fn p_array(
+    toks: &[lexer::Token],
+    pos: usize,
+    data_type: Type,
+    tags: &mut Tags,
+) -> Result<(Type, usize), Diagnostic> {
+    let mut pos = pos;
+    let mut lens = Vec::new();
+    while toks[pos].tok == lexer::TokType::LBracket {
+        pos += 1;
+        let len = if toks[pos].tok == lexer::TokType::RBracket && lens.is_empty() {
+            types::UNKNOWN_LEN
+        } else {
+            let (mut len_node, new_pos) = p_conditional_exp(toks, pos, tags)?;
+            pos = new_pos;
+            let len = sema::constant(&mut len_node, |name| tags.var(name))?;
+            if len <= 0 {
+                return Err(Diagnostic::error(
+                    code::INVALID_INIT,
+                    &len_node.loc,
+                    "zero or negative size array",
+                ));
+            }
+            len
+        };
+        if toks[pos].tok != lexer::TokType::RBracket {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!(
+                    "Expected `]` for array declaration, found `{}`",
+                    toks[pos].tok
+                ),
+            ));
+        }
+        pos += 1;
+        lens.push(len);
     }
-    pos = pos + 1;
+    let data_type = lens
+        .into_iter()
+        .rev()
+        .fold(data_type, |elem, len| Type::Array(Box::new(elem), len));
+    Ok((data_type, pos))
+}
 
-    //println!("out p_fn with pos: {}", pos);
-    Ok((fn_node, pos))
+// whether a declarator in parentheses starts at `pos`, as in `int (*fp)(int)`
+fn is_paren_declarator(toks: &[lexer::Token], pos: usize) -> bool {
+    *tok_at(toks, pos) == lexer::TokType::LParen && *tok_at(toks, pos + 1) == lexer::TokType::Multi
 }
 
-fn p_declare(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    // println!("in p_declare with pos = {}", pos);
-    let tok = &toks[pos];
-    match tok {
-        lexer::TokType::Kwd(lexer::KwdType::Int) => {
-            // "int" <id> [ = <exp> ] ";"
-            // or "int" <id> "[" <int> "]" ";"
-            let pos = pos + 1;
+// <paren-declarator> ::= "(" "*" { "*" } [ <id> ] <array> ")" ( <array> | "(" <parameters> )
+// a pointer, or an array of them, to an array or to a function of the type
+// `base`, the one declarator in parentheses crust knows; gives the name,
+// empty in a type name like `int (*)(int)`, the type and the position after it
+fn p_paren_declarator(
+    toks: &[lexer::Token],
+    pos: usize,
+    base: Type,
+    tags: &mut Tags,
+) -> Result<(String, Type, usize), Diagnostic> {
+    // the `*`s, each a pointer to what the rest of the declarator makes
+    let (mut pos, mut pointers) = (pos + 1, 0);
+    while let lexer::TokType::Multi
+    | lexer::TokType::Kwd(
+        lexer::KwdType::Const | lexer::KwdType::Volatile | lexer::KwdType::Restrict,
+    ) = toks[pos].tok
+    {
+        pointers += usize::from(toks[pos].tok == lexer::TokType::Multi);
+        pos += 1;
+    }
+    let name = match &toks[pos].tok {
+        lexer::TokType::Identifier(name) => {
+            pos += 1;
+            name.to_string()
+        }
+        _ => String::new(),
+    };
+    // the lengths in the parentheses are of an array of the pointers, read
+    // once they are made
+    let lens = pos;
+    let (_, pos) = p_array(toks, pos, Type::Void, tags)?;
+    if toks[pos].tok != lexer::TokType::RParen {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `)` in declarator, found `{}`", toks[pos].tok),
+        ));
+    }
+    let (inner, pos) = if toks[pos + 1].tok == lexer::TokType::LParen {
+        // the parameters have a scope of their own, their names are only
+        // for the reader
+        tags.enter();
+        let (params, variadic, pos) = p_params(toks, pos + 2, tags)?;
+        tags.leave();
+        let params = params.map(|p| p.into_iter().map(|(_, t)| t).collect());
+        (Type::Func(Box::new(base), params, variadic), pos)
+    } else {
+        p_array(toks, pos + 1, base, tags)?
+    };
+    let data_type = (0..pointers).fold(inner, |t, _| Type::ptr_to(t));
+    let (data_type, _) = p_array(toks, lens, data_type, tags)?;
+    Ok((name, data_type, pos))
+}
 
-            let tok = &toks[pos];
-            match tok {
-                lexer::TokType::Identifier(var_name) => {
-                    let mut stmt_node = ParseNode::new();
-                    stmt_node.entry = NodeType::Declare(var_name.to_string(), DataType::I64);
-                    let pos = pos + 1;
-                    let tok = &toks[pos];
-                    match tok {
-                        lexer::TokType::Assign => {
-                            // parse exp
-                            // e.g. int a = exp;
-                            let pos = pos + 1;
-                            let (exp_node, pos) = r#try!(p_exp(toks, pos));
-
-                            let tok = &toks[pos];
-                            if *tok != lexer::TokType::Semicolon {
-                                return Err(format!(
-                                    "Expected `;`, found {:?} at {}",
-                                    toks[pos], pos
-                                ));
-                            }
-                            let pos = pos + 1;
-                            stmt_node.child.push(exp_node);
-                            return Ok((stmt_node, pos));
-                        }
-                        lexer::TokType::Semicolon => {
-                            // if just declare, but no assignment, just record the var_name
-                            // e.g. int var;
-                            let pos = pos + 1;
-                            return Ok((stmt_node, pos));
-                        }
-                        lexer::TokType::LBracket => {
-                            // array declare
-                            // e.g. int a[100];
-                            // XXX: now only just support literal array length
-                            if cfg!(feature = "debug") {
-                                println!("here in p_declare -> LBraket");
-                            }
-                            let mut declare_node = ParseNode::new();
-                            let pos = pos + 1;
-                            let tok = &toks[pos];
-                            match tok {
-                                lexer::TokType::Literal(n) => {
-                                    declare_node.entry = NodeType::Declare(
-                                        var_name.to_string(),
-                                        DataType::Arr64(*n),
-                                    );
-                                    let pos = pos + 1;
-                                    let tok = &toks[pos];
-                                    if *tok != lexer::TokType::RBracket {
-                                        return Err(format!(
-                                            "Expected `]` for array declaration, found {:?} at {}",
-                                            toks[pos], pos
-                                        ));
-                                    }
-
-                                    let pos = pos + 1;
-                                    let tok = &toks[pos];
-                                    if *tok != lexer::TokType::Semicolon {
-                                        return Err(format!("Expected `;` at end of array declaration, found {:?} at {}", toks[pos], pos));
-                                    }
-                                    let pos = pos + 1;
-                                    if cfg!(feature = "debug") {
-                                        println!("got declare_node: {:?}", declare_node);
-                                    }
-                                    return Ok((declare_node, pos));
-                                }
-                                _ => {
-                                    return Err(format!(
-                                        "Expected Array length `literal`, found {:?} at {}",
-                                        toks[pos], pos
-                                    ));
-                                }
-                            }
-                        }
-                        _ => {
-                            return Err(format!(
-                                "Expected Assignment `;` or `=`, found {:?} at {}",
-                                toks[pos], pos
-                            ));
-                        }
-                    }
+// <initializer> ::= <exp> | "{" [ <designation> ] <initializer> { "," [ <designation> ] <initializer> } [ "," ] "}"
+// <designation> ::= { "[" <int> "]" | "." <id> } "="
+fn p_initializer(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    if toks[pos].tok != lexer::TokType::LBrace {
+        return p_exp(toks, pos, tags);
+    }
+    let mut list_node = ParseNode::new(toks[pos].loc.clone());
+    list_node.entry = NodeType::InitList;
+    let mut pos = pos + 1;
+    while pos < toks.len() && toks[pos].tok != lexer::TokType::RBrace {
+        let loc = toks[pos].loc.clone();
+        let mut designators = Vec::new();
+        loop {
+            match (&toks[pos].tok, tok_at(toks, pos + 1)) {
+                (lexer::TokType::LBracket, lexer::TokType::Literal(n, _))
+                    if *tok_at(toks, pos + 2) == lexer::TokType::RBracket =>
+                {
+                    designators.push(Designator::Index(*n));
+                    pos += 3;
                 }
-                _ => {
-                    return Err(format!(
-                        "Expected identifier name, found {:?} at {}",
-                        toks[pos], pos
+                (lexer::TokType::LBracket, _) => {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        loc_at(toks, pos + 1),
+                        &format!(
+                            "Expected array index `literal` and `]` in a designator, found `{}`",
+                            toks[pos + 1].tok
+                        ),
+                    ));
+                }
+                (lexer::TokType::Dot, lexer::TokType::Identifier(name)) => {
+                    designators.push(Designator::Member(name.to_string()));
+                    pos += 2;
+                }
+                (lexer::TokType::Dot, _) => {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        loc_at(toks, pos + 1),
+                        &format!(
+                            "Expected member name after `.`, found `{}`",
+                            toks[pos + 1].tok
+                        ),
                     ));
                 }
+                _ => break,
             }
         }
-        _ => {
-            return Err(format!(
-                "Error: Expected type definition `int`, found {:?} at {}",
-                toks[pos], pos
-            ));
+        if !designators.is_empty() {
+            if toks[pos].tok != lexer::TokType::Assign {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!("Expected `=` after designator, found `{}`", toks[pos].tok),
+                ));
+            }
+            pos += 1;
+        }
+        let (init_node, new_pos) = p_initializer(toks, pos, tags)?;
+        pos = new_pos;
+        if designators.is_empty() {
+            list_node.child.push(init_node);
+        } else {
+            let mut designated_node = ParseNode::new(loc);
+            designated_node.entry = NodeType::Designated(designators);
+            designated_node.child.push(init_node);
+            list_node.child.push(designated_node);
+        }
+        match toks[pos].tok {
+            lexer::TokType::Comma => pos += 1,
+            lexer::TokType::RBrace => {}
+            _ => {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!(
+                        "Expected `,` or `}}` in initializer list, found `{}`",
+                        toks[pos].tok
+                    ),
+                ));
+            }
         }
     }
+    if pos >= toks.len() {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            loc_at(toks, pos),
+            "Missing `}`",
+        ));
+    }
+    Ok((list_node, pos + 1))
 }
 
-fn p_block(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    let tok = &toks[pos];
+fn p_block(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let tok = &toks[pos].tok;
     match tok {
-        lexer::TokType::Kwd(lexer::KwdType::Int) => {
+        // the lowering makes a `static` one a global only the block sees,
+        // and an `extern` one the global of that name
+        lexer::TokType::Kwd(kwd @ (lexer::KwdType::Static | lexer::KwdType::Extern)) => {
+            let storage = match kwd {
+                lexer::KwdType::Static => Storage::Static,
+                _ => Storage::Extern,
+            };
+            let (data_type, pos) = p_type(toks, pos + 1, tags)?;
+            p_declarator(toks, pos, data_type, storage, tags)
+        }
+        tok if is_type_start(tok) => {
             // try to parse declare
-            // let mut block_node = ParseNode::new();
+            // let mut block_node = ParseNode::new(loc.clone());
             // block_node.entry = NodeType::Block;
 
-            let (declare_node, pos) = r#try!(p_declare(toks, pos));
+            let (declare_node, pos) = p_declare(toks, pos, tags)?;
             // block_node.child.push(declare_node);
             // return Ok((block_node, pos));
-            return Ok((declare_node, pos));
+            Ok((declare_node, pos))
         }
         _ => {
             // try to parse statement
-            // let mut block_node = ParseNode::new();
+            // let mut block_node = ParseNode::new(loc.clone());
             // block_node.entry = NodeType::Block;
 
-            let (stmt_node, pos) = r#try!(p_stmt(toks, pos));
-            return Ok((stmt_node, pos));
+            let (stmt_node, pos) = p_stmt(toks, pos, tags)?;
+            Ok((stmt_node, pos))
             // block_node.child.push(stmt_node);
             //return Ok((block_node, pos));
         }
     }
 }
-fn p_stmt(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+// labels have the whole function as their scope, so a `goto` is only matched
+// with its label once the body is parsed
+fn resolve_labels(fn_node: &mut ParseNode) -> Result<(), Diagnostic> {
+    let mut labels: HashMap<String, Loc> = HashMap::new();
+    collect_labels(fn_node, &mut labels)?;
+    link_gotos(fn_node, &labels)
+}
+
+fn collect_labels(tree: &ParseNode, labels: &mut HashMap<String, Loc>) -> Result<(), Diagnostic> {
+    if let NodeType::Stmt(StmtType::Label(name)) = &tree.entry {
+        if let Some(first) = labels.get(name) {
+            return Err(Diagnostic::error(
+                code::REDECLARED,
+                &tree.loc,
+                &format!("duplicate label `{}`", name),
+            )
+            .note(
+                Some(first),
+                &format!("previous definition of `{}` was here", name),
+            ));
+        }
+        labels.insert(name.to_string(), tree.loc.clone());
+    }
+    for it in tree.child.iter() {
+        collect_labels(it, labels)?;
+    }
+    Ok(())
+}
+
+fn link_gotos(tree: &mut ParseNode, labels: &HashMap<String, Loc>) -> Result<(), Diagnostic> {
+    if let NodeType::Stmt(StmtType::Goto(name, target)) = &mut tree.entry {
+        match labels.get(name.as_str()) {
+            Some(label) => *target = label.span.0,
+            None => {
+                return Err(Diagnostic::error(
+                    code::UNDECLARED,
+                    &tree.loc,
+                    &format!("label `{}` used but not defined", name),
+                ))
+            }
+        }
+    }
+    for it in tree.child.iter_mut() {
+        link_gotos(it, labels)?;
+    }
+    Ok(())
+}
+
+// the body of a loop or switch as a block of its own, so a body that is a
+// single statement is not taken for a list of block items
+fn compound_layer(stmt_node: ParseNode) -> ParseNode {
+    let mut compound_layer_node = ParseNode::new(stmt_node.loc.clone());
+    compound_layer_node.entry = NodeType::Stmt(StmtType::Compound);
+    compound_layer_node.child.push(stmt_node);
+    compound_layer_node
+}
+
+fn p_stmt(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // println!("in fn : p_stmt, with pos {}", pos);
-    let tok = &toks[pos];
+    let tok = &toks[pos].tok;
     match tok {
         lexer::TokType::LBrace => {
             // "{" { <block-item> } "}"
             let mut pos = pos + 1;
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Compound);
 
             // try to get some block item
-            while toks[pos] != lexer::TokType::RBrace {
-                let (block_node, tmp_pos) = r#try!(p_block(toks, pos));
+            tags.enter();
+            while toks[pos].tok != lexer::TokType::RBrace {
+                let (block_node, tmp_pos) = p_block(toks, pos, tags)?;
                 stmt_node.child.push(block_node);
                 pos = tmp_pos;
             }
+            tags.leave();
 
             // throw "}"
-            pos = pos + 1;
-            return Ok((stmt_node, pos));
+            pos += 1;
+            Ok((stmt_node, pos))
         }
         lexer::TokType::Kwd(lexer::KwdType::Ret) => {
             // "return" <exp> ";"
             let pos = pos + 1;
-            let (exp_node, mut pos) = r#try!(p_exp(toks, pos));
+            let (exp_node, mut pos) = p_comma_exp(toks, pos, tags)?;
 
-            let tok = &toks[pos];
+            let tok = &toks[pos].tok;
             if *tok != lexer::TokType::Semicolon {
-                return Err(format!(
-                    "Expected `;` in statement, found {:?} at {}",
-                    toks[pos], pos
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!("Expected `;` in statement, found `{}`", toks[pos].tok),
                 ));
             }
-            pos = pos + 1;
+            pos += 1;
 
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Return);
             stmt_node.child.push(exp_node);
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
         }
         lexer::TokType::Kwd(lexer::KwdType::If) => {
             // "if" "(" <exp> ")" <statement> [ "else" <statement> ]
             // this is the conditional statement
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Conditional("if".to_string()));
             let pos = pos + 1;
-            if pos >= toks.len() || toks[pos] != lexer::TokType::LParen {
-                return Err(format!("Missing `(`"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::LParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `(`",
+                ));
             }
             // try to parse exp
             if cfg!(feature = "debug") {
                 println!("here pos = {}", pos);
             }
             let pos = pos + 1;
-            let (exp_node, pos) = r#try!(p_exp(toks, pos));
+            let (exp_node, pos) = p_comma_exp(toks, pos, tags)?;
             // println!("pos = {}", pos);
-            if pos >= toks.len() || toks[pos] != lexer::TokType::RParen {
-                return Err(format!("Missing `)`"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `)`",
+                ));
             }
 
             let pos = pos + 1;
@@ -1089,482 +2038,1011 @@ fn p_stmt(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), Str
             if cfg!(feature = "debug") {
                 println!("If: parse stmt from pos = {}, tok: {:?}", pos, toks[pos]);
             }
-            let (clause_1_node, pos) = r#try!(p_stmt(toks, pos));
+            let (clause_1_node, pos) = p_stmt(toks, pos, tags)?;
             stmt_node.child.push(exp_node);
             stmt_node.child.push(clause_1_node);
 
             // if has 'else'
             // println!("SHOULD BE HERE , POS = {}", pos);
-            if pos < toks.len() && toks[pos] == lexer::TokType::Kwd(lexer::KwdType::Else) {
+            if pos < toks.len() && toks[pos].tok == lexer::TokType::Kwd(lexer::KwdType::Else) {
                 // try to parse statement 2
                 let pos = pos + 1;
-                let (clause_2_node, pos) = r#try!(p_stmt(toks, pos));
+                let (clause_2_node, pos) = p_stmt(toks, pos, tags)?;
                 stmt_node.child.push(clause_2_node);
-                return Ok((stmt_node, pos));
+                Ok((stmt_node, pos))
             } else {
-                return Ok((stmt_node, pos));
+                Ok((stmt_node, pos))
             }
         }
         lexer::TokType::Kwd(lexer::KwdType::For) => {
             // "for" "(" <exp-option> ";" <exp-option> ";" <exp-option> ")" <statement>
             // "for" "(" <declaration> <exp-option> ";" <exp-option> ")" <statement>
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             let pos = pos + 1;
-            if pos >= toks.len() || toks[pos] != lexer::TokType::LParen {
-                return Err(format!("Missing `(`"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::LParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `(`",
+                ));
             }
             let pos = pos + 1;
-            // try to parse declaration
-            let decl_res = p_declare(toks, pos);
-            match decl_res {
-                Ok((declare_node, pos)) => {
+            match &toks[pos].tok {
+                tok if is_type_start(tok) => {
                     // "for" "(" <declaration> <exp-option> ";" <exp-option> ")" <statement>
+                    tags.enter();
+                    let (declare_node, pos) = p_declare(toks, pos, tags)?;
                     stmt_node.child.push(declare_node);
                     stmt_node.entry = NodeType::Stmt(StmtType::ForDecl);
 
-                    let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+                    let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
                     stmt_node.child.push(exp_opt_node);
 
-                    if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                        return Err(format!("Missing `;` needed by For"));
+                    if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos),
+                            "Missing `;` needed by For",
+                        ));
                     }
                     let pos = pos + 1;
 
-                    let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+                    let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
                     stmt_node.child.push(exp_opt_node);
-                    if pos >= toks.len() || toks[pos] != lexer::TokType::RParen {
-                        return Err(format!("Missing `)` needed by For"));
+                    if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos),
+                            "Missing `)` needed by For",
+                        ));
                     }
                     let pos = pos + 1;
                     if cfg!(feature = "debug") {
                         println!("pos: {} tok: {:?} before compound layer", pos, toks[pos]);
                     }
-                    let mut compound_layer_node = ParseNode::new();
+                    let mut compound_layer_node = ParseNode::new(loc.clone());
                     compound_layer_node.entry = NodeType::Stmt(StmtType::Compound);
-                    let (next_stmt_node, pos) = r#try!(p_stmt(toks, pos));
+                    let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
+                    tags.leave();
                     compound_layer_node.child.push(next_stmt_node);
                     stmt_node.child.push(compound_layer_node);
-                    return Ok((stmt_node, pos));
+                    Ok((stmt_node, pos))
                 }
-                Err(_) => {
+                _ => {
                     // "for" "(" <exp-option> ";" <exp-option> ";" <exp-option> ")" <statement>
                     stmt_node.entry = NodeType::Stmt(StmtType::For);
-                    let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+                    let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
                     stmt_node.child.push(exp_opt_node);
 
-                    if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                        return Err(format!("Missing `;` needed by for"));
+                    if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos),
+                            "Missing `;` needed by for",
+                        ));
                     }
                     let pos = pos + 1;
 
-                    let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+                    let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
                     stmt_node.child.push(exp_opt_node);
 
-                    if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                        return Err(format!("Missing `;` needed by for"));
+                    if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos),
+                            "Missing `;` needed by for",
+                        ));
                     }
                     let pos = pos + 1;
 
-                    let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+                    let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
                     stmt_node.child.push(exp_opt_node);
 
-                    if pos >= toks.len() || toks[pos] != lexer::TokType::RParen {
-                        return Err(format!("Missing `)` needed by for"));
+                    if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos),
+                            "Missing `)` needed by for",
+                        ));
                     }
                     let pos = pos + 1;
-                    let mut compound_layer_node = ParseNode::new();
-                    let (next_stmt_node, pos) = r#try!(p_stmt(toks, pos));
+                    let mut compound_layer_node = ParseNode::new(loc.clone());
+                    let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
                     compound_layer_node.child.push(next_stmt_node);
                     compound_layer_node.entry = NodeType::Stmt(StmtType::Compound);
                     stmt_node.child.push(compound_layer_node);
-                    return Ok((stmt_node, pos));
+                    Ok((stmt_node, pos))
                 }
             }
         }
-        lexer::TokType::Kwd(lexer::KwdType::While) => {
-            // "while" "(" <exp> ")" <statement>
-            let mut stmt_node = ParseNode::new();
-            stmt_node.entry = NodeType::Stmt(StmtType::While);
+        lexer::TokType::Kwd(lexer::KwdType::While) => {
+            // "while" "(" <exp> ")" <statement>
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::While);
+            let pos = pos + 1;
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::LParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `(` needed by While",
+                ));
+            }
+
+            let pos = pos + 1;
+            let (exp_node, pos) = p_comma_exp(toks, pos, tags)?;
+            stmt_node.child.push(exp_node);
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `)`",
+                ));
+            }
+            let pos = pos + 1;
+
+            let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
+            stmt_node.child.push(compound_layer(next_stmt_node));
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Kwd(lexer::KwdType::Switch) => {
+            // "switch" "(" <exp> ")" <statement>
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::Switch);
+            let pos = pos + 1;
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::LParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `(` needed by switch",
+                ));
+            }
+
+            let pos = pos + 1;
+            let (exp_node, pos) = p_comma_exp(toks, pos, tags)?;
+            stmt_node.child.push(exp_node);
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `)`",
+                ));
+            }
+            let pos = pos + 1;
+
+            let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
+            stmt_node.child.push(compound_layer(next_stmt_node));
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Kwd(lexer::KwdType::Case) => {
+            // "case" <exp> ":" <statement>
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::Case);
             let pos = pos + 1;
-            if pos >= toks.len() || toks[pos] != lexer::TokType::LParen {
-                return Err(format!("Missing `(` needed by While"));
+            let (exp_node, pos) = p_exp(toks, pos, tags)?;
+            stmt_node.child.push(exp_node);
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Colon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    &format!("Expected `:` after case, found `{}`", toks[pos].tok),
+                ));
             }
+            let pos = pos + 1;
 
+            let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
+            stmt_node.child.push(next_stmt_node);
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Kwd(lexer::KwdType::Default) => {
+            // "default" ":" <statement>
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::Default);
             let pos = pos + 1;
-            let (exp_node, pos) = r#try!(p_exp(toks, pos));
-            stmt_node.child.push(exp_node);
-            if pos >= toks.len() || toks[pos] != lexer::TokType::RParen {
-                return Err(format!("Missing `)`"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Colon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    &format!("Expected `:` after default, found `{}`", toks[pos].tok),
+                ));
             }
             let pos = pos + 1;
 
-            let (next_stmt_node, pos) = r#try!(p_stmt(toks, pos));
+            let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
             stmt_node.child.push(next_stmt_node);
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
         }
         lexer::TokType::Kwd(lexer::KwdType::Do) => {
             // "do" <statement> "while" "(" <exp> ")" ";"
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Do);
             let pos = pos + 1;
-            let (next_stmt_node, pos) = r#try!(p_stmt(toks, pos));
-            stmt_node.child.push(next_stmt_node);
+            let (next_stmt_node, pos) = p_stmt(toks, pos, tags)?;
+            stmt_node.child.push(compound_layer(next_stmt_node));
             // parse while
-            if pos >= toks.len() || toks[pos] != lexer::TokType::Kwd(lexer::KwdType::While) {
-                return Err(format!("Missing `while` needed by do"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Kwd(lexer::KwdType::While) {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `while` needed by do",
+                ));
             }
             let pos = pos + 1;
 
-            if pos >= toks.len() || toks[pos] != lexer::TokType::LParen {
-                return Err(format!("Missing `(` needed by do"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::LParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `(` needed by do",
+                ));
             }
             let pos = pos + 1;
 
-            let (exp_node, pos) = r#try!(p_exp_opt(toks, pos));
+            let (exp_node, pos) = p_exp_opt(toks, pos, tags)?;
 
-            if pos >= toks.len() || toks[pos] != lexer::TokType::RParen {
-                return Err(format!("Missing `)` needed by do"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::RParen {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `)` needed by do",
+                ));
             }
             let pos = pos + 1;
 
-            if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                return Err(format!("Missing `;` needed by do"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `;` needed by do",
+                ));
             }
             let pos = pos + 1;
 
             stmt_node.child.push(exp_node);
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Kwd(lexer::KwdType::Goto) => {
+            // "goto" <id> ";"
+            let pos = pos + 1;
+            let name = match &toks[pos].tok {
+                lexer::TokType::Identifier(name) => name.to_string(),
+                _ => {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[pos].loc,
+                        &format!("Expected label name after goto, found `{}`", toks[pos].tok),
+                    ))
+                }
+            };
+            let pos = pos + 1;
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `;` needed by goto",
+                ));
+            }
+            let pos = pos + 1;
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::Goto(name, 0));
+            Ok((stmt_node, pos))
+        }
+        lexer::TokType::Identifier(name)
+            if pos + 1 < toks.len() && toks[pos + 1].tok == lexer::TokType::Colon =>
+        {
+            // <id> ":" <statement>
+            let mut stmt_node = ParseNode::new(loc.clone());
+            stmt_node.entry = NodeType::Stmt(StmtType::Label(name.to_string()));
+            let (next_stmt_node, pos) = p_stmt(toks, pos + 2, tags)?;
+            stmt_node.child.push(next_stmt_node);
+            Ok((stmt_node, pos))
         }
         lexer::TokType::Kwd(lexer::KwdType::Continue) => {
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Continue);
             let pos = pos + 1;
-            if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                return Err(format!("Missing `;` needed by continue"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `;` needed by continue",
+                ));
             }
             let pos = pos + 1;
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
         }
         lexer::TokType::Kwd(lexer::KwdType::Break) => {
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Break);
             let pos = pos + 1;
-            if pos >= toks.len() || toks[pos] != lexer::TokType::Semicolon {
-                return Err(format!("Missing `;` needed by break"));
+            if pos >= toks.len() || toks[pos].tok != lexer::TokType::Semicolon {
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    loc_at(toks, pos),
+                    "Missing `;` needed by break",
+                ));
             }
             let pos = pos + 1;
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
         }
         _ => {
             // try to parse exp-option;
-            let mut stmt_node = ParseNode::new();
+            let mut stmt_node = ParseNode::new(loc.clone());
             stmt_node.entry = NodeType::Stmt(StmtType::Exp);
             //let pos = pos + 1;
-            let (exp_opt_node, pos) = r#try!(p_exp_opt(toks, pos));
+            let (exp_opt_node, pos) = p_exp_opt(toks, pos, tags)?;
 
-            let tok = &toks[pos];
+            let tok = &toks[pos].tok;
             if *tok != lexer::TokType::Semicolon {
-                return Err(format!("Expected `;`, found {:?} at {}", toks[pos], pos));
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!("Expected `;`, found `{}`", toks[pos].tok),
+                ));
             }
             let pos = pos + 1;
             stmt_node.child.push(exp_opt_node);
-            return Ok((stmt_node, pos));
+            Ok((stmt_node, pos))
+        }
+    }
+}
+
+// <factor> ::= <primary> { "[" <exp> "]" | "." <id> | "->" <id> | "++" | "--" }
+// `e[i]` is kept as `*(e + i)` and `p->m` as `(*p).m`
+fn p_factor(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let (mut factor_node, mut pos) = p_primary(toks, pos, tags)?;
+    loop {
+        let tok = &toks[pos].tok;
+        match tok {
+            lexer::TokType::Dot | lexer::TokType::Arrow => {
+                let name = match &toks[pos + 1].tok {
+                    lexer::TokType::Identifier(name) => name.to_string(),
+                    _ => {
+                        return Err(Diagnostic::error(
+                            code::SYNTAX,
+                            loc_at(toks, pos + 1),
+                            &format!(
+                                "Expected member name after `{}`, found `{}`",
+                                tok,
+                                toks[pos + 1].tok
+                            ),
+                        ));
+                    }
+                };
+                let loc = factor_node.loc.to(&toks[pos + 1].loc);
+                let mut base = factor_node;
+                if *tok == lexer::TokType::Arrow {
+                    let mut deref_node = ParseNode::new(loc.clone());
+                    deref_node.entry = NodeType::UnExp(lexer::TokType::Multi);
+                    deref_node.child.push(base);
+                    base = deref_node;
+                }
+                let mut member_node = ParseNode::new(loc.clone());
+                member_node.entry = NodeType::Member(name);
+                member_node.child.push(base);
+                factor_node = ParseNode::new(loc);
+                factor_node.entry = NodeType::Factor;
+                factor_node.child.push(member_node);
+                pos += 2;
+            }
+            lexer::TokType::LBracket => {
+                let (index_node, new_pos) = p_comma_exp(toks, pos + 1, tags)?;
+                if toks[new_pos].tok != lexer::TokType::RBracket {
+                    return Err(Diagnostic::error(
+                        code::SYNTAX,
+                        &toks[new_pos].loc,
+                        &format!(
+                            "Expected ']' needed by array referencing, found `{}`",
+                            toks[new_pos].tok
+                        ),
+                    ));
+                }
+                let loc = factor_node.loc.to(&toks[new_pos].loc);
+                let mut add_node = ParseNode::new(loc.clone());
+                add_node.entry = NodeType::BinExp(lexer::TokType::Plus);
+                add_node.child.push(factor_node);
+                add_node.child.push(index_node);
+                let mut deref_node = ParseNode::new(loc.clone());
+                deref_node.entry = NodeType::UnExp(lexer::TokType::Multi);
+                deref_node.child.push(add_node);
+                factor_node = ParseNode::new(loc);
+                factor_node.entry = NodeType::Factor;
+                factor_node.child.push(deref_node);
+                pos = new_pos + 1;
+            }
+            lexer::TokType::LParen => {
+                let mut call_node = ParseNode::new(factor_node.loc.clone());
+                call_node.entry = NodeType::CallPtr;
+                call_node.child.push(factor_node);
+                pos = p_args(toks, pos + 1, tags, &mut call_node)?;
+                factor_node = ParseNode::new(call_node.loc.clone());
+                factor_node.entry = NodeType::Factor;
+                factor_node.child.push(call_node);
+            }
+            lexer::TokType::Inc | lexer::TokType::Dec => {
+                let loc = factor_node.loc.to(&toks[pos].loc);
+                let mut inc_node = ParseNode::new(loc.clone());
+                inc_node.entry = NodeType::IncDec(tok.clone(), true);
+                inc_node.child.push(factor_node);
+                factor_node = ParseNode::new(loc);
+                factor_node.entry = NodeType::Factor;
+                factor_node.child.push(inc_node);
+                pos += 1;
+            }
+            _ => return Ok((factor_node, pos)),
         }
     }
 }
 
-fn p_factor(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_primary(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     if cfg!(feature = "debug") {
         println!("in p_factor with pos: {}, tok = {:?}", pos, toks[pos]);
     }
-    let mut next = &toks[pos];
+    let mut next = &toks[pos].tok;
     let mut pos = pos + 1;
 
     match next {
+        // a cast, or a compound literal which postfix operators can follow
+        lexer::TokType::LParen if is_type_start(&toks[pos].tok) => p_cast(toks, pos, loc, tags),
+        lexer::TokType::Kwd(kwd @ lexer::KwdType::Sizeof)
+        | lexer::TokType::Kwd(kwd @ lexer::KwdType::Alignof) => p_sizeof(toks, pos, loc, kwd, tags),
         lexer::TokType::LParen => {
             // parse expression inside parens
             // factor -> exp
-            let (exp_node, tmp_pos) = r#try!(p_exp(toks, pos));
+            let (exp_node, tmp_pos) = p_comma_exp(toks, pos, tags)?;
             pos = tmp_pos;
-            next = &toks[pos];
-            pos = pos + 1;
+            next = &toks[pos].tok;
+            pos += 1;
             if *next != lexer::TokType::RParen {
-                return Err(format!(
-                    "Expected `)` in file:parser.rs, found {:?} at {}",
-                    toks[pos], pos
-                ));
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos - 1].loc,
+                    &format!("Expected `)`, found `{}`", next),
+                )
+                .note(Some(&loc), "to match this `(`"));
             }
-            let mut factor_node = ParseNode::new();
+            let mut factor_node = ParseNode::new(loc.clone());
             factor_node.entry = NodeType::Factor;
             factor_node.child.push(exp_node);
             // println!("out p_factor with pos: {}", pos);
-            return Ok((factor_node, pos));
+            Ok((factor_node, pos))
         }
-        lexer::TokType::Minus | lexer::TokType::Tilde | lexer::TokType::Exclamation | lexer::TokType::Addr => {
+        lexer::TokType::Minus
+        | lexer::TokType::Tilde
+        | lexer::TokType::Exclamation
+        | lexer::TokType::Addr
+        | lexer::TokType::Multi => {
             // factor -> UnExp -> factor
-            let mut factor_node = ParseNode::new();
-            let mut unexp_node = ParseNode::new();
+            let mut factor_node = ParseNode::new(loc.clone());
+            let mut unexp_node = ParseNode::new(loc.clone());
             factor_node.entry = NodeType::Factor;
             unexp_node.entry = NodeType::UnExp(match next {
                 lexer::TokType::Minus => lexer::TokType::Minus,
                 lexer::TokType::Tilde => lexer::TokType::Tilde,
                 lexer::TokType::Exclamation => lexer::TokType::Exclamation,
                 lexer::TokType::Addr => lexer::TokType::Addr,
-                _ => panic!("Something strange"),
+                lexer::TokType::Multi => lexer::TokType::Multi,
+                _ => return Err(Diagnostic::ice(&loc, "Something strange")),
             });
-            let (next_factor_node, pos) = r#try!(p_factor(toks, pos));
+            let (next_factor_node, pos) = p_factor(toks, pos, tags)?;
             unexp_node.child.push(next_factor_node);
             factor_node.child.push(unexp_node);
-            return Ok((factor_node, pos));
+            Ok((factor_node, pos))
+        }
+        lexer::TokType::Inc | lexer::TokType::Dec => {
+            // factor -> IncDec -> factor
+            let mut factor_node = ParseNode::new(loc.clone());
+            let mut inc_node = ParseNode::new(loc.clone());
+            factor_node.entry = NodeType::Factor;
+            inc_node.entry = NodeType::IncDec(next.clone(), false);
+            let (next_factor_node, pos) = p_factor(toks, pos, tags)?;
+            inc_node.child.push(next_factor_node);
+            factor_node.child.push(inc_node);
+            Ok((factor_node, pos))
         }
         lexer::TokType::String(chars, tag) => {
-            let mut string_node = ParseNode::new();
-            let mut factor_node = ParseNode::new();
-            string_node.entry = NodeType::StringLiteral(chars.to_string(), tag.to_string());
+            let mut string_node = ParseNode::new(loc.clone());
+            let mut factor_node = ParseNode::new(loc.clone());
+            string_node.entry = NodeType::StringLiteral(chars.clone(), tag.to_string());
             factor_node.entry = NodeType::Factor;
             factor_node.child.push(string_node);
 
-            return Ok((factor_node, pos));
+            Ok((factor_node, pos))
         }
-        lexer::TokType::Literal(n) => {
+        lexer::TokType::Literal(n, suffix) => {
             // Factor -> Const
-            let mut const_node = ParseNode::new();
-            let mut factor_node = ParseNode::new();
-            const_node.entry = NodeType::Const(*n);
+            let mut const_node = ParseNode::new(loc.clone());
+            let mut factor_node = ParseNode::new(loc.clone());
+            const_node.entry = NodeType::Const(*n, Type::of_int_const(*n, suffix));
             factor_node.entry = NodeType::Factor;
             factor_node.child.push(const_node);
             // println!("out p_factor with pos: {}", pos);
-            return Ok((factor_node, pos));
+            Ok((factor_node, pos))
+        }
+        lexer::TokType::FloatLiteral(c) => {
+            // Factor -> FloatConst
+            let mut const_node = ParseNode::new(loc.clone());
+            let mut factor_node = ParseNode::new(loc.clone());
+            const_node.entry = NodeType::FloatConst(*c, Type::of_float_const(c.suffix));
+            factor_node.entry = NodeType::Factor;
+            factor_node.child.push(const_node);
+            Ok((factor_node, pos))
+        }
+        lexer::TokType::Identifier(name)
+            if name == "__builtin_va_arg" && toks[pos].tok == lexer::TokType::LParen =>
+        {
+            p_va_arg(toks, pos, loc, tags)
         }
         lexer::TokType::Identifier(var_name) => {
             if cfg!(feature = "debug") {
                 println!("here\n");
             }
-            if pos < toks.len() && toks[pos] == lexer::TokType::LParen {
+            if pos < toks.len() && toks[pos].tok == lexer::TokType::LParen {
                 // Factor -> FnCall
-                let mut factor_node = ParseNode::new();
-                pos = pos - 1;
+                let mut factor_node = ParseNode::new(loc.clone());
+                pos -= 1;
                 factor_node.entry = NodeType::Factor;
-                let (fn_call_node, pos) = r#try!(p_fn_call(toks, pos));
+                let (fn_call_node, pos) = p_fn_call(toks, pos, tags)?;
                 factor_node.child.push(fn_call_node);
-                return Ok((factor_node, pos));
-            } else if pos < toks.len() && toks[pos] == lexer::TokType::LBracket {
+                Ok((factor_node, pos))
+            } else if pos < toks.len() && toks[pos].tok == lexer::TokType::LBracket {
                 // Factor -> Array referencing
-                let mut factor_node = ParseNode::new();
-                pos = pos - 1;
+                let mut factor_node = ParseNode::new(loc.clone());
+                pos -= 1;
                 factor_node.entry = NodeType::Factor;
-                let (arr_ref_node, pos) = r#try!(p_arr_ref(toks, pos));
+                let (arr_ref_node, pos) = p_arr_ref(toks, pos, tags)?;
                 factor_node.child.push(arr_ref_node);
-                return Ok((factor_node, pos));
+                Ok((factor_node, pos))
             } else {
                 // Factor -> Var
-                let mut var_node = ParseNode::new();
-                let mut factor_node = ParseNode::new();
+                let mut var_node = ParseNode::new(loc.clone());
+                let mut factor_node = ParseNode::new(loc.clone());
                 var_node.entry = NodeType::Var(var_name.to_string());
                 factor_node.entry = NodeType::Factor;
                 factor_node.child.push(var_node);
                 // println!("out p_factor with pos: {}", pos);
-                return Ok((factor_node, pos));
+                Ok((factor_node, pos))
             }
         }
-        _ => Err(format!("Factor rule not allowed.")),
+        _ => Err(Diagnostic::error(
+            code::SYNTAX,
+            &loc,
+            &format!("Expected expression, found `{}`", next),
+        )),
     }
 }
 
-fn p_arr_ref(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    // array reference ::= <id> "[" <exp> "]"
-    let mut arr_ref_node = ParseNode::new();
-    let mut var_name = String::new();
-    match &toks[pos] {
-        lexer::TokType::Identifier(name) => {
-            var_name = name.to_string();
+// `(type-name)`, the start of a cast or of a compound literal, with `pos`
+// after its `(` at `loc`
+fn p_cast(
+    toks: &[lexer::Token],
+    pos: usize,
+    loc: Loc,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let (data_type, pos) = p_type_name(toks, pos, tags)?;
+    if toks[pos].tok != lexer::TokType::RParen {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `)` after type name, found `{}`", toks[pos].tok),
+        )
+        .note(Some(&loc), "to match this `(`"));
+    }
+    let mut factor_node = ParseNode::new(loc.clone());
+    factor_node.entry = NodeType::Factor;
+    let (node, pos) = if toks[pos + 1].tok == lexer::TokType::LBrace {
+        let (init_node, pos) = p_initializer(toks, pos + 1, tags)?;
+        let mut literal_node = ParseNode::new(loc.clone());
+        literal_node.entry = NodeType::CompoundLiteral(data_type);
+        literal_node.child.push(init_node);
+        (literal_node, pos)
+    } else {
+        let (operand_node, pos) = p_factor(toks, pos + 1, tags)?;
+        let mut cast_node = ParseNode::new(loc.clone());
+        cast_node.entry = NodeType::Cast(data_type);
+        cast_node.child.push(operand_node);
+        (cast_node, pos)
+    };
+    factor_node.child.push(node);
+    Ok((factor_node, pos))
+}
+
+// `sizeof` or `_Alignof` of a type name or of an operand, with `pos` after
+// the keyword at `loc`
+fn p_sizeof(
+    toks: &[lexer::Token],
+    pos: usize,
+    loc: Loc,
+    kwd: &lexer::KwdType,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let mut node = ParseNode::new(loc.clone());
+    let mut data_type = None;
+    let mut pos = pos;
+    if toks[pos].tok == lexer::TokType::LParen && is_type_start(&toks[pos + 1].tok) {
+        let (t, type_pos) = p_type_name(toks, pos + 1, tags)?;
+        if toks[type_pos].tok != lexer::TokType::RParen {
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[type_pos].loc,
+                &format!(
+                    "Expected `)` after type name, found `{}`",
+                    toks[type_pos].tok
+                ),
+            ));
+        }
+        // `sizeof (int[]){ 1, 2 }` is the size of the compound literal
+        if toks[type_pos + 1].tok != lexer::TokType::LBrace {
+            data_type = Some(t);
+            pos = type_pos + 1;
         }
+    }
+    if data_type.is_none() {
+        let (operand_node, new_pos) = p_factor(toks, pos, tags)?;
+        node.child.push(operand_node);
+        pos = new_pos;
+    }
+    node.entry = match kwd {
+        lexer::KwdType::Sizeof => NodeType::Sizeof(data_type),
+        _ => NodeType::Alignof(data_type),
+    };
+    let mut factor_node = ParseNode::new(loc.clone());
+    factor_node.entry = NodeType::Factor;
+    factor_node.child.push(node);
+    Ok((factor_node, pos))
+}
+
+// `__builtin_va_arg(ap, type-name)`, with `pos` at its `(`
+fn p_va_arg(
+    toks: &[lexer::Token],
+    pos: usize,
+    loc: Loc,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let (ap_node, pos) = p_exp(toks, pos + 1, tags)?;
+    if toks[pos].tok != lexer::TokType::Comma {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `,`, found `{}`", toks[pos].tok),
+        ));
+    }
+    let (data_type, pos) = p_type_name(toks, pos + 1, tags)?;
+    if toks[pos].tok != lexer::TokType::RParen {
+        return Err(Diagnostic::error(
+            code::SYNTAX,
+            &toks[pos].loc,
+            &format!("Expected `)` after type name, found `{}`", toks[pos].tok),
+        ));
+    }
+    let mut va_arg_node = ParseNode::new(loc.clone());
+    va_arg_node.entry = NodeType::VaArg(data_type);
+    va_arg_node.child.push(ap_node);
+    let mut factor_node = ParseNode::new(loc);
+    factor_node.entry = NodeType::Factor;
+    factor_node.child.push(va_arg_node);
+    Ok((factor_node, pos + 1))
+}
+
+fn p_arr_ref(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    // array reference ::= <id> "[" <exp> "]"
+    let mut arr_ref_node = ParseNode::new(loc.clone());
+    let var_name = match &toks[pos].tok {
+        lexer::TokType::Identifier(name) => name.to_string(),
         _ => {
-            return Err(format!(
-                "Expected array identifier, foudn {:?} at {}",
-                toks[pos], pos
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!("Expected array identifier, found `{}`", toks[pos].tok),
             ));
         }
-    }
+    };
     arr_ref_node.entry = NodeType::ArrayRef(var_name);
 
     let mut pos = pos + 1;
     // match '['
-    match toks[pos] {
+    match toks[pos].tok {
         lexer::TokType::LBracket => {
-            pos = pos + 1;
+            pos += 1;
         }
         _ => {
-            return Err(format!(
-                "Expected `[` needed by array referencing, found {:?} at {}",
-                toks[pos], pos
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!(
+                    "Expected `[` needed by array referencing, found `{}`",
+                    toks[pos].tok
+                ),
             ));
         }
     }
 
-    let (exp_node, new_pos) = r#try!(p_exp(toks, pos));
+    let (exp_node, new_pos) = p_comma_exp(toks, pos, tags)?;
     arr_ref_node.child.push(exp_node);
-    match toks[new_pos] {
+    match toks[new_pos].tok {
         lexer::TokType::RBracket => {
             pos = new_pos + 1;
         }
         _ => {
-            return Err(format!(
-                "Expected ']' needed by array referencing, found {:?} at {}",
-                toks[pos], pos
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[new_pos].loc,
+                &format!(
+                    "Expected ']' needed by array referencing, found `{}`",
+                    toks[new_pos].tok
+                ),
             ));
         }
     }
-    return Ok((arr_ref_node, pos));
+    Ok((arr_ref_node, pos))
 }
-fn p_fn_call(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_fn_call(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // <function-call> ::= id "(" [ <exp> { "," <exp> } ] ")"
     //println!("in fn p_fn_call");
-    let mut fn_call_node = ParseNode::new();
-    let mut fn_name = String::new();
-    match &toks[pos] {
-        lexer::TokType::Identifier(name) => {
-            fn_name = name.to_string();
-        }
+    let mut fn_call_node = ParseNode::new(loc.clone());
+    let fn_name = match &toks[pos].tok {
+        lexer::TokType::Identifier(name) => name.to_string(),
         _ => {
-            return Err(format!(
-                "Expected function name, found {:?} at {}",
-                toks[pos], pos
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!("Expected function name, found `{}`", toks[pos].tok),
             ));
         }
-    }
+    };
     fn_call_node.entry = NodeType::FnCall(fn_name);
     let mut pos = pos + 1;
     // match '('
-    match toks[pos] {
+    match toks[pos].tok {
         lexer::TokType::LParen => {
-            pos = pos + 1;
+            pos += 1;
         }
         _ => {
-            return Err(format!(
-                "Expected `(` needed by function call, found {:?} at {}",
-                toks[pos], pos
+            return Err(Diagnostic::error(
+                code::SYNTAX,
+                &toks[pos].loc,
+                &format!(
+                    "Expected `(` needed by function call, found `{}`",
+                    toks[pos].tok
+                ),
             ));
         }
     }
-    while pos < toks.len() && toks[pos] != lexer::TokType::RParen {
+    let pos = p_args(toks, pos, tags, &mut fn_call_node)?;
+    Ok((fn_call_node, pos))
+}
+
+// the arguments of a call into the kids of `call_node`, `pos` is after the
+// `(`; gives the position after the `)`
+fn p_args(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+    call_node: &mut ParseNode,
+) -> Result<usize, Diagnostic> {
+    let mut pos = pos;
+    while pos < toks.len() && toks[pos].tok != lexer::TokType::RParen {
         // try to parse argument exp
-        let (exp_node, new_pos) = r#try!(p_exp(toks, pos));
-        fn_call_node.child.push(exp_node);
+        let (exp_node, new_pos) = p_exp(toks, pos, tags)?;
+        call_node.child.push(exp_node);
         pos = new_pos;
 
         // match ,
-        match &toks[pos] {
+        match &toks[pos].tok {
             lexer::TokType::Comma => {
-                pos = pos + 1;
+                pos += 1;
             }
             lexer::TokType::RParen => {
                 continue;
             }
             _ => {
-                return Err(format!(
-                    "Expected `,` or `)` at the end of exp, found {:?} at {}",
-                    toks[pos], pos
+                return Err(Diagnostic::error(
+                    code::SYNTAX,
+                    &toks[pos].loc,
+                    &format!(
+                        "Expected `,` or `)` at the end of exp, found `{}`",
+                        toks[pos].tok
+                    ),
                 ));
             }
         }
-        if toks[pos] == lexer::TokType::RParen {
+        if toks[pos].tok == lexer::TokType::RParen {
             break;
         }
     }
-    pos = pos + 1;
-    return Ok((fn_call_node, pos));
+    Ok(pos + 1)
 }
 
-fn p_logical_and_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    let mut logAndExp_node = ParseNode::new();
-    logAndExp_node.entry = NodeType::LogicalAndExp;
+fn p_logical_and_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let mut log_and_exp_node = ParseNode::new(loc.clone());
+    log_and_exp_node.entry = NodeType::LogicalAndExp;
 
-    // LogicalAndExp -> EqualityExp
+    // LogicalAndExp -> BitOrExp
     let mut pos = pos;
-    let (eq_node, tmp_pos) = r#try!(p_eq_exp(toks, pos));
+    let (eq_node, tmp_pos) = p_bit_or_exp(toks, pos, tags)?;
     pos = tmp_pos;
-    let mut tok = &toks[pos];
-    pos = pos + 1;
+    let mut tok = &toks[pos].tok;
+    pos += 1;
     if *tok != lexer::TokType::And {
-        logAndExp_node.child.push(eq_node);
-        pos = pos - 1;
-        return Ok((logAndExp_node, pos));
+        log_and_exp_node.child.push(eq_node);
+        pos -= 1;
+        return Ok((log_and_exp_node, pos));
     }
-    // Term -> BinExp -> (EqualityExp, EqualityExp)
+    // Term -> BinExp -> (BitOrExp, BitOrExp)
     let mut eq_node = eq_node; // change to mutable
     while *tok == lexer::TokType::And {
-        let mut binexp_node = ParseNode::new();
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(lexer::TokType::And);
 
-        let (rhs, tmp_pos) = r#try!(p_eq_exp(toks, pos));
+        let (rhs, tmp_pos) = p_bit_or_exp(toks, pos, tags)?;
 
         binexp_node.child.push(eq_node);
         binexp_node.child.push(rhs);
         eq_node = binexp_node;
         pos = tmp_pos;
-        tok = &toks[pos];
-        pos = pos + 1;
+        tok = &toks[pos].tok;
+        pos += 1;
+    }
+    log_and_exp_node.child.push(eq_node);
+    pos -= 1;
+    Ok((log_and_exp_node, pos))
+}
+
+type ParseFn = fn(&[lexer::Token], usize, &mut Tags) -> Result<(ParseNode, usize), Diagnostic>;
+
+// <operand> { <op> <operand> } for the left associative binary operators
+// `ops` of one precedence, `next` parses the operands
+fn p_binary(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+    ops: &[lexer::TokType],
+    next: ParseFn,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let (mut lhs, mut pos) = next(toks, pos, tags)?;
+    while ops.contains(&toks[pos].tok) {
+        let mut binexp_node = ParseNode::new(loc.clone());
+        binexp_node.entry = NodeType::BinExp(toks[pos].tok.clone());
+        let (rhs, tmp_pos) = next(toks, pos + 1, tags)?;
+        binexp_node.child.push(lhs);
+        binexp_node.child.push(rhs);
+        lhs = binexp_node;
+        pos = tmp_pos;
     }
-    logAndExp_node.child.push(eq_node);
-    pos = pos - 1;
-    return Ok((logAndExp_node, pos));
+    Ok((lhs, pos))
+}
+
+// <bit-or-exp> ::= <bit-xor-exp> { "|" <bit-xor-exp> }
+fn p_bit_or_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    p_binary(toks, pos, tags, &[lexer::TokType::BitOr], p_bit_xor_exp)
+}
+
+// <bit-xor-exp> ::= <bit-and-exp> { "^" <bit-and-exp> }
+fn p_bit_xor_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    p_binary(toks, pos, tags, &[lexer::TokType::BitXor], p_bit_and_exp)
+}
+
+// <bit-and-exp> ::= <equality-exp> { "&" <equality-exp> }
+fn p_bit_and_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    p_binary(toks, pos, tags, &[lexer::TokType::Addr], p_eq_exp)
 }
 
-fn p_eq_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    let mut eq_node = ParseNode::new();
+// <shift-exp> ::= <additive-exp> { ("<<" | ">>") <additive-exp> }
+fn p_shift_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    p_binary(
+        toks,
+        pos,
+        tags,
+        &[lexer::TokType::Shl, lexer::TokType::Shr],
+        p_additive_exp,
+    )
+}
+
+fn p_eq_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let mut eq_node = ParseNode::new(loc.clone());
     eq_node.entry = NodeType::EqualityExp;
 
     let mut pos = pos;
-    let (relational_node, tmp_pos) = r#try!(p_relational_exp(toks, pos));
+    let (relational_node, tmp_pos) = p_relational_exp(toks, pos, tags)?;
     pos = tmp_pos;
-    let mut tok = &toks[pos];
-    pos = pos + 1;
+    let mut tok = &toks[pos].tok;
+    pos += 1;
     if *tok != lexer::TokType::NotEqual && *tok != lexer::TokType::Equal {
         eq_node.child.push(relational_node);
-        pos = pos - 1;
+        pos -= 1;
         return Ok((eq_node, pos));
     }
 
     let mut relational_node = relational_node;
     while *tok == lexer::TokType::Equal || *tok == lexer::TokType::NotEqual {
-        let mut binexp_node = ParseNode::new();
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(match tok {
             lexer::TokType::Equal => lexer::TokType::Equal,
             lexer::TokType::NotEqual => lexer::TokType::NotEqual,
-            _ => panic!("in p_eq_exp, something went wrong"),
+            _ => return Err(Diagnostic::ice(&loc, "in p_eq_exp, something went wrong")),
         });
 
-        let (next_relational_node, tmp_pos) = r#try!(p_relational_exp(toks, pos));
+        let (next_relational_node, tmp_pos) = p_relational_exp(toks, pos, tags)?;
 
         binexp_node.child.push(relational_node);
         binexp_node.child.push(next_relational_node);
         relational_node = binexp_node;
         pos = tmp_pos;
-        tok = &toks[pos];
-        pos = pos + 1;
+        tok = &toks[pos].tok;
+        pos += 1;
     }
     eq_node.child.push(relational_node);
-    pos = pos - 1;
-    return Ok((eq_node, pos));
+    pos -= 1;
+    Ok((eq_node, pos))
 }
 
-fn p_relational_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    let mut relational_node = ParseNode::new();
+fn p_relational_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
+    let mut relational_node = ParseNode::new(loc.clone());
     relational_node.entry = NodeType::RelationalExp;
 
     let mut pos = pos;
-    let (additive_exp_node, tmp_pos) = r#try!(p_additive_exp(toks, pos));
+    let (additive_exp_node, tmp_pos) = p_shift_exp(toks, pos, tags)?;
     pos = tmp_pos;
-    let mut tok = &toks[pos];
-    pos = pos + 1;
+    let mut tok = &toks[pos].tok;
+    pos += 1;
     if *tok != lexer::TokType::Lt
         && *tok != lexer::TokType::Gt
         && *tok != lexer::TokType::GreaterEqual
         && *tok != lexer::TokType::LessEqual
     {
         relational_node.child.push(additive_exp_node);
-        pos = pos - 1;
+        pos -= 1;
         return Ok((relational_node, pos));
     }
 
@@ -1574,78 +3052,100 @@ fn p_relational_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         || *tok == lexer::TokType::GreaterEqual
         || *tok == lexer::TokType::LessEqual
     {
-        let mut binexp_node = ParseNode::new();
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(match tok {
             lexer::TokType::Lt => lexer::TokType::Lt,
             lexer::TokType::Gt => lexer::TokType::Gt,
             lexer::TokType::GreaterEqual => lexer::TokType::GreaterEqual,
             lexer::TokType::LessEqual => lexer::TokType::LessEqual,
-            _ => panic!("in p_relational_exp, something went wrong"),
+            _ => {
+                return Err(Diagnostic::ice(
+                    &loc,
+                    "in p_relational_exp, something went wrong",
+                ))
+            }
         });
-        let (next_additive_exp_node, tmp_pos) = r#try!(p_additive_exp(toks, pos));
+        let (next_additive_exp_node, tmp_pos) = p_shift_exp(toks, pos, tags)?;
         binexp_node.child.push(additive_exp_node);
         binexp_node.child.push(next_additive_exp_node);
         additive_exp_node = binexp_node;
         pos = tmp_pos;
-        tok = &toks[pos];
-        pos = pos + 1;
+        tok = &toks[pos].tok;
+        pos += 1;
     }
     relational_node.child.push(additive_exp_node);
-    pos = pos - 1;
-    return Ok((relational_node, pos));
+    pos -= 1;
+    Ok((relational_node, pos))
 }
-fn p_term(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_term(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // println!("in p_term with pos: {}", pos);
-    let mut term_node = ParseNode::new();
+    let mut term_node = ParseNode::new(loc.clone());
     term_node.entry = NodeType::Term;
 
     // term -> factor
     let mut pos = pos;
-    let (factor_node, tmp_pos) = r#try!(p_factor(toks, pos));
+    let (factor_node, tmp_pos) = p_factor(toks, pos, tags)?;
     pos = tmp_pos;
-    let mut tok = &toks[pos];
-    pos = pos + 1;
-    if *tok != lexer::TokType::Multi && *tok != lexer::TokType::Splash {
+    let mut tok = &toks[pos].tok;
+    pos += 1;
+    if *tok != lexer::TokType::Multi
+        && *tok != lexer::TokType::Splash
+        && *tok != lexer::TokType::Percent
+    {
         term_node.child.push(factor_node);
-        pos = pos - 1;
+        pos -= 1;
         // println!("1. out p_term with pos: {}", pos);
         return Ok((term_node, pos));
     }
 
     // term -> BinExp -> (factor_left, factor_right)
     let mut factor_node = factor_node;
-    while *tok == lexer::TokType::Multi || *tok == lexer::TokType::Splash {
-        let mut binexp_node = ParseNode::new();
+    while *tok == lexer::TokType::Multi
+        || *tok == lexer::TokType::Splash
+        || *tok == lexer::TokType::Percent
+    {
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(match tok {
             lexer::TokType::Multi => lexer::TokType::Multi,
             lexer::TokType::Splash => lexer::TokType::Splash,
-            _ => panic!("in p_term, something went wrong"),
+            lexer::TokType::Percent => lexer::TokType::Percent,
+            _ => return Err(Diagnostic::ice(&loc, "in p_term, something went wrong")),
         });
 
-        let (next_factor_node, tmp_pos) = r#try!(p_factor(toks, pos));
+        let (next_factor_node, tmp_pos) = p_factor(toks, pos, tags)?;
 
         binexp_node.child.push(factor_node);
         binexp_node.child.push(next_factor_node);
         factor_node = binexp_node;
         pos = tmp_pos;
-        tok = &toks[pos];
-        pos = pos + 1;
+        tok = &toks[pos].tok;
+        pos += 1;
     }
     term_node.child.push(factor_node);
-    pos = pos - 1;
+    pos -= 1;
     // println!("2. out p_term with pos: {}", pos);
-    return Ok((term_node, pos));
+    Ok((term_node, pos))
 }
 
-fn p_additive_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
+fn p_additive_exp(
+    toks: &[lexer::Token],
+    pos: usize,
+    tags: &mut Tags,
+) -> Result<(ParseNode, usize), Diagnostic> {
+    let loc = loc_at(toks, pos).clone();
     // println!("in p_exp with pos: {}", pos);
-    let mut exp_node = ParseNode::new();
+    let mut exp_node = ParseNode::new(loc.clone());
     exp_node.entry = NodeType::AdditiveExp;
     // exp -> term
     let mut pos = pos;
-    let (term_node, tmp_pos) = r#try!(p_term(toks, pos));
+    let (term_node, tmp_pos) = p_term(toks, pos, tags)?;
     pos = tmp_pos;
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].tok;
     if *tok != lexer::TokType::Plus && *tok != lexer::TokType::Minus {
         exp_node.child.push(term_node);
         // println!("1.out p_exp with pos: {}", pos);
@@ -1654,80 +3154,124 @@ fn p_additive_exp(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usi
     // exp -> BinExp()
     //peek next token, if it is lexer::TokType::Plus or lexer::TokType::Minus
     let mut term_node = term_node;
-    let mut pos = pos;
     while *tok == lexer::TokType::Plus || *tok == lexer::TokType::Minus {
-        let mut binexp_node = ParseNode::new();
+        let mut binexp_node = ParseNode::new(loc.clone());
         binexp_node.entry = NodeType::BinExp(match tok {
             lexer::TokType::Plus => lexer::TokType::Plus,
             lexer::TokType::Minus => lexer::TokType::Minus,
-            _ => panic!("in parser::p_exp, something went wrong"),
+            _ => {
+                return Err(Diagnostic::ice(
+                    &loc,
+                    "in parser::p_exp, something went wrong",
+                ))
+            }
         });
-        pos = pos + 1;
-        let (next_term_node, tmp_pos) = r#try!(p_term(toks, pos));
+        pos += 1;
+        let (next_term_node, tmp_pos) = p_term(toks, pos, tags)?;
         pos = tmp_pos;
         binexp_node.child.push(term_node);
         binexp_node.child.push(next_term_node);
         term_node = binexp_node;
-        tok = &toks[pos];
+        tok = &toks[pos].tok;
     }
     exp_node.child.push(term_node);
-    return Ok((exp_node, pos));
+    Ok((exp_node, pos))
+}
+
+// the tree for the tokens `lexer::lex` gives for a translation unit
+pub fn parse_prog(toks: &[lexer::Token], c_src_name: &str) -> Result<ParseNode, Diagnostic> {
+    // every lookahead stops at the end of input `lex` puts after the tokens
+    let eof = match toks.last() {
+        Some(last) if last.tok == lexer::TokType::Eof => &last.loc,
+        _ => {
+            return Err(Diagnostic::new(
+                Severity::Ice,
+                None,
+                None,
+                "the tokens to parse do not end with the end of input",
+            ))
+        }
+    };
+    p_prog(toks, c_src_name).map_err(|e| {
+        // what was expected at the end says little, the input stops too early
+        if e.code == Some(code::SYNTAX) && e.loc.as_ref() == Some(eof) {
+            Diagnostic::error(code::SYNTAX, eof, "unexpected end of input").note(None, &e.message)
+        } else {
+            e
+        }
+    })
 }
 
-pub fn parse_prog(input: &str, c_src_name: &str) -> Result<ParseNode, String> {
-    let toks = r#try!(lexer::lex(&input));
-    let mut prog_node = ParseNode::new();
+fn p_prog(toks: &[lexer::Token], c_src_name: &str) -> Result<ParseNode, Diagnostic> {
+    let end = toks[toks.len() - 1].loc.span.0;
+    let mut prog_node = ParseNode::new(Loc::new(Rc::from(c_src_name), 1, 1, (0, end)));
     prog_node.entry = NodeType::Prog(c_src_name.to_string());
     let mut pos = 0;
+    let mut tags = Tags::new();
     // now we need to add support for global variables
-    while pos < toks.len() {
-        // try to parse global variable declaration
-        let p_res = p_declare(&toks, pos);
-        match p_res {
-            Ok((decl_node, new_pos)) => {
-                pos = new_pos;
-                prog_node.child.push(decl_node);
-            }
-            Err(_) => {
-                // try to parse fn definition
-                if cfg!(feature = "debug") {
-                    println!("try to parse fn definition");
-                }
-                let (fn_node, new_pos) = r#try!(p_fn(&toks, pos));
-                prog_node.child.push(fn_node);
-                pos = new_pos;
+    while toks[pos].tok != lexer::TokType::Eof {
+        let storage = match toks[pos].tok {
+            lexer::TokType::Kwd(lexer::KwdType::Static) => Storage::Static,
+            lexer::TokType::Kwd(lexer::KwdType::Extern) => Storage::Extern,
+            _ => Storage::None,
+        };
+        if storage != Storage::None {
+            pos += 1;
+        }
+        // `<type> <id> (` starts a function definition or prototype, everything else is a global variable declaration
+        let (data_type, name_pos) = p_type(toks, pos, &mut tags)?;
+        if name_pos + 1 < toks.len() && toks[name_pos + 1].tok == lexer::TokType::LParen {
+            if cfg!(feature = "debug") {
+                println!("try to parse fn definition");
             }
+            let (fn_node, new_pos) = p_fn(toks, name_pos, &mut tags, data_type, storage)?;
+            prog_node.child.push(fn_node);
+            pos = new_pos;
+        } else {
+            let (decl_node, new_pos) = p_declarator(toks, name_pos, data_type, storage, &mut tags)?;
+            prog_node.child.push(decl_node);
+            pos = new_pos;
         }
     }
 
-    return Ok(prog_node);
+    fix_spans(&mut prog_node);
+    Ok(prog_node)
+}
+
+// the child `i` of `tree`, a missing one is a bug in the parser
+fn child(tree: &ParseNode, i: usize) -> Result<&ParseNode, Diagnostic> {
+    tree.child.get(i).ok_or_else(|| {
+        Diagnostic::ice(
+            &tree.loc,
+            &format!("{:?} node has no child {}", tree.entry, i),
+        )
+    })
 }
 
-// XXX: should change the return type to Result<String, String> to remove panic!()
-pub fn print(tree: &ParseNode, idt: usize) -> String {
+// dump the AST for --crust-print-source-ast
+pub fn print(tree: &ParseNode, idt: usize) -> Result<String, Diagnostic> {
     let mut idt_prefix = String::new();
     for _i in 0..idt {
-        idt_prefix = idt_prefix + " ";
+        idt_prefix += " ";
     }
-    match &tree.entry {
+    let s = match &tree.entry {
         NodeType::StringLiteral(data, tag) => format!(
             "{}n_type: StringLiteral, tag: {}, data: [{}]",
-            idt_prefix, tag, data,
+            idt_prefix,
+            tag,
+            String::from_utf8_lossy(data).escape_debug(),
         ),
         NodeType::ArrayRef(var_name) => format!(
             "{}n_type: ArrayRef, var_name : {}, [\n{}\n{}]",
             idt_prefix,
             var_name,
-            print(tree.child.get(0).unwrap(), idt + 1),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::Factor => format!(
             "{}n_type: Factor, [\n{}\n{}]",
             idt_prefix,
-            print(
-                tree.child.get(0).expect("Factor Node has no child"),
-                idt + 1
-            ),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::AssignNode(var_name, flag) => {
@@ -1736,10 +3280,7 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                     "{}n_type: AssignNode, Var: {} [\n{}\n{}]",
                     idt_prefix,
                     var_name,
-                    print(
-                        tree.child.get(0).expect("Assign Node has no child"),
-                        idt + 1
-                    ),
+                    print(child(tree, 0)?, idt + 1)?,
                     idt_prefix
                 ),
                 true => {
@@ -1748,57 +3289,81 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                         "{}n_type: AssignNode array: {} [\n{}\n{}\n{}]",
                         idt_prefix,
                         var_name,
-                        print(
-                            tree.child
-                                .get(0)
-                                .expect("Assign to array Node has no index node "),
-                            idt + 1
-                        ),
-                        print(
-                            tree.child.get(1).expect("Assign to array node has no rhs"),
-                            idt + 1
-                        ),
+                        print(child(tree, 0)?, idt + 1)?,
+                        print(child(tree, 1)?, idt + 1)?,
                         idt_prefix,
                     )
                 }
             }
         }
+        NodeType::Assign => format!(
+            "{}n_type: Assign [\n{}\n{}\n{}]",
+            idt_prefix,
+            print(child(tree, 0)?, idt + 1)?,
+            print(child(tree, 1)?, idt + 1)?,
+            idt_prefix,
+        ),
+        NodeType::AssignOp(op) => format!(
+            "{}n_type: AssignOp, Op: {}= [\n{}\n{}\n{}]",
+            idt_prefix,
+            op,
+            print(child(tree, 0)?, idt + 1)?,
+            print(child(tree, 1)?, idt + 1)?,
+            idt_prefix,
+        ),
+        NodeType::IncDec(op, postfix) => format!(
+            "{}n_type: IncDec, Op: {}, postfix: {} [\n{}\n{}]",
+            idt_prefix,
+            op,
+            postfix,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix,
+        ),
         NodeType::BinExp(op) => format!(
             "{}n_type: BinExp, Op: {} [\n{}\n{}\n{}]",
             idt_prefix,
             match op {
-                lexer::TokType::Minus => format!("-"),
-                lexer::TokType::Plus => format!("+"),
-                lexer::TokType::Multi => format!("*"),
-                lexer::TokType::Splash => format!("/"),
-                lexer::TokType::And => format!("&&"),
-                lexer::TokType::Or => format!("||"),
-                lexer::TokType::Equal => format!("=="),
-                lexer::TokType::NotEqual => format!("!="),
-                lexer::TokType::GreaterEqual => format!(">="),
-                lexer::TokType::LessEqual => format!("<="),
-                lexer::TokType::Lt => format!("<"),
-                lexer::TokType::Gt => format!(">"),
-                _ => panic!(format!(
-                    "Operator `{:?}` for Binary Expression not supported",
-                    &op
-                )),
+                lexer::TokType::Minus => "-".to_string(),
+                lexer::TokType::Plus => "+".to_string(),
+                lexer::TokType::Multi => "*".to_string(),
+                lexer::TokType::Splash => "/".to_string(),
+                lexer::TokType::And => "&&".to_string(),
+                lexer::TokType::Or => "||".to_string(),
+                lexer::TokType::Equal => "==".to_string(),
+                lexer::TokType::NotEqual => "!=".to_string(),
+                lexer::TokType::GreaterEqual => ">=".to_string(),
+                lexer::TokType::LessEqual => "<=".to_string(),
+                lexer::TokType::Lt => "<".to_string(),
+                lexer::TokType::Gt => ">".to_string(),
+                lexer::TokType::Percent
+                | lexer::TokType::Addr
+                | lexer::TokType::BitOr
+                | lexer::TokType::BitXor
+                | lexer::TokType::Shl
+                | lexer::TokType::Shr
+                | lexer::TokType::Comma => op.to_string(),
+                _ => {
+                    return Err(Diagnostic::ice(
+                        &tree.loc,
+                        &format!("Operator `{:?}` for Binary Expression not supported", &op),
+                    ));
+                }
             },
-            print(tree.child.get(0).expect("BinExp no lhs"), idt + 1),
-            print(tree.child.get(1).expect("BinExp no rhs"), idt + 1),
+            print(child(tree, 0)?, idt + 1)?,
+            print(child(tree, 1)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::Term => format!(
             "{}n_type: Term, [\n{}\n{}]",
             idt_prefix,
-            print(tree.child.get(0).expect("Term Node has no child"), idt + 1),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::Prog(prog_name) => {
             let mut prog_body = String::new();
             for it in tree.child.iter() {
-                prog_body.push_str(&print(it, idt + 1));
-                prog_body.push_str("\n");
+                prog_body.push_str(&print(it, idt + 1)?);
+                prog_body.push('\n');
             }
             format!(
                 "{}n_type: Prog, Name:{} [\n{}\n{}]",
@@ -1807,13 +3372,11 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
         }
         NodeType::FnCall(fn_name) => {
             let mut tmp = String::new();
-            let mut inc = 0;
-            for it in tree.child.iter() {
+            for (inc, it) in tree.child.iter().enumerate() {
                 if inc > 0 {
-                    tmp.push_str("\n");
+                    tmp.push('\n');
                 }
-                tmp.push_str(&print(it, idt + 1));
-                inc = inc + 1;
+                tmp.push_str(&print(it, idt + 1)?);
             }
 
             format!(
@@ -1822,67 +3385,114 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
             )
             // list of exp
         }
-        NodeType::Fn(fn_name, vars) => {
+        NodeType::CallPtr => {
             let mut tmp = String::new();
-            let mut inc = 0;
-            for it in tree.child.iter() {
+            for (inc, it) in tree.child.iter().enumerate() {
                 if inc > 0 {
-                    tmp.push_str("\n");
+                    tmp.push('\n');
                 }
-                tmp.push_str(&print(it, idt + 1));
-                inc = inc + 1;
+                tmp.push_str(&print(it, idt + 1)?);
             }
+            format!("{}n_type: CallPtr [\n{}\n{}]", idt_prefix, tmp, idt_prefix)
+        }
+        NodeType::FnDecl(fn_name, ret_type, vars, variadic, storage) => {
             let mut var_list_string = String::new();
-            match vars {
-                Some(var_list) => {
-                    for var in var_list {
-                        var_list_string.push_str(" ");
-                        var_list_string.push_str(var);
-                        var_list_string.push_str(" ");
-                    }
+            if let Some(var_list) = vars {
+                for (var, var_type) in var_list {
+                    var_list_string.push_str(&format!(" {} {} ", var_type, var));
+                }
+            }
+            if *variadic {
+                var_list_string.push_str(" ... ");
+            }
+            format!(
+                "{}n_type: FnDecl, Name: {} ret_type: {}{} var_list: [{}]",
+                idt_prefix, fn_name, storage, ret_type, var_list_string
+            )
+        }
+        NodeType::Fn(fn_name, ret_type, vars, variadic, storage) => {
+            let mut tmp = String::new();
+            for (inc, it) in tree.child.iter().enumerate() {
+                if inc > 0 {
+                    tmp.push('\n');
+                }
+                tmp.push_str(&print(it, idt + 1)?);
+            }
+            let mut var_list_string = String::new();
+            if let Some(var_list) = vars {
+                for (var, var_type) in var_list {
+                    var_list_string.push_str(&format!(" {} {} ", var_type, var));
                 }
-                None => {}
+            }
+            if *variadic {
+                var_list_string.push_str(" ... ");
             }
             format!(
-                "{}n_type: Fn, Name: {} var_list: [{}]\n\
+                "{}n_type: Fn, Name: {} ret_type: {}{} var_list: [{}]\n\
                  {}[\n{}\n{}]",
-                idt_prefix, fn_name, var_list_string, idt_prefix, tmp, idt_prefix
+                idt_prefix,
+                fn_name,
+                storage,
+                ret_type,
+                var_list_string,
+                idt_prefix,
+                tmp,
+                idt_prefix
             )
         }
-        NodeType::Declare(var_name, t) => match t {
-            DataType::I64 => {
-                if tree.child.is_empty() {
-                    format!(
-                        "{}n_type: Declare, type: Int var_name: {}",
-                        idt_prefix, var_name
-                    )
-                } else {
-                    format!(
-                        "{}n_type: Declare, type: Int var_name: {}, [\n{}\n{}]",
-                        idt_prefix,
-                        var_name,
-                        print(
-                            tree.child.get(0).expect("Declare Node has no child"),
-                            idt + 1
-                        ),
-                        idt_prefix
-                    )
+        NodeType::Declare(var_name, t, storage) => {
+            let declare = match t {
+                Type::Array(elem, len) => format!(
+                    "{}n_type: Declare, type: {}Array of {}  var_name: {}, length: {}",
+                    idt_prefix, storage, elem, var_name, len,
+                ),
+                _ => format!(
+                    "{}n_type: Declare, type: {}{} var_name: {}",
+                    idt_prefix, storage, t, var_name
+                ),
+            };
+            match tree.child.first() {
+                Some(init) => format!("{}, [\n{}\n{}]", declare, print(init, idt + 1)?, idt_prefix),
+                None => declare,
+            }
+        }
+        NodeType::InitList => {
+            let mut tmp = String::new();
+            for (inc, it) in tree.child.iter().enumerate() {
+                if inc > 0 {
+                    tmp.push('\n');
                 }
+                tmp.push_str(&print(it, idt + 1)?);
             }
-            DataType::Arr64(len) => format!(
-                "{}n_type: Declare, type: Array  var_name: {}, length: {}",
-                idt_prefix, var_name, len,
-            ),
-        },
+            format!(
+                "{}n_type: InitList, [\n{}\n{}]",
+                idt_prefix, tmp, idt_prefix
+            )
+        }
+        NodeType::Designated(designators) => {
+            let designators: Vec<String> = designators.iter().map(|d| d.to_string()).collect();
+            format!(
+                "{}n_type: Designated, designators: {} [\n{}\n{}]",
+                idt_prefix,
+                designators.concat(),
+                print(child(tree, 0)?, idt + 1)?,
+                idt_prefix
+            )
+        }
+        NodeType::InitAt(offset) => format!(
+            "{}n_type: InitAt, offset: {} [\n{}\n{}]",
+            idt_prefix,
+            offset,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix
+        ),
         NodeType::ConditionalExp => {
             let mut tmp = String::new();
-            let mut inc = 0;
-            for it in tree.child.iter() {
+            for (inc, it) in tree.child.iter().enumerate() {
                 if inc > 0 {
-                    tmp.push_str("\n");
+                    tmp.push('\n');
                 }
-                tmp.push_str(&print(it, idt + 1));
-                inc = inc + 1;
+                tmp.push_str(&print(it, idt + 1)?);
             }
             format!(
                 "{}n_type: Stmt::ConditionalExp, [\n{}\n{}]",
@@ -1891,10 +3501,10 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
         }
         NodeType::Stmt(stmt) => match stmt {
             StmtType::For => {
-                let exp_opt_1 = print(tree.child.get(0).expect("No exp1 in for"), idt + 1);
-                let exp_opt_2 = print(tree.child.get(1).expect("No exp2 in for"), idt + 1);
-                let exp_opt_3 = print(tree.child.get(2).expect("No exp3 in for"), idt + 1);
-                let stmt = print(tree.child.get(3).expect("No stmt in for"), idt + 1);
+                let exp_opt_1 = print(child(tree, 0)?, idt + 1)?;
+                let exp_opt_2 = print(child(tree, 1)?, idt + 1)?;
+                let exp_opt_3 = print(child(tree, 2)?, idt + 1)?;
+                let stmt = print(child(tree, 3)?, idt + 1)?;
                 format!(
                     "{}n_type: Stmt:ForDeclare, [\n\
                      {}declare: [\n{}\n{}]\n\
@@ -1919,10 +3529,10 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                 )
             }
             StmtType::ForDecl => {
-                let d = print(tree.child.get(0).expect("No declaration in for"), idt + 1);
-                let exp_opt_1 = print(tree.child.get(1).expect("No exp1 in for"), idt + 1);
-                let exp_opt_2 = print(tree.child.get(2).expect("No exp2 in for"), idt + 1);
-                let stmt = print(tree.child.get(3).expect("No stmt in for"), idt + 1);
+                let d = print(child(tree, 0)?, idt + 1)?;
+                let exp_opt_1 = print(child(tree, 1)?, idt + 1)?;
+                let exp_opt_2 = print(child(tree, 2)?, idt + 1)?;
+                let stmt = print(child(tree, 3)?, idt + 1)?;
 
                 format!(
                     "{}n_type: Stmt:ForDeclare, [\n\
@@ -1948,8 +3558,8 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                 )
             }
             StmtType::Do => {
-                let stmt = print(tree.child.get(0).expect("No stmt in do"), idt + 1);
-                let exp = print(tree.child.get(1).expect("No exp in do"), idt + 1);
+                let stmt = print(child(tree, 0)?, idt + 1)?;
+                let exp = print(child(tree, 1)?, idt + 1)?;
                 format!(
                     "{}n_type: Stmt:Do, [\n\
                      {}           stmt: [\n{}\n{}]\n\
@@ -1966,8 +3576,8 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                 )
             }
             StmtType::While => {
-                let exp = print(tree.child.get(0).expect("No stmt in do"), idt + 1);
-                let stmt = print(tree.child.get(1).expect("No exp in do"), idt + 1);
+                let exp = print(child(tree, 0)?, idt + 1)?;
+                let stmt = print(child(tree, 1)?, idt + 1)?;
                 format!(
                     "{}n_type: Stmt:While, [\n\
                      {}               exp: [\n{}\n{}]\n\
@@ -1983,37 +3593,77 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                     idt_prefix
                 )
             }
+            StmtType::Switch => {
+                let exp = print(child(tree, 0)?, idt + 1)?;
+                let stmt = print(child(tree, 1)?, idt + 1)?;
+                format!(
+                    "{}n_type: Stmt:Switch, [\n\
+                     {}               exp: [\n{}\n{}]\n\
+                     {}              stmt:  [\n{}\n{}]\n\
+                     {}]",
+                    idt_prefix,
+                    idt_prefix,
+                    exp,
+                    idt_prefix,
+                    idt_prefix,
+                    stmt,
+                    idt_prefix,
+                    idt_prefix
+                )
+            }
+            StmtType::Case => {
+                let exp = print(child(tree, 0)?, idt + 1)?;
+                let stmt = print(child(tree, 1)?, idt + 1)?;
+                format!(
+                    "{}n_type: Stmt:Case, [\n\
+                     {}               exp: [\n{}\n{}]\n\
+                     {}              stmt:  [\n{}\n{}]\n\
+                     {}]",
+                    idt_prefix,
+                    idt_prefix,
+                    exp,
+                    idt_prefix,
+                    idt_prefix,
+                    stmt,
+                    idt_prefix,
+                    idt_prefix
+                )
+            }
+            StmtType::Default => format!(
+                "{}n_type: Stmt::Default, [\n{}\n{}]",
+                idt_prefix,
+                print(child(tree, 0)?, idt + 1)?,
+                idt_prefix
+            ),
+            StmtType::Label(name) => format!(
+                "{}n_type: Stmt::Label, Name: {} [\n{}\n{}]",
+                idt_prefix,
+                name,
+                print(child(tree, 0)?, idt + 1)?,
+                idt_prefix
+            ),
+            StmtType::Goto(name, _) => format!("{}n_type: Goto, Label: {}", idt_prefix, name),
             StmtType::Continue => format!("{}n_type: Continue", idt_prefix),
             StmtType::Break => format!("{}n_type: Break", idt_prefix),
             StmtType::Return => format!(
                 "{}n_type: Stmt::Return, [\n{}\n{}]",
                 idt_prefix,
-                print(
-                    tree.child
-                        .get(0)
-                        .expect("Statement::Return Node has no child"),
-                    idt + 1
-                ),
+                print(child(tree, 0)?, idt + 1)?,
                 idt_prefix
             ),
             StmtType::Exp => format!(
                 "{}n_type: Stmt::Exp, [\n{}\n{}]",
                 idt_prefix,
-                print(
-                    tree.child.get(0).expect("Statement::Exp Node has no child"),
-                    idt + 1
-                ),
+                print(child(tree, 0)?, idt + 1)?,
                 idt_prefix
             ),
             StmtType::Conditional(op) => {
                 let mut tmp = String::new();
-                let mut inc = 0;
-                for it in tree.child.iter() {
+                for (inc, it) in tree.child.iter().enumerate() {
                     if inc > 0 {
-                        tmp.push_str("\n");
+                        tmp.push('\n');
                     }
-                    tmp.push_str(&print(it, idt + 1));
-                    inc = inc + 1;
+                    tmp.push_str(&print(it, idt + 1)?);
                 }
                 format!(
                     "{}n_type: Stmt::Conditional, Op: {} [\n{}\n{}]",
@@ -2022,14 +3672,11 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
             }
             StmtType::Compound => {
                 let mut tmp = String::new();
-                let mut inc = 0;
-
-                for it in tree.child.iter() {
+                for (inc, it) in tree.child.iter().enumerate() {
                     if inc > 0 {
-                        tmp.push_str("\n");
+                        tmp.push('\n');
                     }
-                    tmp.push_str(&print(it, idt + 1));
-                    inc += 1;
+                    tmp.push_str(&print(it, idt + 1)?);
                 }
                 format!(
                     "{}n_type: Stmt::Compound, [\n{}\n{}]",
@@ -2037,64 +3684,115 @@ pub fn print(tree: &ParseNode, idt: usize) -> String {
                 )
             }
         },
-        NodeType::LogicalOrExp |
-        NodeType::LogicalAndExp|
-        NodeType::EqualityExp  |
-        NodeType::RelationalExp|
-        NodeType::AdditiveExp =>
-            print(tree.child.get(0).expect(&format!("{:?} Node has no child", tree.entry)), idt),
+        NodeType::LogicalOrExp
+        | NodeType::LogicalAndExp
+        | NodeType::EqualityExp
+        | NodeType::RelationalExp
+        | NodeType::AdditiveExp => print(child(tree, 0)?, idt)?,
 
         NodeType::ExpOption => match tree.child.len() {
             0 => format!("{}n_type: ExpOption", idt_prefix),
             1 => format!(
                 "{}n_type: ExpOption, [\n{}\n{}]",
                 idt_prefix,
-                print(tree.child.get(0).expect("ExpOption has no child"), idt + 1),
+                print(child(tree, 0)?, idt + 1)?,
                 idt_prefix
             ),
-            _ => panic!(format!(
-                "ExpOption can only have 0 or 1 child node, but found {} child node",
-                tree.child.len()
-            )),
+            _ => {
+                return Err(Diagnostic::ice(
+                    &tree.loc,
+                    &format!(
+                        "ExpOption can only have 0 or 1 child node, but found {} child node",
+                        tree.child.len()
+                    ),
+                ))
+            }
         },
         NodeType::Exp => format!(
             "{}n_type: Exp, [\n{}\n{}]",
             idt_prefix,
-            print(
-                tree.child.get(0).expect("Expression Node has no child"),
-                idt + 1
-            ),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::Block => format!(
             "{}n_type: Block, [\n{}\n{}]",
             idt_prefix,
-            print(tree.child.get(0).expect("Block node has no child"), idt + 1),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
-        NodeType::UnExp(Op) => format!(
+        NodeType::UnExp(op) => format!(
             "{}n_type: UnExp, Op: {}, [\n{}\n{}]",
             idt_prefix,
-            match Op {
+            match op {
                 lexer::TokType::Minus => "-".to_string(),
                 lexer::TokType::Tilde => "~".to_string(),
                 lexer::TokType::Exclamation => "!".to_string(),
                 lexer::TokType::Addr => "&".to_string(),
-                _ => panic!("Operator for Unary Expression not supported"),
+                lexer::TokType::Multi => "*".to_string(),
+                _ =>
+                    return Err(Diagnostic::ice(
+                        &tree.loc,
+                        "Operator for Unary Expression not supported"
+                    )),
             },
-            print(
-                tree.child
-                    .get(0)
-                    .expect("Unary Expression Node has no child"),
-                idt + 1
-            ),
+            print(child(tree, 0)?, idt + 1)?,
             idt_prefix
         ),
         NodeType::Var(var_name) => format!("{}n_type, Variable, Name : {}", idt_prefix, var_name),
-        NodeType::Const(n) => format!("{}n_type: Const, Value: {}", idt_prefix, n),
-        _ => panic!(format!(
-            "in parser::print, {:?} Node type not implemented",
-            &tree.entry
-        )),
-    }
+        NodeType::Member(name) => format!(
+            "{}n_type: Member, Name: {} [\n{}\n{}]",
+            idt_prefix,
+            name,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix
+        ),
+        NodeType::TagDecl(t) => format!("{}n_type: TagDecl, type: {}", idt_prefix, t),
+        NodeType::Cast(t) => format!(
+            "{}n_type: Cast, type: {} [\n{}\n{}]",
+            idt_prefix,
+            t,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix
+        ),
+        NodeType::Sizeof(Some(t)) | NodeType::Alignof(Some(t)) => {
+            let op = match tree.entry {
+                NodeType::Sizeof(_) => "Sizeof",
+                _ => "Alignof",
+            };
+            format!("{}n_type: {}, type: {}", idt_prefix, op, t)
+        }
+        NodeType::Sizeof(None) | NodeType::Alignof(None) => {
+            let op = match tree.entry {
+                NodeType::Sizeof(_) => "Sizeof",
+                _ => "Alignof",
+            };
+            format!(
+                "{}n_type: {} [\n{}\n{}]",
+                idt_prefix,
+                op,
+                print(child(tree, 0)?, idt + 1)?,
+                idt_prefix
+            )
+        }
+        NodeType::CompoundLiteral(t) => format!(
+            "{}n_type: CompoundLiteral, type: {} [\n{}\n{}]",
+            idt_prefix,
+            t,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix
+        ),
+        NodeType::VaArg(t) => format!(
+            "{}n_type: VaArg, type: {} [\n{}\n{}]",
+            idt_prefix,
+            t,
+            print(child(tree, 0)?, idt + 1)?,
+            idt_prefix
+        ),
+        NodeType::Const(n, t) => format!("{}n_type: Const, Value: {}, type: {}", idt_prefix, n, t),
+        NodeType::FloatConst(c, t) => format!(
+            "{}n_type: FloatConst, Value: {}, type: {}",
+            idt_prefix, c.value, t
+        ),
+    };
+    Ok(s)
 }
@@ -0,0 +1,1632 @@
+use crate::diagnostic::{code, Diagnostic};
+use crate::loc::Loc;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// The C preprocessor (translation phases 1 - 4).
+//
+// It takes the text of a source file and produces the text that the lexer
+// sees: line splices and comments are removed, directives are executed and
+// macros are expanded.  GNU style line markers (`# <line> "<file>" [flag]`)
+// are left in the output so that later stages still know which file and line
+// every token came from.
+
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+// searched after the -I directories, same as cpp does
+const SYSTEM_INCLUDE_DIRS: [&str; 3] = [
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
+
+// the headers that come with the compiler rather than the C library, where
+// no directory searched has them; the builtins they use are the ones gcc has
+const BUILTIN_DIR: &str = "<built-in>";
+const BUILTIN_HEADERS: [(&str, &str); 1] = [(
+    "stdarg.h",
+    "#ifndef _STDARG_H
+#define _STDARG_H
+#define __gnuc_va_list __builtin_va_list
+#define va_list __builtin_va_list
+#define va_start(ap, last) __builtin_va_start(ap, last)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_end(ap) __builtin_va_end(ap)
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+#define __va_copy(dest, src) __builtin_va_copy(dest, src)
+#endif
+",
+)];
+
+// the text of `path` if it is one of BUILTIN_HEADERS
+fn builtin_header(path: &Path) -> Option<&'static str> {
+    let file = path.strip_prefix(BUILTIN_DIR).ok()?;
+    BUILTIN_HEADERS
+        .iter()
+        .find(|(name, _)| file == Path::new(name))
+        .map(|(_, src)| *src)
+}
+
+const PREDEFINED_MACROS: [(&str, &str); 13] = [
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201112L"),
+    ("__STDC_HOSTED__", "1"),
+    ("__crust__", "1"),
+    ("__x86_64__", "1"),
+    ("__x86_64", "1"),
+    ("__amd64__", "1"),
+    ("__LP64__", "1"),
+    ("_LP64", "1"),
+    ("__ELF__", "1"),
+    ("__linux__", "1"),
+    ("__unix__", "1"),
+    ("__CHAR_BIT__", "8"),
+];
+
+// longest first, so the first match is the one to take
+const PUNCTUATORS: [&str; 54] = [
+    "%:%:", "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "<:", ":>", "<%", "%>", "%:", "[", "]",
+    "(", ")", "{", "}", ".", "&", "*", "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":",
+    ";", "=", ",", "#",
+];
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+enum PpTokType {
+    Identifier,
+    Number,
+    CharConst,
+    StringLit,
+    Punct,
+    Other,
+    Newline,
+    Placemarker, // stands for an empty macro argument around `##`
+}
+
+#[derive(Clone, Debug)]
+struct PpTok {
+    kind: PpTokType,
+    text: String,
+    space: bool,       // preceded by white space
+    line: usize,       // physical line in the file the token was read from
+    col: usize,        // column in that line, 0 for tokens made up by the preprocessor
+    hide: Vec<String>, // names of the macros that must not expand this token again
+}
+
+impl PpTok {
+    fn new(kind: PpTokType, text: &str, line: usize) -> PpTok {
+        PpTok {
+            kind,
+            text: text.to_string(),
+            space: false,
+            line,
+            col: 0,
+            hide: Vec::new(),
+        }
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        self.kind == PpTokType::Punct && self.text == punct
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    params: Option<Vec<String>>, // None for object-like macros
+    variadic: bool,              // the last parameter is __VA_ARGS__
+    body: Vec<PpTok>,
+}
+
+impl Macro {
+    fn param_index(&self, tok: &PpTok) -> Option<usize> {
+        if tok.kind != PpTokType::Identifier {
+            return None;
+        }
+        self.params
+            .as_ref()
+            .and_then(|params| params.iter().position(|p| *p == tok.text))
+    }
+}
+
+// one level of #if / #ifdef / #ifndef nesting
+struct Cond {
+    active: bool,    // the current group is emitted
+    taken: bool,     // one of the groups has already been emitted
+    seen_else: bool, // #else was seen, no more #elif / #else allowed
+    line: usize,
+}
+
+// per-file state, #line can change the name and the line numbers we report
+struct FileCtx {
+    path: PathBuf,
+    name: String,
+    line_delta: isize,
+    conds: Vec<Cond>,
+}
+
+impl FileCtx {
+    fn presumed_line(&self, line: usize) -> usize {
+        (line as isize + self.line_delta).max(1) as usize
+    }
+
+    fn active(&self) -> bool {
+        self.conds.last().is_none_or(|c| c.active)
+    }
+
+    // an error on a directive line, we don't know the column there
+    fn error(&self, code: &'static str, line: usize, msg: &str) -> Diagnostic {
+        let loc = Loc::new(
+            Rc::from(self.name.as_str()),
+            self.presumed_line(line),
+            0,
+            (0, 0),
+        );
+        Diagnostic::error(code, &loc, msg)
+    }
+
+    fn error_at(&self, code: &'static str, tok: &PpTok, msg: &str) -> Diagnostic {
+        let loc = Loc::new(
+            Rc::from(self.name.as_str()),
+            self.presumed_line(tok.line),
+            tok.col,
+            (0, tok.text.len()),
+        );
+        Diagnostic::error(code, &loc, msg)
+    }
+}
+
+// writes tokens back as text, keeping every token on its original line and,
+// where there is room, in its original column
+struct Writer {
+    buf: String,
+    line: usize,
+    col: usize, // column the next character goes to
+    at_line_start: bool,
+    prev: Option<PpTok>,
+}
+
+impl Writer {
+    fn marker(&mut self, line: usize, file: &str, flag: Option<u8>) {
+        if !self.at_line_start {
+            self.buf.push('\n');
+        }
+        self.buf
+            .push_str(&format!("# {} \"{}\"", line, escape_string(file)));
+        if let Some(flag) = flag {
+            self.buf.push_str(&format!(" {}", flag));
+        }
+        self.buf.push('\n');
+        self.line = line;
+        self.col = 1;
+        self.at_line_start = true;
+        self.prev = None;
+    }
+
+    fn token(&mut self, tok: &PpTok, line: usize, file: &str) {
+        if line > self.line {
+            if line - self.line > 8 {
+                self.marker(line, file, None);
+            } else {
+                while self.line < line {
+                    self.buf.push('\n');
+                    self.line += 1;
+                }
+                self.col = 1;
+                self.at_line_start = true;
+                self.prev = None;
+            }
+        }
+        if tok.col > self.col {
+            self.buf.push_str(&" ".repeat(tok.col - self.col));
+            self.col = tok.col;
+        } else {
+            match &self.prev {
+                Some(prev) if tok.space || avoid_paste(prev, tok) => self.space(),
+                None if tok.space => self.space(),
+                _ => {}
+            }
+        }
+        self.buf.push_str(&tok.text);
+        self.col += tok.text.len();
+        self.at_line_start = false;
+        self.prev = Some(tok.clone());
+    }
+
+    fn space(&mut self) {
+        self.buf.push(' ');
+        self.col += 1;
+    }
+
+    fn finish(mut self) -> String {
+        if !self.at_line_start {
+            self.buf.push('\n');
+        }
+        self.buf
+    }
+}
+
+// true if writing `next` right after `prev` would lex as different tokens
+fn avoid_paste(prev: &PpTok, next: &PpTok) -> bool {
+    use PpTokType::*;
+    match (prev.kind, next.kind) {
+        (Identifier, Identifier)
+        | (Identifier, Number)
+        | (Number, Identifier)
+        | (Number, Number) => true,
+        (Identifier, StringLit) | (Identifier, CharConst) => true,
+        (Number, Punct) => {
+            next.text.starts_with('.') || next.text.starts_with('+') || next.text.starts_with('-')
+        }
+        (Punct, Number) => prev.text == "." || prev.text == "...",
+        (Punct, Punct) => {
+            let joined = format!("{}{}", prev.text, &next.text[..1]);
+            PUNCTUATORS.iter().any(|p| p.starts_with(&joined))
+        }
+        _ => false,
+    }
+}
+
+fn escape_string(s: &str) -> String {
+    let mut res = String::new();
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+// phase 1 and 2: join the lines ending with a backslash, but remember the
+// physical line and column of every character.
+fn splice_lines(src: &str) -> Vec<(char, usize, usize)> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut res = Vec::with_capacity(chars.len() + 1);
+    let mut line = 1;
+    let mut col = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' {
+            let mut j = i + 1;
+            if j < chars.len() && chars[j] == '\r' {
+                j += 1;
+            }
+            if j < chars.len() && chars[j] == '\n' {
+                line += 1;
+                col = 1;
+                i = j + 1;
+                continue;
+            }
+        }
+        if c == '\r' {
+            // \r\n and lone \r are both line breaks
+            if i + 1 < chars.len() && chars[i + 1] == '\n' {
+                i += 1;
+                continue;
+            }
+            res.push(('\n', line, col));
+            line += 1;
+            col = 1;
+            i += 1;
+            continue;
+        }
+        res.push((c, line, col));
+        if c == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += c.len_utf8();
+        }
+        i += 1;
+    }
+    if res.last().is_none_or(|&(c, _, _)| c != '\n') {
+        res.push(('\n', line, col));
+    }
+    res
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+// find the closing quote of a character constant or string literal that
+// starts at `start`, returns None if the line ends first.
+fn scan_quoted(cs: &[(char, usize, usize)], start: usize, quote: char) -> Option<usize> {
+    let mut i = start + 1;
+    while i < cs.len() {
+        match cs[i].0 {
+            '\\' => i += 2,
+            '\n' => return None,
+            c if c == quote => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// phase 3: split the file into preprocessing tokens, comments become spaces
+fn tokenize(src: &str, file: &str) -> Result<Vec<PpTok>, Diagnostic> {
+    let cs = splice_lines(src);
+    let text =
+        |from: usize, to: usize| -> String { cs[from..to].iter().map(|&(c, _, _)| c).collect() };
+    let mut toks = Vec::new();
+    let mut space = false;
+    let mut i = 0;
+
+    while i < cs.len() {
+        let (c, line, col) = cs[i];
+        let next = cs.get(i + 1).map_or('\0', |&(c, _, _)| c);
+        let start = i;
+        let kind = match c {
+            '\n' => {
+                toks.push(PpTok::new(PpTokType::Newline, "\n", line));
+                space = false;
+                i += 1;
+                continue;
+            }
+            ' ' | '\t' | '\x0b' | '\x0c' => {
+                space = true;
+                i += 1;
+                continue;
+            }
+            '/' if next == '/' => {
+                while cs[i].0 != '\n' {
+                    i += 1;
+                }
+                space = true;
+                continue;
+            }
+            '/' if next == '*' => {
+                i += 2;
+                loop {
+                    if i + 1 >= cs.len() {
+                        return Err(Diagnostic::error(
+                            code::LEX,
+                            &Loc::new(Rc::from(file), line, col, (0, 2)),
+                            "unterminated comment",
+                        ));
+                    }
+                    if cs[i].0 == '*' && cs[i + 1].0 == '/' {
+                        break;
+                    }
+                    i += 1;
+                }
+                i += 2;
+                space = true;
+                continue;
+            }
+            '"' | '\'' => match scan_quoted(&cs, i, c) {
+                Some(end) => {
+                    i = end + 1;
+                    if c == '"' {
+                        PpTokType::StringLit
+                    } else {
+                        PpTokType::CharConst
+                    }
+                }
+                None => {
+                    // unmatched quote, may be fine in a skipped group
+                    i += 1;
+                    PpTokType::Other
+                }
+            },
+            c if is_ident_start(c) => {
+                while i < cs.len() && is_ident_char(cs[i].0) {
+                    i += 1;
+                }
+                // encoding prefixes: L"...", u8"...", U'...'
+                let word = text(start, i);
+                let quote = cs[i].0;
+                let is_prefix = matches!(word.as_ref(), "L" | "u" | "U" | "u8")
+                    && (quote == '"' || quote == '\'');
+                match if is_prefix {
+                    scan_quoted(&cs, i, quote)
+                } else {
+                    None
+                } {
+                    Some(end) => {
+                        i = end + 1;
+                        if quote == '"' {
+                            PpTokType::StringLit
+                        } else {
+                            PpTokType::CharConst
+                        }
+                    }
+                    None => PpTokType::Identifier,
+                }
+            }
+            c if c.is_ascii_digit() || (c == '.' && next.is_ascii_digit()) => {
+                i += 1;
+                while i < cs.len() {
+                    let c = cs[i].0;
+                    let prev = cs[i - 1].0;
+                    if ((c == '+' || c == '-') && "eEpP".contains(prev))
+                        || is_ident_char(c)
+                        || c == '.'
+                    {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                PpTokType::Number
+            }
+            _ => {
+                let rest = text(i, (i + 4).min(cs.len()));
+                match PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+                    Some(p) => {
+                        i += p.len();
+                        PpTokType::Punct
+                    }
+                    None => {
+                        i += 1;
+                        PpTokType::Other
+                    }
+                }
+            }
+        };
+
+        let mut tok = PpTok::new(kind, &text(start, i), line);
+        tok.col = col;
+        if kind == PpTokType::Punct {
+            // digraphs are spelled the normal way from here on
+            tok.text = match tok.text.as_ref() {
+                "<:" => "[",
+                ":>" => "]",
+                "<%" => "{",
+                "%>" => "}",
+                "%:" => "#",
+                "%:%:" => "##",
+                s => s,
+            }
+            .to_string();
+        }
+        tok.space = space;
+        space = false;
+        toks.push(tok);
+    }
+    Ok(toks)
+}
+
+// the spelling of a string literal holding `s`
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape_string(s))
+}
+
+fn stringify(arg: &[PpTok], line: usize) -> PpTok {
+    let mut s = String::new();
+    for (i, tok) in arg.iter().enumerate() {
+        if i > 0 && tok.space {
+            s.push(' ');
+        }
+        match tok.kind {
+            PpTokType::StringLit | PpTokType::CharConst => s.push_str(&escape_string(&tok.text)),
+            _ => s.push_str(&tok.text),
+        }
+    }
+    PpTok::new(PpTokType::StringLit, &format!("\"{}\"", s), line)
+}
+
+// value of a #if expression, intmax_t or uintmax_t
+#[derive(Clone, Copy)]
+struct Val {
+    v: i64,
+    unsigned: bool,
+}
+
+fn parse_pp_number(text: &str) -> Option<Val> {
+    let lower = text.to_ascii_lowercase();
+    let digits = lower.trim_end_matches(['u', 'l']);
+    let suffix = &lower[digits.len()..];
+    if !matches!(suffix, "" | "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu") {
+        return None;
+    }
+    let (radix, digits) = if let Some(hex) = digits.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        (2, bin)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+    let v = u64::from_str_radix(digits, radix).ok()?;
+    Some(Val {
+        v: v as i64,
+        unsigned: suffix.contains('u') || v > i64::MAX as u64,
+    })
+}
+
+// value of a character constant like 'a', '\n' or '\x41'
+fn parse_char_const(text: &str) -> Option<i64> {
+    let body = &text[text.find('\'')? + 1..text.len() - 1];
+    let mut chars = body.chars().peekable();
+    let mut value: i64 = 0;
+    let mut count = 0;
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next()? {
+                'n' => 0x0a,
+                't' => 0x09,
+                'r' => 0x0d,
+                'a' => 0x07,
+                'b' => 0x08,
+                'f' => 0x0c,
+                'v' => 0x0b,
+                'e' => 0x1b,
+                'x' => {
+                    let mut v: i64 = 0;
+                    while let Some(d) = chars.peek().and_then(|c| c.to_digit(16)) {
+                        v = v.wrapping_mul(16) + i64::from(d);
+                        chars.next();
+                    }
+                    v
+                }
+                d @ '0'..='7' => {
+                    let mut v = i64::from(d.to_digit(8)?);
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(d) => {
+                                v = v * 8 + i64::from(d);
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    v
+                }
+                c => c as i64,
+            }
+        } else {
+            c as i64
+        };
+        value = (value << 8) | (c & 0xff);
+        count += 1;
+    }
+    if count == 1 && text.starts_with('\'') {
+        // plain char is signed
+        value = i64::from(value as u8 as i8);
+    }
+    Some(value)
+}
+
+// evaluator of the controlling expression of #if and #elif
+struct Eval<'a> {
+    toks: &'a [PpTok],
+    pos: usize,
+}
+
+impl<'a> Eval<'a> {
+    fn peek(&self) -> Option<&'a PpTok> {
+        self.toks.get(self.pos)
+    }
+
+    fn peek_punct(&self, punct: &str) -> bool {
+        self.peek().is_some_and(|t| t.is(punct))
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.peek_punct(punct) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}` in preprocessor expression", punct))
+        }
+    }
+
+    fn expr(&mut self, skip: bool) -> Result<Val, String> {
+        let mut v = self.cond(skip)?;
+        while self.peek_punct(",") {
+            self.pos += 1;
+            v = self.cond(skip)?;
+        }
+        Ok(v)
+    }
+
+    fn cond(&mut self, skip: bool) -> Result<Val, String> {
+        let c = self.binary(1, skip)?;
+        if !self.peek_punct("?") {
+            return Ok(c);
+        }
+        self.pos += 1;
+        let lhs = self.expr(skip || c.v == 0)?;
+        self.expect(":")?;
+        let rhs = self.cond(skip || c.v != 0)?;
+        let unsigned = lhs.unsigned || rhs.unsigned;
+        let v = if c.v != 0 { lhs.v } else { rhs.v };
+        Ok(Val { v, unsigned })
+    }
+
+    fn precedence(tok: &PpTok) -> Option<u8> {
+        if tok.kind != PpTokType::Punct {
+            return None;
+        }
+        match tok.text.as_ref() {
+            "||" => Some(1),
+            "&&" => Some(2),
+            "|" => Some(3),
+            "^" => Some(4),
+            "&" => Some(5),
+            "==" | "!=" => Some(6),
+            "<" | ">" | "<=" | ">=" => Some(7),
+            "<<" | ">>" => Some(8),
+            "+" | "-" => Some(9),
+            "*" | "/" | "%" => Some(10),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, min_prec: u8, skip: bool) -> Result<Val, String> {
+        let mut lhs = self.unary(skip)?;
+        while let Some(prec) = self.peek().and_then(Eval::precedence) {
+            if prec < min_prec {
+                break;
+            }
+            let op = self.toks[self.pos].text.clone();
+            self.pos += 1;
+            // && and || do not evaluate their rhs if the lhs decides
+            let rhs_skip = match op.as_ref() {
+                "&&" => skip || lhs.v == 0,
+                "||" => skip || lhs.v != 0,
+                _ => skip,
+            };
+            let rhs = self.binary(prec + 1, rhs_skip)?;
+            lhs = Eval::apply(&op, lhs, rhs, skip)?;
+        }
+        Ok(lhs)
+    }
+
+    fn apply(op: &str, lhs: Val, rhs: Val, skip: bool) -> Result<Val, String> {
+        let unsigned = lhs.unsigned || rhs.unsigned;
+        let (a, b) = (lhs.v, rhs.v);
+        let boolean = |b: bool| Val {
+            v: b as i64,
+            unsigned: false,
+        };
+        let v = match op {
+            "||" => return Ok(boolean(a != 0 || b != 0)),
+            "&&" => return Ok(boolean(a != 0 && b != 0)),
+            "==" => return Ok(boolean(a == b)),
+            "!=" => return Ok(boolean(a != b)),
+            "<" | ">" | "<=" | ">=" => {
+                let ord = if unsigned {
+                    (a as u64).cmp(&(b as u64))
+                } else {
+                    a.cmp(&b)
+                };
+                let res = match op {
+                    "<" => ord.is_lt(),
+                    ">" => ord.is_gt(),
+                    "<=" => ord.is_le(),
+                    _ => ord.is_ge(),
+                };
+                return Ok(boolean(res));
+            }
+            "|" => a | b,
+            "^" => a ^ b,
+            "&" => a & b,
+            "<<" => a.wrapping_shl(b as u32),
+            ">>" => {
+                if lhs.unsigned {
+                    ((a as u64).wrapping_shr(b as u32)) as i64
+                } else {
+                    a.wrapping_shr(b as u32)
+                }
+            }
+            "+" => a.wrapping_add(b),
+            "-" => a.wrapping_sub(b),
+            "*" => a.wrapping_mul(b),
+            "/" | "%" => {
+                if b == 0 {
+                    if skip {
+                        return Ok(Val { v: 0, unsigned });
+                    }
+                    return Err("division by zero in #if".to_string());
+                }
+                match (op, unsigned) {
+                    ("/", true) => ((a as u64) / (b as u64)) as i64,
+                    ("/", false) => a.wrapping_div(b),
+                    (_, true) => ((a as u64) % (b as u64)) as i64,
+                    (_, false) => a.wrapping_rem(b),
+                }
+            }
+            _ => unreachable!("not a binary operator in #if"),
+        };
+        Ok(Val {
+            v,
+            unsigned: if op == "<<" || op == ">>" {
+                lhs.unsigned
+            } else {
+                unsigned
+            },
+        })
+    }
+
+    fn unary(&mut self, skip: bool) -> Result<Val, String> {
+        let tok = match self.peek() {
+            Some(tok) => tok,
+            None if self.pos == 0 => return Err("#if with no expression".to_string()),
+            None => return Err("expected value in expression".to_string()),
+        };
+        self.pos += 1;
+        match tok.kind {
+            PpTokType::Punct => match tok.text.as_ref() {
+                "+" => self.unary(skip),
+                "-" => {
+                    let v = self.unary(skip)?;
+                    Ok(Val {
+                        v: v.v.wrapping_neg(),
+                        ..v
+                    })
+                }
+                "~" => {
+                    let v = self.unary(skip)?;
+                    Ok(Val { v: !v.v, ..v })
+                }
+                "!" => {
+                    let v = self.unary(skip)?;
+                    Ok(Val {
+                        v: (v.v == 0) as i64,
+                        unsigned: false,
+                    })
+                }
+                "(" => {
+                    let v = self.expr(skip)?;
+                    self.expect(")")?;
+                    Ok(v)
+                }
+                _ => Err(format!(
+                    "token \"{}\" is not valid in preprocessor expressions",
+                    tok.text
+                )),
+            },
+            PpTokType::Number => {
+                let lower = tok.text.to_ascii_lowercase();
+                if lower.contains('.') || (!lower.starts_with("0x") && lower.contains('e')) {
+                    return Err("floating constant in preprocessor expression".to_string());
+                }
+                parse_pp_number(&tok.text)
+                    .ok_or_else(|| format!("invalid integer constant \"{}\" in #if", tok.text))
+            }
+            PpTokType::CharConst => match parse_char_const(&tok.text) {
+                Some(v) => Ok(Val { v, unsigned: false }),
+                None => Err(format!("invalid character constant {} in #if", tok.text)),
+            },
+            // identifiers left after macro expansion are 0
+            PpTokType::Identifier => Ok(Val {
+                v: 0,
+                unsigned: false,
+            }),
+            _ => Err(format!(
+                "token \"{}\" is not valid in preprocessor expressions",
+                tok.text
+            )),
+        }
+    }
+}
+
+struct Preprocessor {
+    include_dirs: Vec<PathBuf>,
+    macros: HashMap<String, Macro>,
+    once: HashSet<PathBuf>, // files with #pragma once
+    out: Writer,
+    depth: usize,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Preprocessor {
+    fn define(&mut self, name: &str, value: &str) -> Result<(), Diagnostic> {
+        let mut body = tokenize(value, "<command-line>")?;
+        body.retain(|t| t.kind != PpTokType::Newline);
+        if let Some(first) = body.first_mut() {
+            first.space = false;
+        }
+        self.macros.insert(
+            name.to_string(),
+            Macro {
+                params: None,
+                variadic: false,
+                body,
+            },
+        );
+        Ok(())
+    }
+
+    fn run_file(&mut self, path: &Path, src: &str, flag: Option<u8>) -> Result<(), Diagnostic> {
+        let name = path.display().to_string();
+        let toks = tokenize(src, &name)?;
+        let mut ctx = FileCtx {
+            path: path.to_path_buf(),
+            name,
+            line_delta: 0,
+            conds: Vec::new(),
+        };
+        self.out.marker(1, &ctx.name, flag);
+
+        let mut pending: Vec<PpTok> = Vec::new();
+        let mut i = 0;
+        while i < toks.len() {
+            let start = i;
+            while toks[i].kind != PpTokType::Newline {
+                i += 1;
+            }
+            let line = &toks[start..i];
+            let next_line = toks[i].line + 1;
+            i += 1;
+
+            match line.first() {
+                Some(hash) if hash.is("#") => {
+                    if ctx.active() {
+                        self.flush(&mut pending, &ctx)?;
+                    }
+                    self.directive(&mut ctx, &line[1..], hash.line, next_line)?;
+                }
+                Some(_) if ctx.active() => pending.extend_from_slice(line),
+                _ => {}
+            }
+        }
+        self.flush(&mut pending, &ctx)?;
+
+        if let Some(cond) = ctx.conds.last() {
+            return Err(ctx.error(
+                code::PP_DIRECTIVE,
+                cond.line,
+                "unterminated conditional directive",
+            ));
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, pending: &mut Vec<PpTok>, ctx: &FileCtx) -> Result<(), Diagnostic> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let toks = self.expand(std::mem::take(pending), ctx)?;
+        for tok in toks.iter() {
+            self.out.token(tok, ctx.presumed_line(tok.line), &ctx.name);
+        }
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        ctx: &mut FileCtx,
+        toks: &[PpTok],
+        line: usize,
+        next_line: usize,
+    ) -> Result<(), Diagnostic> {
+        let name = match toks.first() {
+            Some(tok) => tok.text.clone(),
+            None => return Ok(()), // the null directive
+        };
+        let args = &toks[1..];
+        let parent_active = ctx.conds.iter().all(|c| c.active);
+
+        match name.as_ref() {
+            "if" | "ifdef" | "ifndef" => {
+                let active = parent_active
+                    && match name.as_ref() {
+                        "if" => self.eval_if(args, ctx, line)?,
+                        "ifdef" => self.macros.contains_key(&self.macro_name(args, ctx, line)?),
+                        _ => !self.macros.contains_key(&self.macro_name(args, ctx, line)?),
+                    };
+                ctx.conds.push(Cond {
+                    active,
+                    taken: active || !parent_active,
+                    seen_else: false,
+                    line,
+                });
+                return Ok(());
+            }
+            "elif" | "else" | "endif" => {
+                let outer_active = ctx.conds.len() < 2
+                    || ctx.conds[..ctx.conds.len() - 1].iter().all(|c| c.active);
+                let (taken, seen_else) = match ctx.conds.last() {
+                    Some(c) => (c.taken, c.seen_else),
+                    None => {
+                        return Err(ctx.error(
+                            code::PP_DIRECTIVE,
+                            line,
+                            &format!("#{} without #if", name),
+                        ))
+                    }
+                };
+                if name == "endif" {
+                    ctx.conds.pop();
+                    return Ok(());
+                }
+                if seen_else {
+                    return Err(ctx.error(
+                        code::PP_DIRECTIVE,
+                        line,
+                        &format!("#{} after #else", name),
+                    ));
+                }
+                let active = if name == "else" {
+                    outer_active && !taken
+                } else {
+                    outer_active && !taken && self.eval_if(args, ctx, line)?
+                };
+                let cond = ctx.conds.last_mut().expect("checked above");
+                cond.active = active;
+                cond.taken = taken || active;
+                cond.seen_else = name == "else";
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if !parent_active {
+            return Ok(());
+        }
+        match name.as_ref() {
+            "define" => self.p_define(args, ctx, line),
+            "undef" => {
+                let macro_name = self.macro_name(args, ctx, line)?;
+                self.macros.remove(&macro_name);
+                Ok(())
+            }
+            "include" => self.p_include(args, ctx, line, next_line),
+            "line" => self.p_line(args, ctx, line, next_line),
+            "error" => Err(ctx.error(code::PP_ERROR, line, &format!("#error {}", join_text(args)))),
+            "warning" => {
+                let loc = Loc::new(
+                    Rc::from(ctx.name.as_str()),
+                    ctx.presumed_line(line),
+                    0,
+                    (0, 0),
+                );
+                let msg = format!("#warning {}", join_text(args));
+                self.warnings
+                    .push(Diagnostic::warning(code::PP_WARNING, &loc, &msg));
+                Ok(())
+            }
+            "pragma" => {
+                if args.first().is_some_and(|t| t.text == "once") {
+                    self.once.insert(canonical(&ctx.path));
+                }
+                Ok(())
+            }
+            _ if toks[0].kind == PpTokType::Number => {
+                // GNU line marker left by an earlier preprocessing run
+                self.p_line(toks, ctx, line, next_line)
+            }
+            _ => Err(ctx.error(
+                code::PP_DIRECTIVE,
+                line,
+                &format!("invalid preprocessing directive #{}", name),
+            )),
+        }
+    }
+
+    fn macro_name(&self, args: &[PpTok], ctx: &FileCtx, line: usize) -> Result<String, Diagnostic> {
+        match args.first() {
+            Some(tok) if tok.kind == PpTokType::Identifier => Ok(tok.text.clone()),
+            _ => Err(ctx.error(code::PP_DIRECTIVE, line, "macro names must be identifiers")),
+        }
+    }
+
+    fn p_define(&mut self, args: &[PpTok], ctx: &FileCtx, line: usize) -> Result<(), Diagnostic> {
+        let name = self.macro_name(args, ctx, line)?;
+        if name == "defined" {
+            return Err(ctx.error(
+                code::PP_DIRECTIVE,
+                line,
+                "\"defined\" cannot be used as a macro name",
+            ));
+        }
+        let mut pos = 1;
+        let mut params = None;
+        let mut variadic = false;
+
+        // a function-like macro has its `(` right after the name
+        if args.get(1).is_some_and(|t| t.is("(") && !t.space) {
+            let mut list = Vec::new();
+            pos = 2;
+            loop {
+                let tok = match args.get(pos) {
+                    Some(tok) => tok,
+                    None => {
+                        return Err(ctx.error(
+                            code::PP_DIRECTIVE,
+                            line,
+                            "missing ')' in macro parameter list",
+                        ))
+                    }
+                };
+                pos += 1;
+                if tok.is(")") && list.is_empty() {
+                    break;
+                }
+                if tok.is("...") {
+                    variadic = true;
+                    list.push("__VA_ARGS__".to_string());
+                } else if tok.kind == PpTokType::Identifier {
+                    if list.contains(&tok.text) {
+                        return Err(ctx.error(
+                            code::PP_DIRECTIVE,
+                            line,
+                            &format!("duplicate macro parameter \"{}\"", tok.text),
+                        ));
+                    }
+                    list.push(tok.text.clone());
+                } else {
+                    return Err(ctx.error(
+                        code::PP_DIRECTIVE,
+                        line,
+                        &format!("\"{}\" may not appear in macro parameter list", tok.text),
+                    ));
+                }
+                match args.get(pos) {
+                    Some(t) if t.is(")") => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(t) if t.is(",") && !variadic => pos += 1,
+                    _ => {
+                        return Err(ctx.error(
+                            code::PP_DIRECTIVE,
+                            line,
+                            "expected ',' or ')' in macro parameter list",
+                        ))
+                    }
+                }
+            }
+            params = Some(list);
+        }
+
+        let mut body = args[pos..].to_vec();
+        if let Some(first) = body.first_mut() {
+            first.space = false;
+        }
+        let m = Macro {
+            params,
+            variadic,
+            body,
+        };
+
+        if m.body.first().is_some_and(|t| t.is("##")) || m.body.last().is_some_and(|t| t.is("##")) {
+            return Err(ctx.error(
+                code::PP_DIRECTIVE,
+                line,
+                "'##' cannot appear at either end of a macro expansion",
+            ));
+        }
+        if m.params.is_some() {
+            for (i, tok) in m.body.iter().enumerate() {
+                if tok.is("#") && m.body.get(i + 1).and_then(|t| m.param_index(t)).is_none() {
+                    return Err(ctx.error(
+                        code::PP_DIRECTIVE,
+                        line,
+                        "'#' is not followed by a macro parameter",
+                    ));
+                }
+            }
+        }
+        self.macros.insert(name, m);
+        Ok(())
+    }
+
+    fn p_include(
+        &mut self,
+        args: &[PpTok],
+        ctx: &FileCtx,
+        line: usize,
+        next_line: usize,
+    ) -> Result<(), Diagnostic> {
+        let expanded;
+        let toks = match args.first() {
+            Some(t) if t.kind == PpTokType::StringLit || t.is("<") => args,
+            _ => {
+                // #include MACRO
+                expanded = self.expand(args.to_vec(), ctx)?;
+                &expanded[..]
+            }
+        };
+
+        let (file, angled) = match toks.first() {
+            Some(t) if t.kind == PpTokType::StringLit && t.text.starts_with('"') => {
+                (t.text[1..t.text.len() - 1].to_string(), false)
+            }
+            Some(t) if t.is("<") => {
+                let mut file = String::new();
+                let mut closed = false;
+                for (i, tok) in toks[1..].iter().enumerate() {
+                    if tok.is(">") {
+                        closed = true;
+                        break;
+                    }
+                    if i > 0 && tok.space {
+                        file.push(' ');
+                    }
+                    file.push_str(&tok.text);
+                }
+                if !closed {
+                    return Err(ctx.error(
+                        code::PP_DIRECTIVE,
+                        line,
+                        "missing terminating > character",
+                    ));
+                }
+                (file, true)
+            }
+            _ => {
+                return Err(ctx.error(
+                    code::PP_DIRECTIVE,
+                    line,
+                    "#include expects \"FILENAME\" or <FILENAME>",
+                ))
+            }
+        };
+
+        let path = match self.find_include(&file, angled, &ctx.path) {
+            Some(path) => path,
+            None => {
+                return Err(ctx.error(
+                    code::PP_INCLUDE,
+                    line,
+                    &format!("{}: No such file or directory", file),
+                ))
+            }
+        };
+        if self.once.contains(&canonical(&path)) {
+            return Ok(());
+        }
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(ctx.error(
+                code::PP_INCLUDE,
+                line,
+                &format!("#include nested depth {} exceeds maximum", self.depth),
+            ));
+        }
+        let src = match builtin_header(&path)
+            .map_or_else(|| fs::read_to_string(&path), |src| Ok(src.to_string()))
+        {
+            Ok(src) => src,
+            Err(e) => {
+                return Err(ctx.error(
+                    code::PP_INCLUDE,
+                    line,
+                    &format!("{}: {}", path.display(), e),
+                ))
+            }
+        };
+
+        self.depth += 1;
+        self.run_file(&path, &src, Some(1))?;
+        self.depth -= 1;
+        self.out
+            .marker(ctx.presumed_line(next_line), &ctx.name, Some(2));
+        Ok(())
+    }
+
+    fn find_include(&self, file: &str, angled: bool, current: &Path) -> Option<PathBuf> {
+        let file = Path::new(file);
+        if file.is_absolute() {
+            return Some(file.to_path_buf()).filter(|p| p.is_file());
+        }
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        if !angled {
+            dirs.push(
+                current
+                    .parent()
+                    .map_or_else(PathBuf::new, Path::to_path_buf),
+            );
+        }
+        dirs.extend(self.include_dirs.iter().cloned());
+        dirs.extend(SYSTEM_INCLUDE_DIRS.iter().map(PathBuf::from));
+        dirs.into_iter()
+            .map(|dir| dir.join(file))
+            .find(|p| p.is_file())
+            .or_else(|| {
+                let builtin = Path::new(BUILTIN_DIR).join(file);
+                builtin_header(&builtin).map(|_| builtin)
+            })
+    }
+
+    fn p_line(
+        &mut self,
+        args: &[PpTok],
+        ctx: &mut FileCtx,
+        line: usize,
+        next_line: usize,
+    ) -> Result<(), Diagnostic> {
+        let toks = self.expand(args.to_vec(), ctx)?;
+        let new_line = match toks.first() {
+            Some(t)
+                if t.kind == PpTokType::Number && t.text.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                t.text
+                    .parse::<usize>()
+                    .map_err(|_| ctx.error(code::PP_DIRECTIVE, line, "line number out of range"))?
+            }
+            _ => {
+                return Err(ctx.error(
+                    code::PP_DIRECTIVE,
+                    line,
+                    "#line directive requires a simple digit sequence",
+                ))
+            }
+        };
+        match toks.get(1) {
+            Some(t) if t.kind == PpTokType::StringLit && t.text.starts_with('"') => {
+                ctx.name = t.text[1..t.text.len() - 1]
+                    .replace("\\\\", "\\")
+                    .replace("\\\"", "\"");
+            }
+            Some(t) => {
+                return Err(ctx.error(
+                    code::PP_DIRECTIVE,
+                    line,
+                    &format!("invalid filename {} in #line directive", t.text),
+                ));
+            }
+            None => {}
+        }
+        ctx.line_delta = new_line as isize - next_line as isize;
+        self.out.marker(new_line, &ctx.name, None);
+        Ok(())
+    }
+
+    fn eval_if(&mut self, args: &[PpTok], ctx: &FileCtx, line: usize) -> Result<bool, Diagnostic> {
+        // `defined X` and `defined(X)` are replaced before expanding macros
+        let mut toks = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            let tok = &args[i];
+            if tok.kind != PpTokType::Identifier || tok.text != "defined" {
+                toks.push(tok.clone());
+                i += 1;
+                continue;
+            }
+            let paren = args.get(i + 1).is_some_and(|t| t.is("("));
+            let name_pos = if paren { i + 2 } else { i + 1 };
+            let name = match args.get(name_pos) {
+                Some(t) if t.kind == PpTokType::Identifier => t.text.clone(),
+                _ => {
+                    return Err(ctx.error(
+                        code::PP_EXPR,
+                        line,
+                        "operator \"defined\" requires an identifier",
+                    ))
+                }
+            };
+            if paren && !args.get(name_pos + 1).is_some_and(|t| t.is(")")) {
+                return Err(ctx.error(code::PP_EXPR, line, "missing ')' after \"defined\""));
+            }
+            let value = if self.macros.contains_key(&name) {
+                "1"
+            } else {
+                "0"
+            };
+            toks.push(PpTok::new(PpTokType::Number, value, tok.line));
+            i = if paren { name_pos + 2 } else { name_pos + 1 };
+        }
+
+        let toks = self.expand(toks, ctx)?;
+        let mut eval = Eval {
+            toks: &toks,
+            pos: 0,
+        };
+        let v = eval
+            .expr(false)
+            .map_err(|e| ctx.error(code::PP_EXPR, line, &e))?;
+        if let Some(tok) = eval.peek() {
+            return Err(ctx.error(
+                code::PP_EXPR,
+                line,
+                &format!("missing binary operator before token \"{}\"", tok.text),
+            ));
+        }
+        Ok(v.v != 0)
+    }
+
+    // macro expansion (Prosser's algorithm), every token carries the set of
+    // macros it was produced by, so no macro can expand itself.
+    fn expand(&mut self, toks: Vec<PpTok>, ctx: &FileCtx) -> Result<Vec<PpTok>, Diagnostic> {
+        let mut res = Vec::new();
+        let mut rest: VecDeque<PpTok> = toks.into();
+
+        while let Some(tok) = rest.pop_front() {
+            if tok.kind != PpTokType::Identifier || tok.hide.contains(&tok.text) {
+                res.push(tok);
+                continue;
+            }
+            match tok.text.as_ref() {
+                "__LINE__" => {
+                    let line = ctx.presumed_line(tok.line).to_string();
+                    res.push(PpTok {
+                        kind: PpTokType::Number,
+                        text: line,
+                        ..tok
+                    });
+                    continue;
+                }
+                "__FILE__" => {
+                    res.push(PpTok {
+                        kind: PpTokType::StringLit,
+                        text: quote(&ctx.name),
+                        ..tok
+                    });
+                    continue;
+                }
+                _ => {}
+            }
+            let m = match self.macros.get(&tok.text) {
+                Some(m) => m.clone(),
+                None => {
+                    res.push(tok);
+                    continue;
+                }
+            };
+
+            let (args, hide) = if m.params.is_none() {
+                let mut hide = tok.hide.clone();
+                hide.push(tok.text.clone());
+                (Vec::new(), hide)
+            } else {
+                // a function-like macro name not followed by `(` is left alone
+                if !rest.front().is_some_and(|t| t.is("(")) {
+                    res.push(tok);
+                    continue;
+                }
+                let (args, rparen) = self.collect_args(&mut rest, &m, &tok, ctx)?;
+                let mut hide: Vec<String> = tok
+                    .hide
+                    .iter()
+                    .filter(|h| rparen.hide.contains(h))
+                    .cloned()
+                    .collect();
+                hide.push(tok.text.clone());
+                (args, hide)
+            };
+
+            let body = self.subst(&m, &args, ctx)?;
+            for (i, mut t) in body.into_iter().enumerate().rev() {
+                for h in hide.iter() {
+                    if !t.hide.contains(h) {
+                        t.hide.push(h.clone());
+                    }
+                }
+                t.line = tok.line;
+                // only the first token of the expansion keeps the column of the macro name
+                t.col = if i == 0 { tok.col } else { 0 };
+                if i == 0 {
+                    t.space = tok.space;
+                }
+                rest.push_front(t);
+            }
+        }
+        Ok(res)
+    }
+
+    fn collect_args(
+        &self,
+        rest: &mut VecDeque<PpTok>,
+        m: &Macro,
+        name: &PpTok,
+        ctx: &FileCtx,
+    ) -> Result<(Vec<Vec<PpTok>>, PpTok), Diagnostic> {
+        let nparams = m.params.as_ref().map_or(0, Vec::len);
+        rest.pop_front(); // (
+        let mut args: Vec<Vec<PpTok>> = vec![Vec::new()];
+        let mut depth = 0;
+        let rparen = loop {
+            let tok = match rest.pop_front() {
+                Some(tok) => tok,
+                None => {
+                    return Err(ctx.error_at(
+                        code::PP_MACRO,
+                        name,
+                        &format!(
+                            "unterminated argument list invoking macro \"{}\"",
+                            name.text
+                        ),
+                    ))
+                }
+            };
+            if tok.is("(") {
+                depth += 1;
+            } else if tok.is(")") {
+                if depth == 0 {
+                    break tok;
+                }
+                depth -= 1;
+            } else if tok.is(",") && depth == 0 && !(m.variadic && args.len() == nparams) {
+                args.push(Vec::new());
+                continue;
+            }
+            args.last_mut().expect("at least one argument").push(tok);
+        };
+
+        if nparams == 0 && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+        if m.variadic && args.len() + 1 == nparams {
+            args.push(Vec::new());
+        }
+        if args.len() != nparams {
+            return Err(ctx.error_at(
+                code::PP_MACRO,
+                name,
+                &format!(
+                    "macro \"{}\" requires {} arguments, but {} given",
+                    name.text,
+                    nparams,
+                    args.len()
+                ),
+            ));
+        }
+        Ok((args, rparen))
+    }
+
+    // replace the parameters in the body of `m` and apply `#` and `##`
+    fn subst(
+        &mut self,
+        m: &Macro,
+        args: &[Vec<PpTok>],
+        ctx: &FileCtx,
+    ) -> Result<Vec<PpTok>, Diagnostic> {
+        let body = &m.body;
+        let mut res: Vec<PpTok> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let tok = &body[i];
+            let next = body.get(i + 1);
+
+            if m.params.is_some() && tok.is("#") {
+                let arg = &args[m.param_index(&body[i + 1]).expect("checked by #define")];
+                let mut s = stringify(arg, tok.line);
+                s.space = tok.space;
+                res.push(s);
+                i += 2;
+                continue;
+            }
+
+            if tok.is(",") && m.variadic && next.is_some_and(|t| t.is("##")) {
+                // GNU extension: `, ## __VA_ARGS__` drops the comma if there are no variable arguments
+                if let Some(va) = body.get(i + 2).filter(|t| t.text == "__VA_ARGS__") {
+                    let arg = &args[m.param_index(va).expect("__VA_ARGS__ is a parameter")];
+                    if !arg.is_empty() {
+                        res.push(tok.clone());
+                        res.extend(arg.iter().cloned());
+                    }
+                    i += 3;
+                    continue;
+                }
+            }
+
+            if tok.is("##") {
+                let rhs = &body[i + 1];
+                let rhs_toks = match m.param_index(rhs) {
+                    Some(idx) => args[idx].clone(),
+                    None => vec![rhs.clone()],
+                };
+                i += 2;
+                if rhs_toks.is_empty() {
+                    continue;
+                }
+                let lhs = res.pop().expect("## is never the first token");
+                if lhs.kind == PpTokType::Placemarker {
+                    let mut rhs_toks = rhs_toks;
+                    rhs_toks[0].space = lhs.space;
+                    res.extend(rhs_toks);
+                    continue;
+                }
+                let pasted = paste(&lhs, &rhs_toks[0])
+                    .map_err(|e| ctx.error_at(code::PP_MACRO, &lhs, &e))?;
+                res.push(pasted);
+                res.extend(rhs_toks[1..].iter().cloned());
+                continue;
+            }
+
+            match m.param_index(tok) {
+                Some(idx) => {
+                    let arg = if next.is_some_and(|t| t.is("##")) {
+                        if args[idx].is_empty() {
+                            let mut placemarker = PpTok::new(PpTokType::Placemarker, "", tok.line);
+                            placemarker.space = tok.space;
+                            vec![placemarker]
+                        } else {
+                            args[idx].clone()
+                        }
+                    } else {
+                        self.expand(args[idx].clone(), ctx)?
+                    };
+                    let first = res.len();
+                    res.extend(arg);
+                    if let Some(t) = res.get_mut(first) {
+                        t.space = tok.space;
+                    }
+                }
+                None => res.push(tok.clone()),
+            }
+            i += 1;
+        }
+        res.retain(|t| t.kind != PpTokType::Placemarker);
+        Ok(res)
+    }
+}
+
+fn paste(lhs: &PpTok, rhs: &PpTok) -> Result<PpTok, String> {
+    let text = format!("{}{}", lhs.text, rhs.text);
+    let invalid = || {
+        format!(
+            "pasting \"{}\" and \"{}\" does not give a valid preprocessing token",
+            lhs.text, rhs.text
+        )
+    };
+    let mut toks = tokenize(&text, "<paste>").map_err(|_| invalid())?;
+    toks.retain(|t| t.kind != PpTokType::Newline);
+    if toks.len() != 1 || toks[0].space {
+        return Err(invalid());
+    }
+    let mut tok = toks.remove(0);
+    tok.space = lhs.space;
+    tok.line = lhs.line;
+    tok.col = lhs.col;
+    tok.hide = lhs.hide.clone();
+    Ok(tok)
+}
+
+fn join_text(toks: &[PpTok]) -> String {
+    let mut s = String::new();
+    for (i, tok) in toks.iter().enumerate() {
+        if i > 0 && tok.space {
+            s.push(' ');
+        }
+        s.push_str(&tok.text);
+    }
+    s
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// preprocess `input`, the contents of the file at `path`.
+// `defines` are the -D options, in the form `NAME` or `NAME=VALUE`.
+// #warning directives end up in `warnings`.
+pub fn preprocess(
+    path: &Path,
+    input: &str,
+    include_dirs: &[PathBuf],
+    defines: &[String],
+    warnings: &mut Vec<Diagnostic>,
+) -> Result<String, Diagnostic> {
+    let mut pp = Preprocessor {
+        include_dirs: include_dirs.to_vec(),
+        macros: HashMap::new(),
+        once: HashSet::new(),
+        out: Writer {
+            buf: String::new(),
+            line: 1,
+            col: 1,
+            at_line_start: true,
+            prev: None,
+        },
+        depth: 0,
+        warnings: Vec::new(),
+    };
+    for (name, value) in PREDEFINED_MACROS.iter() {
+        pp.define(name, value)?;
+    }
+    for def in defines {
+        let (name, value) = match def.find('=') {
+            Some(idx) => (&def[..idx], &def[idx + 1..]),
+            None => (&def[..], "1"),
+        };
+        pp.define(name, value)?;
+    }
+    let res = pp.run_file(path, input, None);
+    warnings.append(&mut pp.warnings);
+    res?;
+    Ok(pp.out.finish())
+}
@@ -0,0 +1,205 @@
+use crate::ir::liveness::liveness;
+use crate::ir::{CallRet, Function, Inst, Ty, VReg};
+use std::collections::{HashMap, HashSet};
+
+// Linear scan register allocation over the virtual registers of a function
+// (Poletto and Sarkar).  The instructions are numbered in the order of the
+// blocks, and each virtual register gets the one interval from the first
+// to the last place it is live; the intervals are walked by their start,
+// each taking a machine register that is free by then, and when none is,
+// the one of them that goes on longest is spilled to an eightbyte of the
+// frame.  A register with a hint, the one gen is going to move it from or
+// to, gets that one if it is free.
+//
+// gen keeps %rax, %rcx, %rdx and %r11 for itself: division, shifts and
+// copies need the first three, and they are where it puts the operands that
+// can not be used where they are.  A call clobbers the caller-saved
+// registers, so an interval that is live across one only gets a
+// callee-saved register, which the function saves in its frame.
+//
+// A floating value takes one of %xmm0 to %xmm13, gen keeping %xmm14 and
+// %xmm15 for itself the way it does %rcx and %rax.  All of them are
+// caller-saved, so one that is live across a call is spilled.  A long
+// double never gets a register, the x87 only computes on it, and always
+// takes two eightbytes of the frame.
+
+// where a virtual register lives
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Loc {
+    Reg(&'static str),
+    Spill(usize), // the index of its eightbyte among the spills, the lower of a long double's two
+}
+
+const CALLER_SAVED: [&str; 5] = ["%rsi", "%rdi", "%r8", "%r9", "%r10"];
+const CALLEE_SAVED: [&str; 5] = ["%rbx", "%r12", "%r13", "%r14", "%r15"];
+const XMM: [&str; 14] = [
+    "%xmm0", "%xmm1", "%xmm2", "%xmm3", "%xmm4", "%xmm5", "%xmm6", "%xmm7", "%xmm8", "%xmm9",
+    "%xmm10", "%xmm11", "%xmm12", "%xmm13",
+];
+
+pub struct Allocation {
+    pub locs: Vec<Option<Loc>>, // None for a register the function never uses
+    pub spills: usize,
+    pub saved: Vec<&'static str>, // the callee-saved registers it takes
+    pub entry: HashSet<VReg>,     // the registers live when the function is entered
+}
+
+struct Interval {
+    reg: VReg,
+    start: usize,
+    end: usize,
+    across_call: bool,
+    float: bool,
+}
+
+// An instruction at index `i` reads its operands at 2i and writes its result
+// at 2i + 1, so an operand that dies there can share the register of the
+// result, but not with a value that is live through it.
+pub fn allocate(func: &Function, hints: &HashMap<VReg, &'static str>) -> Allocation {
+    let live = liveness(func);
+    let mut ranges: Vec<Option<(usize, usize)>> = vec![None; func.vregs.len()];
+    let mut extend = |r: VReg, pos: usize| {
+        ranges[r.0] = Some(match ranges[r.0] {
+            Some((start, end)) => (start.min(pos), end.max(pos)),
+            None => (pos, pos),
+        });
+    };
+    let mut calls = Vec::new();
+    let mut pos = 0;
+    for (b, block) in func.blocks.iter().enumerate() {
+        for r in live.live_in[b].iter() {
+            extend(*r, 2 * pos);
+        }
+        for inst in block.insts.iter() {
+            for r in inst.uses() {
+                extend(r, 2 * pos);
+            }
+            if let Some(r) = inst.def() {
+                extend(r, 2 * pos + 1);
+            }
+            if let Inst::Call { ret, .. } = inst {
+                calls.push(pos);
+                // the struct is stored through its address after the call
+                if let CallRet::Struct(addr, _) = ret {
+                    extend(*addr, 2 * pos + 2);
+                }
+            }
+            pos += 1;
+        }
+        for r in block.term.uses() {
+            extend(r, 2 * pos);
+        }
+        for r in live.live_out[b].iter() {
+            extend(*r, 2 * pos + 1);
+        }
+        pos += 1;
+    }
+
+    let mut intervals: Vec<Interval> = ranges
+        .iter()
+        .enumerate()
+        .filter_map(|(r, range)| {
+            let (start, end) = (*range)?;
+            // the first call at or after the start, if it returns before the end
+            let i = calls.partition_point(|c| 2 * c < start);
+            Some(Interval {
+                reg: VReg(r),
+                start,
+                end,
+                across_call: calls.get(i).is_some_and(|c| 2 * c + 2 <= end),
+                float: func.ty(VReg(r)).is_float(),
+            })
+        })
+        .collect();
+    intervals.sort_by_key(|iv| iv.start);
+
+    // the registers not taken, the ones to take first last
+    let mut caller_free: Vec<&'static str> = CALLER_SAVED.iter().rev().copied().collect();
+    let mut callee_free: Vec<&'static str> = CALLEE_SAVED.iter().rev().copied().collect();
+    let mut xmm_free: Vec<&'static str> = XMM.iter().rev().copied().collect();
+    let mut active: Vec<(usize, &'static str, VReg)> = Vec::new(); // end, register, owner
+    let mut locs = vec![None; func.vregs.len()];
+    let mut spills = 0;
+    let mut saved = Vec::new();
+    for iv in intervals.iter() {
+        active.retain(|(end, reg, _)| {
+            if *end < iv.start {
+                if CALLEE_SAVED.contains(reg) {
+                    callee_free.push(reg);
+                } else if XMM.contains(reg) {
+                    xmm_free.push(reg);
+                } else {
+                    caller_free.push(reg);
+                }
+                false
+            } else {
+                true
+            }
+        });
+        if func.ty(iv.reg) == Ty::F80 {
+            locs[iv.reg.0] = Some(Loc::Spill(spills + 1));
+            spills += 2;
+            continue;
+        }
+        let hint = hints.get(&iv.reg).copied();
+        let take = |free: &mut Vec<&'static str>| match hint
+            .and_then(|h| free.iter().position(|reg| *reg == h))
+        {
+            Some(i) => Some(free.remove(i)),
+            None => free.pop(),
+        };
+        let free = if iv.float {
+            if iv.across_call {
+                None
+            } else {
+                take(&mut xmm_free)
+            }
+        } else if iv.across_call {
+            take(&mut callee_free)
+        } else {
+            take(&mut caller_free).or_else(|| take(&mut callee_free))
+        };
+        let reg = match free {
+            Some(reg) => reg,
+            None => {
+                // the one going on longest of those whose register would do
+                let victim = active
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, reg, _))| match (iv.float, iv.across_call) {
+                        (true, across_call) => !across_call && XMM.contains(reg),
+                        (false, true) => CALLEE_SAVED.contains(reg),
+                        (false, false) => !XMM.contains(reg),
+                    })
+                    .max_by_key(|(_, (end, _, _))| *end)
+                    .map(|(i, _)| i);
+                match victim {
+                    Some(i) if active[i].0 > iv.end => {
+                        let (_, reg, owner) = active.remove(i);
+                        locs[owner.0] = Some(Loc::Spill(spills));
+                        spills += 1;
+                        reg
+                    }
+                    _ => {
+                        locs[iv.reg.0] = Some(Loc::Spill(spills));
+                        spills += 1;
+                        continue;
+                    }
+                }
+            }
+        };
+        if CALLEE_SAVED.contains(&reg) && !saved.contains(&reg) {
+            saved.push(reg);
+        }
+        locs[iv.reg.0] = Some(Loc::Reg(reg));
+        active.push((iv.end, reg, iv.reg));
+    }
+    saved.sort_by_key(|reg| CALLEE_SAVED.iter().position(|r| r == reg));
+
+    Allocation {
+        locs,
+        spills,
+        saved,
+        entry: live.live_in.into_iter().next().unwrap_or_default(),
+    }
+}
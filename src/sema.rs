@@ -0,0 +1,1613 @@
+use crate::diagnostic::{code, Diagnostic};
+use crate::ir::lower;
+use crate::lexer::TokType;
+use crate::loc::Loc;
+use crate::parser::{Designator, NodeType, ParseNode, StmtType, Storage};
+use crate::types::{Type, UNKNOWN_LEN};
+use std::collections::HashMap;
+use std::mem;
+
+// The semantic analysis between the parser and the lowering.  It resolves every
+// name to the variable or function it means, works out the type of every
+// expression and writes it into the `ty` of the node, and rejects what the
+// grammar lets through but C does not: an undeclared name, `*1`, `1 = a`, a
+// call with the wrong number of arguments, a definition that does not match the
+// prototype.  The arguments of a call are converted to the types of the
+// parameters by `Cast` nodes it adds.  The lowering trusts the tree it is
+// given, what is still wrong there is a bug in crust.
+
+// an aggregate an initializer list is going through, the one the braces are
+// for and those it went into for a designator or without braces of their own
+struct Frame {
+    ty: Type,
+    offset: i64, // in the object initialized
+    next: i64,   // the element or member the next initializer is for
+}
+
+// a function declared so far, the parameters only when a declaration lists
+// them: `int f()` takes any arguments
+struct Function {
+    ret: Type,
+    params: Option<Vec<Type>>,
+    variadic: bool,
+    defined: bool,
+    implicit: bool, // by calling it before any declaration
+    loc: Loc,       // of the first declaration
+}
+
+impl Function {
+    fn new(tree: &ParseNode, ret: &Type, params: &Option<Vec<(String, Type)>>) -> Function {
+        Function {
+            ret: ret.clone(),
+            params: params
+                .as_ref()
+                .map(|p| p.iter().map(|(_, t)| t.clone()).collect()),
+            variadic: false,
+            defined: false,
+            implicit: false,
+            loc: tree.loc.clone(),
+        }
+    }
+}
+
+// what an assignment converts its value for, the messages differ
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    Assign,
+    Init,
+    Return,
+    Argument(usize, &'a str), // the position counted from 1, the function
+}
+
+struct Checker<'a> {
+    functions: HashMap<String, Function>,
+    globals: HashMap<String, Type>,
+    scopes: Vec<HashMap<String, Type>>, // the blocks we are in, the innermost last
+    ret: Type,                          // of the function we are in
+    variadic: bool,                     // whether it takes `...`
+    warnings: &'a mut Vec<Diagnostic>,
+}
+
+pub fn check(tree: &mut ParseNode, warnings: &mut Vec<Diagnostic>) -> Result<(), Diagnostic> {
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        ret: Type::Int,
+        variadic: false,
+        warnings,
+    };
+    for it in tree.child.iter_mut() {
+        match it.entry.clone() {
+            NodeType::FnDecl(name, ret, params, variadic, _) => {
+                let f = Function {
+                    variadic,
+                    ..Function::new(it, &ret, &params)
+                };
+                checker.declare_fn(it, &name, f)?
+            }
+            NodeType::Fn(name, ret, params, variadic, _) => {
+                let f = Function {
+                    variadic,
+                    defined: true,
+                    ..Function::new(it, &ret, &params)
+                };
+                checker.declare_fn(it, &name, f)?;
+                checker.function(it, ret, params, variadic)?
+            }
+            NodeType::Declare(name, t, storage) => {
+                checker.declare(it, &name, &t, storage == Storage::Extern)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// the value of the integer constant expression `tree`, like the length of
+// an array, which the parser needs before sema has run; it is checked and
+// folded like a `case` label, a variable can only be the operand of
+// `sizeof`, whose type `var` gives
+pub fn constant(
+    tree: &mut ParseNode,
+    var: impl Fn(&str) -> Option<Type>,
+) -> Result<i64, Diagnostic> {
+    if let Some(name) = named(tree) {
+        return Err(error(
+            name,
+            code::NOT_CONSTANT,
+            "expression is not an integer constant expression",
+        ));
+    }
+    let mut operands = Vec::new();
+    sizeof_vars(tree, &mut operands);
+    let scope = operands
+        .into_iter()
+        .filter_map(|name| var(&name).map(|t| (name, t)))
+        .collect();
+    let mut warnings = Vec::new();
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        scopes: vec![scope],
+        ret: Type::Int,
+        variadic: false,
+        warnings: &mut warnings,
+    };
+    if !checker.value(tree)?.is_integer() {
+        return Err(error(
+            tree,
+            code::NOT_CONSTANT,
+            "expression is not an integer constant expression",
+        ));
+    }
+    lower::compute_const(tree)
+}
+
+// the first node in `tree` outside the operand of `sizeof` that names a
+// variable or a function, which has no value before the program runs
+fn named(tree: &ParseNode) -> Option<&ParseNode> {
+    match tree.entry {
+        NodeType::Var(_)
+        | NodeType::ArrayRef(_)
+        | NodeType::AssignNode(..)
+        | NodeType::FnCall(_) => Some(tree),
+        NodeType::Sizeof(_) | NodeType::Alignof(_) => None,
+        _ => tree.child.iter().find_map(named),
+    }
+}
+
+// the names of the variables in the operands of `sizeof` in `tree`
+fn sizeof_vars(tree: &ParseNode, found: &mut Vec<String>) {
+    match &tree.entry {
+        NodeType::Var(name) | NodeType::ArrayRef(name) | NodeType::AssignNode(name, _) => {
+            found.push(name.clone())
+        }
+        _ => {}
+    }
+    for it in tree.child.iter() {
+        sizeof_vars(it, found);
+    }
+}
+
+fn error(tree: &ParseNode, code: &'static str, msg: &str) -> Diagnostic {
+    Diagnostic::error(code, &tree.loc, msg)
+}
+
+fn ice(tree: &ParseNode, msg: &str) -> Diagnostic {
+    Diagnostic::ice(&tree.loc, msg)
+}
+
+fn is_scalar(t: &Type) -> bool {
+    t.is_arithmetic() || matches!(t, Type::Ptr(_))
+}
+
+// strip the nodes that only wrap the expression inside
+fn inner(tree: &ParseNode) -> &ParseNode {
+    match tree.entry {
+        NodeType::Exp
+        | NodeType::ExpOption
+        | NodeType::Factor
+        | NodeType::Term
+        | NodeType::AdditiveExp
+        | NodeType::RelationalExp
+        | NodeType::EqualityExp
+        | NodeType::LogicalAndExp
+        | NodeType::LogicalOrExp
+        | NodeType::ConditionalExp
+            if tree.child.len() == 1 =>
+        {
+            inner(&tree.child[0])
+        }
+        _ => tree,
+    }
+}
+
+// whether the expression designates an object, which has an address
+fn is_lvalue(tree: &ParseNode) -> bool {
+    let tree = inner(tree);
+    match &tree.entry {
+        NodeType::Var(_)
+        | NodeType::ArrayRef(_)
+        | NodeType::UnExp(TokType::Multi)
+        | NodeType::CompoundLiteral(_) => true,
+        // the member of a struct a call returns lives no longer than the
+        // expression, the lowering only gives it an address for reading it
+        NodeType::Member(_) => tree.child.first().is_some_and(is_lvalue),
+        _ => false,
+    }
+}
+
+// an error unless `tree` of type `t` is an lvalue an assignment can store
+// to, `what` tells which operand of which operator it is
+fn check_lvalue(tree: &ParseNode, t: &Type, what: &str) -> Result<(), Diagnostic> {
+    if !is_lvalue(tree) || matches!(t, Type::Array(_, _) | Type::Func(..)) {
+        return Err(error(
+            tree,
+            code::NOT_LVALUE,
+            &format!("lvalue required as {}", what),
+        ));
+    }
+    Ok(())
+}
+
+// the integer constant 0, which converts to any pointer
+fn is_null(tree: &ParseNode) -> bool {
+    matches!(inner(tree).entry, NodeType::Const(0, _))
+}
+
+// the type of `lhs op rhs` for the operands of types `lhs` and `rhs` once
+// they decay, None if the operator does not take them
+fn binary_type(op: &TokType, lhs: &Type, rhs: &Type) -> Option<Type> {
+    match (op, lhs.pointee(), rhs.pointee()) {
+        // the bitwise operators and `%` only take integers, a shift has the
+        // type of its left operand
+        (TokType::Shl, _, _) | (TokType::Shr, _, _) if lhs.is_integer() && rhs.is_integer() => {
+            Some(lhs.promote())
+        }
+        (TokType::Percent, _, _)
+        | (TokType::Addr, _, _)
+        | (TokType::BitOr, _, _)
+        | (TokType::BitXor, _, _)
+            if lhs.is_integer() && rhs.is_integer() =>
+        {
+            Some(Type::common(lhs, rhs))
+        }
+        (TokType::Percent, _, _)
+        | (TokType::Addr, _, _)
+        | (TokType::BitOr, _, _)
+        | (TokType::BitXor, _, _)
+        | (TokType::Shl, _, _)
+        | (TokType::Shr, _, _) => None,
+        _ if lhs.is_arithmetic() && rhs.is_arithmetic() => match op {
+            TokType::Plus | TokType::Minus | TokType::Multi | TokType::Splash => {
+                Some(Type::common(lhs, rhs))
+            }
+            _ => Some(Type::Int),
+        },
+        (TokType::Plus, Some(_), None) | (TokType::Minus, Some(_), None) => Some(lhs.clone()),
+        (TokType::Plus, None, Some(_)) => Some(rhs.clone()),
+        // pointer - pointer, ptrdiff_t
+        (TokType::Minus, Some(l), Some(r)) if l.size() == r.size() => Some(Type::Long),
+        // comparisons and logical operators
+        (TokType::Plus, _, _)
+        | (TokType::Minus, _, _)
+        | (TokType::Multi, _, _)
+        | (TokType::Splash, _, _) => None,
+        (TokType::And, _, _) | (TokType::Or, _, _) if is_scalar(lhs) && is_scalar(rhs) => {
+            Some(Type::Int)
+        }
+        // a pointer compares with a pointer or an integer
+        _ if is_scalar(lhs) && is_scalar(rhs) => {
+            if lhs.is_floating() || rhs.is_floating() {
+                None
+            } else {
+                Some(Type::Int)
+            }
+        }
+        _ => None,
+    }
+}
+
+// wrap `tree` in a conversion to `to`
+fn cast(tree: &mut ParseNode, to: &Type) {
+    let mut node = ParseNode::new(tree.loc.clone());
+    node.entry = NodeType::Cast(to.clone());
+    node.ty = Some(to.clone());
+    std::mem::swap(tree, &mut node);
+    tree.child.push(node);
+}
+
+// the default argument promotions of the arguments no prototype gives a type
+// to: a float is passed as a double, the lowering promotes the integers
+fn promote_args(args: &mut [ParseNode]) {
+    for arg in args.iter_mut() {
+        if arg.ty == Some(Type::Float) {
+            cast(arg, &Type::Double);
+        }
+    }
+}
+
+// the type two declarations of the same variable give it, if they agree: a
+// length tells what `extern int a[];` left out
+fn composite(a: &Type, b: &Type) -> Option<Type> {
+    match (a, b) {
+        _ if a == b => Some(a.clone()),
+        (Type::Array(x, UNKNOWN_LEN), Type::Array(y, _)) if x == y => Some(b.clone()),
+        (Type::Array(x, _), Type::Array(y, UNKNOWN_LEN)) if x == y => Some(a.clone()),
+        _ => None,
+    }
+}
+
+// the element or member `i` of the aggregate `t` and its offset in it, None
+// past the end
+fn sub_object(t: &Type, i: i64) -> Option<(Type, i64)> {
+    match t {
+        Type::Array(elem, len) if *len == UNKNOWN_LEN || i < *len => {
+            Some(((**elem).clone(), i * elem.size()))
+        }
+        Type::Struct(s) => {
+            let m = s.members().into_iter().nth(i as usize)?;
+            Some((m.ty, m.offset))
+        }
+        _ => None,
+    }
+}
+
+// where the initializer after the one for the element or member `i` of `t`
+// goes: a union has room for one
+fn after(t: &Type, i: i64) -> i64 {
+    match t {
+        Type::Struct(s) if s.is_union => i64::MAX,
+        _ => i + 1,
+    }
+}
+
+// the string literal that initializes the array of char `t`, if `tree` is one
+fn string_for<'t>(tree: &'t ParseNode, t: &Type) -> Option<&'t [u8]> {
+    match (t, &inner(tree).entry) {
+        (Type::Array(elem, _), NodeType::StringLiteral(data, _))
+            if matches!(**elem, Type::Char | Type::SChar | Type::UChar) =>
+        {
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+// the offset and the size of the item `it` of an initializer
+fn span(it: &ParseNode) -> (i64, i64) {
+    match (&it.entry, &it.ty) {
+        (NodeType::InitAt(at), Some(t)) => (*at, t.size()),
+        _ => (0, 0),
+    }
+}
+
+// forget what initialized the `size` bytes at `offset` before, a later
+// initializer for them overrides it; `items` are in the order of their
+// offsets and do not overlap, gives where an item at `offset` goes
+fn overwrite(items: &mut Vec<ParseNode>, offset: i64, size: i64) -> usize {
+    // the usual case, the initializers in the order of the object
+    match items.last().map(span) {
+        Some((at, n)) if at + n > offset => {}
+        _ => return items.len(),
+    }
+    let first = items.partition_point(|it| {
+        let (at, n) = span(it);
+        at + n <= offset
+    });
+    let end = first + items[first..].partition_point(|it| span(it).0 < offset + size);
+    items.drain(first..end);
+    first
+}
+
+// `value` initializes the `ty` at `offset`
+fn init_at(items: &mut Vec<ParseNode>, offset: i64, ty: &Type, value: ParseNode) {
+    let i = overwrite(items, offset, ty.size());
+    let mut node = ParseNode::new(value.loc.clone());
+    node.entry = NodeType::InitAt(offset);
+    node.ty = Some(ty.clone());
+    node.child.push(value);
+    items.insert(i, node);
+}
+
+// go to what `designators` name, from the aggregate on top of `frames`
+fn designate(
+    tree: &ParseNode,
+    frames: &mut Vec<Frame>,
+    designators: &[Designator],
+) -> Result<(), Diagnostic> {
+    let (d, rest) = match designators.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    let top = frames
+        .last_mut()
+        .ok_or_else(|| ice(tree, "no object to designate in"))?;
+    let index = match (d, &top.ty) {
+        (Designator::Index(i), Type::Array(_, len)) => {
+            if *i < 0 || (*len != UNKNOWN_LEN && i >= len) {
+                return Err(error(
+                    tree,
+                    code::INVALID_INIT,
+                    &format!("array index {} in initializer exceeds array bounds", i),
+                ));
+            }
+            *i
+        }
+        (Designator::Index(_), _) => {
+            return Err(error(
+                tree,
+                code::INVALID_INIT,
+                "array index in non-array initializer",
+            ))
+        }
+        (Designator::Member(name), Type::Struct(s)) => {
+            let members = s.members();
+            match members.iter().position(|m| m.name.as_ref() == Some(name)) {
+                Some(i) => i as i64,
+                None => {
+                    // a member of an anonymous struct or union is reached
+                    // through it
+                    let anonymous = members.iter().position(|m| {
+                        m.name.is_none()
+                            && matches!(&m.ty, Type::Struct(inner) if inner.member(name).is_some())
+                    });
+                    let i = match anonymous {
+                        Some(i) => i as i64,
+                        None => {
+                            return Err(error(
+                                tree,
+                                code::INVALID_INIT,
+                                &format!("`{}` has no member named `{}`", s.name, name),
+                            ))
+                        }
+                    };
+                    let m = &members[i as usize];
+                    let offset = top.offset + m.offset;
+                    top.next = after(&top.ty, i);
+                    frames.push(Frame {
+                        ty: m.ty.clone(),
+                        offset,
+                        next: 0,
+                    });
+                    return designate(tree, frames, designators);
+                }
+            }
+        }
+        (Designator::Member(_), _) => {
+            return Err(error(
+                tree,
+                code::INVALID_INIT,
+                "field name not in record or union initializer",
+            ))
+        }
+    };
+    top.next = index;
+    if rest.is_empty() {
+        return Ok(());
+    }
+    let (ty, at) =
+        sub_object(&top.ty, index).ok_or_else(|| ice(tree, "designator out of the object"))?;
+    let offset = top.offset + at;
+    top.next = after(&top.ty, index);
+    frames.push(Frame {
+        ty,
+        offset,
+        next: 0,
+    });
+    designate(tree, frames, rest)
+}
+
+fn child(tree: &mut ParseNode, i: usize) -> Result<&mut ParseNode, Diagnostic> {
+    let msg = format!("{:?} node has no child {}", tree.entry, i);
+    let loc = tree.loc.clone();
+    tree.child
+        .get_mut(i)
+        .ok_or_else(|| Diagnostic::ice(&loc, &msg))
+}
+
+impl<'a> Checker<'a> {
+    // a variable in scope, local ones shadow the globals
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    fn var(&self, tree: &ParseNode, name: &str) -> Result<Type, Diagnostic> {
+        match self.lookup(name) {
+            Some(t) => Ok(t.clone()),
+            // a function used other than by calling it is its address
+            None => match self.functions.get(name) {
+                Some(f) => Ok(Type::Func(
+                    Box::new(f.ret.clone()),
+                    f.params.clone(),
+                    f.variadic,
+                )),
+                None => Err(error(
+                    tree,
+                    code::UNDECLARED,
+                    &format!("Use of undeclared variable `{}`", name),
+                )),
+            },
+        }
+    }
+
+    // a declaration or the definition of the function `name`, which has to
+    // agree with the ones before
+    fn declare_fn(&mut self, tree: &ParseNode, name: &str, f: Function) -> Result<(), Diagnostic> {
+        if self.globals.contains_key(name) {
+            return Err(error(
+                tree,
+                code::REDECLARED,
+                &format!("`{}` redeclared as different kind of symbol", name),
+            ));
+        }
+        let prev = match self.functions.get_mut(name) {
+            Some(prev) => prev,
+            None => {
+                self.functions.insert(name.to_string(), f);
+                return Ok(());
+            }
+        };
+        let same_params = match (&prev.params, &f.params) {
+            (Some(a), Some(b)) => a == b && prev.variadic == f.variadic,
+            _ => true,
+        };
+        let (msg, note) = if prev.defined && f.defined {
+            ("redefinition of", "previous definition of")
+        } else if prev.ret != f.ret || !same_params {
+            let note = if prev.implicit {
+                "previous implicit declaration of"
+            } else {
+                "previous declaration of"
+            };
+            ("conflicting types for", note)
+        } else {
+            // together the declarations say what each of them says
+            if prev.params.is_none() {
+                prev.params = f.params;
+                prev.variadic = f.variadic;
+            }
+            if f.defined {
+                prev.defined = true;
+                prev.loc = f.loc;
+            }
+            return Ok(());
+        };
+        Err(
+            error(tree, code::REDECLARED, &format!("{} `{}`", msg, name))
+                .note(Some(&prev.loc), &format!("{} `{}` is here", note, name)),
+        )
+    }
+
+    fn function(
+        &mut self,
+        tree: &mut ParseNode,
+        ret: Type,
+        params: Option<Vec<(String, Type)>>,
+        variadic: bool,
+    ) -> Result<(), Diagnostic> {
+        // the body may declare a parameter again, which shadows it as it does
+        // in the lowering
+        let mut scope = HashMap::new();
+        for (name, t) in params.into_iter().flatten() {
+            if scope.insert(name.clone(), t).is_some() {
+                return Err(error(
+                    tree,
+                    code::REDECLARED,
+                    &format!("redefinition of parameter `{}`", name),
+                ));
+            }
+        }
+        self.ret = ret;
+        self.variadic = variadic;
+        self.scopes = vec![scope, HashMap::new()];
+        for it in tree.child.iter_mut() {
+            self.item(it)?;
+        }
+        self.scopes.clear();
+        Ok(())
+    }
+
+    // a variable at file scope or in a block, whichever we are in
+    fn declare(
+        &mut self,
+        tree: &mut ParseNode,
+        name: &str,
+        t: &Type,
+        external: bool,
+    ) -> Result<(), Diagnostic> {
+        if let Type::Struct(s) = t.innermost() {
+            if !s.is_complete() && !external {
+                return Err(error(
+                    tree,
+                    code::INCOMPLETE_TYPE,
+                    &format!("storage size of `{}` isn't known", name),
+                ));
+            }
+        }
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                if scope.insert(name.to_string(), t.clone()).is_some() {
+                    return Err(error(
+                        tree,
+                        code::REDECLARED,
+                        &format!("redeclaration of variable `{}` in the same scope", name),
+                    ));
+                }
+            }
+            // the lowering sorts out the linkage of file scope declarations
+            None => {
+                if self.functions.contains_key(name) {
+                    return Err(error(
+                        tree,
+                        code::REDECLARED,
+                        &format!("`{}` redeclared as different kind of symbol", name),
+                    ));
+                }
+                let t = match self.globals.get(name) {
+                    Some(prev) => composite(prev, t).ok_or_else(|| {
+                        error(
+                            tree,
+                            code::REDECLARED,
+                            &format!("conflicting types for `{}`", name),
+                        )
+                    })?,
+                    None => t.clone(),
+                };
+                self.globals.insert(name.to_string(), t);
+            }
+        }
+        if let Some(init) = tree.child.first_mut() {
+            let t = self.initializer(init, t)?;
+            // the initializer tells how long `int a[] = { ... };` is
+            if let NodeType::Declare(_, ty, _) = &mut tree.entry {
+                *ty = t.clone();
+            }
+            match self.scopes.last_mut() {
+                Some(scope) => scope.insert(name.to_string(), t),
+                None => self.globals.insert(name.to_string(), t),
+            };
+        }
+        Ok(())
+    }
+
+    // check the initializer `init` of an object of type `t`, and bring one in
+    // braces to the form the lowering takes: an InitList of InitAt nodes for
+    // the scalars it gives a value, and the structs it copies, in the order
+    // of their offsets, which leaves the rest of the object 0.  Returns `t`
+    // with the length an array declared `[]` gets from it.
+    fn initializer(&mut self, init: &mut ParseNode, t: &Type) -> Result<Type, Diagnostic> {
+        if init.entry != NodeType::InitList {
+            if let Some(data) = string_for(init, t) {
+                // `char s[] = "abc";` is `char s[] = { 'a', 'b', 'c', 0 };`
+                let t = match t {
+                    Type::Array(elem, UNKNOWN_LEN) => {
+                        Type::Array(elem.clone(), data.len() as i64 + 1)
+                    }
+                    t => t.clone(),
+                };
+                let mut items = Vec::new();
+                self.string(&mut items, init, &t, 0);
+                init.entry = NodeType::InitList;
+                init.child = items;
+                init.ty = Some(t.clone());
+                return Ok(t);
+            }
+            if let Type::Array(_, _) = t {
+                return Err(error(
+                    init,
+                    code::INVALID_INIT,
+                    &format!("invalid initializer for `{}`, it needs braces", t),
+                ));
+            }
+            let from = self.value(init)?;
+            self.convert(init, t, &from, Target::Init)?;
+            return Ok(t.clone());
+        }
+        if is_scalar(t) {
+            self.unbrace(init)?;
+            return self.initializer(init, t);
+        }
+        let mut items = Vec::new();
+        let t = self.list(&mut items, init, t, 0)?;
+        init.child = items;
+        init.ty = Some(t.clone());
+        Ok(t)
+    }
+
+    // `{ 1 }` for a scalar is `1`
+    fn unbrace(&mut self, init: &mut ParseNode) -> Result<(), Diagnostic> {
+        if init.child.len() > 1 {
+            self.warnings.push(Diagnostic::warning(
+                code::EXCESS_INIT,
+                &init.child[1].loc,
+                "excess elements in scalar initializer",
+            ));
+        }
+        let first = match init.child.first() {
+            Some(first) if matches!(first.entry, NodeType::InitList | NodeType::Designated(_)) => {
+                return Err(error(
+                    first,
+                    code::INVALID_INIT,
+                    "braces or a designator in a scalar initializer",
+                ))
+            }
+            Some(first) => first.clone(),
+            None => return Err(error(init, code::INVALID_INIT, "empty scalar initializer")),
+        };
+        *init = first;
+        Ok(())
+    }
+
+    // the initializer `list` in braces for the aggregate `t` at `offset` in
+    // the object, adds its items to `items`; returns `t` with the length of
+    // an array declared `[]`
+    fn list(
+        &mut self,
+        items: &mut Vec<ParseNode>,
+        list: &mut ParseNode,
+        t: &Type,
+        offset: i64,
+    ) -> Result<Type, Diagnostic> {
+        overwrite(items, offset, t.size());
+        // `char s[] = { "abc" };` is the same as without the braces
+        if let [first] = list.child.as_slice() {
+            if let Some(data) = string_for(first, t) {
+                let t = match t {
+                    Type::Array(elem, UNKNOWN_LEN) => {
+                        Type::Array(elem.clone(), data.len() as i64 + 1)
+                    }
+                    t => t.clone(),
+                };
+                self.string(items, first, &t, offset);
+                return Ok(t);
+            }
+        }
+        let mut frames = vec![Frame {
+            ty: t.clone(),
+            offset,
+            next: 0,
+        }];
+        // the elements an array declared `[]` gets
+        let mut reach = 0;
+        for it in list.child.iter_mut() {
+            let value = match it.entry.clone() {
+                NodeType::Designated(designators) => {
+                    frames.truncate(1);
+                    designate(it, &mut frames, &designators)?;
+                    child(it, 0)?
+                }
+                _ => {
+                    // after the last member of what a designator or a value
+                    // without braces went into, on with the one it is in
+                    while frames.len() > 1 {
+                        let top = &frames[frames.len() - 1];
+                        if sub_object(&top.ty, top.next).is_some() {
+                            break;
+                        }
+                        frames.pop();
+                    }
+                    it
+                }
+            };
+            let top = frames
+                .last_mut()
+                .ok_or_else(|| ice(value, "no object to initialize"))?;
+            let (mut ty, mut at) = match sub_object(&top.ty, top.next) {
+                Some((ty, at)) => (ty, top.offset + at),
+                None => {
+                    let what = match &top.ty {
+                        Type::Array(_, _) => "array",
+                        Type::Struct(s) if s.is_union => "union",
+                        _ => "struct",
+                    };
+                    self.warnings.push(Diagnostic::warning(
+                        code::EXCESS_INIT,
+                        &value.loc,
+                        &format!("excess elements in {} initializer", what),
+                    ));
+                    continue;
+                }
+            };
+            top.next = after(&top.ty, top.next);
+            reach = reach.max(frames[0].next);
+            if value.entry == NodeType::InitList {
+                if !is_scalar(&ty) {
+                    self.list(items, value, &ty, at)?;
+                    continue;
+                }
+                self.unbrace(value)?;
+            }
+            let from = self.value(value)?;
+            // a value for an aggregate, other than a struct of its type or
+            // a string for an array of char, is for its first scalar; the
+            // values after it are for the rest of the aggregate, as if it
+            // had its braces
+            loop {
+                if string_for(value, &ty).is_some() {
+                    self.string(items, value, &ty, at);
+                    break;
+                }
+                if is_scalar(&ty) || ty == from {
+                    self.convert(value, &ty, &from, Target::Init)?;
+                    // the list is done with it, the item takes it over
+                    let loc = value.loc.clone();
+                    init_at(items, at, &ty, mem::replace(value, ParseNode::new(loc)));
+                    break;
+                }
+                let (first, first_at) = sub_object(&ty, 0).ok_or_else(|| {
+                    error(
+                        value,
+                        code::INVALID_INIT,
+                        &format!("invalid initializer for `{}`", ty),
+                    )
+                })?;
+                frames.push(Frame {
+                    next: after(&ty, 0),
+                    ty,
+                    offset: at,
+                });
+                ty = first;
+                at += first_at;
+            }
+        }
+        match t {
+            Type::Array(_, UNKNOWN_LEN) if reach == 0 => Err(error(
+                list,
+                code::INVALID_INIT,
+                "zero or negative size array",
+            )),
+            Type::Array(elem, UNKNOWN_LEN) => Ok(Type::Array(elem.clone(), reach)),
+            t => Ok(t.clone()),
+        }
+    }
+
+    // the string literal `value` for the array of char `t` at `offset`: a
+    // Const for every char, and the 0 after them if there is room
+    fn string(&mut self, items: &mut Vec<ParseNode>, value: &ParseNode, t: &Type, offset: i64) {
+        let (elem, len, data) = match (t, string_for(value, t)) {
+            (Type::Array(elem, len), Some(data)) => (elem, *len, data),
+            _ => return,
+        };
+        if data.len() as i64 > len {
+            self.warnings.push(Diagnostic::warning(
+                code::EXCESS_INIT,
+                &value.loc,
+                &format!("initializer-string for array of `{}` is too long", elem),
+            ));
+        }
+        overwrite(items, offset, t.size());
+        for (i, c) in data.iter().chain([0].iter()).take(len as usize).enumerate() {
+            let mut node = ParseNode::new(value.loc.clone());
+            node.entry = NodeType::Const(elem.wrap(*c as i64), (**elem).clone());
+            node.ty = Some((**elem).clone());
+            init_at(items, offset + i as i64, elem, node);
+        }
+    }
+
+    fn item(&mut self, tree: &mut ParseNode) -> Result<(), Diagnostic> {
+        match tree.entry.clone() {
+            NodeType::Declare(name, t, Storage::Extern) => self.declare_extern(tree, &name, &t),
+            NodeType::Declare(name, t, _) => self.declare(tree, &name, &t, false),
+            NodeType::TagDecl(_) => Ok(()),
+            _ => self.stmt(tree),
+        }
+    }
+
+    // `extern` in a block brings the global `name` into it, which has to
+    // agree with the one at file scope, and is initialized there if anywhere
+    fn declare_extern(
+        &mut self,
+        tree: &mut ParseNode,
+        name: &str,
+        t: &Type,
+    ) -> Result<(), Diagnostic> {
+        if !tree.child.is_empty() {
+            return Err(error(
+                tree,
+                code::INVALID_INIT,
+                &format!("`{}` has both `extern` and initializer", name),
+            ));
+        }
+        let t = match self.globals.get(name) {
+            Some(prev) => composite(prev, t).ok_or_else(|| {
+                error(
+                    tree,
+                    code::REDECLARED,
+                    &format!("conflicting types for `{}`", name),
+                )
+            })?,
+            None => t.clone(),
+        };
+        // the lowering needs the type the global has now
+        if let NodeType::Declare(_, ty, _) = &mut tree.entry {
+            *ty = t.clone();
+        }
+        self.declare(tree, name, &t, true)
+    }
+
+    fn stmt(&mut self, tree: &mut ParseNode) -> Result<(), Diagnostic> {
+        let stmt = match &tree.entry {
+            NodeType::Stmt(stmt) => stmt.clone(),
+            // an expression statement in a `for` and the like
+            _ => {
+                self.expr(tree)?;
+                return Ok(());
+            }
+        };
+        match stmt {
+            StmtType::Return => {
+                let res = child(tree, 0)?;
+                if self.ret == Type::Void {
+                    self.expr(res)?;
+                } else {
+                    let from = self.value(res)?;
+                    let ret = self.ret.clone();
+                    self.convert(res, &ret, &from, Target::Return)?;
+                }
+            }
+            StmtType::Exp => {
+                for it in tree.child.iter_mut() {
+                    self.expr(it)?;
+                }
+            }
+            StmtType::Conditional(_) | StmtType::While => {
+                self.condition(child(tree, 0)?)?;
+                for it in tree.child.iter_mut().skip(1) {
+                    self.stmt(it)?;
+                }
+            }
+            StmtType::Do => {
+                self.stmt(child(tree, 0)?)?;
+                self.condition(child(tree, 1)?)?;
+            }
+            StmtType::Compound => {
+                self.scopes.push(HashMap::new());
+                for it in tree.child.iter_mut() {
+                    self.item(it)?;
+                }
+                self.scopes.pop();
+            }
+            StmtType::For | StmtType::ForDecl => {
+                self.scopes.push(HashMap::new());
+                let first = child(tree, 0)?;
+                if stmt == StmtType::ForDecl {
+                    self.item(first)?;
+                } else {
+                    self.expr(first)?;
+                }
+                self.condition(child(tree, 1)?)?;
+                self.expr(child(tree, 2)?)?;
+                self.stmt(child(tree, 3)?)?;
+                self.scopes.pop();
+            }
+            StmtType::Switch => {
+                let exp = child(tree, 0)?;
+                let t = self.value(exp)?;
+                if !t.is_integer() {
+                    return Err(error(
+                        exp,
+                        code::INVALID_OPERAND,
+                        &format!("switch quantity of type `{}` is not an integer", t),
+                    ));
+                }
+                self.stmt(child(tree, 1)?)?;
+            }
+            StmtType::Case => {
+                self.value(child(tree, 0)?)?;
+                self.stmt(child(tree, 1)?)?;
+            }
+            StmtType::Default | StmtType::Label(_) => self.stmt(child(tree, 0)?)?,
+            StmtType::Goto(_, _) | StmtType::Break | StmtType::Continue => {}
+        }
+        Ok(())
+    }
+
+    // the controlling expression of `if`, a loop or `?:`, an empty one is true
+    fn condition(&mut self, tree: &mut ParseNode) -> Result<(), Diagnostic> {
+        if tree.entry == NodeType::ExpOption && tree.child.is_empty() {
+            return self.expr(tree).map(|_| ());
+        }
+        let t = self.value(tree)?;
+        if !is_scalar(&t) {
+            return Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!("used `{}` where a scalar is required", t),
+            ));
+        }
+        Ok(())
+    }
+
+    // the type of `tree` once arrays decay, where a value is needed
+    fn value(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        let t = self.expr(tree)?.decay();
+        if t == Type::Void {
+            return Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                "void value not ignored as it ought to be",
+            ));
+        }
+        Ok(t)
+    }
+
+    // the type of the expression `tree`, recorded in it and its children
+    fn expr(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        let t = self.expr_type(tree)?;
+        tree.ty = Some(t.clone());
+        Ok(t)
+    }
+
+    // every arm is a function of its own: this recurses once per level of
+    // the expression, and a frame with all of their locals would be big
+    fn expr_type(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        match tree.entry.clone() {
+            NodeType::Const(_, t) | NodeType::FloatConst(_, t) => Ok(t),
+            // an array of char, with the 0 at the end
+            NodeType::StringLiteral(data, _) => {
+                Ok(Type::Array(Box::new(Type::Char), data.len() as i64 + 1))
+            }
+            NodeType::Var(name) => self.var(tree, &name),
+            NodeType::ArrayRef(name) => self.elem(tree, &name),
+            NodeType::AssignNode(name, elem) => self.assign_name(tree, &name, elem),
+            NodeType::Assign => self.assign(tree),
+            NodeType::AssignOp(op) => self.assign_op(tree, &op),
+            NodeType::IncDec(op, _) => self.inc_dec(tree, &op),
+            NodeType::Member(name) => self.member(tree, &name),
+            NodeType::FnCall(name) => self.call(tree, &name),
+            NodeType::CallPtr => self.call_ptr(tree),
+            NodeType::VaArg(t) => self.va_arg(tree, t),
+            NodeType::Cast(to) => self.explicit_cast(tree, to),
+            NodeType::Sizeof(t) | NodeType::Alignof(t) => self.size_of(tree, t),
+            NodeType::CompoundLiteral(t) => self.compound_literal(tree, &t),
+            NodeType::UnExp(op) => self.unary(tree, &op),
+            NodeType::BinExp(op) => self.binary(tree, &op),
+            NodeType::ConditionalExp if tree.child.len() == 3 => self.conditional(tree),
+            // an empty <exp-option>, where the lowering computes nothing
+            NodeType::ExpOption if tree.child.is_empty() => Ok(Type::Int),
+            NodeType::Exp
+            | NodeType::ExpOption
+            | NodeType::Factor
+            | NodeType::Term
+            | NodeType::AdditiveExp
+            | NodeType::RelationalExp
+            | NodeType::EqualityExp
+            | NodeType::LogicalAndExp
+            | NodeType::LogicalOrExp
+            | NodeType::ConditionalExp => self.expr(child(tree, 0)?),
+            _ => Err(ice(
+                tree,
+                &format!("`{:?}` is not an expression", tree.entry),
+            )),
+        }
+    }
+
+    // `name = exp` or `name[index] = exp`
+    fn assign_name(
+        &mut self,
+        tree: &mut ParseNode,
+        name: &str,
+        elem: bool,
+    ) -> Result<Type, Diagnostic> {
+        let to = if elem {
+            self.elem(tree, name)?
+        } else {
+            self.var(tree, name)?
+        };
+        if let Type::Array(_, _) = to {
+            return Err(error(
+                tree,
+                code::NOT_LVALUE,
+                "assignment to an expression with array type",
+            ));
+        }
+        if let Type::Func(..) = to {
+            return Err(error(
+                tree,
+                code::NOT_LVALUE,
+                "lvalue required as left operand of assignment",
+            ));
+        }
+        let res = child(tree, usize::from(elem))?;
+        let from = self.value(res)?;
+        self.convert(res, &to, &from, Target::Assign)?;
+        Ok(to)
+    }
+
+    fn assign(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        let lhs = child(tree, 0)?;
+        let to = self.expr(lhs)?;
+        if let Type::Array(_, _) = to {
+            return Err(error(
+                lhs,
+                code::NOT_LVALUE,
+                "assignment to an expression with array type",
+            ));
+        }
+        check_lvalue(lhs, &to, "left operand of assignment")?;
+        let res = child(tree, 1)?;
+        let from = self.value(res)?;
+        self.convert(res, &to, &from, Target::Assign)?;
+        Ok(to)
+    }
+
+    // `a op= b` computes `a op b` and stores it back in `a`, which keeps a
+    // pointer a pointer
+    fn assign_op(&mut self, tree: &mut ParseNode, op: &TokType) -> Result<Type, Diagnostic> {
+        let lhs = child(tree, 0)?;
+        let to = self.expr(lhs)?;
+        if let Type::Array(_, _) = to {
+            return Err(error(
+                lhs,
+                code::NOT_LVALUE,
+                "assignment to an expression with array type",
+            ));
+        }
+        check_lvalue(lhs, &to, "left operand of assignment")?;
+        let from = self.value(child(tree, 1)?)?;
+        match binary_type(op, &to, &from) {
+            Some(t) if t.pointee().is_some() == to.pointee().is_some() => Ok(to),
+            _ => Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!("invalid operands to `{}=` (`{}` and `{}`)", op, to, from),
+            )),
+        }
+    }
+
+    fn inc_dec(&mut self, tree: &mut ParseNode, op: &TokType) -> Result<Type, Diagnostic> {
+        let operand = child(tree, 0)?;
+        let t = self.expr(operand)?;
+        let what = match op {
+            TokType::Inc => "increment operand",
+            _ => "decrement operand",
+        };
+        check_lvalue(operand, &t, what)?;
+        if !is_scalar(&t) {
+            return Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!("wrong type argument `{}` to `{}`", t, op),
+            ));
+        }
+        Ok(t)
+    }
+
+    fn member(&mut self, tree: &mut ParseNode, name: &str) -> Result<Type, Diagnostic> {
+        match self.expr(child(tree, 0)?)? {
+            Type::Struct(s) if !s.is_complete() => Err(error(
+                tree,
+                code::INCOMPLETE_TYPE,
+                &format!("invalid use of incomplete type `{}`", s.name),
+            )),
+            Type::Struct(s) => match s.member(name) {
+                Some(m) => Ok(m.ty),
+                None => Err(error(
+                    tree,
+                    code::UNDECLARED,
+                    &format!("`{}` has no member named `{}`", s.name, name),
+                )),
+            },
+            t => Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!(
+                    "request for member `{}` in something of type `{}`, which is not a struct or union",
+                    name, t
+                ),
+            )),
+        }
+    }
+
+    fn va_arg(&mut self, tree: &mut ParseNode, t: Type) -> Result<Type, Diagnostic> {
+        if !self.value(child(tree, 0)?)?.is_va_list() {
+            return Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                "first argument to `va_arg` is not a `va_list`",
+            ));
+        }
+        let msg = match &t {
+            Type::Void | Type::Array(_, _) => {
+                Some(format!("`va_arg` can not take an argument of type `{}`", t))
+            }
+            Type::Struct(s) if !s.is_complete() => {
+                Some(format!("`va_arg` of incomplete type `{}`", t))
+            }
+            _ => None,
+        };
+        match msg {
+            Some(msg) => Err(error(tree, code::INVALID_OPERAND, &msg)),
+            None => Ok(t),
+        }
+    }
+
+    // `(to)operand`
+    fn explicit_cast(&mut self, tree: &mut ParseNode, to: Type) -> Result<Type, Diagnostic> {
+        let operand = child(tree, 0)?;
+        // a cast to void throws the value away, whatever it is
+        if to == Type::Void {
+            self.expr(operand)?;
+            return Ok(to);
+        }
+        let from = self.value(operand)?;
+        let msg = match (&to, &from) {
+            (Type::Array(_, _), _) => Some("cast specifies array type".to_string()),
+            (Type::Struct(_), _) => {
+                Some(format!("conversion to non-scalar type `{}` requested", to))
+            }
+            (_, Type::Struct(_)) => Some(format!("used `{}` where a scalar is required", from)),
+            (Type::Ptr(_), t) | (t, Type::Ptr(_)) if t.is_floating() => Some(format!(
+                "can not convert `{}` to `{}`, a pointer to or from a floating type",
+                from, to
+            )),
+            _ => None,
+        };
+        match msg {
+            Some(msg) => Err(error(tree, code::INVALID_OPERAND, &msg)),
+            None => Ok(to),
+        }
+    }
+
+    // `sizeof` or `_Alignof` of the type `t`, or of the type of the kid
+    fn size_of(&mut self, tree: &mut ParseNode, t: Option<Type>) -> Result<Type, Diagnostic> {
+        let t = match t {
+            Some(t) => t,
+            None => self.expr(child(tree, 0)?)?,
+        };
+        let (op, n) = match tree.entry {
+            NodeType::Sizeof(_) => ("sizeof", t.size()),
+            _ => ("_Alignof", t.align()),
+        };
+        let incomplete = match &t {
+            Type::Struct(s) => !s.is_complete(),
+            t => t.is_unknown_array(),
+        };
+        if incomplete {
+            return Err(error(
+                tree,
+                code::INCOMPLETE_TYPE,
+                &format!("invalid application of `{}` to incomplete type `{}`", op, t),
+            ));
+        }
+        // a constant, the lowering never sees the operand, which is not
+        // evaluated
+        tree.entry = NodeType::Const(n, Type::ULong);
+        tree.child.clear();
+        Ok(Type::ULong)
+    }
+
+    fn compound_literal(&mut self, tree: &mut ParseNode, t: &Type) -> Result<Type, Diagnostic> {
+        if let Type::Struct(s) = t.innermost() {
+            if !s.is_complete() {
+                return Err(error(
+                    tree,
+                    code::INCOMPLETE_TYPE,
+                    &format!("compound literal has incomplete type `{}`", t),
+                ));
+            }
+        }
+        let t = self.initializer(child(tree, 0)?, t)?;
+        // `(int[]){ 1, 2 }` is as long as its initializer says
+        tree.entry = NodeType::CompoundLiteral(t.clone());
+        Ok(t)
+    }
+
+    fn unary(&mut self, tree: &mut ParseNode, op: &TokType) -> Result<Type, Diagnostic> {
+        match op {
+            TokType::Addr => {
+                let operand = child(tree, 0)?;
+                let t = self.expr(operand)?;
+                if !is_lvalue(operand) {
+                    return Err(error(
+                        operand,
+                        code::NOT_LVALUE,
+                        "lvalue required as unary `&` operand",
+                    ));
+                }
+                Ok(Type::ptr_to(t))
+            }
+            TokType::Multi => {
+                let t = self.value(child(tree, 0)?)?;
+                match t.pointee() {
+                    Some(pointee) => Ok(pointee.clone()),
+                    None => Err(error(
+                        tree,
+                        code::INVALID_OPERAND,
+                        &format!("invalid operand to unary `*`: `{}` is not a pointer", t),
+                    )),
+                }
+            }
+            _ => {
+                let t = self.value(child(tree, 0)?)?;
+                let ok = match op {
+                    TokType::Exclamation => is_scalar(&t),
+                    TokType::Minus => t.is_arithmetic(),
+                    _ => t.is_integer(),
+                };
+                if !ok {
+                    return Err(error(
+                        tree,
+                        code::INVALID_OPERAND,
+                        &format!("wrong type argument `{}` to unary `{}`", t, op),
+                    ));
+                }
+                match op {
+                    TokType::Exclamation => Ok(Type::Int),
+                    _ => Ok(t.promote()),
+                }
+            }
+        }
+    }
+
+    fn binary(&mut self, tree: &mut ParseNode, op: &TokType) -> Result<Type, Diagnostic> {
+        // the left operand of a comma only runs for what it does
+        if *op == TokType::Comma {
+            self.expr(child(tree, 0)?)?;
+            return Ok(self.expr(child(tree, 1)?)?.decay());
+        }
+        let lhs = self.value(child(tree, 0)?)?;
+        let rhs = self.value(child(tree, 1)?)?;
+        match binary_type(op, &lhs, &rhs) {
+            Some(t) => Ok(t),
+            None => Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!(
+                    "invalid operands to binary `{}` (`{}` and `{}`)",
+                    op, lhs, rhs
+                ),
+            )),
+        }
+    }
+
+    // `cond ? lhs : rhs`
+    fn conditional(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        self.condition(child(tree, 0)?)?;
+        let lhs = self.expr(child(tree, 1)?)?.decay();
+        let rhs = self.expr(child(tree, 2)?)?.decay();
+        if lhs.is_arithmetic() && rhs.is_arithmetic() {
+            Ok(Type::common(&lhs, &rhs))
+        } else if lhs.is_integer() && rhs.pointee().is_some() {
+            Ok(rhs) // like p ? p : 0
+        } else if lhs == rhs || (lhs.pointee().is_some() && rhs.is_integer()) {
+            Ok(lhs)
+        } else if lhs.pointee().is_some() && rhs.pointee().is_some() {
+            self.warnings.push(Diagnostic::warning(
+                code::POINTER_CONVERSION,
+                &tree.loc,
+                &format!(
+                    "pointer type mismatch in conditional expression (`{}` and `{}`)",
+                    lhs, rhs
+                ),
+            ));
+            Ok(lhs)
+        } else {
+            Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!(
+                    "type mismatch in conditional expression (`{}` and `{}`)",
+                    lhs, rhs
+                ),
+            ))
+        }
+    }
+
+    // the element `name[...]` of the array or pointer `name`
+    fn elem(&mut self, tree: &mut ParseNode, name: &str) -> Result<Type, Diagnostic> {
+        let base = self.var(tree, name)?;
+        let index = child(tree, 0)?;
+        let t = self.value(index)?;
+        if !t.is_integer() {
+            return Err(error(
+                index,
+                code::INVALID_OPERAND,
+                "array subscript is not an integer",
+            ));
+        }
+        match base.pointee() {
+            Some(elem) => Ok(elem.clone()),
+            None => Err(error(
+                tree,
+                code::INVALID_OPERAND,
+                &format!("subscripted value `{}` is neither array nor pointer", name),
+            )),
+        }
+    }
+
+    fn call(&mut self, tree: &mut ParseNode, name: &str) -> Result<Type, Diagnostic> {
+        if let "__builtin_va_start" | "__builtin_va_end" | "__builtin_va_copy" = name {
+            return self.va_builtin(tree, name);
+        }
+        // a variable is called through the pointer to a function in it
+        if let Some(t) = self.lookup(name) {
+            if !matches!(t.decay().pointee(), Some(Type::Func(..))) {
+                return Err(error(
+                    tree,
+                    code::BAD_CALL,
+                    &format!("called object `{}` is not a function", name),
+                ));
+            }
+            let mut callee = ParseNode::new(tree.loc.clone());
+            callee.entry = NodeType::Var(name.to_string());
+            tree.child.insert(0, callee);
+            tree.entry = NodeType::CallPtr;
+            return self.call_ptr(tree);
+        }
+        let mut args = Vec::new();
+        for it in tree.child.iter_mut() {
+            args.push(self.value(it)?);
+        }
+        let (ret, params, variadic) = match self.functions.get(name) {
+            Some(f) => (f.ret.clone(), f.params.clone(), f.variadic),
+            // C90 declared it as `int f()`, C99 wants a declaration first
+            None => {
+                self.warnings.push(Diagnostic::warning(
+                    code::IMPLICIT_DECLARATION,
+                    &tree.loc,
+                    &format!("implicit declaration of function `{}`", name),
+                ));
+                let f = Function {
+                    implicit: true,
+                    ..Function::new(tree, &Type::Int, &None)
+                };
+                self.functions.insert(name.to_string(), f);
+                promote_args(&mut tree.child);
+                return Ok(Type::Int);
+            }
+        };
+        self.arguments(tree, 0, name, &args, params, variadic)?;
+        Ok(ret)
+    }
+
+    // a call through a pointer to a function, the first kid
+    fn call_ptr(&mut self, tree: &mut ParseNode) -> Result<Type, Diagnostic> {
+        let callee = child(tree, 0)?;
+        let name = match &inner(callee).entry {
+            NodeType::Var(name) => name.clone(),
+            _ => "function pointer".to_string(),
+        };
+        let (ret, params, variadic) = match self.value(callee)?.pointee() {
+            Some(Type::Func(ret, params, variadic)) => ((**ret).clone(), params.clone(), *variadic),
+            _ => {
+                return Err(error(
+                    tree,
+                    code::BAD_CALL,
+                    "called object is not a function or function pointer",
+                ))
+            }
+        };
+        let mut args = Vec::new();
+        for it in tree.child[1..].iter_mut() {
+            args.push(self.value(it)?);
+        }
+        self.arguments(tree, 1, &name, &args, params, variadic)?;
+        Ok(ret)
+    }
+
+    // the arguments of the types `args` in the kids of the call `tree` from
+    // `first` on, converted to the parameters of the function `name`
+    fn arguments(
+        &mut self,
+        tree: &mut ParseNode,
+        first: usize,
+        name: &str,
+        args: &[Type],
+        params: Option<Vec<Type>>,
+        variadic: bool,
+    ) -> Result<(), Diagnostic> {
+        let kids = &mut tree.child[first..];
+        // the arguments of a function without a prototype are only promoted
+        let params = match params {
+            Some(params) => params,
+            None => {
+                promote_args(kids);
+                return Ok(());
+            }
+        };
+        if args.len() < params.len() || (args.len() > params.len() && !variadic) {
+            let few = if args.len() < params.len() {
+                "few"
+            } else {
+                "many"
+            };
+            return Err(error(
+                tree,
+                code::BAD_CALL,
+                &format!("too {} arguments to function `{}`", few, name),
+            ));
+        }
+        for (i, (to, from)) in params.iter().zip(args.iter()).enumerate() {
+            self.convert(&kids[i], to, from, Target::Argument(i + 1, name))?;
+            if is_scalar(to) && to != from {
+                cast(&mut kids[i], to);
+            }
+        }
+        promote_args(&mut kids[params.len()..]);
+        Ok(())
+    }
+
+    // what `va_start(ap, last)`, `va_end(ap)` and `va_copy(dest, src)` of
+    // <stdarg.h> are; `last` is not looked at, the lowering knows where the
+    // arguments after the named ones are
+    fn va_builtin(&mut self, tree: &mut ParseNode, name: &str) -> Result<Type, Diagnostic> {
+        let what = &name["__builtin_".len()..];
+        let n = if what == "va_end" { 1 } else { 2 };
+        if tree.child.len() != n {
+            return Err(error(
+                tree,
+                code::BAD_CALL,
+                &format!("wrong number of arguments to `{}`", what),
+            ));
+        }
+        if what == "va_start" && !self.variadic {
+            return Err(error(
+                tree,
+                code::BAD_CALL,
+                "`va_start` used in function with fixed arguments",
+            ));
+        }
+        // every argument is a va_list, but the `last` of va_start
+        let lists = if what == "va_start" { 1 } else { n };
+        for (i, it) in tree.child.iter_mut().enumerate() {
+            let t = self.value(it)?;
+            if i < lists && !t.is_va_list() {
+                return Err(error(
+                    it,
+                    code::INVALID_OPERAND,
+                    &format!("argument {} of `{}` is not a `va_list`", i + 1, what),
+                ));
+            }
+        }
+        Ok(Type::Void)
+    }
+
+    // check the value `res` of type `from` can be converted to `to` as if by
+    // assignment; what C only allows with a cast is a warning, like gcc
+    fn convert(
+        &mut self,
+        res: &ParseNode,
+        to: &Type,
+        from: &Type,
+        target: Target,
+    ) -> Result<(), Diagnostic> {
+        let what = match target {
+            Target::Assign => "assignment".to_string(),
+            Target::Init => "initialization".to_string(),
+            Target::Return => "returning".to_string(),
+            Target::Argument(i, f) => format!("passing argument {} of `{}`", i, f),
+        };
+        // no conversion between a struct or a floating value and a pointer
+        let incompatible = match (to, from) {
+            (Type::Struct(_), _) | (_, Type::Struct(_)) => to != from,
+            (Type::Ptr(_), t) | (t, Type::Ptr(_)) => t.is_floating(),
+            _ => false,
+        };
+        if incompatible {
+            let msg = match target {
+                Target::Assign => format!(
+                    "incompatible types when assigning to type `{}` from type `{}`",
+                    to, from
+                ),
+                Target::Init => format!(
+                    "incompatible types when initializing type `{}` using type `{}`",
+                    to, from
+                ),
+                Target::Return => format!(
+                    "incompatible types when returning type `{}` but `{}` was expected",
+                    from, to
+                ),
+                Target::Argument(i, f) => {
+                    format!("incompatible type for argument {} of `{}`", i, f)
+                }
+            };
+            return Err(error(res, code::INVALID_OPERAND, &msg));
+        }
+        let msg = match (to.pointee(), from.pointee()) {
+            (Some(_), None) if !is_null(res) => {
+                format!("{} makes pointer from integer without a cast", what)
+            }
+            (None, Some(_)) if *to != Type::Bool => {
+                format!("{} makes integer from pointer without a cast", what)
+            }
+            (Some(a), Some(b)) if a != b && *a != Type::Void && *b != Type::Void => format!(
+                "incompatible pointer types in {}: `{}` from `{}`",
+                what, to, from
+            ),
+            _ => return Ok(()),
+        };
+        self.warnings.push(Diagnostic::warning(
+            code::POINTER_CONVERSION,
+            &res.loc,
+            &msg,
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{self, Token};
+use crate::loc::SourceMap;
+use crate::parser::{self, ParseNode};
+use crate::{asm, gen, ir, opt, preprocessor, sema};
+use std::path::{Path, PathBuf};
+
+// What the preprocessor and the optimizer are told on the command line, the
+// rest of the stages have no options yet.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub include_dirs: Vec<PathBuf>, // `-I`
+    pub defines: Vec<String>,       // `-D`, as `NAME` or `NAME=VALUE`
+    pub opt_level: u8,              // `-O`, 0 for none
+}
+
+// One run of the compiler over any number of translation units.  Each stage
+// is a method taking the result of the one before, so a caller can stop
+// wherever it likes:
+//
+//     preprocess -> lex -> parse -> check -> lower -> optimize -> codegen -> assemble
+//
+// The first error of a stage is its `Err`, the warnings pile up in the
+// session.  The session also keeps the text of every source it was given, so
+// the diagnostics can be rendered with the line they point at.
+pub struct Session {
+    options: Options,
+    sources: SourceMap,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Session {
+    pub fn new(options: Options) -> Session {
+        Session {
+            options,
+            sources: SourceMap::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    // the translation unit for the C source `text`; `name` is the file it
+    // is reported as and the one `#include "..."` looks next to
+    pub fn preprocess(&mut self, name: &str, text: &str) -> Result<String, Diagnostic> {
+        self.sources.add(name, text);
+        preprocessor::preprocess(
+            Path::new(name),
+            text,
+            &self.options.include_dirs,
+            &self.options.defines,
+            &mut self.warnings,
+        )
+    }
+
+    // the tokens of a translation unit, ending with a `TokType::Eof`
+    pub fn lex(&mut self, unit: &str) -> Result<Vec<Token>, Diagnostic> {
+        lexer::lex(unit)
+    }
+
+    // the tree for the tokens of the translation unit `name`
+    pub fn parse(&mut self, name: &str, tokens: &[Token]) -> Result<ParseNode, Diagnostic> {
+        parser::parse_prog(tokens, name)
+    }
+
+    // resolve the names of a parsed translation unit and give every
+    // expression in it its type, which `lower` needs
+    pub fn check(&mut self, tree: &mut ParseNode) -> Result<(), Diagnostic> {
+        sema::check(tree, &mut self.warnings)
+    }
+
+    // the IR for a checked translation unit
+    pub fn lower(&mut self, tree: &ParseNode) -> Result<ir::Module, Diagnostic> {
+        ir::lower::lower(tree)
+    }
+
+    // run the passes of the `-O` level over the IR, showing `after` the IR
+    // with the name of each pass that ran
+    pub fn optimize(&mut self, module: &mut ir::Module, after: &mut dyn FnMut(&str, &ir::Module)) {
+        opt::optimize(module, self.options.opt_level, after)
+    }
+
+    // the x86-64 assembly for the IR of a translation unit, which can not fail
+    pub fn codegen(&mut self, module: &ir::Module) -> String {
+        gen::gen_prog(module)
+    }
+
+    // the ELF relocatable object for the assembly `text`, named `name` in
+    // the diagnostics
+    pub fn assemble(&mut self, name: &str, text: &str) -> Result<Vec<u8>, Diagnostic> {
+        self.sources.add(name, text);
+        asm::assemble(text, name)
+    }
+
+    // all of the above up to the assembly, for the C source `text`
+    pub fn compile(&mut self, name: &str, text: &str) -> Result<String, Diagnostic> {
+        let unit = self.preprocess(name, text)?;
+        let tokens = self.lex(&unit)?;
+        let mut tree = self.parse(name, &tokens)?;
+        self.check(&mut tree)?;
+        let mut module = self.lower(&tree)?;
+        self.optimize(&mut module, &mut |_, _| {});
+        Ok(self.codegen(&module))
+    }
+
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    // the warnings so far, the session forgets them
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // `diag` with the source line it points at, read from disk for the
+    // files the session was not given, like headers
+    pub fn render(&mut self, diag: &Diagnostic) -> String {
+        diag.render(&mut self.sources)
+    }
+}
@@ -0,0 +1,472 @@
+use crate::lexer::{FloatSuffix, IntSuffix};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// The types of C values.
+//
+// Sizes and alignments follow the LP64 model of the x86-64 System V ABI, so
+// crust's output can be linked with code compiled by gcc: `char` is 1 byte,
+// `short` 2, `int` 4, `long`, `long long` and pointers 8.  `float` and
+// `double` are the IEEE single and double formats, `long double` the 80 bits
+// of the x87 in 16 bytes.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum Type {
+    Void,
+    Bool,  // _Bool
+    Char,  // plain char, signed like gcc does on x86-64
+    SChar, // signed char
+    UChar, // unsigned char
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    Float,
+    Double,
+    LongDouble,
+    Ptr(Box<Type>),        // pointer to the inner type
+    Array(Box<Type>, i64), // element type, length (UNKNOWN_LEN for `[]`)
+    Struct(Rc<Aggregate>), // a struct or a union
+    // a function returning the inner type, with the types of its parameters
+    // unless it is declared `()`, and whether `...` follows them
+    Func(Box<Type>, Option<Vec<Type>>, bool),
+}
+
+// the length of an array declared `[]`, until an initializer tells it
+pub const UNKNOWN_LEN: i64 = -1;
+
+impl Type {
+    pub fn ptr_to(t: Type) -> Type {
+        Type::Ptr(Box::new(t))
+    }
+
+    pub fn size(&self) -> i64 {
+        match self {
+            // gcc steps `void *` and pointers to functions byte by byte
+            Type::Void | Type::Func(..) | Type::Bool | Type::Char | Type::SChar | Type::UChar => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt | Type::Float => 4,
+            Type::Long | Type::ULong | Type::LongLong | Type::ULongLong | Type::Ptr(_) => 8,
+            Type::Double => 8,
+            Type::LongDouble => 16,
+            Type::Array(t, len) => t.size() * len.max(&0),
+            Type::Struct(s) => s.size(),
+        }
+    }
+
+    pub fn align(&self) -> i64 {
+        match self {
+            Type::Array(t, _) => t.align(),
+            Type::Struct(s) => s.align(),
+            t => t.size(),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !self.is_floating()
+            && !matches!(
+                self,
+                Type::Void | Type::Ptr(_) | Type::Array(_, _) | Type::Struct(_) | Type::Func(..)
+            )
+    }
+
+    pub fn is_floating(&self) -> bool {
+        matches!(self, Type::Float | Type::Double | Type::LongDouble)
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || self.is_floating()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Type::Char | Type::SChar | Type::Short | Type::Int | Type::Long | Type::LongLong
+        )
+    }
+
+    // integer conversion rank (C11 6.3.1.1), 0 for everything else
+    fn rank(&self) -> u8 {
+        match self {
+            Type::Bool => 1,
+            Type::Char | Type::SChar | Type::UChar => 2,
+            Type::Short | Type::UShort => 3,
+            Type::Int | Type::UInt => 4,
+            Type::Long | Type::ULong => 5,
+            Type::LongLong | Type::ULongLong => 6,
+            _ => 0,
+        }
+    }
+
+    // the unsigned type with the same rank
+    fn to_unsigned(&self) -> Type {
+        match self {
+            Type::Char | Type::SChar => Type::UChar,
+            Type::Short => Type::UShort,
+            Type::Int => Type::UInt,
+            Type::Long => Type::ULong,
+            Type::LongLong => Type::ULongLong,
+            t => t.clone(),
+        }
+    }
+
+    // the integer promotions: everything smaller than int is computed as int
+    pub fn promote(&self) -> Type {
+        if self.is_integer() && self.rank() < Type::Int.rank() {
+            Type::Int
+        } else {
+            self.clone()
+        }
+    }
+
+    // the usual arithmetic conversions (C11 6.3.1.8): the common type both
+    // arithmetic operands of a binary operator are converted to, the wider
+    // floating one if either is floating
+    pub fn common(a: &Type, b: &Type) -> Type {
+        for t in [Type::LongDouble, Type::Double, Type::Float] {
+            if *a == t || *b == t {
+                return t;
+            }
+        }
+        let (a, b) = (a.promote(), b.promote());
+        if a == b {
+            return a;
+        }
+        if a.is_signed() == b.is_signed() {
+            return if a.rank() >= b.rank() { a } else { b };
+        }
+        let (s, u) = if a.is_signed() { (a, b) } else { (b, a) };
+        if u.rank() >= s.rank() {
+            u
+        } else if s.size() > u.size() {
+            s
+        } else {
+            s.to_unsigned()
+        }
+    }
+
+    // arrays are used as a pointer to their first element, functions as a
+    // pointer to them
+    pub fn decay(&self) -> Type {
+        match self {
+            Type::Array(t, _) => Type::ptr_to((**t).clone()),
+            Type::Func(..) => Type::ptr_to(self.clone()),
+            t => t.clone(),
+        }
+    }
+
+    // whether a scalar that is not floating is in the bytes from `start` up
+    // to `end` of a value of this type, which the System V convention passes
+    // in a general register then, padding taking no part
+    pub fn has_integer_in(&self, start: i64, end: i64) -> bool {
+        match self {
+            _ if end <= 0 || start >= self.size() => false,
+            Type::Array(t, len) => {
+                (0..*len).any(|i| t.has_integer_in(start - i * t.size(), end - i * t.size()))
+            }
+            Type::Struct(s) => s
+                .members()
+                .iter()
+                .any(|m| m.ty.has_integer_in(start - m.offset, end - m.offset)),
+            t => !t.is_floating(),
+        }
+    }
+
+    // whether a long double is in a value of this type
+    pub fn has_long_double(&self) -> bool {
+        match self {
+            Type::Array(t, _) => t.has_long_double(),
+            Type::Struct(s) => s.members().iter().any(|m| m.ty.has_long_double()),
+            t => *t == Type::LongDouble,
+        }
+    }
+
+    // whether it is what a `va_list` decays to, a pointer to the struct
+    pub fn is_va_list(&self) -> bool {
+        matches!(self, Type::Ptr(t) if matches!(&**t, Type::Struct(s) if s.name == VA_LIST_TAG))
+    }
+
+    // `int a[];` declares an array of a length still unknown
+    pub fn is_unknown_array(&self) -> bool {
+        matches!(self, Type::Array(_, UNKNOWN_LEN))
+    }
+
+    // the elements of an array of arrays, or the type itself if no array
+    pub fn innermost(&self) -> &Type {
+        match self {
+            Type::Array(t, _) => t.innermost(),
+            t => t,
+        }
+    }
+
+    // the type a pointer (or an array) points to
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Ptr(t) | Type::Array(t, _) => Some(t),
+            _ => None,
+        }
+    }
+
+    // `v` converted to this integer type, as gcc does: wrapped around modulo 2^n
+    pub fn wrap(&self, v: i64) -> i64 {
+        match (self, self.size()) {
+            (Type::Bool, _) => (v != 0) as i64,
+            (t, 1) if t.is_signed() => v as i8 as i64,
+            (_, 1) => v as u8 as i64,
+            (t, 2) if t.is_signed() => v as i16 as i64,
+            (_, 2) => v as u16 as i64,
+            (t, 4) if t.is_signed() => v as i32 as i64,
+            (_, 4) => v as u32 as i64,
+            _ => v,
+        }
+    }
+
+    // the type of a floating constant, by its suffix
+    pub fn of_float_const(suffix: FloatSuffix) -> Type {
+        match suffix {
+            FloatSuffix::None => Type::Double,
+            FloatSuffix::F => Type::Float,
+            FloatSuffix::L => Type::LongDouble,
+        }
+    }
+
+    // the type of an integer constant: the first of the candidates its value
+    // fits in, which ones depends on the suffix and the base (C11 6.4.4.1)
+    pub fn of_int_const(value: i64, suffix: &IntSuffix) -> Type {
+        use Type::*;
+        let candidates: &[Type] = match (suffix.unsigned, suffix.long, suffix.decimal) {
+            (false, 0, true) => &[Int, Long, LongLong],
+            (false, 0, false) => &[Int, UInt, Long, ULong, LongLong, ULongLong],
+            (true, 0, _) => &[UInt, ULong, ULongLong],
+            (false, 1, true) => &[Long, LongLong],
+            (false, 1, false) => &[Long, ULong, LongLong, ULongLong],
+            (true, 1, _) => &[ULong, ULongLong],
+            (false, _, true) => &[LongLong],
+            (false, _, false) => &[LongLong, ULongLong],
+            (true, _, _) => &[ULongLong],
+        };
+        let value = value as u64;
+        candidates
+            .iter()
+            .find(|t| {
+                let bits = 8 * t.size() as u32 - t.is_signed() as u32;
+                bits == 64 || value < 1 << bits
+            })
+            .cloned()
+            // like gcc, a decimal constant too large for long long is unsigned
+            .unwrap_or(ULongLong)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::Bool => write!(f, "_Bool"),
+            Type::Char => write!(f, "char"),
+            Type::SChar => write!(f, "signed char"),
+            Type::UChar => write!(f, "unsigned char"),
+            Type::Short => write!(f, "short"),
+            Type::UShort => write!(f, "unsigned short"),
+            Type::Int => write!(f, "int"),
+            Type::UInt => write!(f, "unsigned int"),
+            Type::Long => write!(f, "long"),
+            Type::ULong => write!(f, "unsigned long"),
+            Type::LongLong => write!(f, "long long"),
+            Type::ULongLong => write!(f, "unsigned long long"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::LongDouble => write!(f, "long double"),
+            Type::Ptr(t) => match &**t {
+                Type::Func(ret, params, variadic) => {
+                    write!(f, "{} (*)({})", ret, param_list(params, *variadic))
+                }
+                Type::Array(_, _) => {
+                    write!(f, "{} (*)", t.innermost())?;
+                    fmt_lengths(t, f)
+                }
+                t => write!(f, "{}*", t),
+            },
+            Type::Func(ret, params, variadic) => {
+                write!(f, "{} ({})", ret, param_list(params, *variadic))
+            }
+            Type::Array(_, _) => {
+                write!(f, "{}", self.innermost())?;
+                fmt_lengths(self, f)
+            }
+            Type::Struct(s) => write!(f, "{}", s.name),
+        }
+    }
+}
+
+// the lengths of the array `t` in the order C writes them, `int[2][3]` is
+// two arrays of three ints
+fn fmt_lengths(t: &Type, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut t = t;
+    while let Type::Array(elem, len) = t {
+        match *len {
+            UNKNOWN_LEN => write!(f, "[]")?,
+            len => write!(f, "[{}]", len)?,
+        }
+        t = elem;
+    }
+    Ok(())
+}
+
+// the parameters of a function type the way a prototype lists them
+fn param_list(params: &Option<Vec<Type>>, variadic: bool) -> String {
+    let mut list: Vec<String> = match params {
+        None => return String::new(),
+        Some(params) if params.is_empty() && !variadic => return "void".to_string(),
+        Some(params) => params.iter().map(|t| t.to_string()).collect(),
+    };
+    if variadic {
+        list.push("...".to_string());
+    }
+    list.join(", ")
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: Option<String>, // None for an anonymous struct or union
+    pub ty: Type,
+    pub offset: i64,
+}
+
+struct Layout {
+    members: Vec<Member>,
+    size: i64,
+    align: i64,
+}
+
+// A struct or a union.  Types only refer to it, so a struct can hold a
+// pointer to itself and a tag can be used before the `{ ... }` that lists
+// the members; until then the aggregate is incomplete.
+pub struct Aggregate {
+    pub name: String, // like `struct node`, for diagnostics
+    pub is_union: bool,
+    layout: RefCell<Option<Layout>>,
+}
+
+impl Aggregate {
+    pub fn new(name: String, is_union: bool) -> Rc<Aggregate> {
+        Rc::new(Aggregate {
+            name,
+            is_union,
+            layout: RefCell::new(None),
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.layout.borrow().is_some()
+    }
+
+    // lay the members out the way the System V ABI does: every member at the
+    // next offset aligned for it (all of them at 0 in a union), and the size
+    // rounded up to the strictest alignment, so arrays of it stay aligned
+    pub fn complete(&self, members: Vec<(Option<String>, Type)>) {
+        let (mut size, mut align) = (0, 1);
+        let mut laid_out = Vec::new();
+        for (name, ty) in members {
+            let offset = if self.is_union {
+                0
+            } else {
+                align_to(size, ty.align())
+            };
+            size = size.max(offset + ty.size());
+            align = align.max(ty.align());
+            laid_out.push(Member { name, ty, offset });
+        }
+        *self.layout.borrow_mut() = Some(Layout {
+            members: laid_out,
+            size: align_to(size, align),
+            align,
+        });
+    }
+
+    pub fn size(&self) -> i64 {
+        self.layout.borrow().as_ref().map_or(0, |l| l.size)
+    }
+
+    pub fn align(&self) -> i64 {
+        self.layout.borrow().as_ref().map_or(1, |l| l.align)
+    }
+
+    // the members in the order they are declared, empty while incomplete
+    pub fn members(&self) -> Vec<Member> {
+        let layout = self.layout.borrow();
+        layout.as_ref().map_or(Vec::new(), |l| l.members.clone())
+    }
+
+    // the member `name`, looking into anonymous members too; its offset is
+    // from the start of this aggregate
+    pub fn member(&self, name: &str) -> Option<Member> {
+        let layout = self.layout.borrow();
+        for m in layout.as_ref()?.members.iter() {
+            match (&m.name, &m.ty) {
+                (Some(n), _) if n == name => return Some(m.clone()),
+                (None, Type::Struct(inner)) => {
+                    if let Some(mut found) = inner.member(name) {
+                        found.offset += m.offset;
+                        return Some(found);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+// every aggregate is a distinct type, even when the members are the same
+impl PartialEq for Aggregate {
+    fn eq(&self, other: &Aggregate) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Aggregate {}
+
+// the members are left out, a struct pointing to itself would never end
+impl fmt::Debug for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// A `va_list` is an array of one of this struct, laid out the way the System
+// V ABI has it: the offsets in the register save area of the next argument
+// in a general and in an xmm register, where the next one on the stack is,
+// and where the register save area is.
+pub const VA_LIST_TAG: &str = "struct __va_list_tag";
+pub const VA_GP_OFFSET: i64 = 0;
+pub const VA_FP_OFFSET: i64 = 4;
+pub const VA_OVERFLOW_ARG_AREA: i64 = 8;
+pub const VA_REG_SAVE_AREA: i64 = 16;
+// the register save area has the 6 general argument registers, then the 8
+// xmm ones 16 bytes apart
+pub const REG_SAVE_GP_SIZE: i64 = 48;
+pub const REG_SAVE_SIZE: i64 = 176;
+
+impl Aggregate {
+    pub fn va_list_tag() -> Rc<Aggregate> {
+        let tag = Aggregate::new(VA_LIST_TAG.to_string(), false);
+        let void_ptr = Type::ptr_to(Type::Void);
+        tag.complete(vec![
+            (Some("gp_offset".to_string()), Type::UInt),
+            (Some("fp_offset".to_string()), Type::UInt),
+            (Some("overflow_arg_area".to_string()), void_ptr.clone()),
+            (Some("reg_save_area".to_string()), void_ptr),
+        ]);
+        tag
+    }
+}
+
+// `n` rounded up to a multiple of `align`
+pub fn align_to(n: i64, align: i64) -> i64 {
+    (n + align - 1) / align * align
+}
@@ -10,7 +10,7 @@ BLUE='\033[0;34m'
 NC='\033[0m'
 inc=0
 test_fun() {
-    $1.crust
+    $1.${2:-crust}
     a=$?
     $1.gcc
     b=$?
@@ -25,23 +25,21 @@ test_fun() {
 }
 
 crust_compile() {
-    echo "crust compile $1.c -> $2.s" && ./target/debug/crust -o $2.s $1.c
+    echo "crust compile $1.c -> $2.crust" && ./target/debug/crust -o $2.crust $1.c
 }
 
 gcc_compile() {
-    echo "gcc compile $2.s -> $2.crust"
-    gcc -o $2.crust $2.s
     echo "gcc compile $1.c -> $2.gcc"
     gcc -std=c99 -o $2.gcc $1.c
 }
 srcdir=test/valid
-echo -e "[${BLUE}crust compile all the test file now, generated file should be in CRUST_HOME/gen/test/valid/${NC}]"
+echo -e "[${BLUE}crust compile all the test file now, the executables should be in CRUST_HOME/gen/test/valid/${NC}]"
 for f in $srcdir/*.c
 do
     file=${f%.*}
-    crust_compile $file ./gen/$file
+    crust_compile $file ./gen/$file || { echo -e "[${RED}Error${NC}] crust rejected $f"; exit 1; }
 done
-echo -e "[${BLUE}Now gcc compile the assembly code and prepare for test${NC}]"
+echo -e "[${BLUE}Now gcc compile the test files and prepare for test${NC}]"
 for f in $srcdir/*.c
 do
     file=${f%.*}
@@ -54,4 +52,69 @@ do
     test_fun $exec
 done
 
+echo -e "[${BLUE}the same again optimized, which must not change what they return${NC}]"
+for f in $srcdir/*.c
+do
+    file=${f%.*}
+    echo "crust -O2 compile $f -> ./gen/$file.opt" && ./target/debug/crust -O2 -o ./gen/$file.opt $f || { echo -e "[${RED}Error${NC}] crust -O2 rejected $f"; exit 1; }
+    test_fun ./gen/$file opt
+done
+
+echo -e "[${BLUE}the files in each directory of test/multi are one program${NC}]"
+mkdir -p gen/test/multi
+for d in test/multi/*/
+do
+    name=gen/test/multi/$(basename $d)
+    echo "crust compile $d*.c -> $name.crust" && ./target/debug/crust -o $name.crust $d*.c || { echo -e "[${RED}Error${NC}] crust rejected $d"; exit 1; }
+    echo "gcc compile $d*.c -> $name.gcc" && gcc -std=c99 -w -o $name.gcc $d*.c
+    test_fun $name
+done
+
+echo -e "[${BLUE}the same built like a Makefile would with CC=crust${NC}]"
+for d in test/multi/*/
+do
+    name=gen/test/multi/$(basename $d)
+    for f in $d*.c
+    do
+        obj=$name-$(basename ${f%.*}).o
+        echo "crust -c $f -> $obj" && ./target/debug/crust -g -std=c99 -fPIC -MMD -MP -MF $obj.d -c -o $obj $f || { echo -e "[${RED}Error${NC}] crust -c rejected $f"; exit 1; }
+    done
+    echo "crust link $name-*.o -> $name.cc" && ./target/debug/crust -g -pthread -o $name.cc $name-*.o || { echo -e "[${RED}Error${NC}] crust could not link $name"; exit 1; }
+    test_fun $name cc
+done
+
+echo -e "[${BLUE}the same with main.c from crust and the rest from gcc -fPIC in an archive, which crust links itself${NC}]"
+for d in test/multi/*/
+do
+    name=gen/test/multi/$(basename $d)
+    rm -f $name.a
+    for f in $d*.c
+    do
+        [ "$(basename $f)" = main.c ] && continue
+        obj=$name-$(basename ${f%.*}).pic.o
+        gcc -std=c99 -w -fPIC -c -o $obj $f && ar rcs $name.a $obj
+    done
+    echo "crust link ${d}main.c $name.a -> $name.ar" && ./target/debug/crust -o $name.ar ${d}main.c $name.a || { echo -e "[${RED}Error${NC}] crust could not link $name.a"; exit 1; }
+    if readelf -l $name.ar | grep -q interpreter; then
+        echo -e "[${RED}Error${NC}] $name.a was linked by cc, not by crust"
+        exit 1
+    fi
+    test_fun $name ar
+done
+
+echo -e "[${BLUE}crust must reject the invalid programs with a diagnostic${NC}]"
+for f in test/invalid/*.c
+do
+    inc=$(($inc+1))
+    ./target/debug/crust -S -o gen/invalid.s $f
+    a=$?
+    echo "TEST $inc: [$f] -> crustRet: $a"
+    if [ "$a" -eq 1 ]; then
+        echo -e "[${BLUE}Passed${NC}]"
+    else
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    fi
+done
+
 echo -e "Passed ${BLUE}All${NC} tests :)"
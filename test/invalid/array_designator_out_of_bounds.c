@@ -0,0 +1,4 @@
+int main() {
+    int a[2] = { [5] = 1 };
+    return a[0];
+}
@@ -0,0 +1,5 @@
+int main() {
+    int n = 4;
+    int a[n + 1];
+    return 0;
+}
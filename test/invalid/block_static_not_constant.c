@@ -0,0 +1,9 @@
+int f(void) {
+    int x = 1;
+    static int *p = &x;
+    return *p;
+}
+
+int main(void) {
+    return f();
+}
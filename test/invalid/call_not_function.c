@@ -0,0 +1,4 @@
+int main() {
+    int f = 1;
+    return f(2);
+}
@@ -0,0 +1,5 @@
+int twice(int n) { return n; }
+int main() {
+    int (*f)(int) = twice;
+    return f(1, 2);
+}
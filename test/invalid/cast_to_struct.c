@@ -0,0 +1,9 @@
+struct p {
+    int x;
+};
+
+int main() {
+    int a = 1;
+    struct p v = (struct p)a;
+    return v.x;
+}
@@ -0,0 +1,9 @@
+int twice(int x);
+
+long twice(int x) {
+    return 2 * x;
+}
+
+int main() {
+    return twice(1);
+}
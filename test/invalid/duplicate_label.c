@@ -0,0 +1,10 @@
+int main() {
+    int x = 1;
+again:
+    x = x + 1;
+    {
+    again:
+        x = x + 2;
+    }
+    return x;
+}
@@ -0,0 +1,5 @@
+char c = "str";
+
+int main() {
+    return c;
+}
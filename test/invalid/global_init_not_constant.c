@@ -0,0 +1,7 @@
+int x;
+int *p = &x;
+int *q = p;
+
+int main() {
+    return *q;
+}
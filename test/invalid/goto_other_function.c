@@ -0,0 +1,9 @@
+int f() {
+out:
+    return 1;
+}
+
+int main() {
+    goto out;
+    return 0;
+}
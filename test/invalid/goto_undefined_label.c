@@ -0,0 +1,8 @@
+int main() {
+    int x = 1;
+    if (x)
+        goto done;
+    x = 2;
+finish:
+    return x;
+}
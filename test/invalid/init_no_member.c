@@ -0,0 +1,9 @@
+struct point {
+    int x;
+    int y;
+};
+
+int main() {
+    struct point p = { .z = 1 };
+    return p.x;
+}
@@ -0,0 +1,5 @@
+int main() {
+    double d = 1;
+    int *p = d;
+    return 0;
+}
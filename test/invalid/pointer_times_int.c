@@ -0,0 +1,5 @@
+int main() {
+    int a = 2;
+    int *p = &a;
+    return *(p * 2);
+}
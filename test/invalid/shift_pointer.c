@@ -0,0 +1,6 @@
+int main(void) {
+    int a = 1;
+    int *p = &a;
+    p <<= 1;
+    return a;
+}
@@ -0,0 +1,5 @@
+struct s;
+
+int main() {
+    return sizeof(struct s);
+}
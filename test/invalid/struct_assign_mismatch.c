@@ -0,0 +1,15 @@
+struct a {
+    int x;
+};
+
+struct b {
+    int x;
+};
+
+int main() {
+    struct a p;
+    struct b q;
+    p.x = 1;
+    q = p;
+    return q.x;
+}
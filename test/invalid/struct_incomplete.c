@@ -0,0 +1,6 @@
+struct list;
+
+int main() {
+    struct list l;
+    return 0;
+}
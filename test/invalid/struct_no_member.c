@@ -0,0 +1,10 @@
+struct point {
+    int x;
+    int y;
+};
+
+int main() {
+    struct point p;
+    p.x = 1;
+    return p.z;
+}
@@ -0,0 +1,11 @@
+struct s {
+    int a;
+};
+
+struct s {
+    long b;
+};
+
+int main() {
+    return 0;
+}
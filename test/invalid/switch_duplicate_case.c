@@ -0,0 +1,10 @@
+int main() {
+    int x = 3;
+    switch (x) {
+    case 1:
+        return 1;
+    case 4 - 3:
+        return 2;
+    }
+    return 0;
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    int a = 2;
+    return a +
@@ -0,0 +1,13 @@
+#include <stdarg.h>
+
+int first(int n) {
+    va_list ap;
+    va_start(ap, n);
+    int x = va_arg(ap, int);
+    va_end(ap);
+    return x;
+}
+
+int main() {
+    return first(1);
+}
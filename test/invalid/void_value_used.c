@@ -0,0 +1,7 @@
+void nothing() {
+}
+
+int main() {
+    int a = nothing();
+    return a;
+}
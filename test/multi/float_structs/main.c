@@ -0,0 +1,37 @@
+struct vec { double x, y; };
+struct pair { float x, y; };
+struct three { float a, b, c; };
+struct tagged { double d; long n; };
+struct counted { int n; double d; };
+struct lead { long n; float f; };
+double dot(struct vec a, struct vec b);
+struct vec stretch(struct vec v, double k);
+struct pair swap(struct pair p);
+float sum3(struct three t);
+struct tagged bump(struct tagged t, struct counted c);
+struct counted count(int n, double d);
+struct three three_of(float a);
+struct lead lead_of(struct lead l, int k);
+double last(struct vec a, struct vec b, struct vec c, struct vec d, struct vec e, double f);
+int main() {
+    struct vec u = { 1.5, 2 };
+    struct vec v = { 4, -0.5 };
+    if (dot(u, v) != 5) return 1;
+    struct vec w = stretch(u, 3);
+    if (w.x != 4.5 || w.y != 6) return 2;
+    struct pair p = { 1.25f, 2.5f };
+    p = swap(p);
+    if (p.x != 2.5f || p.y != 1.25f) return 3;
+    struct three t = { 1, 2, 3 };
+    if (sum3(t) != 321) return 4;
+    struct tagged g = { 0.5, 7 };
+    g = bump(g, count(3, 0.25));
+    if (g.d != 0.75 || g.n != 10) return 5;
+    if (last(u, v, u, v, w, 1) != 1647) return 6;
+    t = three_of(2);
+    if (t.a != 2 || t.b != 4 || t.c != 6) return 7;
+    struct lead l = { 5, 1.5f };
+    l = lead_of(l, 4);
+    if (l.n != 20 || l.f != 6) return 8;
+    return 42;
+}
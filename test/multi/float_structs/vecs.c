@@ -0,0 +1,17 @@
+struct vec { double x, y; };
+struct pair { float x, y; };
+struct three { float a, b, c; };
+struct tagged { double d; long n; };
+struct counted { int n; double d; };
+struct lead { long n; float f; };
+double dot(struct vec a, struct vec b) { return a.x * b.x + a.y * b.y; }
+struct vec stretch(struct vec v, double k) { v.x = v.x * k; v.y = v.y * k; return v; }
+struct pair swap(struct pair p) { struct pair r; r.x = p.y; r.y = p.x; return r; }
+float sum3(struct three t) { return t.a + t.b * 10 + t.c * 100; }
+struct tagged bump(struct tagged t, struct counted c) { t.d = t.d + c.d; t.n = t.n + c.n; return t; }
+struct counted count(int n, double d) { struct counted c; c.n = n; c.d = d; return c; }
+struct three three_of(float a) { struct three t; t.a = a; t.b = a * 2; t.c = a * 3; return t; }
+struct lead lead_of(struct lead l, int k) { l.n = l.n * k; l.f = l.f * k; return l; }
+double last(struct vec a, struct vec b, struct vec c, struct vec d, struct vec e, double f) {
+    return a.x + b.y + c.x + d.y + e.x * 10 + e.y * 100 + f * 1000;
+}
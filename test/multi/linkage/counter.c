@@ -0,0 +1,14 @@
+extern int shared;
+static int counter;
+int total = 4;
+
+static int bump(int n) {
+    counter = counter + n;
+    return counter;
+}
+
+int from_counter() {
+    bump(2);
+    bump(3);
+    return counter + shared;
+}
@@ -0,0 +1,14 @@
+// `counter` and `bump` are static in both files, each file sees its own
+extern int total;
+int shared = 10;
+static int counter = 100;
+
+static int bump(int n) {
+    return n * 2;
+}
+
+int main() {
+    int r;
+    r = from_counter();
+    return r + total + bump(counter) - counter;
+}
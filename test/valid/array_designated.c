@@ -0,0 +1,22 @@
+union u { int i; char c[4]; };
+struct pair { int a[2]; int b; };
+struct pair ps[2] = { [1].a[1] = 5, 6, 7 };
+union u gu = { 258 };
+long double ld[2] = { 1.5L };
+struct has { char s[4]; int n; };
+struct has hs[] = { "ab", 3, "cd", 4 };
+
+int main(void) {
+    struct pair lp[2] = { [1].a[1] = 5, 6, 7 };
+    union u lu = { .c = { 1, 1 } };
+    struct has h[] = { "ab", 3, "cd", 4 };
+    short sh[3] = { 1 };
+    if (ps[1].a[1] != 5 || ps[1].b != 6 || ps[1].a[0] != 0) return 1;
+    if (lp[1].a[1] != 5 || lp[1].b != 6) return 2;
+    if (gu.c[0] != 2 || gu.c[1] != 1) return 3;
+    if (lu.i != 257) return 4;
+    if (ld[0] != 1.5L || ld[1] != 0) return 5;
+    if (h[1].s[1] != 'd' || h[1].n != 4 || hs[1].n != 4 || hs[0].s[0] != 'a') return 6;
+    if (sh[0] != 1 || sh[2] != 0) return 7;
+    return 42;
+}
@@ -0,0 +1,49 @@
+struct point { int x; int y; };
+struct line { struct point a; struct point b; char name[8]; };
+
+int table[3][4] = { {1, 2, 3, 4}, {5, 6}, [2][3] = 9 };
+char greeting[] = "hello";
+struct line gl = { {1, 2}, 3, 4, "ln" };
+double ds[4] = { 1.5, [3] = 2.5 };
+
+int sum(int m[][4], int rows) {
+    int s = 0;
+    int i = 0;
+    while (i < rows) {
+        int j = 0;
+        while (j < 4) {
+            s = s + m[i][j];
+            j = j + 1;
+        }
+        i = i + 1;
+    }
+    return s;
+}
+
+int main(void) {
+    int a[2][3] = { 1, 2, 3, 4, 5 };
+    int b[] = { [4] = 1, 2 };
+    char s[10] = "abc";
+    char t[] = { "xy" };
+    struct point p = { .y = 7 };
+    struct line l = { .b.x = 3, 8, .name = "zz" };
+    int big[40] = { [39] = 1 };
+    int k = 0;
+    int total = 0;
+    while (k < 40) {
+        total = total + big[k];
+        k = k + 1;
+    }
+    if (a[1][2] != 0 || a[1][1] != 5 || a[0][2] != 3) return 1;
+    if (b[5] != 2 || b[0] != 0) return 2;
+    if (s[2] != 'c' || s[3] != 0 || s[9] != 0) return 3;
+    if (t[1] != 'y' || t[2] != 0) return 4;
+    if (p.x != 0 || p.y != 7) return 5;
+    if (l.b.x != 3 || l.b.y != 8 || l.name[1] != 'z' || l.a.x != 0) return 6;
+    if (table[1][1] != 6 || table[1][2] != 0 || table[2][3] != 9) return 7;
+    if (greeting[4] != 'o' || greeting[5] != 0) return 8;
+    if (gl.b.x != 3 || gl.b.y != 4 || gl.name[1] != 'n') return 9;
+    if (ds[3] != 2.5 || ds[1] != 0) return 10;
+    if (total != 1) return 11;
+    return sum(table, 3);
+}
@@ -0,0 +1,27 @@
+#define N 4
+#define ROWS (N / 2)
+
+struct buf {
+    char data[sizeof(int) * 2];
+    int len;
+};
+
+int table[N + 1] = {1, 2, 3, 4, 5};
+int grid[ROWS][N << 1];
+long sizes[sizeof(struct buf) > 8 ? 3 : 1];
+
+int sum(int a[N - 1], int n) {
+    int s = 0;
+    for (int i = 0; i < n; i++)
+        s += a[i];
+    return s;
+}
+
+int main() {
+    char b[sizeof(table) / sizeof(table[0]) + 'a' - 'a'];
+    int (*row)[N * 2] = grid;
+    struct buf buf;
+    row[1][7] = 6;
+    return sizeof(b) + sizeof(buf.data) + sizeof(grid) + sizeof(sizes) + sum(table, N + 1)
+        + grid[1][7];
+}
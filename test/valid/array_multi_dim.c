@@ -0,0 +1,20 @@
+extern int ext[];
+int get(int i) { return ext[i]; }
+int ext[3] = { 4, 5, 6 };
+
+void fill(int m[3][3]) {
+    int i = 0;
+    while (i < 3) {
+        m[i][i] = i + 1;
+        i = i + 1;
+    }
+}
+
+int main(void) {
+    int m[3][3] = { 0 };
+    int cube[2][2][2] = { { { 1, 2 }, { 3, 4 } }, { { 5, 6 }, { 7, 8 } } };
+    int *row = m[1];
+    fill(m);
+    cube[1][0][1] = 9;
+    return m[2][2] * 100 + row[1] * 10 + cube[1][0][1] + get(2) - cube[0][1][1];
+}
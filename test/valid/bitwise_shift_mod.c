@@ -0,0 +1,28 @@
+int a = 0x0f & 0x3c | 1 << 6 ^ 3;
+int b = -17 % 5;
+unsigned c = 0xffffffffu >> 28;
+int d = -64 >> 3;
+int main(void) {
+    int x = 0xf0;
+    int y = 0x3c;
+    unsigned u = 0x80000000u;
+    long l = -1;
+    int r = 0;
+    /* & binds tighter than ^, which binds tighter than | */
+    if ((x & y | 1 ^ 3) != ((x & y) | (1 ^ 3)))
+        return 1;
+    /* a shift binds looser than + and tighter than < */
+    if ((1 << 2 + 1) != 8 || 1 << 3 < 9 != 1)
+        return 2;
+    if (-17 % 5 != -2 || 17 % -5 != 2 || 29u % 8 != 5)
+        return 3;
+    /* >> of a signed value keeps the sign, of an unsigned one shifts in zeros */
+    if (x >> 4 != 15 || -x >> 4 != -15 || u >> 31 != 1 || (int)u >> 31 != -1)
+        return 4;
+    if (l >> 40 != -1 || (unsigned long)l >> 60 != 15)
+        return 5;
+    if (~x & 0xff != 0x0f && (~x & 0xff) != 0x0f)
+        return 6;
+    r = (x ^ y) + (x | y) + (x & y) + a + b + c + d;
+    return r % 256;
+}
@@ -0,0 +1,59 @@
+int next(void) {
+    static int n = 0;
+    return ++n;
+}
+
+int other(void) {
+    static int n = 10;
+    return n++;
+}
+
+int total = 5;
+int n = 40;
+
+int *counter(void) {
+    static int count;
+    count += 2;
+    return &count;
+}
+
+int sum(void) {
+    static int table[] = {1, 2, 3, 4};
+    static int *last = &table[3];
+    static char *name = "abc";
+    int s = 0;
+    for (int i = 0; i < 4; i++) {
+        s += table[i];
+        table[i]++;
+    }
+    return s + *last + name[1];
+}
+
+int use_later(void) {
+    extern int later;
+    extern int total;
+    int total_here = total;
+    {
+        int total = 100;
+        {
+            extern int total;
+            total_here += total;
+        }
+    }
+    return later + total_here + n;
+}
+
+int later = 7;
+
+int main(void) {
+    next();
+    next();
+    int a = next();
+    other();
+    int b = other();
+    counter();
+    int c = *counter();
+    int d = sum();
+    int e = sum();
+    return a + b + c + d + e + use_later();
+}
@@ -0,0 +1,12 @@
+_Bool flag;
+
+int main() {
+    _Bool b = 5;
+    flag = 256;
+    if (b != 1)
+        return 1;
+    if (flag != 1)
+        return 2;
+    _Bool z = 0;
+    return b + b + z + 3;
+}
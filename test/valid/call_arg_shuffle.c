@@ -0,0 +1,43 @@
+struct pair {
+    long x;
+    long y;
+};
+
+struct tri {
+    char c[3];
+};
+
+int sub(int a, int b) {
+    return a - b;
+}
+
+int swapped(int a, int b) {
+    return sub(b, a);
+}
+
+int rotate(int a, int b, int c) {
+    return sub(c, a) * 100 + sub(b, c) * 10 + sub(a, b);
+}
+
+long pair_diff(struct pair p, int k) {
+    return (p.x - p.y) * k;
+}
+
+long tri_sum(int k, struct tri t) {
+    return t.c[0] + t.c[1] + t.c[2] + k;
+}
+
+long pass_on(int k, struct pair p, struct tri t) {
+    return pair_diff(p, k) + tri_sum(k, t);
+}
+
+int main(void) {
+    struct pair p;
+    struct tri t;
+    p.x = 9;
+    p.y = 4;
+    t.c[0] = 1;
+    t.c[1] = 2;
+    t.c[2] = 3;
+    return swapped(3, 10) + rotate(1, 2, 3) + pass_on(2, p, t);
+}
@@ -0,0 +1,7 @@
+int main() {
+    printf("tab\there, \x41\102\103, \\ and \"quotes\"" " joined" "\n");
+    int a = '\x41' - '\101';
+    int b = '\n' + '\0' + '\'' - '\"';
+    int c = 'A' + '\?';
+    return a + b + c - 100;
+}
@@ -0,0 +1,18 @@
+char buf[8];
+
+int len(char *s) {
+    int n = 0;
+    while (*s) {
+        s = s + 1;
+        n = n + 1;
+    }
+    return n;
+}
+
+int main() {
+    char *p = buf;
+    *p = 'h';
+    p[1] = 'i';
+    p[2] = 0;
+    return len(buf) + len("hello") * 10;
+}
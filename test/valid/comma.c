@@ -0,0 +1,32 @@
+int g;
+
+int bump(void) {
+    return ++g;
+}
+
+int add(int a, int b) {
+    return a + b;
+}
+
+int main(void) {
+    int a;
+    int b;
+    int i;
+    int j;
+    int sum = 0;
+    int arr[4] = {1, 2, 3, 4};
+    /* the value of a comma expression is its right operand */
+    a = (1, 2, 3);
+    b = (bump(), bump(), g * 10);
+    sum += a + b;
+    for (i = 0, j = 10; i < j; i++, j--)
+        sum += j - i;
+    /* the commas of a call separate the arguments */
+    sum += add((a, 4), (bump(), 5));
+    sum += arr[(a, 1)];
+    if (a = 0, a + 1)
+        sum += 7;
+    while (i = i - 1, i > 0)
+        sum++;
+    return sum;
+}
@@ -0,0 +1,8 @@
+// a line comment
+int main() {
+    /* a block
+       comment */
+    int a = 1; // trailing comment
+    int b = /* inside an expression */ 2;
+    /**/ return a + b; /* 3 */
+}
@@ -0,0 +1,55 @@
+int g = 10;
+int arr[4] = {1, 2, 3, 4};
+int calls = 0;
+
+int idx(void) {
+    calls++;
+    return 2;
+}
+
+int main(void) {
+    int a = 7;
+    char c = 100;
+    unsigned char uc = 250;
+    double d = 1.5;
+    int *p = arr;
+    long sum = 0;
+    a += 3;
+    a -= 1;
+    a *= 4;
+    a /= 3;
+    a %= 7;
+    a <<= 4;
+    a >>= 2;
+    a |= 0x40;
+    a &= 0x5c;
+    a ^= 0x0f;
+    sum += a;
+    /* the value of the assignment is what is stored, converted */
+    c += 100;
+    sum += c;
+    uc += 10;
+    sum += uc;
+    d *= 3;
+    sum += d;
+    a = 5;
+    a += d;
+    sum += a;
+    /* the left operand is only evaluated once */
+    arr[idx()] += 10;
+    arr[idx()] <<= 1;
+    sum += arr[2] + calls;
+    p += 3;
+    sum += *p;
+    p -= 2;
+    sum += *p;
+    g -= 3;
+    g *= g;
+    sum += g;
+    /* right associative, like = */
+    a = 1;
+    c = 2;
+    a += c += 3;
+    sum += a * 100 + c;
+    return sum % 256;
+}
@@ -0,0 +1,40 @@
+/* what -O2 works out before the program runs has to be what it would have */
+int main() {
+    char c = 100;
+    c = c + c;
+    if (c != -56)
+        return 1;
+    unsigned char uc = 250;
+    if (uc + 10 != 260)
+        return 2;
+    unsigned u = 0;
+    if (u - 1 < 5)
+        return 3;
+    unsigned big = 4294967295;
+    if (big + 1 != 0)
+        return 4;
+    long l = 4294967296;
+    if (l * 2 != 8589934592)
+        return 5;
+    unsigned short us = 65535;
+    unsigned short us2 = us + 1;
+    if (us2 != 0)
+        return 6;
+    int x = 5;
+    int y = x;
+    int z = 3;
+    if (0)
+        x = 100;
+    switch (z) {
+    case 1:
+        return 7;
+    case 3:
+        y = y * 4;
+        break;
+    default:
+        return 8;
+    }
+    while (z > 0)
+        z = z - 1;
+    return x + y + z;
+}
@@ -0,0 +1,97 @@
+// deep expressions: the parser, sema and the lowering recurse once per level
+int main() {
+    int x = 1;
+    int deep = ((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((((x + 2))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))))));
+    int wide = x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x
+        * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x
+        * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x
+        * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x
+        * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x
+        * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x
+        * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x
+        * 2 + x * 3 + x * 4 + x * 5 + x * 6 + x * 0 + x * 1 + x * 2 + x * 3 + x * 4 + x * 5;
+    return (deep + wide) % 256;
+}
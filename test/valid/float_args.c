@@ -0,0 +1,104 @@
+int printf(const char *fmt, ...);
+
+struct vec { double x, y; };
+struct pair { float x, y; };
+struct three { float a, b, c; };
+struct tagged { double d; long n; };
+struct counted { int n; double d; };
+struct big { double a, b, c; };
+
+double mix(int a, float b, double c, long double d, long e, double f) {
+    return a + b + c + d + e + f;
+}
+
+float many(float a, float b, float c, float d, float e, float f, float g, float h,
+           float i, double j) {
+    return a + b + c + d + e + f + g + h + i * 10 + j * 100;
+}
+
+double scaled(double x, int scale) {
+    return x * scale;
+}
+
+double dot(struct vec a, struct vec b) {
+    return a.x * b.x + a.y * b.y;
+}
+
+struct vec stretch(struct vec v, double k) {
+    v.x = v.x * k;
+    v.y = v.y * k;
+    return v;
+}
+
+struct pair swap(struct pair p) {
+    struct pair r;
+    r.x = p.y;
+    r.y = p.x;
+    return r;
+}
+
+float sum3(struct three t) {
+    return t.a + t.b * 10 + t.c * 100;
+}
+
+struct tagged bump(struct tagged t, struct counted c) {
+    t.d = t.d + c.d;
+    t.n = t.n + c.n;
+    return t;
+}
+
+struct counted count(int n, double d) {
+    struct counted c;
+    c.n = n;
+    c.d = d;
+    return c;
+}
+
+// four of them take all eight xmm registers, the fifth goes on the stack
+double last(struct vec a, struct vec b, struct vec c, struct vec d, struct vec e, double f) {
+    return a.x + b.y + c.x + d.y + e.x * 10 + e.y * 100 + f * 1000;
+}
+
+double unnamed(struct { double x, y; } p) {
+    return p.x - p.y;
+}
+
+double total(struct big b) {
+    return b.a + b.b + b.c;
+}
+
+int main() {
+    if (mix(1, 2.5f, 3.25, 4, 5, 6.25) != 22)
+        return 1;
+    if (many(1, 1, 1, 1, 1, 1, 1, 1, 2, 3) != 328)
+        return 2;
+    double l = scaled(1.5, 4);
+    if (l != 6)
+        return 3;
+    scaled(2, 3);
+    struct vec u = { 1.5, 2 };
+    struct vec v = { 4, -0.5 };
+    if (dot(u, v) != 5)
+        return 4;
+    struct vec w = stretch(u, 3);
+    if (w.x != 4.5 || w.y != 6)
+        return 5;
+    struct pair p = { 1.25f, 2.5f };
+    p = swap(p);
+    if (p.x != 2.5f || p.y != 1.25f)
+        return 6;
+    struct three t = { 1, 2, 3 };
+    if (sum3(t) != 321)
+        return 7;
+    struct tagged g = { 0.5, 7 };
+    g = bump(g, count(3, 0.25));
+    if (g.d != 0.75 || g.n != 10)
+        return 8;
+    if (last(u, v, u, v, w, 1) != 1647)
+        return 9;
+    struct big b = { 1, 2, 4 };
+    if (total(b) != 7)
+        return 10;
+    int n = printf("%.2f %g %f %g\n", 1.5f, 0.125, l, dot(w, w));
+    return n + (scaled(0.5, 2) == 1);
+}
@@ -0,0 +1,41 @@
+float f = 1.5f;
+double d = -0.25;
+long double ld = 3.0L;
+double tiny = 4.9e-324;
+double stored[2];
+
+double half(double x) {
+    return x / 2;
+}
+
+int main() {
+    double x = 10;
+    x = x / 4 + d;
+    if (x != 2.25)
+        return 1;
+    float g = f * f;
+    if (g != 2.25f)
+        return 2;
+    if (-x >= 0)
+        return 3;
+    if (half(7) != 3.5)
+        return 4;
+    long double l = ld * ld - 1;
+    if (l != 8)
+        return 5;
+    if (!(tiny > 0) || tiny / 2 > 0)
+        return 6;
+    double zero = 0;
+    double nan = zero / zero;
+    if (nan == nan || !(nan != nan) || nan < 1 || nan >= 1)
+        return 7;
+    if (!nan)
+        return 8;
+    double big = 1e308;
+    if (big * 10 <= big)
+        return 9;
+    stored[1] = 1e300;
+    if (stored[1] / 1e299 != 10)
+        return 10;
+    return x * 10 + g;
+}
@@ -0,0 +1,48 @@
+unsigned long ul(double x) {
+    return x;
+}
+
+double from_ul(unsigned long u) {
+    return u;
+}
+
+int main() {
+    double d = 3.99;
+    int i = d;
+    if (i != 3)
+        return 1;
+    i = -d;
+    if (i != -3)
+        return 2;
+    unsigned u = 4000000000.0;
+    if (u != 4000000000u)
+        return 3;
+    if (ul(1e19) != 10000000000000000000ul)
+        return 4;
+    if (ul(2.5) != 2)
+        return 5;
+    if (from_ul(18446744073709551615ul) != 18446744073709551616.0)
+        return 6;
+    if (from_ul(3) != 3)
+        return 7;
+    float f = 16777217;
+    if (f != 16777216)
+        return 8;
+    char c = 65.7;
+    if (c != 65)
+        return 9;
+    _Bool b = 0.1;
+    if (b != 1)
+        return 10;
+    unsigned char uc = 200.0f;
+    long double l = uc;
+    double back = l;
+    if (back != 200)
+        return 11;
+    float small = 1.0 / 3;
+    double wide = small;
+    if (wide == 1.0 / 3)
+        return 12;
+    long neg = -2.5e10;
+    return neg / -1000000000 + (1 ? 0.5 : 1) * 4;
+}
@@ -0,0 +1,47 @@
+struct mixed {
+    int a;
+    double d;
+    float f;
+    long double l;
+    char c;
+};
+struct mixed g;
+double arr[3];
+
+double id(double x) {
+    return x;
+}
+
+int main() {
+    double a = 1;
+    double b = 2;
+    double c = 3;
+    double e = 4;
+    double f = 5;
+    double h = 6;
+    double i = 7;
+    double j = 8;
+    double k = 9;
+    double m = 10;
+    double n = 11;
+    double o = 12;
+    double q = 13;
+    double r = 14;
+    double t = 15;
+    double u = 16;
+    // all of them live across the call
+    double sum = id(a) + b;
+    sum = sum + c + e + f + h + i + j + k + m + n + o + q + r + t + u;
+    int count = 0;
+    for (double x = 0; x < 3; x = x + 0.5)
+        count = count + 1;
+    if (sum <= 135)
+        count = count + 100;
+    g.d = 2.5;
+    g.f = 1.25f;
+    g.l = g.d * 2;
+    g.c = g.l;
+    struct mixed *p = &g;
+    arr[1] = p->f;
+    return count + sum + g.c + arr[1] * 4 + (a < b) + (b <= a) + (a > -b);
+}
@@ -0,0 +1,41 @@
+int twice(int n) { return 2 * n; }
+int square(int n) { return n * n; }
+double half(double x, int n) { return x / 2 + n; }
+
+int (*fp)(int) = twice;
+
+struct op {
+    char name;
+    int (*apply)(int);
+};
+
+int apply(int (*f)(int), int n) {
+    return f(n) + (*f)(n);
+}
+
+// a function that gives back a function pointer, through a cast
+void *pick(int i) {
+    if (i)
+        return (void *)square;
+    return (void *)twice;
+}
+
+int grid[3][4] = {{1, 2, 3, 4}, {5, 6, 7, 8}, {9, 10, 11, 12}};
+
+int main() {
+    int (*local)(int) = square;
+    int (*table[2])(int);
+    table[0] = twice;
+    table[1] = square;
+    struct op op = {'s', square};
+    double (*h)(double, int) = half;
+    int (*row)[4] = grid;
+    int r = fp(3) + (*local)(4) + apply(twice, 5) + op.apply(3);
+    for (int i = 0; i < 2; i++)
+        r += table[i](i + 2);
+    r += (int)h(5.0, 1);
+    r += ((int (*)(int))pick(1))(2);
+    r += row[1][2] + sizeof(*row) + (*(row + 2))[3];
+    fp = local;
+    return r + fp(2) + (fp == square) + (fp != twice);
+}
@@ -0,0 +1,36 @@
+int puts(const char *s);
+struct point { int x; int y; };
+int counter = 5;
+int zeros[64] = { 0 };
+int arr[10] = { 0, 1, 2, 3, 4, 5, 6, 7, 8, 9 };
+int grid[3][3] = { { 1, 2, 3 }, { 4, 5, 6 }, { 7, 8, 9 } };
+int *pc = &counter;
+int *p3 = &arr[3];
+int *p5 = arr + 5;
+int *g12 = &grid[1][2];
+char *msg = "hello";
+char *msg2 = "world" + 1;
+char buf[] = "text";
+char *names[] = { "zero", "one", "two" };
+struct point pts[2] = { { 1, 2 }, { 3, 4 } };
+int *py = &pts[1].y;
+struct point *pp = pts + 1;
+int twice(int x) { return 2 * x; }
+void *fns[] = { twice, puts, 0 };
+void *fn = &twice;
+long zero_l = 0;
+double dbl = 1.5 * 2;
+struct holder { int *p; char *s; int n; } h = { &counter, "holder", 3 };
+
+int main(void) {
+    void *local = twice;
+    if (*pc != 5 || *p3 != 3 || *p5 != 5 || *g12 != 6) return 1;
+    if (msg[1] != 'e' || msg2[0] != 'o' || buf[3] != 't') return 2;
+    if (names[2][1] != 'w' || names[0][0] != 'z') return 3;
+    if (*py != 4 || pp->x != 3) return 4;
+    if (fns[0] != fn || fns[2] != 0 || fn != local) return 5;
+    if (zeros[63] != 0 || zero_l != 0 || dbl != 3) return 6;
+    if (*h.p != 5 || h.s[0] != 'h' || h.n != 3) return 7;
+    counter = 7;
+    return *pc + twice(3);
+}
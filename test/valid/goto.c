@@ -0,0 +1,75 @@
+int add3(int a, int b, int c) {
+    return a + b + c;
+}
+
+// the error cleanup idiom: every failure jumps to the undo of what was done
+int setup(int fail_at) {
+    int done = 0;
+    if (fail_at == 1)
+        goto fail;
+    done = done + 1;
+    {
+        int tmp = 5;
+        if (fail_at == 2)
+            goto undo_first;
+        done = done + tmp;
+    }
+    return done;
+
+undo_first:
+    done = done - 1;
+fail:
+    return 100 + done;
+}
+
+int count_down(int n) {
+    int steps = 0;
+again:
+    if (n > 0) {
+        int a = n;
+        int b = 1;
+        n = a - b;
+        steps = steps + 1;
+        goto again;
+    }
+    return steps;
+}
+
+int nested(int n) {
+    int total = 0;
+    for (int i = 0; i < n; i = i + 1) {
+        for (int j = 0; j < n; j = j + 1) {
+            long deep = i * j;
+            if (deep > 6)
+                goto out;
+            total = total + 1;
+        }
+    }
+out:
+    // the locals of the loops are gone, the stack is what it is here
+    return add3(total, 0, 0) + add3(1, 2, 3);
+}
+
+int into_block(int x) {
+    if (x)
+        goto inside;
+    return 0;
+    {
+        int y;
+    inside:
+        y = x * 2;
+        return add3(y, y, 1);
+    }
+}
+
+int main() {
+    if (setup(0) != 6 || setup(1) != 100 || setup(2) != 100)
+        return 1;
+    if (count_down(7) != 7)
+        return 2;
+    if (nested(5) != 20)
+        return 3;
+    if (into_block(3) != 13)
+        return 4;
+    return nested(4);
+}
@@ -0,0 +1,11 @@
+int var1 = 4;
+
+int add2(int x1, int y_2) {
+    return x1 + y_2;
+}
+
+int main() {
+    int a1 = 2;
+    int b2c3 = 3;
+    return add2(a1, b2c3) + var1;
+}
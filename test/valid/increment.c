@@ -0,0 +1,55 @@
+struct s { int n; char *p; };
+int g;
+
+int main(void) {
+    int a = 5;
+    int b;
+    int arr[3] = {10, 20, 30};
+    int *p = arr;
+    char str[] = "hello";
+    struct s st = {0, str};
+    double d = 0.5;
+    _Bool flag = 0;
+    int i;
+    int sum = 0;
+    b = a++;
+    sum += a * 10 + b;
+    b = ++a;
+    sum += a * 10 + b;
+    b = a--;
+    sum += a + b;
+    b = --a;
+    sum += a + b;
+    /* postfix binds tighter than a unary operator */
+    b = -a++;
+    sum += b + a;
+    sum += *p++;
+    sum += *++p;
+    sum += (*p)++;
+    sum += arr[2];
+    arr[0]++;
+    --arr[1];
+    sum += arr[0] + arr[1];
+    st.n++;
+    ++st.n;
+    sum += st.n;
+    sum += *st.p++;
+    sum += *st.p;
+    d++;
+    ++d;
+    sum += d * 2;
+    flag++;
+    flag++;
+    sum += flag;
+    flag--;
+    sum += flag;
+    g++;
+    g++;
+    sum += g;
+    for (i = 0; i < 10; i++)
+        sum++;
+    i = 0;
+    while (i++ < 5)
+        sum += i;
+    return sum % 256;
+}
@@ -0,0 +1,7 @@
+int main() {
+    int hex = 0x1F + 0XaBu;
+    int oct = 017 + 0;
+    int bin = 0b101;
+    int suffixed = 5u + 6l + 7UL + 8ll + 9LLU;
+    return hex + oct + bin + suffixed - 200;
+}
@@ -0,0 +1,39 @@
+char c;
+short s = 70000;
+unsigned char uc = 300;
+long big = 4294967296;
+
+int sign_extends() {
+    signed char a = -1;
+    unsigned long long b = a;
+    return b == 18446744073709551615ULL ? 42 : 9;
+}
+
+int main() {
+    char x = 200;
+    if (x >= 0)
+        return 1;
+    unsigned char y = 200;
+    if (y + y != 400)
+        return 2;
+    if (s != 4464)
+        return 3;
+    if (uc != 44)
+        return 4;
+    c = 127;
+    c = c + 1;
+    if (c != -128)
+        return 5;
+    if (big / 2 != 2147483648)
+        return 6;
+    int i = 2147483647;
+    long l = i;
+    l = l + 1;
+    if (l != 2147483648)
+        return 7;
+    short neg = -3;
+    unsigned short un = neg;
+    if (un != 65533)
+        return 8;
+    return sign_extends();
+}
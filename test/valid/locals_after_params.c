@@ -0,0 +1,7 @@
+int f(int a, int b) {
+    int t = 5;
+    return a * 100 + b * 10 + t;
+}
+int main() {
+    return f(1, 2);
+}
@@ -0,0 +1,61 @@
+int printf(const char *fmt, ...);
+
+long double third = 1.0L / 3;
+long double tenth = 0.1L;
+long double table[3] = { 1.5L, 0.1L, -2 };
+struct tagged { char c; long double x; } tagged = { 'a', 2.5L };
+int tiny_is_true = 1e-4000L != 0;
+
+long double sub(long double a, long double b) {
+    return a - b;
+}
+
+long double quotient(long double a, long double b) {
+    return a / b;
+}
+
+int main() {
+    long double x = 1;
+    long double y = 3;
+    long double q = x / y;
+    // computed in 64 bits of mantissa, which a double does not have
+    if (q != third || q * 3 != 1)
+        return 1;
+    if (tenth == 0.1 || (double)tenth != 0.1)
+        return 2;
+    if (sub(1, tenth) != 0.9L || quotient(1, 3) != third)
+        return 3;
+    if (table[1] != tenth || table[2] != -2 || tagged.x != 2.5L || tagged.c != 'a')
+        return 4;
+    if (!tiny_is_true || !1e-4000L || (double)1e-4000L != 0)
+        return 5;
+    // every integer, of 64 bits too, converts exactly
+    unsigned long big = 18446744073709551615ul;
+    long double b = big;
+    if (b != 18446744073709551615.0L || (unsigned long)b != big)
+        return 6;
+    long double nb = -9223372036854775807L;
+    if ((long)nb != -9223372036854775807L || (unsigned long)1e19L != 10000000000000000000ul)
+        return 7;
+    if ((int)-2.75L != -2 || (unsigned)4e9L != 4000000000u || (char)(-5.5L * 2) != -11)
+        return 8;
+    float f = tenth;
+    if (f != 0.1f || (long double)f == tenth)
+        return 9;
+    if (-q >= 0 || q < 0.3L || !(q <= 0.34L) || q > 0.34L)
+        return 10;
+    long double zero = 0;
+    long double nan = zero / zero;
+    if (nan == nan || !(nan != nan) || nan < 1 || nan >= 1)
+        return 11;
+    double d = 1e300;
+    long double huge = (long double)d * d;
+    if (!(huge > 1.7976931348623157e308))
+        return 12;
+    long double sum = 0;
+    for (int i = 0; i < 10; i++)
+        sum += tenth;
+    long double *p = &sum;
+    *p -= 1;
+    return printf("%.20Lf %Lg\n", q, sum * 1e20L);
+}
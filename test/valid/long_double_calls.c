@@ -0,0 +1,66 @@
+#include <stdarg.h>
+
+int printf(const char *fmt, ...);
+
+struct one { long double x; };
+struct two { int tag; long double x; };
+
+struct one twice(long double x) {
+    struct one o;
+    o.x = x * 2;
+    return o;
+}
+
+struct two make(int tag, long double x) {
+    struct two w;
+    w.tag = tag;
+    w.x = x;
+    return w;
+}
+
+long double get(struct one o, struct two w) {
+    return o.x + w.x + w.tag;
+}
+
+// more arguments than registers, a long double always on the stack
+long double many(int a, long double b, double c, long double d, int e, int f, int g, int h,
+                 int i, double j, long double k) {
+    return a + b + c + d + e + f + g + h + i + j + k;
+}
+
+long double sum(int n, ...) {
+    va_list ap;
+    va_start(ap, n);
+    long double s = 0;
+    for (int i = 0; i < n; i++) {
+        if (i % 2)
+            s += va_arg(ap, long double);
+        else
+            s += va_arg(ap, int);
+    }
+    va_end(ap);
+    return s;
+}
+
+long double half(void) {
+    return 0.5L;
+}
+
+int main() {
+    struct one o = twice(1.25L);
+    if (o.x != 2.5L)
+        return 1;
+    struct two w = make(3, 0.1L);
+    if (w.tag != 3 || w.x != 0.1L)
+        return 2;
+    if (get(o, w) != 2.5L + 0.1L + 3)
+        return 3;
+    if (many(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11) != 66)
+        return 4;
+    if (sum(6, 1, 0.5L, 2, 0.25L, 3, 1e-4000L) != 6.75L + 1e-4000L)
+        return 5;
+    // what is returned comes off the x87 stack even when nothing reads it
+    for (int i = 0; i < 10; i++)
+        half();
+    return printf("%Lg %Lg %Lg %g\n", o.x, w.x, sum(2, 1, 1e-30L), (double)half());
+}
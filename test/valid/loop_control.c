@@ -0,0 +1,28 @@
+int main() {
+    int t = 0;
+    for (int i = 0; i < 5; i = i + 1) {
+        if (i == 2)
+            continue;
+        t = t + i;
+    }
+    int j = 0;
+    for (j = 0; j < 10; j = j + 1)
+        if (j == 1)
+            continue;
+        else if (j == 6)
+            break;
+    int k = 0;
+    int n = 0;
+    while (n < 4) {
+        do {
+            k = k + 1;
+            if (k > 100)
+                break;
+            continue;
+        } while (k < 3);
+        n = n + 1;
+    }
+    int w = 0;
+    while (w < 5) if (w > 2) w = w + 2; else w = w + 1;
+    return t * 1000 + j * 100 + k * 10 + w;
+}
@@ -0,0 +1,9 @@
+int eight(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h;
+}
+
+int main() {
+    // the arguments after the sixth go on the stack, first one lowest
+    int n = printf("%d %d %d %d %d %s %d\n", 1, 2, 3, 4, 5, "six", 7);
+    return n + eight(1, 1, 1, 1, 1, 1, 2, 3);
+}
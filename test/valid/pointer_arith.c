@@ -0,0 +1,18 @@
+int arr[10];
+
+int main() {
+    for (int i = 0; i < 10; i = i + 1) {
+        arr[i] = i * i;
+    }
+    int *p = arr;
+    int *q = p + 7;
+    int *r = 2 + p;
+    *(q - 1) = 50;
+    if (q - p != 7)
+        return 1;
+    if (!(p < q) || q <= r || p == q)
+        return 2;
+    if (*r != 4)
+        return 3;
+    return *q + p[6] - *(arr + 3);
+}
@@ -0,0 +1,8 @@
+int main() {
+    int a = 3;
+    int *p = &a;
+    *p = *p + 4;
+    int **pp = &p;
+    **pp = **pp * 2;
+    return a + *p;
+}
@@ -0,0 +1,30 @@
+int buf[8];
+
+int swap(int *a, int *b) {
+    int t = *a;
+    *a = *b;
+    *b = t;
+    return 0;
+}
+
+int sum(int *xs, int n) {
+    int s = 0;
+    for (int i = 0; i < n; i = i + 1)
+        s = s + xs[i];
+    return s;
+}
+
+int *at(int *xs, int i) {
+    return xs + i;
+}
+
+int main() {
+    int x = 1;
+    int y = 2;
+    swap(&x, &y);
+    for (int i = 0; i < 8; i = i + 1)
+        buf[i] = i;
+    buf[3] = 20;
+    *at(buf, 2) = 10;
+    return x * 100 + y * 10 + sum(buf, 8) - 50;
+}
@@ -0,0 +1,30 @@
+/* a division by a power of two rounds towards 0, also for a negative dividend */
+int div4(int x) {
+    return x / 4;
+}
+
+long div8(long x) {
+    return x / 8;
+}
+
+unsigned udiv16(unsigned x) {
+    return x / 16;
+}
+
+int scale(int x) {
+    return x * 32 + x * 1 - x * 0;
+}
+
+int main() {
+    int s = 0;
+    int i;
+    for (i = -9; i <= 12; i = i + 1)
+        s = s + div4(i) * 100 + div8(i) * 10 + scale(i);
+    if (div4(-2147483647 - 1) != -536870912)
+        return 1;
+    if (udiv16(4294967295) != 268435455)
+        return 2;
+    if (div8(-9) != -1)
+        return 3;
+    return s + 50;
+}
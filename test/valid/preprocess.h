@@ -0,0 +1,11 @@
+#ifndef PREPROCESS_H
+#define PREPROCESS_H
+
+#define SQUARE(x) ((x) * (x))
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+
+int twice(int a) {
+    return a + a;
+}
+
+#endif
@@ -0,0 +1,32 @@
+#define LEVEL 3
+
+#if LEVEL > 5
+#error LEVEL is too big
+#elif LEVEL == 3 && defined(LEVEL) && !defined UNDEFINED
+int level = 3;
+#else
+int level = 0;
+#endif
+
+#ifdef LEVEL
+#undef LEVEL
+#endif
+
+#ifndef LEVEL
+#define LEVEL 7
+#endif
+
+#if 0
+this isn't C at all
+#if 1
+#error nested in a skipped group
+#endif
+#endif
+
+int main() {
+#if (1 ? 2 : 1 / 0) == 2 && 0x10 == 16 && 'A' == 65 && (-1 < 0)
+    return level + LEVEL;
+#else
+    return 0;
+#endif
+}
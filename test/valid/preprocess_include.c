@@ -0,0 +1,6 @@
+#include "preprocess.h"
+#include "preprocess.h"
+
+int main() {
+    return twice(SQUARE(3)) + MAX(1, 2);
+}
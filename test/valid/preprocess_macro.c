@@ -0,0 +1,21 @@
+#define ANSWER 42
+#define EMPTY
+#define ADD(a, b) a + b
+#define CAT(a, b) a ## b
+#define CALL(f, ...) f(__VA_ARGS__)
+#define MULTI_LINE(a, \
+                   b) ((a) - \
+                       (b))
+
+int sum(int a, int b) {
+    return a + b;
+}
+
+int main() {
+    int CAT(var, iable) = ANSWER EMPTY;
+    int x = 2 * ADD(1, 2); /* not 6, the body is not parenthesized */
+    int y = CALL(sum, 3,
+                 4);
+    if (__LINE__ != 19) return 1;
+    return variable + x + y - MULTI_LINE(10, 5);
+}
@@ -0,0 +1,30 @@
+int printf(const char *fmt, ...);
+long twice(long);
+int is_set(_Bool flag);
+struct pair {
+    int a;
+    long b;
+};
+struct pair make(int, long);
+extern int sum(int count, int *values);
+
+int main() {
+    struct pair p = make(3, 4);
+    int n = printf("%d %ld\n", p.a, p.b);
+    return twice(n) + is_set(256) + p.b;
+}
+
+long twice(long x) {
+    return 2 * x;
+}
+
+int is_set(_Bool flag) {
+    return flag;
+}
+
+struct pair make(int a, long b) {
+    struct pair p;
+    p.a = a;
+    p.b = b;
+    return p;
+}
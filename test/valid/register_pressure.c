@@ -0,0 +1,40 @@
+int id(int x) {
+    return x;
+}
+
+int mix(int a, int b, int c, int d, int e, int f, int g, int h) {
+    return a - b + c - d + e - f + g - h;
+}
+
+/* more values live across the calls than there are callee-saved registers */
+int pressure(int n) {
+    int a = id(n + 1);
+    int b = id(n + 2);
+    int c = id(n + 3);
+    int d = id(n + 4);
+    int e = id(n + 5);
+    int f = id(n + 6);
+    int g = id(n + 7);
+    int h = id(n + 8);
+    int i = id(n + 9);
+    int j = id(n + 10);
+    int k = id(n + 11);
+    int l = id(n + 12);
+    return a * 1 + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8 + i * 9 + j * 10
+        + k * 11 + l * 12;
+}
+
+/* arguments passed on in another order take each other's registers */
+int shuffle(int a, int b, int c, int d, int e, int f, int g, int h) {
+    if (a == 0)
+        return mix(a, b, c, d, e, f, g, h);
+    return shuffle(a - 1, f, a, h, b, c, e, d) + shuffle(a - 1, b, a, d, c, f, h, g);
+}
+
+int main(void) {
+    long acc = 0;
+    for (int i = 0; i < 10; i = i + 1) {
+        acc = acc + pressure(i) - shuffle(i / 3, i, 2 * i, 3, 4 - i, 5, i * i, 7);
+    }
+    return acc / 16;
+}
@@ -0,0 +1,7 @@
+int main() {
+    int a = 1<2;
+    int b = 3>2;
+    int c = 2<=2;
+    int d = 3>=3;
+    return a+b+c+d+(1>2)+(2<1);
+}
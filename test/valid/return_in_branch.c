@@ -0,0 +1,9 @@
+int id(int x) {
+    return x;
+}
+
+int main() {
+    // `id` has a `return`, `main` still returns 0 when it runs off its end
+    if (id(0))
+        return 1;
+}
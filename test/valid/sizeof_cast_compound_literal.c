@@ -0,0 +1,53 @@
+void *malloc(unsigned long n);
+void free(void *p);
+struct point { int x; int y; };
+struct big { char c; long double ld; };
+union u { char c; double d; };
+
+int sum(int *a, int n) {
+    int s = 0;
+    int i = 0;
+    while (i < n) {
+        s = s + a[i];
+        i = i + 1;
+    }
+    return s;
+}
+
+int dist(struct point p) { return p.x + p.y; }
+
+int main(void) {
+    int arr[10];
+    int *p = malloc(4 * sizeof *p);
+    struct point *pp = malloc(sizeof(struct point));
+    char c = (char)300;
+    unsigned char uc = (unsigned char)-1;
+    short sh = (short)70000;
+    long l = (long)(int)4294967295u;
+    unsigned long ul = (unsigned int)-1;
+    double d = (double)7 / 2;
+    int t = (int)3.9;
+    int neg = (int)-3.9;
+    int *q = (int *)0;
+    long addr = (long)(arr + 2) - (long)arr;
+    struct point lit = (struct point){ .y = 5 };
+    int n = sum((int[]){ 1, 2, 3, 4 }, 4);
+    int m = dist((struct point){ 3, 4 });
+    int *cl = (int[3]){ 7 };
+    int z = (int){ 9 };
+    (void)z;
+    if (sizeof(int) != 4 || sizeof(long) != 8 || sizeof(char) != 1) return 1;
+    if (sizeof arr != 40 || sizeof arr[0] != 4 || sizeof(int[3][5]) != 60) return 2;
+    if (sizeof(struct big) != 32 || _Alignof(struct big) != 16 || _Alignof(union u) != 8) return 3;
+    if (sizeof "abc" != 4 || sizeof(char *) != 8 || sizeof p != 8) return 4;
+    if (c != 44 || uc != 255 || sh != 4464 || l != -1 || ul != 4294967295u) return 5;
+    if (d != 3.5 || t != 3 || neg != -3 || q != 0 || addr != 8) return 6;
+    if (lit.x != 0 || lit.y != 5 || n != 10 || m != 7 || cl[0] != 7 || cl[2] != 0) return 7;
+    if (sizeof (int[]){ 1, 2, 3 } != 12 || sizeof(double) != _Alignof(double)) return 8;
+    pp->x = 1;
+    p[3] = (int)sizeof(struct point);
+    n = p[3] + pp->x + (int)sizeof(lit) + z;
+    free(p);
+    free(pp);
+    return n;
+}
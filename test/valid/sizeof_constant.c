@@ -0,0 +1,10 @@
+struct point { int x; int y; };
+unsigned long gs = sizeof(struct point) * 2;
+int ga = _Alignof(double);
+char gc = (char)511;
+int main(void) {
+    switch (8) {
+    case sizeof(long): return gs + ga + gc;
+    default: return 1;
+    }
+}
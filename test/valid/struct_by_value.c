@@ -0,0 +1,68 @@
+struct small {
+    int a;
+    int b;
+};
+
+struct odd {
+    char c[3];
+    short s;
+    int i;
+};
+
+struct big {
+    long x;
+    long y;
+    long z;
+};
+
+struct small make_small(int a, int b) {
+    struct small s;
+    s.a = a;
+    s.b = b;
+    return s;
+}
+
+struct odd make_odd(char c, int i) {
+    struct odd o;
+    o.c[0] = c;
+    o.c[2] = c + 2;
+    o.s = 300;
+    o.i = i;
+    return o;
+}
+
+struct big make_big(long v) {
+    struct big b;
+    b.x = v;
+    b.y = v * 2;
+    b.z = v * 3;
+    return b;
+}
+
+// the struct arguments no longer fit in registers after the first ones
+long mix(struct small s, struct big b, int n, struct odd o, struct small t, struct small u, int m) {
+    return s.a + s.b + b.x + b.y + b.z + n + o.c[0] + o.c[2] + o.s + o.i + t.a + u.b + m;
+}
+
+struct big twice(struct big b) {
+    b.x = b.x * 2;
+    return b;
+}
+
+int main() {
+    struct small s = make_small(1, 2);
+    struct big b = make_big(5);
+    struct odd o = make_odd(10, 20);
+    if (o.c[2] != 12 || o.s != 300 || o.i != 20)
+        return 1;
+    if (twice(b).x != 10 || b.x != 5)
+        return 2;
+    struct small t;
+    t = s;
+    t.a = 7;
+    if (s.a != 1)
+        return 3;
+    long r = mix(s, b, 4, o, t, make_small(8, 9), 11);
+    // 3 + 30 + 4 + 22 + 300 + 20 + 7 + 9 + 11
+    return r - 300;
+}
@@ -0,0 +1,75 @@
+struct point {
+    int x;
+    int y;
+};
+
+struct node {
+    int value;
+    struct node *next;
+};
+
+// c at 0, i at 4, l at 8, s at 16: 24 bytes in all
+struct padded {
+    char c;
+    int i;
+    long l;
+    short s;
+};
+
+union bytes {
+    struct padded p;
+    char b[24];
+};
+
+struct shape {
+    struct point corners[2];
+    union {
+        int radius;
+        char kind;
+    };
+};
+
+struct node nodes[4];
+union bytes u;
+
+int sum(struct node *n) {
+    int total = 0;
+    while (n) {
+        total = total + n->value;
+        n = n->next;
+    }
+    return total;
+}
+
+int main() {
+    struct point a;
+    a.x = 3;
+    a.y = 4;
+    struct point *pa = &a;
+    pa->y = pa->y + 1;
+    if (a.y != 5)
+        return 1;
+
+    for (int i = 0; i < 4; i = i + 1) {
+        nodes[i].value = i + 1;
+        nodes[i].next = 0;
+        if (i > 0)
+            nodes[i - 1].next = &nodes[i];
+    }
+    if (sum(nodes) != 10 || sum(nodes[1].next) != 7)
+        return 2;
+
+    u.p.i = 258;
+    u.p.s = 7;
+    u.p.l = 9;
+    if (u.b[4] != 2 || u.b[5] != 1 || u.b[16] != 7 || u.b[8] != 9)
+        return 3;
+
+    struct shape sh;
+    sh.corners[1].x = 6;
+    sh.radius = 65;
+    if (sh.kind != 'A')
+        return 4;
+    struct point *c = sh.corners;
+    return c[1].x + a.x * 10;
+}
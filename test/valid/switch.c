@@ -0,0 +1,95 @@
+// dense cases go through a jump table, sparse ones through a binary search
+int dense(int x) {
+    switch (x) {
+    case 0:
+        return 10;
+    case 1:
+    case 2:
+        return 20;
+    case 4:
+        x = x + 1;
+    case 5:
+        return x * 3;
+    default:
+        return -1;
+    case 6:
+        return 60;
+    }
+}
+
+int sparse(long x) {
+    int r = 0;
+    switch (x) {
+    case -100000:
+        r = 1;
+        break;
+    case 7:
+        r = 2;
+        break;
+    case 1000:
+        r = 3;
+        break;
+    case 123456789012:
+        r = 4;
+        break;
+    case 99:
+        r = 5;
+    case 5000:
+        r = r + 6;
+        break;
+    }
+    return r;
+}
+
+int letters(char c) {
+    switch (c) {
+    case 'a':
+    case 'e':
+    case 'i':
+    case 'o':
+    case 'u':
+        return 1;
+    }
+    return 0;
+}
+
+unsigned big(unsigned x) {
+    switch (x) {
+    case 4000000000:
+        return 1;
+    case 1:
+        return 2;
+    case 2:
+        return 3;
+    case 3:
+        return 4;
+    case 5:
+        return 5;
+    }
+    return 0;
+}
+
+int main() {
+    int total = 0;
+    for (int i = -1; i < 8; i = i + 1) {
+        switch (i / 2 * 2 == i) {
+        case 1:
+            continue;
+        }
+        total = total + dense(i);
+    }
+    // -1 + 20 + -1 + 15 + -1 for the odd ones
+    if (total != 32)
+        return 1;
+    if (dense(0) + dense(2) + dense(4) != 45)
+        return 2;
+    if (sparse(-100000) != 1 || sparse(7) != 2 || sparse(1000) != 3 ||
+        sparse(123456789012) != 4 || sparse(99) != 11 || sparse(5000) != 6 ||
+        sparse(8) != 0)
+        return 3;
+    if (letters('e') + letters('x') + letters('u') != 2)
+        return 4;
+    if (big(4000000000) != 1 || big(5) != 5 || big(4) != 0)
+        return 5;
+    return total;
+}
@@ -0,0 +1,76 @@
+int state_machine(int input) {
+    int state = 0;
+    int steps = 0;
+    while (1) {
+        steps = steps + 1;
+        switch (state) {
+        case 0:
+            if (input > 10)
+                state = 2;
+            else
+                state = 1;
+            break;
+        case 1:
+            switch (input) {
+            case 1:
+                state = 3;
+                break;
+            default:
+                input = input - 1;
+                break;
+            }
+            break;
+        case 2: {
+            int half = input / 2;
+            input = half;
+            state = 0;
+            break;
+        }
+        default:
+            return steps * 100 + input;
+        }
+    }
+}
+
+int duff(int n) {
+    int count = 0;
+    int k = (n + 3) / 4;
+    switch (n - n / 4 * 4) {
+    case 0:
+        do {
+            count = count + 1;
+        case 3:
+            count = count + 1;
+        case 2:
+            count = count + 1;
+        case 1:
+            count = count + 1;
+        } while ((k = k - 1) > 0);
+    }
+    return count;
+}
+
+int scoped(int x) {
+    switch (x) {
+        int y;
+    case 1:
+        y = 5;
+        int z = 7;
+        return y + z + x;
+    default:
+        y = 2;
+        return y;
+    }
+}
+
+int main() {
+    if (state_machine(1) != 301)
+        return 1;
+    if (state_machine(24) != 1201)
+        return 2;
+    if (duff(13) != 13 || duff(4) != 4 || duff(1) != 1)
+        return 3;
+    if (scoped(1) != 13 || scoped(9) != 2)
+        return 4;
+    return duff(27);
+}
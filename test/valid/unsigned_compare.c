@@ -0,0 +1,18 @@
+int main() {
+    unsigned int u = 1;
+    int i = -1;
+    if (i < u)
+        return 1;
+    unsigned int d = -7;
+    if (d / 2 != 2147483644)
+        return 2;
+    if (-7 / 2 != -3)
+        return 3;
+    long m = -20;
+    if (m / 3 != -6)
+        return 4;
+    unsigned long ul = 3000000000;
+    if (ul * 2 != 6000000000)
+        return 5;
+    return 17;
+}
@@ -0,0 +1,94 @@
+#include <stdarg.h>
+
+int vsnprintf(char *buf, unsigned long size, const char *fmt, va_list ap);
+
+struct point { int x, y; };
+struct vec { double x, y; };
+struct mixed { double d; long n; };
+struct big { long a, b, c; };
+
+int sum(int n, ...) {
+    va_list ap;
+    int total = 0;
+    va_start(ap, n);
+    for (int i = 0; i < n; i++)
+        total += va_arg(ap, int);
+    va_end(ap);
+    return total;
+}
+
+// more doubles than there are xmm registers, the last ones on the stack
+double fsum(int n, ...) {
+    va_list ap;
+    double total = 0;
+    va_start(ap, n);
+    while (n-- > 0)
+        total += va_arg(ap, double);
+    va_end(ap);
+    return total;
+}
+
+// the named arguments take registers of both kinds and the stack first
+long after(long a, double b, long c, long d, long e, long f, long g, ...) {
+    va_list ap;
+    va_start(ap, g);
+    long x = va_arg(ap, long);
+    double y = va_arg(ap, double);
+    char *s = va_arg(ap, char *);
+    va_end(ap);
+    return a + (long)b + c + d + e + f + g + x + (long)y + s[1];
+}
+
+int structs(int n, ...) {
+    va_list ap;
+    va_start(ap, n);
+    struct point p = va_arg(ap, struct point);
+    struct vec v = va_arg(ap, struct vec);
+    struct mixed m = va_arg(ap, struct mixed);
+    struct big b = va_arg(ap, struct big);
+    float f = va_arg(ap, double);
+    char c = va_arg(ap, int);
+    va_end(ap);
+    return n + p.x + p.y + (int)(v.x * v.y) + (int)m.d + m.n + b.a + b.b + b.c + (int)f + c;
+}
+
+// a va_list goes on to another function as a pointer to what it holds
+int take(va_list ap) {
+    return va_arg(ap, int);
+}
+
+int twice(int n, ...) {
+    va_list ap;
+    va_list again;
+    va_start(ap, n);
+    va_copy(again, ap);
+    int first = take(ap);
+    int second = take(ap);
+    int first_again = va_arg(again, int);
+    va_end(again);
+    va_end(ap);
+    return n * first + second - first_again;
+}
+
+int format(char *buf, const char *fmt, ...) {
+    va_list ap;
+    va_start(ap, fmt);
+    int n = vsnprintf(buf, 64, fmt, ap);
+    va_end(ap);
+    return n;
+}
+
+int main() {
+    char buf[64];
+    struct point p = {1, 2};
+    struct vec v = {2.5, 4};
+    struct mixed m = {3.5, 4};
+    struct big b = {5, 6, 7};
+    int r = sum(0) + sum(3, 1, 2, 3) + sum(8, 1, 1, 1, 1, 1, 1, 1, 1);
+    r += fsum(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.5) * 2;
+    r += after(1, 2.5, 3, 4, 5, 6, 7, 8L, 9.5, "abc");
+    r += structs(1, p, v, m, b, 2.5f, 'a');
+    r += twice(3, 10, 20);
+    r += format(buf, "%d-%s-%.1f", 42, "xy", 1.5);
+    return r + buf[0] + buf[3];
+}
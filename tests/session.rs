@@ -0,0 +1,70 @@
+// The library API: a Session runs the stages on sources it is given in
+// memory, without the `crust` binary or any file.
+
+use crust::diagnostic::{code, Severity};
+use crust::lexer::TokType;
+use crust::{Options, Session};
+
+#[test]
+fn stages_from_memory() {
+    let mut session = Session::new(Options::default());
+    let unit = session
+        .preprocess(
+            "answer.c",
+            "#define ANSWER 42\nint main(void) { return ANSWER; }\n",
+        )
+        .unwrap();
+    let tokens = session.lex(&unit).unwrap();
+    assert!(tokens
+        .iter()
+        .any(|t| matches!(t.tok, TokType::Literal(42, _))));
+    assert_eq!(tokens.last().map(|t| &t.tok), Some(&TokType::Eof));
+    let mut tree = session.parse("answer.c", &tokens).unwrap();
+    session.check(&mut tree).unwrap();
+    let mut module = session.lower(&tree).unwrap();
+    session.optimize(&mut module, &mut |_, _| {});
+    let asm = session.codegen(&module);
+    assert!(asm.contains("main:"));
+    let object = session.assemble("answer.s", &asm).unwrap();
+    assert_eq!(&object[..4], b"\x7fELF");
+    assert!(session.warnings().is_empty());
+}
+
+#[test]
+fn warnings_stay_in_the_session() {
+    let mut session = Session::new(Options::default());
+    session
+        .compile("implicit.c", "int main(void) {\n    return twice(21);\n}\n")
+        .unwrap();
+    let warnings = session.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert_eq!(warnings[0].code, Some(code::IMPLICIT_DECLARATION));
+    let loc = warnings[0].loc.as_ref().unwrap();
+    assert_eq!((&*loc.file, loc.line), ("implicit.c", 2));
+    assert!(session.warnings().is_empty());
+}
+
+// tokens that do not come from `lex` lack the end of input the parser stops at
+#[test]
+fn parse_without_end_of_input() {
+    let mut session = Session::new(Options::default());
+    let err = session.parse("empty.c", &[]).unwrap_err();
+    assert!(err.is_ice());
+    let mut tokens = session.lex("int x;").unwrap();
+    tokens.pop();
+    assert!(session.parse("cut.c", &tokens).unwrap_err().is_ice());
+}
+
+#[test]
+fn errors_render_with_their_line() {
+    let mut session = Session::new(Options::default());
+    let err = session
+        .compile("broken.c", "int main(void) {\n    return 1 +;\n}\n")
+        .unwrap_err();
+    assert_eq!(err.code, Some(code::SYNTAX));
+    assert!(!err.is_ice());
+    let rendered = session.render(&err);
+    assert!(rendered.starts_with("broken.c:2:"), "{}", rendered);
+    assert!(rendered.contains("    return 1 +;"), "{}", rendered);
+}